@@ -0,0 +1,85 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Bluetooth HCI Reset
+//!
+//! The bluetooth module of the Raspberry Pi 3 is connected to the Uart0 (PL011) on GPIO 32 and 33. This example sends
+//! the HCI ``Reset`` command to the module and reports the ``Command Complete`` event it answers with on the Uart1
+//! (miniUART).
+
+#![cfg_attr(any(target_arch = "aarch64", target_arch = "arm"), no_std, no_main)]
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+#[macro_use]
+extern crate ruspiro_boot;
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+extern crate ruspiro_allocator;
+
+use ruspiro_uart::{Uart0, Uart1};
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+come_alive_with!(alive);
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+run_with!(run);
+
+// HCI command packet: Reset (OGF 0x03, OCF 0x0003) without parameters
+const HCI_RESET: [u8; 4] = [0x01, 0x03, 0x0C, 0x00];
+
+// HCI event packet: Command Complete of the Reset command with status success
+const HCI_RESET_COMPLETE: [u8; 7] = [0x04, 0x0E, 0x04, 0x01, 0x03, 0x0C, 0x00];
+
+// the time in micro seconds the module is given to answer
+const HCI_TIMEOUT: u64 = 1_000_000;
+
+fn alive(_core: u32) {}
+
+fn run(_core: u32) -> ! {
+    let mut console = Uart1::new();
+    let mut bluetooth = Uart0::new();
+    if console.initialize(250_000_000, 115_200).is_ok() {
+        if bluetooth.initialize(3_000_000, 115_200).is_ok() {
+            bluetooth.write_data(&HCI_RESET);
+            let mut event = [0u8; 7];
+            let received = read_event(&bluetooth, &mut event);
+            if event[..received] == HCI_RESET_COMPLETE {
+                console.send_string("bluetooth module reset\r\n");
+            } else {
+                console.send_string("unexpected answer from the bluetooth module: ");
+                for byte in &event[..received] {
+                    console.send_hex(*byte as u64);
+                    console.send_char(' ');
+                }
+                console.send_string("\r\n");
+            }
+        } else {
+            console.send_string("unable to initialize the Uart0\r\n");
+        }
+    }
+    loop {
+        ruspiro_timer::sleep(1_000_000);
+    }
+}
+
+// read the event into the buffer until it is full or the module stays silent for too long. Returns the number of
+// bytes received
+fn read_event(bluetooth: &Uart0, event: &mut [u8]) -> usize {
+    let mut received = 0;
+    let start = ruspiro_timer::now();
+    while received < event.len() && ruspiro_timer::now() - start < HCI_TIMEOUT {
+        if let Some(byte) = bluetooth.read_data() {
+            event[received] = byte;
+            received += 1;
+        }
+    }
+    received
+}
+
+// the example runs on the Raspberry Pi only, building it for the host just checks that it compiles
+#[cfg(not(any(target_arch = "aarch64", target_arch = "arm")))]
+fn main() {
+    let _ = (alive as fn(u32), run as fn(u32) -> !);
+}
@@ -0,0 +1,59 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Dual Console
+//!
+//! Use the Uart1 (miniUART) as the console of the ``ruspiro-console`` crate and the software Uart on GPIO 21 as a
+//! second debug output. The output written before the Uart1 has been initialized is kept in the early log buffer and
+//! shows up on the console once it is available.
+
+#![cfg_attr(any(target_arch = "aarch64", target_arch = "arm"), no_std, no_main)]
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+#[macro_use]
+extern crate ruspiro_boot;
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+extern crate ruspiro_allocator;
+
+use ruspiro_console::*;
+use ruspiro_uart::early::EARLY_LOG;
+use ruspiro_uart::softuart::SoftUartTx;
+use ruspiro_uart::{Prefix, Uart1};
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+come_alive_with!(alive);
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+run_with!(run);
+
+fn alive(_core: u32) {
+    EARLY_LOG.write(b"core alive before the console is available\r\n");
+}
+
+fn run(_core: u32) -> ! {
+    let mut debug = SoftUartTx::new(21);
+    let _ = debug.initialize(115_200);
+    debug.puts("debug output on GPIO21\r\n");
+
+    let mut uart = Uart1::new();
+    match uart.initialize(250_000_000, 115_200) {
+        Ok(_) => {
+            uart.set_log_prefix(Prefix::Both);
+            CONSOLE.take_for(|console| console.replace(uart));
+            info!("console output on the Uart1");
+        }
+        Err(_) => debug.puts("unable to initialize the Uart1\r\n"),
+    }
+    loop {
+        ruspiro_timer::sleep(1_000_000);
+    }
+}
+
+// the example runs on the Raspberry Pi only, building it for the host just checks that it compiles
+#[cfg(not(any(target_arch = "aarch64", target_arch = "arm")))]
+fn main() {
+    let _ = (alive as fn(u32), run as fn(u32) -> !);
+}
@@ -0,0 +1,49 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Echo
+//!
+//! Send every byte received on the Uart1 (miniUART) back to the sender.
+
+#![cfg_attr(any(target_arch = "aarch64", target_arch = "arm"), no_std, no_main)]
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+#[macro_use]
+extern crate ruspiro_boot;
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+extern crate ruspiro_allocator;
+
+use ruspiro_uart::{Timeout, Uart1};
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+come_alive_with!(alive);
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+run_with!(run);
+
+fn alive(_core: u32) {}
+
+fn run(_core: u32) -> ! {
+    let mut uart = Uart1::new();
+    if uart.initialize(250_000_000, 115_200).is_ok() {
+        uart.send_string("echo example - type something\r\n");
+        let mut buffer = [0u8; 16];
+        loop {
+            if let Ok(count) = uart.try_receive_data(&mut buffer, Timeout::Micros(1_000)) {
+                uart.send_data(&buffer[..count]);
+            }
+        }
+    }
+    loop {
+        ruspiro_timer::sleep(1_000_000);
+    }
+}
+
+// the example runs on the Raspberry Pi only, building it for the host just checks that it compiles
+#[cfg(not(any(target_arch = "aarch64", target_arch = "arm")))]
+fn main() {
+    let _ = (alive as fn(u32), run as fn(u32) -> !);
+}
@@ -0,0 +1,49 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Hardware Test
+//!
+//! Run the hardware test of the Uart1 (miniUART) against the host script ``tools/hwtest.py``, which need to be started
+//! before the device is powered on. The outcome is reported by the host script and sent as a summary line once the
+//! test is over.
+
+#![cfg_attr(any(target_arch = "aarch64", target_arch = "arm"), no_std, no_main)]
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+#[macro_use]
+extern crate ruspiro_boot;
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+extern crate ruspiro_allocator;
+
+use ruspiro_uart::Uart1;
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+come_alive_with!(alive);
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+run_with!(run);
+
+fn alive(_core: u32) {}
+
+fn run(_core: u32) -> ! {
+    let mut uart = Uart1::new();
+    if uart.initialize(250_000_000, 115_200).is_ok() {
+        match uart.run_hw_test(&[9_600, 57_600, 230_400, 460_800, 921_600]) {
+            Ok(report) if report.failed == 0 => uart.send_string("hardware test passed\r\n"),
+            Ok(_) => uart.send_string("hardware test failed\r\n"),
+            Err(_) => uart.send_string("host script not connected\r\n"),
+        }
+    }
+    loop {
+        ruspiro_timer::sleep(1_000_000);
+    }
+}
+
+// the example runs on the Raspberry Pi only, building it for the host just checks that it compiles
+#[cfg(not(any(target_arch = "aarch64", target_arch = "arm")))]
+fn main() {
+    let _ = (alive as fn(u32), run as fn(u32) -> !);
+}
@@ -0,0 +1,82 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Interrupt Driven Receive
+//!
+//! Receive data on the Uart1 (miniUART) from the Aux interrupt instead of polling the peripheral. The interrupt handler
+//! only queues the events, the received data is processed and echoed in the main loop.
+
+#![cfg_attr(any(target_arch = "aarch64", target_arch = "arm"), no_std, no_main)]
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+#[macro_use]
+extern crate ruspiro_boot;
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+extern crate ruspiro_allocator;
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+use ruspiro_interrupt::*;
+use ruspiro_uart::{InterruptType, RxError, Timeout, Uart1, UartEvent};
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+come_alive_with!(alive);
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+run_with!(run);
+
+fn alive(_core: u32) {}
+
+fn run(_core: u32) -> ! {
+    let mut uart = Uart1::new();
+    if uart.initialize(250_000_000, 115_200).is_ok() {
+        uart.send_string("interrupt receive example - type something\r\n");
+        uart.enable_interrupts(InterruptType::Receive);
+        #[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+        {
+            // the interrupt manager need to be initialized before any interrupt can be activated
+            IRQ_MANAGER.take_for(|irq_mgr| {
+                irq_mgr.initialize();
+                irq_mgr.activate(Interrupt::Aux);
+            });
+            enable_interrupts();
+        }
+
+        let mut buffer = [0u8; 8];
+        loop {
+            for event in uart.events() {
+                match event {
+                    UartEvent::DataAvailable(_) => {
+                        if let Ok(count) = uart.try_receive_data(&mut buffer, Timeout::Micros(100))
+                        {
+                            uart.send_data(&buffer[..count]);
+                        }
+                        // the handler disabled the receive interrupt until the data has been read
+                        uart.enable_interrupts(InterruptType::Receive);
+                    }
+                    UartEvent::Error(RxError::Overrun) => uart.send_string("\r\n[data lost]\r\n"),
+                    _ => (),
+                }
+            }
+        }
+    }
+    loop {
+        ruspiro_timer::sleep(1_000_000);
+    }
+}
+
+#[cfg_attr(
+    any(target_arch = "aarch64", target_arch = "arm"),
+    IrqHandler(Aux, Uart1)
+)]
+fn uart1_handler() {
+    Uart1::handle_interrupt();
+}
+
+// the example runs on the Raspberry Pi only, building it for the host just checks that it compiles
+#[cfg(not(any(target_arch = "aarch64", target_arch = "arm")))]
+fn main() {
+    let _ = (alive as fn(u32), run as fn(u32) -> !, uart1_handler as fn());
+}
@@ -0,0 +1,119 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # XMODEM Receive
+//!
+//! Receive a file sent with the XMODEM protocol (128 byte blocks, 8 bit checksum) on the Uart1 (miniUART), e.g. with
+//! ``sx`` or the file transfer of a terminal program. The size and checksum of the received file are reported once the
+//! transfer is complete.
+
+#![cfg_attr(any(target_arch = "aarch64", target_arch = "arm"), no_std, no_main)]
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+#[macro_use]
+extern crate ruspiro_boot;
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+extern crate ruspiro_allocator;
+
+use ruspiro_uart::{Timeout, Uart1};
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+come_alive_with!(alive);
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+run_with!(run);
+
+// the XMODEM control characters
+const SOH: u8 = 0x01;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+
+// the payload size of a single block
+const BLOCK_SIZE: usize = 128;
+
+// the time in micro seconds to wait for the sender to start a block or to send the next byte of a block
+const BLOCK_TIMEOUT: u64 = 1_000_000;
+const BYTE_TIMEOUT: u64 = 100_000;
+
+fn alive(_core: u32) {}
+
+fn run(_core: u32) -> ! {
+    let mut uart = Uart1::new();
+    if uart.initialize(250_000_000, 115_200).is_ok() {
+        uart.send_string("xmodem receive example - start the transfer\r\n");
+        let mut size = 0;
+        let mut checksum = 0u32;
+        let mut expected = 1u8;
+        let mut block = [0u8; BLOCK_SIZE + 3];
+        loop {
+            // the first NAK asks the sender to start, every further one to repeat the last block
+            match receive_byte(&uart, BLOCK_TIMEOUT) {
+                Some(SOH) => (),
+                Some(EOT) => {
+                    uart.send_data(&[ACK]);
+                    break;
+                }
+                _ => {
+                    uart.send_data(&[NAK]);
+                    continue;
+                }
+            }
+            if !receive_block(&uart, &mut block) {
+                uart.send_data(&[NAK]);
+                continue;
+            }
+            let payload = &block[2..BLOCK_SIZE + 2];
+            // a block received twice, as the ACK got lost, is acknowledged but not taken again
+            if block[0] == expected {
+                size += BLOCK_SIZE;
+                checksum = payload
+                    .iter()
+                    .fold(checksum, |sum, byte| sum.wrapping_add(*byte as u32));
+                expected = expected.wrapping_add(1);
+            }
+            uart.send_data(&[ACK]);
+        }
+        uart.send_string("\r\nreceived bytes: ");
+        uart.send_dec(size as u64);
+        uart.send_string(", sum: ");
+        uart.send_hex(checksum as u64);
+        uart.send_string("\r\n");
+    }
+    loop {
+        ruspiro_timer::sleep(1_000_000);
+    }
+}
+
+// receive the rest of a block following the SOH, which is the block number, it's complement, the payload and the
+// checksum. Returns whether a valid block has been received
+fn receive_block(uart: &Uart1, block: &mut [u8]) -> bool {
+    for byte in block.iter_mut() {
+        match receive_byte(uart, BYTE_TIMEOUT) {
+            Some(value) => *byte = value,
+            None => return false,
+        }
+    }
+    let sum = block[2..BLOCK_SIZE + 2]
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    block[0] == !block[1] && block[BLOCK_SIZE + 2] == sum
+}
+
+// wait at most ``timeout`` micro seconds for a single byte to arrive
+fn receive_byte(uart: &Uart1, timeout: u64) -> Option<u8> {
+    let mut byte = [0u8; 1];
+    match uart.try_receive_data(&mut byte, Timeout::Micros(timeout)) {
+        Ok(1) => Some(byte[0]),
+        _ => None,
+    }
+}
+
+// the example runs on the Raspberry Pi only, building it for the host just checks that it compiles
+#[cfg(not(any(target_arch = "aarch64", target_arch = "arm")))]
+fn main() {
+    let _ = (alive as fn(u32), run as fn(u32) -> !);
+}
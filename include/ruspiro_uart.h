@@ -0,0 +1,57 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+/*
+ * C interface to the Uart1 (miniUART) driver of the ruspiro-uart crate, available with the "ffi" feature.
+ * Negative return values are errors.
+ */
+
+#ifndef RUSPIRO_UART_H
+#define RUSPIRO_UART_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define RUSPIRO_UART_OK 0
+#define RUSPIRO_UART_ERR_NOT_INITIALIZED -1
+#define RUSPIRO_UART_ERR_INIT_TIMEOUT -2
+#define RUSPIRO_UART_ERR_GPIO -3
+#define RUSPIRO_UART_ERR_BAUD_RATE -4
+#define RUSPIRO_UART_ERR_INVALID_ARGUMENT -5
+#define RUSPIRO_UART_ERR_PARITY -6
+#define RUSPIRO_UART_ERR_IN_USE -7
+#define RUSPIRO_UART_ERR_OTHER -99
+
+/* the function called with each byte received */
+typedef void (*ruspiro_uart_rx_callback)(uint8_t data);
+
+/* initialize the Uart1 with the core clock rate and the baud rate, later calls keep the configuration, fails with
+ * RUSPIRO_UART_ERR_IN_USE if the Rust side initialized the Uart1 already */
+int32_t ruspiro_uart1_init(uint32_t clock_rate, uint32_t baud_rate);
+
+/* send len bytes starting at data */
+int32_t ruspiro_uart1_send(const uint8_t *data, size_t len);
+
+/* receive up to len bytes without waiting for data to arrive, returns the number of bytes received */
+int32_t ruspiro_uart1_recv(uint8_t *buffer, size_t len);
+
+/* register the function called with each byte received from ruspiro_uart1_handle_interrupt, NULL removes it */
+void ruspiro_uart1_set_rx_callback(ruspiro_uart_rx_callback callback);
+
+/* handle the Aux interrupt, returns whether the Uart1 raised it */
+bool ruspiro_uart1_handle_interrupt(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RUSPIRO_UART_H */
@@ -0,0 +1,36 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Async Support
+//!
+//! The future polling a closure the async wrappers of the Uarts are built on. The closure registers the waker woken
+//! by the interrupt handler of the Uart before it returns ``Poll::Pending``.
+//!
+
+#![forbid(unsafe_code)]
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+// the future returned by ``poll_fn``
+pub(crate) struct PollFn<F> {
+    poll: F,
+}
+
+// a future completing once the closure returns ``Poll::Ready``
+pub(crate) fn poll_fn<T, F: FnMut(&mut Context<'_>) -> Poll<T> + Unpin>(poll: F) -> PollFn<F> {
+    PollFn { poll }
+}
+
+impl<T, F: FnMut(&mut Context<'_>) -> Poll<T> + Unpin> Future for PollFn<F> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        (self.get_mut().poll)(cx)
+    }
+}
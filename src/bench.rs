@@ -0,0 +1,370 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Micro Benchmarks
+//!
+//! Measure the transmit and receive paths of the Uarts on the target, so redesigns motivated by performance can be
+//! validated and regressions caught. This is only available with the ``bench`` feature active and requires the Uart to
+//! be initialized already. The benchmarks cover:
+//!
+//! - the time it takes to send a given buffer with the FIFO level aware transmit loop compared to the plain loop that
+//!   polls the status of the peripheral before each and every byte
+//! - the CPU cycles the polled transmit loop spends per byte
+//! - the latency from a byte arriving at the Uart0 to the interrupt handler seeing it
+//! - the throughput of a burst of data at several baud rates
+//!
+//! To see any difference in the transmit loops the baud rate should be as high as possible (e.g. 3 Mbaud), otherwise
+//! the time spent on the wire will dominate the result. Each result prints as a single line of ``key=value`` pairs
+//! starting with the name of the benchmark, so it can be sent over the Uart itself and parsed by a script on the host
+//! comparing it against a previous run.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::*;
+//! # fn doc() {
+//! let mut uart = Uart0::new();
+//! let _ = uart.initialize(48_000_000, 3_000_000);
+//! let result = ruspiro_uart::bench::uart0_tx(&uart, &[0x55; 4096]);
+//! // prints "bench=tx bytes=4096 batched_us=... bytewise_us=..."
+//! println!("{}", result);
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+use crate::clock;
+use crate::uart0::IrqFlags;
+use crate::{watchdog, FifoMode, RxFlag, Uart0, Uart1, UartError};
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+// the time in micro seconds the interrupt handler need to see a looped back byte after it's character time
+const LATENCY_TIMEOUT: u64 = 10_000;
+
+// the time the interrupt handler has seen the looped back byte, 0 while it has not been seen
+static RX_SEEN: AtomicU64 = AtomicU64::new(0);
+
+/// The result of a transmit benchmark run
+#[derive(Debug, Clone, Copy)]
+pub struct TxBenchmark {
+    /// the number of bytes that has been sent with each variant
+    pub bytes: usize,
+    /// the micro seconds it took to send the data with the FIFO level aware loop
+    pub batched: u64,
+    /// the micro seconds it took to send the data polling the status before each byte
+    pub bytewise: u64,
+}
+
+impl fmt::Display for TxBenchmark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bench=tx bytes={} batched_us={} bytewise_us={}",
+            self.bytes, self.batched, self.bytewise
+        )
+    }
+}
+
+/// The CPU cycles spent by the polled transmit loop
+#[derive(Debug, Clone, Copy)]
+pub struct TxCycles {
+    /// the number of bytes that has been sent
+    pub bytes: usize,
+    /// the CPU cycles spent per byte, derived from the time it took to send the data and the core clock rate
+    pub cycles_per_byte: u32,
+}
+
+impl fmt::Display for TxCycles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bench=tx_cycles bytes={} cycles_per_byte={}",
+            self.bytes, self.cycles_per_byte
+        )
+    }
+}
+
+/// The latency of the receive interrupt in micro seconds
+#[derive(Debug, Clone, Copy)]
+pub struct RxLatency {
+    /// the number of bytes the latency has been measured for
+    pub samples: u32,
+    /// the shortest latency
+    pub min: u64,
+    /// the longest latency
+    pub max: u64,
+    /// the average latency
+    pub mean: u64,
+}
+
+impl fmt::Display for RxLatency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bench=rx_latency samples={} min_us={} max_us={} mean_us={}",
+            self.samples, self.min, self.max, self.mean
+        )
+    }
+}
+
+/// The throughput of a burst of data at a single baud rate
+#[derive(Debug, Clone, Copy)]
+pub struct Throughput {
+    /// the baud rate the burst has been sent with
+    pub baud_rate: u32,
+    /// the number of bytes sent per second
+    pub bytes_per_second: u32,
+}
+
+impl fmt::Display for Throughput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bench=throughput baud={} bytes_per_s={}",
+            self.baud_rate, self.bytes_per_second
+        )
+    }
+}
+
+/// Run the transmit benchmark with the Uart0 sending the given data with each transmit loop variant.
+pub fn uart0_tx(uart: &Uart0, data: &[u8]) -> TxBenchmark {
+    uart.flush();
+    let start = clock::now();
+    uart.write_data(data);
+    uart.flush();
+    let batched = clock::elapsed_since(start);
+
+    let start = clock::now();
+    uart.write_data_bytewise(data);
+    uart.flush();
+    let bytewise = clock::elapsed_since(start);
+
+    TxBenchmark {
+        bytes: data.len(),
+        batched,
+        bytewise,
+    }
+}
+
+/// Run the transmit benchmark with the Uart1 sending the given data with each transmit loop variant.
+pub fn uart1_tx(uart: &Uart1, data: &[u8]) -> TxBenchmark {
+    uart.flush();
+    let start = clock::now();
+    uart.send_data(data);
+    uart.flush();
+    let batched = clock::elapsed_since(start);
+
+    let start = clock::now();
+    uart.send_data_bytewise(data);
+    uart.flush();
+    let bytewise = clock::elapsed_since(start);
+
+    TxBenchmark {
+        bytes: data.len(),
+        batched,
+        bytewise,
+    }
+}
+
+/// Measure the CPU cycles per byte the Uart0 spends sending the given data with the loop polling the status before
+/// each byte. The ``core_clock`` is the clock rate of the ARM core in Hz.
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::*;
+/// # fn doc() {
+/// # let mut uart = Uart0::new();
+/// # let _ = uart.initialize(48_000_000, 3_000_000);
+/// let result = ruspiro_uart::bench::uart0_tx_cycles(&uart, &[0x55; 4096], 1_200_000_000);
+/// println!("{}", result);
+/// # }
+/// ```
+pub fn uart0_tx_cycles(uart: &Uart0, data: &[u8], core_clock: u32) -> TxCycles {
+    uart.flush();
+    let start = clock::now();
+    uart.write_data_bytewise(data);
+    tx_cycles(data.len(), clock::elapsed_since(start), core_clock)
+}
+
+/// Measure the CPU cycles per byte the Uart1 spends sending the given data with the loop polling the status before
+/// each byte. The ``core_clock`` is the clock rate of the ARM core in Hz.
+pub fn uart1_tx_cycles(uart: &Uart1, data: &[u8], core_clock: u32) -> TxCycles {
+    uart.flush();
+    let start = clock::now();
+    uart.send_data_bytewise(data);
+    tx_cycles(data.len(), clock::elapsed_since(start), core_clock)
+}
+
+// convert the micro seconds it took to send the bytes into CPU cycles per byte
+fn tx_cycles(bytes: usize, elapsed: u64, core_clock: u32) -> TxCycles {
+    let cycles = elapsed * core_clock as u64 / 1_000_000;
+    TxCycles {
+        bytes,
+        cycles_per_byte: (cycles / (bytes as u64).max(1)) as u32,
+    }
+}
+
+/// Measure the latency from a byte arriving at the Uart0 to [Uart0::handle_interrupt] being called for it, with the
+/// character time of the byte itself taken out. The Uart0 is switched to loopback mode for the measurement, so nothing
+/// is sent on the line, and the FIFOs are disabled to raise the receive interrupt for each byte. The interrupt of the
+/// Uart0 need to be routed to an interrupt handler calling [Uart0::handle_interrupt]. The bytes received and the
+/// events raised during the measurement are dropped.
+///
+/// Afterwards the FIFOs are enabled again, the receive interrupt is masked and any hook registered with
+/// [Uart0::set_irq_hook] is removed. Fails with ``Err(UartError::ReceiveDataTimeOut)`` if the interrupt handler has
+/// not seen a byte in time.
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::*;
+/// # fn doc() {
+/// # let mut uart = Uart0::new();
+/// # let _ = uart.initialize(48_000_000, 3_000_000);
+/// let result = ruspiro_uart::bench::uart0_rx_latency(&mut uart, 100).unwrap();
+/// println!("{}", result);
+/// # }
+/// ```
+pub fn uart0_rx_latency(uart: &mut Uart0, samples: u32) -> Result<RxLatency, UartError> {
+    let char_time = uart.char_time_ns()? as u64 / 1_000;
+    uart.set_fifo_mode(FifoMode::Disabled)?;
+    uart.set_loopback(true);
+    uart.set_irq_hook(Some(record_rx));
+    uart.enable_interrupts(IrqFlags::RX);
+
+    let mut latency = RxLatency {
+        samples: 0,
+        min: u64::MAX,
+        max: 0,
+        mean: 0,
+    };
+    let mut total = 0;
+    let mut result = Ok(());
+    for _ in 0..samples {
+        RX_SEEN.store(0, Ordering::Release);
+        let start = clock::now();
+        uart.write_data(&[0x55]);
+        let seen = loop {
+            let seen = RX_SEEN.load(Ordering::Acquire);
+            if seen != 0 {
+                break seen;
+            }
+            if clock::elapsed_since(start) > char_time + LATENCY_TIMEOUT {
+                result = Err(UartError::ReceiveDataTimeOut);
+                break 0;
+            }
+            watchdog::kick();
+        };
+        uart.read_tagged(&mut [(0, RxFlag::Ok)]);
+        uart.events().for_each(drop);
+        if result.is_err() {
+            break;
+        }
+        let sample = seen.saturating_sub(start).saturating_sub(char_time);
+        latency.samples += 1;
+        latency.min = latency.min.min(sample);
+        latency.max = latency.max.max(sample);
+        total += sample;
+    }
+
+    uart.disable_interrupts(IrqFlags::RX);
+    uart.set_irq_hook(None);
+    uart.set_loopback(false);
+    uart.set_fifo_mode(FifoMode::Enabled)?;
+    result?;
+    if latency.samples == 0 {
+        latency.min = 0;
+    } else {
+        latency.mean = total / latency.samples as u64;
+    }
+    Ok(latency)
+}
+
+// record the time the receive interrupt has been seen first
+fn record_rx(flags: IrqFlags) {
+    if flags.contains(IrqFlags::RX) {
+        let _ = RX_SEEN.compare_exchange(0, clock::now(), Ordering::AcqRel, Ordering::Relaxed);
+    }
+}
+
+/// Measure the throughput of the Uart0 sending the given data as a burst at each of the baud rates given. The Uart0
+/// switches back to it's configured baud rate afterwards. As the remote side does not follow the switches it receives
+/// garbage, so the results should be printed once the benchmark is done. Fails with
+/// ``Err(UartError::UnsupportedBaudRate)`` if a baud rate can not be configured with the clock rate of the Uart0.
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::*;
+/// # fn doc() {
+/// # let mut uart = Uart0::new();
+/// # let _ = uart.initialize(48_000_000, 115_200);
+/// let results = ruspiro_uart::bench::uart0_throughput(&uart, &[115_200, 921_600, 3_000_000], &[0x55; 4096]);
+/// for result in results.unwrap() {
+///     println!("{}", result);
+/// }
+/// # }
+/// ```
+pub fn uart0_throughput(
+    uart: &Uart0,
+    baud_rates: &[u32],
+    data: &[u8],
+) -> Result<Vec<Throughput>, UartError> {
+    throughput(
+        baud_rates,
+        data,
+        |baud_rate| uart.switch_baud_rate(baud_rate),
+        |data| {
+            uart.write_data(data);
+            uart.flush();
+        },
+    )
+}
+
+/// Measure the throughput of the Uart1 sending the given data as a burst at each of the baud rates given. The Uart1
+/// switches back to it's configured baud rate afterwards. As the remote side does not follow the switches it receives
+/// garbage, so the results should be printed once the benchmark is done. Fails with
+/// ``Err(UartError::UnsupportedBaudRate)`` if a baud rate can not be configured with the clock rate of the Uart1.
+pub fn uart1_throughput(
+    uart: &Uart1,
+    baud_rates: &[u32],
+    data: &[u8],
+) -> Result<Vec<Throughput>, UartError> {
+    throughput(
+        baud_rates,
+        data,
+        |baud_rate| uart.switch_baud_rate(baud_rate),
+        |data| {
+            uart.send_data(data);
+            uart.flush();
+        },
+    )
+}
+
+// send the data at each baud rate and measure the time until it has been sent out, switching back to the configured
+// baud rate in the end. A switch passing ``None`` restores the configured baud rate
+fn throughput(
+    baud_rates: &[u32],
+    data: &[u8],
+    mut switch: impl FnMut(Option<u32>) -> Result<(), UartError>,
+    send: impl Fn(&[u8]),
+) -> Result<Vec<Throughput>, UartError> {
+    let mut results = Vec::with_capacity(baud_rates.len());
+    let mut result = Ok(());
+    for baud_rate in baud_rates {
+        result = switch(Some(*baud_rate));
+        if result.is_err() {
+            break;
+        }
+        let start = clock::now();
+        send(data);
+        let elapsed = clock::elapsed_since(start).max(1);
+        results.push(Throughput {
+            baud_rate: *baud_rate,
+            bytes_per_second: (data.len() as u64 * 1_000_000 / elapsed) as u32,
+        });
+    }
+    switch(None)?;
+    result.map(|_| results)
+}
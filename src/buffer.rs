@@ -0,0 +1,36 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Buffer Storage
+//!
+//! The memory used by the internal buffers of the Uart drivers can be provided by the caller to keep the memory budget
+//! explicit on constrained systems, or it can be allocated on the heap with a given size.
+//!
+
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+use alloc::{boxed::Box, vec};
+
+/// The memory used by an internal buffer of the Uart drivers
+pub enum BufferStorage {
+    /// Use the memory provided by the caller
+    Static(&'static mut [u8]),
+    /// Allocate the given number of bytes on the heap. As the buffers are used from within interrupt handlers as well
+    /// the memory is never freed once allocated.
+    Alloc(usize),
+}
+
+impl BufferStorage {
+    // get the memory of this storage, allocating it if required
+    pub(crate) fn into_slice(self) -> &'static mut [u8] {
+        match self {
+            BufferStorage::Static(buffer) => buffer,
+            BufferStorage::Alloc(size) => Box::leak(vec![0; size].into_boxed_slice()),
+        }
+    }
+}
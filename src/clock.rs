@@ -0,0 +1,179 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Clock
+//!
+//! All time based features of the Uarts, like the receive timeouts, the timestamps of the console prefix and the
+//! trace, the pacing of sends or the heartbeat, take the current time from a [Clock]. By default this is the system
+//! timer of the Raspberry Pi. Registering another clock makes this timing behavior portable, e.g. to a board with a
+//! different timer, and testable with the [MockClock] advanced by the test itself. Short delays, e.g. while switching
+//! the baud rate, still wait on the system timer. There is only one clock shared by all Uarts.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::clock::{self, MockClock};
+//! # fn doc() {
+//! clock::set_clock::<MockClock>();
+//! MockClock::set(1_000);
+//! MockClock::advance(500);
+//! assert_eq!(clock::now(), 1_500);
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::sealed::FnHook;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// A monotonic source of the current time
+pub trait Clock {
+    /// The current time in micro seconds, never going backwards
+    fn now() -> u64;
+}
+
+/// The system timer of the Raspberry Pi, the default clock
+pub struct SystemTimer;
+
+impl Clock for SystemTimer {
+    fn now() -> u64 {
+        ruspiro_timer::now()
+    }
+}
+
+/// A clock that only advances when told to, so time based behavior can be tested without waiting
+pub struct MockClock;
+
+// the current time of the mock clock in micro seconds
+static MOCK_TIME: AtomicU64 = AtomicU64::new(0);
+
+impl MockClock {
+    /// Set the current time in micro seconds. Setting an earlier time than the current one breaks the promise of a
+    /// monotonic clock, the Uarts treat the time passed since a moment in the future as 0.
+    pub fn set(now: u64) {
+        MOCK_TIME.store(now, Ordering::Relaxed);
+    }
+
+    /// Advance the current time by the given micro seconds
+    pub fn advance(duration: u64) {
+        MOCK_TIME.fetch_add(duration, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now() -> u64 {
+        MOCK_TIME.load(Ordering::Relaxed)
+    }
+}
+
+// the registered clock, the system timer if none is registered
+static NOW: FnHook<fn() -> u64> = FnHook::new();
+
+/// Take the time from the clock ``C`` from now on
+pub fn set_clock<C: Clock>() {
+    NOW.set(Some(C::now as fn() -> u64));
+}
+
+/// The current time in micro seconds of the clock in use
+pub fn now() -> u64 {
+    match NOW.get() {
+        Some(now) => now(),
+        None => SystemTimer::now(),
+    }
+}
+
+/// The micro seconds passed since the given time of the clock in use. A clock set back to an earlier time, like the
+/// [MockClock] might be, yields 0 instead of overflowing.
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::clock;
+/// # fn doc() {
+/// let start = clock::now();
+/// // do some work
+/// let spent = clock::elapsed_since(start);
+/// # }
+/// ```
+pub fn elapsed_since(start: u64) -> u64 {
+    now().saturating_sub(start)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::{set_clock, MockClock};
+    use crate::throttle::ConsoleThrottle;
+    use crate::{Timeout, TimeoutWait};
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    // the mock clock is shared by all tests, so they must not run concurrently
+    static CLOCK: AtomicBool = AtomicBool::new(false);
+
+    struct ClockLock;
+
+    fn lock_clock() -> ClockLock {
+        while CLOCK.swap(true, Ordering::Acquire) {
+            thread::yield_now();
+        }
+        set_clock::<MockClock>();
+        ClockLock
+    }
+
+    impl Drop for ClockLock {
+        fn drop(&mut self) {
+            CLOCK.store(false, Ordering::Release);
+        }
+    }
+
+    #[test]
+    fn timeout_wait_expires_once_the_timeout_has_passed() {
+        let _clock = lock_clock();
+        MockClock::set(1_000);
+        let mut wait = TimeoutWait::start(Timeout::Micros(100));
+        assert!(wait.wait());
+        MockClock::advance(100);
+        assert!(wait.wait());
+        MockClock::advance(1);
+        assert!(!wait.wait());
+    }
+
+    #[test]
+    fn timeout_wait_keeps_waiting_when_the_clock_is_set_back() {
+        let _clock = lock_clock();
+        MockClock::set(1_000);
+        let mut wait = TimeoutWait::start(Timeout::Micros(100));
+        MockClock::set(0);
+        assert!(wait.wait());
+        MockClock::set(1_101);
+        assert!(!wait.wait());
+    }
+
+    #[test]
+    fn throttle_grants_the_budget_again_in_the_next_interval() {
+        let _clock = lock_clock();
+        MockClock::set(1_000);
+        let mut throttle = ConsoleThrottle::new();
+        throttle.set(10_000, 20);
+        assert_eq!(throttle.remaining(1_000), 2_000);
+        throttle.charge(1_500);
+        assert_eq!(throttle.remaining(10_999), 500);
+        throttle.charge(1_000);
+        assert_eq!(throttle.remaining(10_999), 0);
+        assert_eq!(throttle.remaining(11_000), 2_000);
+    }
+
+    #[test]
+    fn throttle_starts_a_new_interval_when_the_clock_is_set_back() {
+        let _clock = lock_clock();
+        MockClock::set(1_000);
+        let mut throttle = ConsoleThrottle::new();
+        throttle.set(10_000, 20);
+        throttle.charge(2_000);
+        assert_eq!(throttle.remaining(5_000), 0);
+        assert_eq!(throttle.remaining(500), 2_000);
+    }
+}
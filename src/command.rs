@@ -0,0 +1,149 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Command Dispatcher
+//!
+//! Turns the receiving side of an Uart into a simple control channel. The received data is split into lines, the first
+//! word of each line is looked up in the registered commands and the handler of the matching command is called with
+//! the remaining arguments of the line. The built-in ``help`` command lists all registered commands with their
+//! description. Lines not matching any command are passed to a fallback handler, or reported as unknown command if
+//! there is none.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! fn reboot(_args: &str) {
+//!     // trigger the reboot
+//! }
+//!
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! uart.register_command("reboot", "restart the device", reboot);
+//! loop {
+//!     uart.process_commands();
+//! }
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+/// The maximum length of a command line. Longer lines are discarded.
+pub const MAX_LINE_LENGTH: usize = 128;
+
+/// The handler of a command, called with the arguments following the command name
+pub type CommandHandler = fn(&str);
+
+// a registered command
+struct Command {
+    name: &'static str,
+    help: &'static str,
+    handler: CommandHandler,
+}
+
+// splits the received data into lines and dispatches them to the registered commands
+pub(crate) struct CommandDispatcher {
+    commands: Vec<Command>,
+    fallback: Option<CommandHandler>,
+    line: [u8; MAX_LINE_LENGTH],
+    length: usize,
+    overflow: bool,
+}
+
+impl CommandDispatcher {
+    pub(crate) const fn new() -> Self {
+        CommandDispatcher {
+            commands: Vec::new(),
+            fallback: None,
+            line: [0; MAX_LINE_LENGTH],
+            length: 0,
+            overflow: false,
+        }
+    }
+
+    // register a command, replacing a command already registered with the same name
+    pub(crate) fn register(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        handler: CommandHandler,
+    ) {
+        self.commands.retain(|command| command.name != name);
+        self.commands.push(Command {
+            name,
+            help,
+            handler,
+        });
+    }
+
+    // set the handler called with the whole line if it does not match any command
+    pub(crate) fn set_fallback(&mut self, fallback: Option<CommandHandler>) {
+        self.fallback = fallback;
+    }
+
+    // add a received byte to the current line and dispatch the line once it is complete. The output of the built-in
+    // commands is passed to ``out``. Returns ``true`` if a command has been dispatched
+    pub(crate) fn feed(&mut self, byte: u8, out: impl Fn(&[u8])) -> bool {
+        match byte {
+            b'\r' | b'\n' => {
+                let overflow = self.overflow;
+                let length = self.length;
+                self.overflow = false;
+                self.length = 0;
+                if overflow {
+                    out(b"command line too long\r\n");
+                    return false;
+                }
+                match core::str::from_utf8(&self.line[..length]) {
+                    Ok(line) => self.dispatch(line.trim(), out),
+                    Err(_) => false,
+                }
+            }
+            _ => {
+                if self.length < MAX_LINE_LENGTH {
+                    self.line[self.length] = byte;
+                    self.length += 1;
+                } else {
+                    self.overflow = true;
+                }
+                false
+            }
+        }
+    }
+
+    // dispatch a complete line to the matching command
+    fn dispatch(&self, line: &str, out: impl Fn(&[u8])) -> bool {
+        if line.is_empty() {
+            return false;
+        }
+        let (name, args) = match line.find(char::is_whitespace) {
+            Some(idx) => (&line[..idx], line[idx..].trim_start()),
+            None => (line, ""),
+        };
+        if let Some(command) = self.commands.iter().find(|command| command.name == name) {
+            (command.handler)(args);
+        } else if name == "help" {
+            for command in &self.commands {
+                out(command.name.as_bytes());
+                out(b" - ");
+                out(command.help.as_bytes());
+                out(b"\r\n");
+            }
+        } else if let Some(fallback) = self.fallback {
+            fallback(line);
+        } else {
+            out(b"unknown command: ");
+            out(name.as_bytes());
+            out(b"\r\n");
+            return false;
+        }
+        true
+    }
+}
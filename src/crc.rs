@@ -0,0 +1,41 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # CRC
+//!
+//! The checksum protecting the frames sent by the higher level protocols of this crate.
+//!
+
+// update the CRC-16/CCITT with the given data. A new calculation starts with 0xFFFF
+pub(crate) fn crc16(mut crc: u16, data: &[u8]) -> u16 {
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+// update the CRC-8 (polynomial 0x07) with the given data. A new calculation starts with 0x00
+pub(crate) fn crc8(mut crc: u8, data: &[u8]) -> u8 {
+    for byte in data {
+        crc ^= *byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
@@ -0,0 +1,223 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Diagnostics
+//!
+//! Garbled output on the terminal is the most common problem when getting an Uart up and running. The ``diagnose``
+//! function of the Uarts checks the usual suspects by reading back the peripheral and GPIO registers and reports each
+//! problem found as a [Finding]. The ``Display`` implementation of a finding describes the problem together with a
+//! suggested fix.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # use ruspiro_uart::diagnose::Finding;
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! for finding in uart.diagnose() {
+//!     // report the finding through some other channel, e.g. the Uart0
+//! }
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::sealed::MmioBlock;
+use crate::UartError;
+use core::fmt;
+use ruspiro_register::define_mmio_register;
+
+// Peripheral MMIO base address - depends on the right feature
+#[cfg(feature = "ruspiro_pi3")]
+const PERIPHERAL_BASE: u32 = 0x3F00_0000;
+
+// GPIO MMIO base address
+const GPIO_BASE: u32 = PERIPHERAL_BASE + 0x0020_0000;
+
+/// The maximum deviation of the actual baud rate from the requested one in percent most receivers tolerate
+pub const MAX_BAUD_DEVIATION: u32 = 2;
+
+/// A problem found while diagnosing an Uart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Finding {
+    /// The Uart has not been initialized
+    NotInitialized,
+    /// The peripheral itself is disabled
+    PeripheralDisabled,
+    /// The transmitter is disabled
+    TransmitterDisabled,
+    /// The receiver is disabled
+    ReceiverDisabled,
+    /// A pin of the Uart is not switched to the alternative function routing it to the peripheral
+    PinFunction {
+        /// The GPIO pin number
+        pin: u32,
+        /// The alternative function expected
+        expected: u32,
+        /// The function select value read back from the GPIO, 0 = input, 1 = output, 2..7 alternative functions
+        actual: u32,
+    },
+    /// The data size is not 8 bits
+    DataSize(u32),
+    /// The FIFO's are disabled
+    FifoDisabled,
+    /// The baud rate resulting from the divisor and the given clock rate deviates from the requested one by more
+    /// than [MAX_BAUD_DEVIATION] percent
+    BaudRateMismatch {
+        /// The requested baud rate
+        requested: u32,
+        /// The baud rate actually generated
+        actual: u32,
+    },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::NotInitialized => write!(f, "Uart not initialized - call initialize first"),
+            Finding::PeripheralDisabled => write!(
+                f,
+                "peripheral disabled - check that enable_uart=1 is set in the config.txt"
+            ),
+            Finding::TransmitterDisabled => write!(
+                f,
+                "transmitter disabled - initialize the Uart for sending"
+            ),
+            Finding::ReceiverDisabled => {
+                write!(f, "receiver disabled - initialize the Uart for receiving")
+            }
+            Finding::PinFunction {
+                pin,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "GPIO{} uses function {} instead of alt{} - the pin might be claimed by another driver or a \
+                 device tree overlay",
+                pin,
+                function_name(*actual),
+                expected
+            ),
+            Finding::DataSize(bits) => write!(
+                f,
+                "{} data bits configured - the terminal most likely expects 8 data bits",
+                bits
+            ),
+            Finding::FifoDisabled => write!(
+                f,
+                "FIFO disabled - data is lost if it is not read immediately"
+            ),
+            Finding::BaudRateMismatch { requested, actual } => write!(
+                f,
+                "actual baud rate {} deviates from the requested {} - check the clock rate passed to initialize, \
+                 the Uart1 depends on the core clock (fix it with core_freq=250), the Uart0 on init_uart_clock",
+                actual, requested
+            ),
+        }
+    }
+}
+
+// the name of the function selected for a GPIO pin
+pub(crate) fn function_name(function: u32) -> &'static str {
+    match function {
+        0b000 => "input",
+        0b001 => "output",
+        0b100 => "alt0",
+        0b101 => "alt1",
+        0b110 => "alt2",
+        0b111 => "alt3",
+        0b011 => "alt4",
+        _ => "alt5",
+    }
+}
+
+// the function select value of the given alternative function
+pub(crate) fn alt_function(alt: u32) -> u32 {
+    match alt {
+        0..=3 => 0b100 + alt,
+        4 => 0b011,
+        _ => 0b010,
+    }
+}
+
+// the function select value of the pin
+fn pin_function(pin: u32) -> u32 {
+    let fsel = match pin / 10 {
+        0 => GPFSEL0::Register.get(),
+        1 => GPFSEL1::Register.get(),
+        2 => GPFSEL2::Register.get(),
+        3 => GPFSEL3::Register.get(),
+        4 => GPFSEL4::Register.get(),
+        _ => GPFSEL5::Register.get(),
+    };
+    (fsel >> ((pin % 10) * 3)) & 0b111
+}
+
+// the function select value of the pin in the given GPIO block, used for the GPIO blocks at other addresses than the
+// one of the Raspberry Pi 3
+pub(crate) fn function_at(gpio: &MmioBlock, pin: u32) -> u32 {
+    (gpio.read(4 * (pin / 10) as usize) >> ((pin % 10) * 3)) & 0b111
+}
+
+// switch the pin in the given GPIO block to the function select value
+pub(crate) fn select_function(gpio: &MmioBlock, pin: u32, function: u32) {
+    let offset = 4 * (pin / 10) as usize;
+    let shift = (pin % 10) * 3;
+    let fsel = gpio.read(offset) & !(0b111 << shift);
+    gpio.write(offset, fsel | (function << shift));
+}
+
+// check that the pin uses the expected alternative function
+pub(crate) fn check_pin(pin: u32, alt: u32) -> Option<Finding> {
+    let actual = pin_function(pin);
+    if actual == alt_function(alt) {
+        None
+    } else {
+        Some(Finding::PinFunction {
+            pin,
+            expected: alt,
+            actual,
+        })
+    }
+}
+
+// check that the pin can be switched to the alternative function without clobbering the mux setting of another
+// subsystem. A pin used as input or output is free, as those are claimed through the GPIO crate, a pin switched to
+// another alternative function is not
+pub(crate) fn check_pin_free(pin: u32, alt: u32) -> Result<(), UartError> {
+    check_free(pin, pin_function(pin), alt)
+}
+
+// check that the pin with the current function select value can be switched to the alternative function
+pub(crate) fn check_free(pin: u32, current_fn: u32, alt: u32) -> Result<(), UartError> {
+    match current_fn {
+        0b000 | 0b001 => Ok(()),
+        current_fn if current_fn == alt_function(alt) => Ok(()),
+        current_fn => Err(UartError::PinBusy { pin, current_fn }),
+    }
+}
+
+// check that the actual baud rate is close enough to the requested one
+pub(crate) fn check_baud_rate(requested: u32, actual: u32) -> Option<Finding> {
+    let deviation = (requested as u64).max(actual as u64) - (requested as u64).min(actual as u64);
+    if deviation * 100 > requested as u64 * MAX_BAUD_DEVIATION as u64 {
+        Some(Finding::BaudRateMismatch { requested, actual })
+    } else {
+        None
+    }
+}
+
+// the GPIO function select registers, each covering 10 pins
+define_mmio_register! [
+    GPFSEL0<ReadOnly<u32>@(GPIO_BASE)>,
+    GPFSEL1<ReadOnly<u32>@(GPIO_BASE + 0x04)>,
+    GPFSEL2<ReadOnly<u32>@(GPIO_BASE + 0x08)>,
+    GPFSEL3<ReadOnly<u32>@(GPIO_BASE + 0x0C)>,
+    GPFSEL4<ReadOnly<u32>@(GPIO_BASE + 0x10)>,
+    GPFSEL5<ReadOnly<u32>@(GPIO_BASE + 0x14)>
+];
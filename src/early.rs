@@ -0,0 +1,36 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Early Boot Log Buffer
+//!
+//! Console output written to an Uart before it has been initialized would be lost. With the ``early_log`` feature
+//! active this output is captured in the static [EARLY_LOG] buffer instead and automatically sent out as soon as an
+//! Uart has been initialized successfully. Boot stages that run before there is even an Uart instance may write into
+//! this buffer directly.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::early::EARLY_LOG;
+//! # use ruspiro_uart::Uart1;
+//! # fn doc() {
+//! EARLY_LOG.write(b"first boot stage reached\r\n");
+//!
+//! let mut uart = Uart1::new();
+//! // sends "first boot stage reached" once the initialization succeeded
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+pub use crate::sealed::EarlyLogBuffer;
+
+/// The size of the early log buffer in bytes
+pub const EARLY_LOG_SIZE: usize = 1024;
+
+/// The buffer capturing all output written to an Uart before it has been initialized
+pub static EARLY_LOG: EarlyLogBuffer = EarlyLogBuffer::new();
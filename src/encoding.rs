@@ -0,0 +1,92 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Character Encoding
+//!
+//! Strings are sent as UTF-8 encoded bytes by default. Legacy terminals and serial displays often can not render
+//! UTF-8, so the strings might be converted into a single byte encoding while sending. Characters that are not
+//! available in the selected encoding are replaced with a fallback character.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # use ruspiro_uart::encoding::Encoding;
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! uart.set_encoding(Encoding::Cp437, b'?');
+//! uart.send_string("Temperature: 21°C\r\n");
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+/// The character encoding used to send strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// send the strings as they are
+    Utf8,
+    /// ISO 8859-1, covering the first 256 unicode code points
+    Latin1,
+    /// The character set of the original IBM PC, still used by many serial displays
+    Cp437,
+}
+
+impl Encoding {
+    /// Convert a single character into this encoding. Characters not available in this encoding are replaced with
+    /// the ``fallback``. As UTF-8 is not a single byte encoding any non ASCII character is replaced with the fallback
+    /// in this case.
+    pub fn encode(self, c: char, fallback: u8) -> u8 {
+        let code = c as u32;
+        if code < 0x80 {
+            return code as u8;
+        }
+        match self {
+            Encoding::Utf8 => fallback,
+            Encoding::Latin1 => {
+                if code <= 0xFF {
+                    code as u8
+                } else {
+                    fallback
+                }
+            }
+            Encoding::Cp437 => CP437_UPPER
+                .iter()
+                .position(|&upper| upper == c)
+                .map_or(fallback, |idx| 0x80 + idx as u8),
+        }
+    }
+}
+
+// convert the string into the given encoding and pass the converted data in chunks to the sender
+pub(crate) fn encode_str(s: &str, encoding: Encoding, fallback: u8, mut send: impl FnMut(&[u8])) {
+    let mut chunk = [0u8; 32];
+    let mut len = 0;
+    for c in s.chars() {
+        chunk[len] = encoding.encode(c, fallback);
+        len += 1;
+        if len == chunk.len() {
+            send(&chunk);
+            len = 0;
+        }
+    }
+    if len != 0 {
+        send(&chunk[..len]);
+    }
+}
+
+// the characters of the code page 437 from 0x80 to 0xFF
+const CP437_UPPER: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
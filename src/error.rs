@@ -0,0 +1,136 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Uart Errors
+//!
+
+#![forbid(unsafe_code)]
+
+use core::fmt;
+
+/// The errors that may occur while using the Uart peripherals. The errors are plain ``Copy`` values returned by value,
+/// so no error path of the Uarts allocates memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartError {
+    /// The Uart has not been initialized yet
+    NotInitialized,
+    /// The requested operation is only possible as long as the Uart is not initialized
+    AlreadyInitialized,
+    /// The buffer passed to a receive function does not provide space for at least one byte
+    EmptyBuffer,
+    /// No data has been received within the requested time
+    ReceiveDataTimeOut,
+    /// The peripheral did not respond in time while being initialized. This typically happens if the firmware has not
+    /// enabled the Uart or it's clock (e.g. ``enable_uart=0`` in the ``config.txt``)
+    InitializationTimeout(InitStage),
+    /// The GPIO pins could not be configured for the Uart
+    GpioError(&'static str),
+    /// The Uart has not been configured by the firmware or a boot loader and can therefore not be taken over
+    NotConfigured,
+    /// The message exceeds the maximum payload size of a single frame
+    PayloadTooLarge,
+    /// The message has not been acknowledged by the other side
+    NoAcknowledge,
+    /// The received message does not fit into the buffer passed to the receive function
+    BufferTooSmall,
+    /// The parity of the received data does not match the configured parity
+    ParityError,
+    /// The received data did not have a valid stop bit
+    FramingError,
+    /// The receive line has been held low for longer than a full character time
+    BreakReceived,
+    /// The message could not be serialized
+    SerializeError,
+    /// The received message has been corrupted or could not be deserialized
+    InvalidMessage,
+    /// The requested baud rate is not supported
+    UnsupportedBaudRate,
+    /// The period of the poll service is too long to drain the receive FIFO before it overflows
+    PollPeriodTooLong,
+    /// The requested line settings are not supported by the Uart
+    UnsupportedSettings,
+    /// The time at which the transmission should have started has already passed
+    SlotMissed,
+    /// The core id does not name one of the cores of the Raspberry Pi
+    InvalidCore,
+    /// The Uart is currently re-configured or suspended and can not be used until this has finished
+    Busy,
+    /// The DMA channel number is not one of the channels 0 to 14
+    InvalidDmaChannel,
+    /// The DMA controller reported an error during the transfer
+    DmaError,
+    /// The receive buffer is already filled by another producer, the receive interrupt or the poll service
+    RxBufferInUse,
+    /// The receive buffer is not in use, so there is no buffered data to take
+    RxNotBuffered,
+    /// The initialization of a lazy initialized Uart panicked, so the Uart can not be handed out
+    Poisoned,
+    /// The transmitter is used by another transfer, e.g. the one interrupted by the call, try again later
+    WouldBlock,
+    /// A GPIO pin of the Uart is switched to an alternative function used by another peripheral, e.g. PCM audio
+    PinBusy {
+        /// The GPIO pin number
+        pin: u32,
+        /// The function select value read back from the GPIO, see [Finding::PinFunction](crate::diagnose::Finding)
+        current_fn: u32,
+    },
+}
+
+/// The stages of the Uart initialization that wait for the peripheral to respond
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitStage {
+    /// Enabling the miniUART in the AUX block
+    AuxEnable,
+    /// Waiting for the transmitter to finish an ongoing transfer before re-configuring the peripheral
+    TransmitterIdle,
+    /// Waiting for the FIFO's to be cleared
+    FifoClear,
+}
+
+impl fmt::Display for UartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UartError::NotInitialized => write!(f, "Uart not initialized"),
+            UartError::AlreadyInitialized => write!(f, "Uart already initialized"),
+            UartError::EmptyBuffer => write!(f, "buffer size expected to be at least 1"),
+            UartError::ReceiveDataTimeOut => write!(f, "Timeout"),
+            UartError::InitializationTimeout(stage) => {
+                write!(f, "Uart initialization timed out at stage {:?}", stage)
+            }
+            UartError::GpioError(msg) => write!(f, "GPIO error: {}", msg),
+            UartError::NotConfigured => write!(f, "Uart not configured by the firmware"),
+            UartError::PayloadTooLarge => write!(f, "payload exceeds the maximum frame size"),
+            UartError::NoAcknowledge => write!(f, "message not acknowledged"),
+            UartError::BufferTooSmall => write!(f, "buffer too small for the received message"),
+            UartError::ParityError => write!(f, "parity error"),
+            UartError::FramingError => write!(f, "framing error"),
+            UartError::BreakReceived => write!(f, "break received"),
+            UartError::SerializeError => write!(f, "message could not be serialized"),
+            UartError::InvalidMessage => write!(f, "invalid message received"),
+            UartError::UnsupportedBaudRate => write!(f, "baud rate not supported"),
+            UartError::PollPeriodTooLong => {
+                write!(f, "poll period exceeds the receive FIFO capacity")
+            }
+            UartError::UnsupportedSettings => write!(f, "line settings not supported"),
+            UartError::SlotMissed => write!(f, "transmit time slot missed"),
+            UartError::InvalidCore => write!(f, "invalid core id"),
+            UartError::Busy => write!(f, "Uart busy with re-configuration"),
+            UartError::InvalidDmaChannel => write!(f, "invalid DMA channel"),
+            UartError::DmaError => write!(f, "DMA transfer failed"),
+            UartError::RxBufferInUse => write!(f, "receive buffer already in use"),
+            UartError::RxNotBuffered => write!(f, "receive buffer not in use"),
+            UartError::Poisoned => write!(f, "Uart initialization panicked"),
+            UartError::WouldBlock => write!(f, "Uart transmitter in use by another transfer"),
+            UartError::PinBusy { pin, current_fn } => write!(
+                f,
+                "GPIO{} already in use as {}",
+                pin,
+                crate::diagnose::function_name(*current_fn)
+            ),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Console Escaping
+//!
+//! Replaces the non-printable bytes of the console output, so binary data printed by accident does not put the
+//! terminal into an unexpected state.
+//!
+
+use crate::Escape;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+// whether the byte is sent as it is, the line endings and tabs are kept to not break the layout of the output. Bytes
+// above 0x7F belong to an UTF-8 sequence or the configured encoding and pass as well
+fn is_passed(byte: u8) -> bool {
+    matches!(byte, b'\t' | b'\n' | b'\r' | 0x20..=0x7E | 0x80..=0xFF)
+}
+
+// the substitution stored in the atomic of an Uart, which can be changed while the Uart is shared
+pub(crate) fn load(escape: &AtomicU8) -> Escape {
+    match escape.load(Ordering::Relaxed) {
+        e if e == Escape::Caret as u8 => Escape::Caret,
+        e if e == Escape::Hex as u8 => Escape::Hex,
+        _ => Escape::None,
+    }
+}
+
+// split the console output at each non-printable byte and send the substitution in it's place
+pub(crate) fn write_escaped(escape: Escape, data: &[u8], send: impl Fn(&[u8])) {
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (passed, remaining) = match rest.iter().position(|byte| !is_passed(*byte)) {
+            Some(idx) => rest.split_at(idx),
+            None => (rest, &[][..]),
+        };
+        if !passed.is_empty() {
+            send(passed);
+        }
+        match remaining.split_first() {
+            Some((byte, remaining)) => {
+                match escape {
+                    Escape::None => send(&[*byte]),
+                    // ^@ to ^_ for the control characters and ^? for DEL
+                    Escape::Caret => send(&[b'^', *byte ^ 0x40]),
+                    Escape::Hex => {
+                        send(&[b'\\', b'x', hex_digit(*byte >> 4), hex_digit(*byte & 0xF)])
+                    }
+                }
+                rest = remaining;
+            }
+            None => break,
+        }
+    }
+}
+
+// the upper case hex digit of the nibble
+fn hex_digit(nibble: u8) -> u8 {
+    match nibble {
+        0..=9 => b'0' + nibble,
+        _ => b'A' + nibble - 10,
+    }
+}
@@ -0,0 +1,162 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # C Interface
+//!
+//! Bare metal projects mixing C and Rust, e.g. with C boot code or drivers, can log through the same Uart1 driver
+//! instead of driving the hardware twice. The functions declared in ``include/ruspiro_uart.h`` use a single Uart1
+//! instance, which is shared with the Rust side through [uart1]. The Rust side must therefore not initialize an Uart1
+//! of its own, e.g. one attached to the ``Console``: once it did, [ruspiro_uart1_init] refuses to take the Uart1 over
+//! with ``RUSPIRO_UART_ERR_IN_USE``. The functions return one of the ``RUSPIRO_UART_*`` codes, negative values are
+//! errors.
+//!
+//! The C interface is ``unsafe`` by intention, as the C side passes raw pointers.
+//!
+//! # Example
+//! ```c
+//! #include "ruspiro_uart.h"
+//!
+//! void boot(void) {
+//!     if (ruspiro_uart1_init(250000000, 115200) == RUSPIRO_UART_OK) {
+//!         ruspiro_uart1_send((const uint8_t *)"hello from C\r\n", 14);
+//!     }
+//! }
+//! ```
+
+use crate::once::OnceUart1;
+use crate::sealed::FnHook;
+use crate::uart1::interface;
+use crate::{Timeout, Uart1, UartError, UartState};
+
+/// The call succeeded
+pub const RUSPIRO_UART_OK: i32 = 0;
+/// The Uart1 has not been initialized
+pub const RUSPIRO_UART_ERR_NOT_INITIALIZED: i32 = -1;
+/// The peripheral did not respond while being initialized
+pub const RUSPIRO_UART_ERR_INIT_TIMEOUT: i32 = -2;
+/// The GPIO pins could not be configured for the Uart1
+pub const RUSPIRO_UART_ERR_GPIO: i32 = -3;
+/// The baud rate is not supported
+pub const RUSPIRO_UART_ERR_BAUD_RATE: i32 = -4;
+/// A pointer passed is null
+pub const RUSPIRO_UART_ERR_INVALID_ARGUMENT: i32 = -5;
+/// Data has been received with a wrong parity
+pub const RUSPIRO_UART_ERR_PARITY: i32 = -6;
+/// The Uart1 is driven by another instance on the Rust side, e.g. the one attached to the console
+pub const RUSPIRO_UART_ERR_IN_USE: i32 = -7;
+/// Any other error
+pub const RUSPIRO_UART_ERR_OTHER: i32 = -99;
+
+/// The function called with each byte received from [ruspiro_uart1_handle_interrupt]
+pub type RxCallback = extern "C" fn(u8);
+
+// the Uart1 driven through the C interface and the callback of the received data
+static UART: OnceUart1 = OnceUart1::new();
+static RX_CALLBACK: FnHook<RxCallback> = FnHook::new();
+
+/// The Uart1 driven through the C interface, if it has been initialized
+pub fn uart1() -> Option<&'static Uart1> {
+    UART.get()
+}
+
+// the code returned for an error
+fn error_code(error: UartError) -> i32 {
+    match error {
+        UartError::NotInitialized => RUSPIRO_UART_ERR_NOT_INITIALIZED,
+        UartError::InitializationTimeout(_) => RUSPIRO_UART_ERR_INIT_TIMEOUT,
+        UartError::GpioError(_) | UartError::PinBusy { .. } => RUSPIRO_UART_ERR_GPIO,
+        UartError::UnsupportedBaudRate => RUSPIRO_UART_ERR_BAUD_RATE,
+        UartError::EmptyBuffer => RUSPIRO_UART_ERR_INVALID_ARGUMENT,
+        UartError::ParityError => RUSPIRO_UART_ERR_PARITY,
+        _ => RUSPIRO_UART_ERR_OTHER,
+    }
+}
+
+/// Initialize the Uart1 with the core clock rate and the baud rate. Later calls succeed without changing the
+/// configuration. Fails with ``RUSPIRO_UART_ERR_IN_USE`` if the Uart1 has been initialized by the Rust side already.
+#[no_mangle]
+pub extern "C" fn ruspiro_uart1_init(clock_rate: u32, baud_rate: u32) -> i32 {
+    if UART.get().is_none() && interface::uart1_state() != UartState::Idle {
+        return RUSPIRO_UART_ERR_IN_USE;
+    }
+    match UART.get_or_init(clock_rate, baud_rate) {
+        Ok(_) => RUSPIRO_UART_OK,
+        Err(error) => error_code(error),
+    }
+}
+
+/// Send ``len`` bytes starting at ``data``.
+///
+/// # Safety
+/// ``data`` need to point to at least ``len`` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ruspiro_uart1_send(data: *const u8, len: usize) -> i32 {
+    if data.is_null() {
+        return RUSPIRO_UART_ERR_INVALID_ARGUMENT;
+    }
+    match UART.get() {
+        Some(uart) => {
+            // the caller guarantees the pointer to be valid for len bytes
+            uart.send_data(unsafe { core::slice::from_raw_parts(data, len) });
+            RUSPIRO_UART_OK
+        }
+        None => RUSPIRO_UART_ERR_NOT_INITIALIZED,
+    }
+}
+
+/// Receive up to ``len`` bytes into ``buffer`` without waiting for data to arrive. Returns the number of bytes
+/// received, which is 0 if no data is available right now or ``len`` is 0, or an error code. Up to version 0.3 this waited about
+/// 1,000,000 CPU cycles for each byte to arrive, callers waiting for data need to poll this now.
+///
+/// # Safety
+/// ``buffer`` need to point to at least ``len`` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ruspiro_uart1_recv(buffer: *mut u8, len: usize) -> i32 {
+    if buffer.is_null() {
+        return RUSPIRO_UART_ERR_INVALID_ARGUMENT;
+    }
+    let uart = match UART.get() {
+        Some(uart) => uart,
+        None => return RUSPIRO_UART_ERR_NOT_INITIALIZED,
+    };
+    if len == 0 {
+        return 0;
+    }
+    // the caller guarantees the pointer to be valid for len bytes
+    let buffer = unsafe { core::slice::from_raw_parts_mut(buffer, len.min(i32::MAX as usize)) };
+    match uart.try_receive_data(buffer, Timeout::Cycles(0)) {
+        Ok(count) => count as i32,
+        Err(UartError::ReceiveDataTimeOut) => 0,
+        Err(error) => error_code(error),
+    }
+}
+
+/// Register the function called with each byte received, or remove it if ``NULL`` is given.
+#[no_mangle]
+pub extern "C" fn ruspiro_uart1_set_rx_callback(callback: Option<RxCallback>) {
+    RX_CALLBACK.set(callback);
+}
+
+/// Handle the Aux interrupt raised by the Uart1. This need to be called from the interrupt handler of the C side. Each
+/// byte received is passed to the callback, if one is registered, before the remaining interrupts are handled like
+/// [Uart1::handle_interrupt] does. Data sent from within the callback is queued like [Uart1::send_data] does from
+/// within the interrupt handler. Returns whether the Uart1 raised the interrupt.
+#[no_mangle]
+pub extern "C" fn ruspiro_uart1_handle_interrupt() -> bool {
+    let _irq = interface::uart1_enter_irq();
+    // draining the receive FIFO removes the cause of the receive interrupt, so the pending state is taken before
+    let pending = interface::uart1_irq_snapshot();
+    if let (Some(_), Some(callback)) = (UART.get(), RX_CALLBACK.get()) {
+        // bytes received with a wrong parity are dropped
+        while let Some(received) = interface::uart1_try_receive() {
+            if let Ok(byte) = received {
+                callback(byte);
+            }
+        }
+    }
+    Uart1::handle_pending_interrupt(pending)
+}
@@ -0,0 +1,173 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # FIQ Receive Path
+//!
+//! Protocols like bus arbitration need to react to an incoming byte within a micro second, which the regular interrupt
+//! handling can not guarantee. The interrupt of a single Uart can be routed as the fast interrupt (FIQ) of a core
+//! instead, which preempts all regular interrupts. The interrupt crate does not dispatch FIQ's, so [handle_fiq] need
+//! to be called from the FIQ vector of the core the FIQ is routed to.
+//!
+//! The FIQ handling reads each received byte straight from the receive FIFO and passes it together with it's error
+//! state to the registered handler. The parity is checked and the glitch filter of the Uart0 is applied like for the
+//! regular receive functions. If no handler is registered the byte is stored in a fixed buffer of [FIQ_BUFFER_SIZE]
+//! bytes to be picked up with [read]. The handler runs in the FIQ context, so it must not allocate, block or take any
+//! lock.
+//!
+//! The Raspberry Pi supports only one FIQ source at a time, the routing of an Uart replaces any other one.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::{RxFlag, Uart0};
+//! fn arbitrate(byte: u8, flag: RxFlag) {
+//!     if flag == RxFlag::Ok {
+//!         // answer the bus arbitration right away
+//!     }
+//! }
+//!
+//! # fn doc() {
+//! let mut uart = Uart0::new();
+//! let _ = uart.initialize(48_000_000, 1_000_000);
+//! uart.route_rx_to_fiq(0, Some(arbitrate)).unwrap();
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::ringbuffer::SpscRing;
+use crate::sealed::FnHook;
+use crate::Peripheral;
+use crate::{uart0, uart1, RxFlag};
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use ruspiro_register::{define_mmio_register, RegisterFieldValue};
+
+/// The number of bytes received through the FIQ that are stored without a handler
+pub const FIQ_BUFFER_SIZE: usize = 64;
+
+// Peripheral MMIO base address - depends on the right feature
+#[cfg(feature = "ruspiro_pi3")]
+const PERIPHERAL_BASE: u32 = 0x3F00_0000;
+
+// ARM local peripherals MMIO base address
+#[cfg(feature = "ruspiro_pi3")]
+const ARM_LOCAL_BASE: u32 = 0x4000_0000;
+
+// the GPU interrupt numbers of the Uarts
+const AUX_IRQ: u32 = 29;
+const UART0_IRQ: u32 = 57;
+
+// the Uart routed to the FIQ, 0 if none
+const SOURCE_NONE: u8 = 0;
+const SOURCE_UART0: u8 = 1;
+const SOURCE_UART1: u8 = 2;
+static SOURCE: AtomicU8 = AtomicU8::new(SOURCE_NONE);
+
+// the handler of each byte received, the bytes stored with their error state without a handler and the number of
+// bytes dropped as the buffer was full
+static HANDLER: FnHook<fn(u8, RxFlag)> = FnHook::new();
+static RECEIVED: SpscRing<(u8, RxFlag), FIQ_BUFFER_SIZE> = SpscRing::new();
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+// reads the next byte and it's error state from the receive FIFO of an Uart
+type ReadFifo = fn() -> Option<(u8, RxFlag)>;
+
+// route the interrupt of the Uart as FIQ to the given core
+pub(crate) fn route(peripheral: Peripheral, core: usize, handler: Option<fn(u8, RxFlag)>) {
+    HANDLER.set(handler);
+    let (source, irq) = match peripheral {
+        Peripheral::Uart0 => (SOURCE_UART0, UART0_IRQ),
+        Peripheral::Uart1 => (SOURCE_UART1, AUX_IRQ),
+    };
+    SOURCE.store(source, Ordering::Release);
+    GPU_INT_ROUTING::Register.write(GPU_INT_ROUTING::FIQ_CORE, core as u32);
+    FIQ_CONTROL::Register.write_value(
+        RegisterFieldValue::<u32>::new(FIQ_CONTROL::SOURCE, irq)
+            | RegisterFieldValue::<u32>::new(FIQ_CONTROL::ENABLE, 0x1),
+    );
+}
+
+// stop routing the interrupt of the Uart as FIQ, if it is routed currently
+pub(crate) fn release(peripheral: Peripheral) {
+    let source = match peripheral {
+        Peripheral::Uart0 => SOURCE_UART0,
+        Peripheral::Uart1 => SOURCE_UART1,
+    };
+    if SOURCE
+        .compare_exchange(source, SOURCE_NONE, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok()
+    {
+        FIQ_CONTROL::Register.write(FIQ_CONTROL::ENABLE, 0);
+        HANDLER.set(None);
+    }
+}
+
+/// Handle the FIQ of the routed Uart. This need to be called from the FIQ vector. Each byte in the receive FIFO is
+/// passed to the handler or stored for [read] together with it's error state, which clears the interrupt. Returns the
+/// number of bytes received.
+pub fn handle_fiq() -> usize {
+    // the handler must not wait for the code the FIQ has interrupted when sending on the Uart
+    let (read_fifo, _irq): (ReadFifo, _) = match SOURCE.load(Ordering::Acquire) {
+        SOURCE_UART0 => (
+            uart0::interface::read_fifo_filtered,
+            uart0::interface::enter_irq(),
+        ),
+        SOURCE_UART1 => (
+            uart1::interface::uart1_read_fifo_tagged,
+            uart1::interface::uart1_enter_irq(),
+        ),
+        _ => return 0,
+    };
+    let handler = HANDLER.get();
+    let mut count = 0;
+    while let Some((data, flag)) = read_fifo() {
+        match handler {
+            Some(handler) => handler(data, flag),
+            None => {
+                if !RECEIVED.push((data, flag)) {
+                    DROPPED.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Take the bytes received through the FIQ while no handler has been registered, each with it's error state. Returns
+/// the number of bytes copied into the buffer.
+pub fn read(buffer: &mut [(u8, RxFlag)]) -> usize {
+    let mut count = 0;
+    for slot in buffer.iter_mut() {
+        match RECEIVED.pop() {
+            Some(data) => *slot = data,
+            None => break,
+        }
+        count += 1;
+    }
+    count
+}
+
+/// The number of bytes dropped so far as the buffer of the bytes received through the FIQ was full
+pub fn dropped() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+// the FIQ source selection of the interrupt controller
+define_mmio_register! [
+    FIQ_CONTROL<ReadWrite<u32>@(PERIPHERAL_BASE + 0xB20C)> {
+        SOURCE OFFSET(0) BITS(7),
+        ENABLE OFFSET(7) BITS(1)
+    }
+];
+
+// the routing of the GPU interrupts in the ARM local peripherals
+define_mmio_register! [
+    GPU_INT_ROUTING<ReadWrite<u32>@(ARM_LOCAL_BASE + 0x0C)> {
+        IRQ_CORE OFFSET(0) BITS(2),
+        FIQ_CORE OFFSET(2) BITS(2)
+    }
+];
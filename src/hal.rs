@@ -0,0 +1,144 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Common Uart Interface
+//!
+//! The Uart0 and the Uart1 differ in their feature set, but the basic operation is the same. The [HalUart] trait
+//! covers this common part, so generic code like a console, a log sink or a boot loader can be written once for
+//! either Uart, and the Uart in use can be chosen at runtime through a ``&mut dyn HalUart``. Everything beyond the
+//! common part stays available through the methods of [Uart0](crate::Uart0) and [Uart1](crate::Uart1).
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::*;
+//! # use ruspiro_uart::hal::HalUart;
+//! fn greet(uart: &mut dyn HalUart) {
+//!     if uart.init(250_000_000, 115_200).is_ok() {
+//!         uart.write(b"Hello Uart...\r\n");
+//!         uart.flush();
+//!     }
+//! }
+//!
+//! # fn doc(use_uart0: bool) {
+//! let mut uart0 = Uart0::new();
+//! let mut uart1 = Uart1::new();
+//! let uart: &mut dyn HalUart = if use_uart0 { &mut uart0 } else { &mut uart1 };
+//! greet(uart);
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::uart0::IrqFlags;
+use crate::{InterruptType, Timeout, Uart0, Uart1, UartError, UartEvent};
+
+/// The operations common to both Uarts
+pub trait HalUart {
+    /// Initialize the Uart with the clock rate of it's peripheral clock and the baud rate, see
+    /// [Uart0::initialize] and [Uart1::initialize].
+    fn init(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError>;
+
+    /// Send the data, blocking until all of it has been handed to the transmitter
+    fn write(&self, data: &[u8]);
+
+    /// Receive data into the buffer, waiting at most the timeout for the buffer to be filled. Returns the number of
+    /// bytes received, or fails with ``Err(UartError::ReceiveDataTimeOut)`` if no data has been received at all.
+    fn read(&self, buffer: &mut [u8], timeout: Timeout) -> Result<usize, UartError>;
+
+    /// Wait until all data sent has left the transmitter
+    fn flush(&self);
+
+    /// Change the baud rate once all data sent has left the transmitter, see [Uart0::set_baud_rate] and
+    /// [Uart1::set_baud_rate]
+    fn set_baud(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError>;
+
+    /// Enable the interrupts of the given type
+    fn enable_interrupts(&self, i_type: InterruptType);
+
+    /// Disable the interrupts of the given type
+    fn disable_interrupts(&self, i_type: InterruptType);
+
+    /// Take the next event raised by the interrupt handling of the Uart, if any
+    fn next_event(&self) -> Option<UartEvent>;
+}
+
+// the Uart0 interrupts matching the interrupt type, the receive timeout is needed to see data below the FIFO level
+fn uart0_irq_flags(i_type: InterruptType) -> IrqFlags {
+    match i_type {
+        InterruptType::Receive => IrqFlags::RX | IrqFlags::RX_TIMEOUT,
+        InterruptType::Transmit => IrqFlags::TX,
+        InterruptType::RecieveTransmit => IrqFlags::RX | IrqFlags::RX_TIMEOUT | IrqFlags::TX,
+    }
+}
+
+impl HalUart for Uart0 {
+    fn init(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.initialize(clock_rate, baud_rate)
+    }
+
+    fn write(&self, data: &[u8]) {
+        self.write_data(data);
+    }
+
+    fn read(&self, buffer: &mut [u8], timeout: Timeout) -> Result<usize, UartError> {
+        self.try_receive_data(buffer, timeout)
+    }
+
+    fn flush(&self) {
+        Uart0::flush(self);
+    }
+
+    fn set_baud(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.set_baud_rate(clock_rate, baud_rate)
+    }
+
+    fn enable_interrupts(&self, i_type: InterruptType) {
+        Uart0::enable_interrupts(self, uart0_irq_flags(i_type));
+    }
+
+    fn disable_interrupts(&self, i_type: InterruptType) {
+        Uart0::disable_interrupts(self, uart0_irq_flags(i_type));
+    }
+
+    fn next_event(&self) -> Option<UartEvent> {
+        self.events().next()
+    }
+}
+
+impl HalUart for Uart1 {
+    fn init(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.initialize(clock_rate, baud_rate)
+    }
+
+    fn write(&self, data: &[u8]) {
+        self.send_data(data);
+    }
+
+    fn read(&self, buffer: &mut [u8], timeout: Timeout) -> Result<usize, UartError> {
+        self.try_receive_data(buffer, timeout)
+    }
+
+    fn flush(&self) {
+        Uart1::flush(self);
+    }
+
+    fn set_baud(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.set_baud_rate(clock_rate, baud_rate)
+    }
+
+    fn enable_interrupts(&self, i_type: InterruptType) {
+        Uart1::enable_interrupts(self, i_type);
+    }
+
+    fn disable_interrupts(&self, i_type: InterruptType) {
+        Uart1::disable_interrupts(self, i_type);
+    }
+
+    fn next_event(&self) -> Option<UartEvent> {
+        self.events().next()
+    }
+}
@@ -0,0 +1,143 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Heartbeat
+//!
+//! Field devices often only have the Uart left to tell whether they are still alive. With a heartbeat enabled a small
+//! status frame is sent periodically from a timer interrupt handler, so it keeps coming even if the main loop is
+//! wedged. The registered function fills the frame, e.g. with the uptime and an error counter, and the frame is sent
+//! as is. There is only one heartbeat shared by all Uarts.
+//!
+//! The frame is sent in between any other output, so it should carry it's own delimiter to be picked out of the
+//! console on the host. The send gives up if the transmitter does not accept data for [HEARTBEAT_SEND_TIMEOUT] micro
+//! seconds, so a stuck line does not block the timer interrupt.
+//!
+//! The frame is written straight to the transmit FIFO and does not wait in the queue of a buffered or throttled
+//! console. Console output already queued is therefore sent after the frame, and if the transmit interrupt drains
+//! that queue on another core while the frame is sent, the bytes of both can interleave. The host should drop frames
+//! that do not match the expected layout between the delimiters instead of trusting each byte in between.
+//!
+//! The heartbeat is available with the ``heartbeat`` feature, which pulls in the ``heapless`` dependency for the frame.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # use ruspiro_uart::heartbeat::{self, HEARTBEAT_FRAME_SIZE};
+//! # use heapless::Vec;
+//! fn status(frame: &mut Vec<u8, HEARTBEAT_FRAME_SIZE>) {
+//!     let _ = frame.extend_from_slice(b"\x02ALIVE\x03");
+//! }
+//!
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! uart.enable_heartbeat(1_000_000, status).unwrap();
+//! // from now on heartbeat::service() is called regularly by a timer interrupt handler
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::clock;
+use crate::sealed::FnHook;
+use crate::Peripheral;
+use crate::{uart0, uart1};
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use heapless::Vec;
+
+/// The maximum size of a heartbeat frame
+pub const HEARTBEAT_FRAME_SIZE: usize = 64;
+
+/// The time in micro seconds the heartbeat waits for the transmitter to accept the next byte before giving up
+pub const HEARTBEAT_SEND_TIMEOUT: u64 = 10_000;
+
+/// The function filling the heartbeat frame
+pub type HeartbeatFn = fn(&mut Vec<u8, HEARTBEAT_FRAME_SIZE>);
+
+// the Uart sending the heartbeat, 0 if disabled
+const TARGET_NONE: u8 = 0;
+const TARGET_UART0: u8 = 1;
+const TARGET_UART1: u8 = 2;
+static TARGET: AtomicU8 = AtomicU8::new(TARGET_NONE);
+
+// the function filling the frame, the period in micro seconds and the time the next frame is due
+static HOOK: FnHook<HeartbeatFn> = FnHook::new();
+static PERIOD: AtomicU64 = AtomicU64::new(0);
+static NEXT: AtomicU64 = AtomicU64::new(0);
+
+fn target(peripheral: Peripheral) -> u8 {
+    match peripheral {
+        Peripheral::Uart0 => TARGET_UART0,
+        Peripheral::Uart1 => TARGET_UART1,
+    }
+}
+
+// send the heartbeat on the given Uart with the period in micro seconds, the first frame is due after one period
+pub(crate) fn enable(peripheral: Peripheral, period: u64, hook: HeartbeatFn) {
+    TARGET.store(TARGET_NONE, Ordering::Release);
+    HOOK.set(Some(hook));
+    PERIOD.store(period, Ordering::Relaxed);
+    NEXT.store(clock::now() + period, Ordering::Relaxed);
+    TARGET.store(target(peripheral), Ordering::Release);
+}
+
+// stop the heartbeat if it is sent on the given Uart
+pub(crate) fn disable(peripheral: Peripheral) {
+    if TARGET
+        .compare_exchange(
+            target(peripheral),
+            TARGET_NONE,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_ok()
+    {
+        HOOK.set(None);
+    }
+}
+
+/// Send the heartbeat frame if it is due. This need to be called regularly from a timer interrupt handler, at least
+/// as often as the period of the heartbeat. Returns whether a frame has been sent.
+pub fn service() -> bool {
+    let target = TARGET.load(Ordering::Acquire);
+    let hook = match HOOK.get() {
+        Some(hook) if target != TARGET_NONE => hook,
+        _ => return false,
+    };
+    let now = clock::now();
+    let next = NEXT.load(Ordering::Relaxed);
+    if now < next {
+        return false;
+    }
+    // the next frame is due one period after this one, frames missed meanwhile are not caught up. Only the core that
+    // advances the due time sends the frame, if the timer interrupt calls this on several cores at once
+    let period = PERIOD.load(Ordering::Relaxed);
+    if NEXT
+        .compare_exchange(
+            next,
+            (next + period).max(now + 1),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        )
+        .is_err()
+    {
+        return false;
+    }
+    let mut frame = Vec::new();
+    hook(&mut frame);
+    match target {
+        TARGET_UART0 => {
+            frame
+                .iter()
+                .all(|byte| uart0::interface::write_byte_timeout(*byte, HEARTBEAT_SEND_TIMEOUT));
+        }
+        _ => {
+            uart1::interface::uart1_send_data_timeout(&frame, HEARTBEAT_SEND_TIMEOUT);
+        }
+    }
+    true
+}
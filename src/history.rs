@@ -0,0 +1,167 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Transmit History
+//!
+//! With the ``tx_history`` feature active the last [TX_HISTORY_SIZE] bytes transmitted by any of the Uarts are mirrored
+//! into a static ring buffer. After a crash or a watchdog reset this shows what the device reported last, even if the
+//! terminal on the host was not recording. The buffer is placed in the ``.noinit`` section, which the linker script
+//! need to keep out of the loaded image and the memory cleared at boot for the history to survive a reset. The
+//! fragment ``link/noinit.ld`` shipped with this crate declares the section as ``NOLOAD`` and can be included into the
+//! ``SECTIONS`` of the linker script after the ``.bss`` section. Before the history is used the first time after a
+//! reset it's marker and the checksum of it's contents are validated. A history found to be invalid, e.g. after
+//! powering up, is discarded. The lines a [SeverityRouter](crate::router::SeverityRouter) has routed as
+//! errors are kept in a second history of the same size, so they are not pushed out by the routine output.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::history;
+//! # use ruspiro_uart::Uart1;
+//! # fn doc() {
+//! let mut last = [0u8; 256];
+//! let count = history::last_tx(&mut last);
+//!
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! uart.send_string("output before the reset:\r\n");
+//! uart.send_data(&last[..count]);
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::sealed::{ERROR_HISTORY, TX_HISTORY as HISTORY};
+use crate::watchdog;
+use core::sync::atomic::{AtomicU32, AtomicU8, AtomicUsize, Ordering};
+
+/// The number of transmitted bytes kept in the history
+pub const TX_HISTORY_SIZE: usize = 1024;
+
+// the marker of a valid history. Memory that has not been preserved over a reset will hardly contain this value
+const VALID: u32 = 0x5458_4842;
+
+// whether a history has been validated since the last reset. The state lives in the memory cleared at boot
+const UNCHECKED: u8 = 0;
+const CHECKING: u8 = 1;
+const CHECKED: u8 = 2;
+static TX_CHECK: AtomicU8 = AtomicU8::new(UNCHECKED);
+static ERROR_CHECK: AtomicU8 = AtomicU8::new(UNCHECKED);
+
+// the history, the total number of bytes recorded, the sum of the bytes held by the buffer and the marker whether the
+// history is valid
+pub(crate) struct TxHistory {
+    buffer: [AtomicU8; TX_HISTORY_SIZE],
+    written: AtomicUsize,
+    checksum: AtomicU32,
+    valid: AtomicU32,
+}
+
+impl TxHistory {
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: AtomicU8 = AtomicU8::new(0);
+        TxHistory {
+            buffer: [EMPTY; TX_HISTORY_SIZE],
+            written: AtomicUsize::new(0),
+            checksum: AtomicU32::new(0),
+            valid: AtomicU32::new(0),
+        }
+    }
+
+    // validate the history found after a reset the first time it is used, discarding it if the marker is not set or
+    // the contents do not match their checksum. Other cores using the history at the same time wait for the check
+    fn check(&self, state: &AtomicU8) -> &Self {
+        if state.load(Ordering::Acquire) == CHECKED {
+            return self;
+        }
+        if state
+            .compare_exchange(UNCHECKED, CHECKING, Ordering::Acquire, Ordering::Acquire)
+            .is_ok()
+        {
+            if !self.is_intact() {
+                self.reset();
+            }
+            state.store(CHECKED, Ordering::Release);
+        } else {
+            while state.load(Ordering::Acquire) != CHECKED {
+                watchdog::kick();
+            }
+        }
+        self
+    }
+
+    // whether the marker is set and the sum of the bytes held by the buffer matches the checksum
+    fn is_intact(&self) -> bool {
+        let sum = self.buffer.iter().fold(0u32, |sum, byte| {
+            sum.wrapping_add(byte.load(Ordering::Relaxed) as u32)
+        });
+        self.valid.load(Ordering::Acquire) == VALID && sum == self.checksum.load(Ordering::Relaxed)
+    }
+
+    // discard the recorded bytes and mark the empty history as valid
+    fn reset(&self) {
+        for byte in self.buffer.iter() {
+            byte.store(0, Ordering::Relaxed);
+        }
+        self.written.store(0, Ordering::Relaxed);
+        self.checksum.store(0, Ordering::Relaxed);
+        self.valid.store(VALID, Ordering::Release);
+    }
+
+    // record a byte. Each byte reserves it's own slot, so several cores may record at the same time. The checksum is
+    // updated by the difference to the byte replaced
+    fn push(&self, byte: u8) {
+        let slot = self.written.fetch_add(1, Ordering::Relaxed) % TX_HISTORY_SIZE;
+        let replaced = self.buffer[slot].swap(byte, Ordering::Relaxed);
+        self.checksum.fetch_add(
+            (byte as u32).wrapping_sub(replaced as u32),
+            Ordering::Relaxed,
+        );
+    }
+
+    // copy the most recently recorded bytes into the buffer, the oldest byte first
+    fn last(&self, buffer: &mut [u8]) -> usize {
+        let written = self.written.load(Ordering::Relaxed);
+        let count = buffer.len().min(written).min(TX_HISTORY_SIZE);
+        let first = written - count;
+        for (offset, byte) in buffer[..count].iter_mut().enumerate() {
+            *byte = self.buffer[(first + offset) % TX_HISTORY_SIZE].load(Ordering::Relaxed);
+        }
+        count
+    }
+}
+
+// record a transmitted byte
+pub(crate) fn record(byte: u8) {
+    HISTORY.check(&TX_CHECK).push(byte);
+}
+
+// record the data of a line routed as error
+pub(crate) fn record_error(data: &[u8]) {
+    let history = ERROR_HISTORY.check(&ERROR_CHECK);
+    for byte in data {
+        history.push(*byte);
+    }
+}
+
+/// Copy the most recently transmitted bytes into the buffer, the oldest byte first. Returns the number of bytes
+/// copied, which is less than the size of the buffer if fewer bytes have been recorded.
+pub fn last_tx(buffer: &mut [u8]) -> usize {
+    HISTORY.check(&TX_CHECK).last(buffer)
+}
+
+/// Copy the most recent error lines routed by a [SeverityRouter](crate::router::SeverityRouter) into the buffer, the
+/// oldest byte first. Returns the number of bytes copied.
+pub fn last_errors(buffer: &mut [u8]) -> usize {
+    ERROR_HISTORY.check(&ERROR_CHECK).last(buffer)
+}
+
+/// Discard the recorded history, including the error lines
+pub fn clear() {
+    HISTORY.check(&TX_CHECK).reset();
+    ERROR_HISTORY.check(&ERROR_CHECK).reset();
+}
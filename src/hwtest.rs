@@ -0,0 +1,251 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Hardware Test
+//!
+//! Regressions of the drivers often only show up on the real peripheral, e.g. a baud rate divisor that is off at
+//! higher rates. The hardware test runs a scripted series of exchanges with the host script ``tools/hwtest.py``
+//! connected through an USB-serial adapter and reports which of them failed:
+//!
+//! - an echo of a counting pattern at the current baud rate
+//! - a switch to each of the given baud rates, each verified with an echo, and back to the initial rate
+//! - a break sent by the device, which need to be detected by the host
+//! - error injection, the host sends a byte with a wrong parity bit that need to be detected by the device
+//!
+//! Flow control is not covered, as it needs the additional RTS and CTS wires.
+//!
+//! ## Protocol
+//!
+//! The device leads the test. Each step starts with a command ``ESC 'T' <command>`` followed by a 4 byte little endian
+//! argument. The host acknowledges each command with [ACK] within [ACK_TIMEOUT] and performs it's part of the step:
+//!
+//! | Command    | Argument      | Host                                                                             |
+//! |------------|---------------|----------------------------------------------------------------------------------|
+//! | [HELLO]    | 0             | nothing                                                                          |
+//! | [ECHO]     | byte count    | sends every byte received back, until the byte count has been echoed             |
+//! | [BAUD]     | baud rate     | switches to the baud rate once the acknowledge has been sent                     |
+//! | [BREAK]    | duration (us) | answers with [BREAK_SEEN] once it has received the break (a ``0x00`` byte)       |
+//! | [PARITY]   | 0             | sends [PARITY_PROBE] after the acknowledge, checked with even parity (bit 7)     |
+//! | [DONE]     | failed steps  | nothing, the test is over                                                        |
+//!
+//! The device switches it's baud rate [SWITCH_DELAY] after it has received the acknowledge of [BAUD].
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! let report = uart.run_hw_test(&[9_600, 460_800, 921_600]).unwrap();
+//! assert_eq!(report.failed, 0);
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::clock;
+use crate::uart1::interface;
+use crate::{watchdog, Parity, UartError, UartResult};
+use ruspiro_timer as timer;
+
+/// The command checking that the host script is connected
+pub const HELLO: [u8; 3] = [0x1B, b'T', b'h'];
+
+/// The command letting the host echo the data
+pub const ECHO: [u8; 3] = [0x1B, b'T', b'e'];
+
+/// The command switching both sides to another baud rate
+pub const BAUD: [u8; 3] = [0x1B, b'T', b'b'];
+
+/// The command announcing a break sent by the device
+pub const BREAK: [u8; 3] = [0x1B, b'T', b'k'];
+
+/// The command letting the host send data with a wrong parity bit
+pub const PARITY: [u8; 3] = [0x1B, b'T', b'p'];
+
+/// The command ending the test
+pub const DONE: [u8; 3] = [0x1B, b'T', b'q'];
+
+/// The acknowledge of a command sent by the host
+pub const ACK: [u8; 3] = [0x1B, b'T', b'a'];
+
+/// The answer of the host once it has received the break
+pub const BREAK_SEEN: [u8; 3] = [0x1B, b'T', b's'];
+
+/// The data sent by the host for the parity step, the first byte carries the correct even parity in bit 7, the second
+/// one does not
+pub const PARITY_PROBE: [u8; 2] = [0x41, 0xC1];
+
+/// The time in micro seconds the host has to acknowledge a command
+pub const ACK_TIMEOUT: u64 = 1_000_000;
+
+/// The time in micro seconds the device waits for the host to switch the baud rate
+pub const SWITCH_DELAY: u64 = 50_000;
+
+/// The number of bytes echoed in each echo step
+pub const ECHO_LENGTH: u32 = 256;
+
+// the number of bytes sent before the echo is verified, fits into the receive FIFO of both Uarts
+const CHUNK_SIZE: usize = 8;
+
+// the time in micro seconds given to the host to echo a chunk
+const CHUNK_TIMEOUT: u64 = 100_000;
+
+// the length of the break in micro seconds, long enough to be seen as break at any baud rate down to 1200
+const BREAK_DURATION: u32 = 20_000;
+
+/// A step of the hardware test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HwTestStep {
+    /// the host script answered
+    Hello,
+    /// the data echoed at the current baud rate matched
+    Echo,
+    /// the switch to the baud rate and the echo at this rate succeeded
+    Baud(u32),
+    /// the host has seen the break
+    Break,
+    /// the device has detected the wrong parity bit
+    Parity,
+}
+
+/// The result of a hardware test
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HwTestReport {
+    /// The number of steps passed
+    pub passed: u32,
+    /// The number of steps failed
+    pub failed: u32,
+    /// The first step that failed
+    pub first_failure: Option<HwTestStep>,
+}
+
+impl HwTestReport {
+    // count the outcome of a step
+    fn record(&mut self, step: HwTestStep, passed: bool) {
+        if passed {
+            self.passed += 1;
+        } else {
+            self.failed += 1;
+            self.first_failure = self.first_failure.or(Some(step));
+        }
+    }
+}
+
+// run the hardware test on the Uart1 at the current baud rate, switching to each of the baud rates given and back to
+// the current one. Fails with ``Err(UartError::NoAcknowledge)`` if the host script does not answer at all
+pub(crate) fn run(clock_rate: u32, current: u32, baud_rates: &[u32]) -> UartResult<HwTestReport> {
+    let mut report = HwTestReport::default();
+    if !command(&HELLO, 0) {
+        return Err(UartError::NoAcknowledge);
+    }
+    report.record(HwTestStep::Hello, true);
+
+    report.record(HwTestStep::Echo, echo());
+
+    for baud_rate in baud_rates.iter().chain(Some(&current)) {
+        let passed = switch_baud(clock_rate, *baud_rate);
+        report.record(HwTestStep::Baud(*baud_rate), passed);
+    }
+
+    let passed = command(&BREAK, BREAK_DURATION) && {
+        interface::uart1_send_break(BREAK_DURATION as u64);
+        expect(&BREAK_SEEN, ACK_TIMEOUT)
+    };
+    report.record(HwTestStep::Break, passed);
+
+    // the parity is checked once the data is taken from the receive FIFO, so it can be enabled after the acknowledge
+    // has been received without missing the probe
+    let passed = command(&PARITY, 0) && {
+        let parity = interface::uart1_parity();
+        interface::uart1_set_parity(Parity::Even);
+        let good = receive_timeout(ACK_TIMEOUT);
+        let bad = receive_timeout(ACK_TIMEOUT);
+        interface::uart1_set_parity(parity);
+        good == Some(Ok(PARITY_PROBE[0] & 0x7F)) && bad == Some(Err(UartError::ParityError))
+    };
+    report.record(HwTestStep::Parity, passed);
+
+    command(&DONE, report.failed);
+    Ok(report)
+}
+
+// send the command and wait for the acknowledge of the host
+fn command(command: &[u8], argument: u32) -> bool {
+    // drop anything left over from the previous step
+    while interface::uart1_try_receive().is_some() {}
+    interface::uart1_send_data(command);
+    interface::uart1_send_data(&argument.to_le_bytes());
+    expect(&ACK, ACK_TIMEOUT)
+}
+
+// let the host echo a counting pattern and verify the data received
+fn echo() -> bool {
+    if !command(&ECHO, ECHO_LENGTH) {
+        return false;
+    }
+    let mut passed = true;
+    let mut value = 0u8;
+    for _ in 0..ECHO_LENGTH as usize / CHUNK_SIZE {
+        let mut chunk = [0u8; CHUNK_SIZE];
+        chunk.iter_mut().for_each(|byte| {
+            *byte = value;
+            value = value.wrapping_add(1);
+        });
+        interface::uart1_send_data(&chunk);
+        for expected in chunk.iter() {
+            if receive_timeout(CHUNK_TIMEOUT) != Some(Ok(*expected)) {
+                passed = false;
+            }
+        }
+    }
+    passed
+}
+
+// switch both sides to the baud rate and verify it with an echo
+fn switch_baud(clock_rate: u32, baud_rate: u32) -> bool {
+    if !command(&BAUD, baud_rate) {
+        return false;
+    }
+    timer::sleep(SWITCH_DELAY);
+    if interface::uart1_set_baud_rate(clock_rate, baud_rate).is_err() {
+        return false;
+    }
+    timer::sleep(SWITCH_DELAY);
+    echo()
+}
+
+// wait for the pattern to be received within the timeout in micro seconds, skipping any other data
+fn expect(pattern: &[u8], timeout: u64) -> bool {
+    let start = clock::now();
+    let mut matched = 0;
+    while matched < pattern.len() {
+        let remaining = timeout.saturating_sub(clock::elapsed_since(start));
+        match receive_timeout(remaining) {
+            Some(Ok(byte)) if byte == pattern[matched] => matched += 1,
+            Some(Ok(byte)) if byte == pattern[0] => matched = 1,
+            Some(_) => matched = 0,
+            None => return false,
+        }
+    }
+    true
+}
+
+// wait at most the timeout in micro seconds for the next byte
+fn receive_timeout(timeout: u64) -> Option<UartResult<u8>> {
+    let start = clock::now();
+    loop {
+        if let Some(byte) = interface::uart1_try_receive() {
+            return Some(byte);
+        }
+        if clock::elapsed_since(start) > timeout {
+            return None;
+        }
+        watchdog::kick();
+        timer::sleepcycles(10);
+    }
+}
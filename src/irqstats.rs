@@ -0,0 +1,159 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Interrupt Statistics
+//!
+//! The interrupt handlers of the Uarts count each interrupt they have been entered for and the spurious ones, where the
+//! Uart does not report any pending cause in it's interrupt status (MIS of the Uart0, IIR of the Uart1). A floating
+//! interrupt line or a mis-configured interrupt controller can raise such interrupts at a rate that leaves no time for
+//! anything else. With a limit set, the handler disables the interrupt line of the Uart at the interrupt controller
+//! once it has been entered spuriously more than the limit within a second, and notifies the application through a
+//! hook. A storm raised without any cause at the Uart can only be stopped there, masking the interrupts at the Uart
+//! itself would not help. The Uart0 has the GPU interrupt 57 on it's own, while the Uart1 shares the AUX interrupt 29
+//! with SPI1 and SPI2, which are disabled along with it. The hook is called from the interrupt handler. The interrupt
+//! line is enabled again with ``resume_interrupts`` of the Uart.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # use ruspiro_uart::Peripheral;
+//! fn storm(peripheral: Peripheral) {
+//!     // log the storm and check the wiring
+//! }
+//!
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! uart.set_spurious_irq_limit(1_000, Some(storm));
+//! let stats = uart.irq_stats();
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::clock;
+use crate::sealed::FnHook;
+use crate::Peripheral;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use ruspiro_register::define_mmio_register;
+
+// Peripheral MMIO base address - depends on the right feature
+#[cfg(feature = "ruspiro_pi3")]
+const PERIPHERAL_BASE: u32 = 0x3F00_0000;
+
+// the time in micro seconds the spurious interrupts are counted over for the limit
+const WINDOW: u64 = 1_000_000;
+
+/// The interrupt statistics of an Uart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IrqStats {
+    /// The number of times the interrupt handler has been entered
+    pub total: u32,
+    /// The number of times the interrupt handler has been entered without any pending cause
+    pub spurious: u32,
+    /// Whether the interrupt line has been disabled as the spurious interrupts exceeded the limit
+    pub masked: bool,
+}
+
+// The interrupt counters of a single Uart
+pub(crate) struct IrqCounter {
+    peripheral: Peripheral,
+    total: AtomicU32,
+    spurious: AtomicU32,
+    limit: AtomicU32,
+    window_start: AtomicU64,
+    window_spurious: AtomicU32,
+    masked: AtomicBool,
+    hook: FnHook<fn(Peripheral)>,
+}
+
+impl IrqCounter {
+    pub(crate) const fn new(peripheral: Peripheral) -> Self {
+        IrqCounter {
+            peripheral,
+            total: AtomicU32::new(0),
+            spurious: AtomicU32::new(0),
+            limit: AtomicU32::new(0),
+            window_start: AtomicU64::new(0),
+            window_spurious: AtomicU32::new(0),
+            masked: AtomicBool::new(false),
+            hook: FnHook::new(),
+        }
+    }
+
+    // set the number of spurious interrupts per second tolerated and the hook notified once they are exceeded. A limit
+    // of 0 removes the limit
+    pub(crate) fn set_limit(&self, limit: u32, hook: Option<fn(Peripheral)>) {
+        self.hook.set(hook);
+        self.window_spurious.store(0, Ordering::Relaxed);
+        self.limit.store(limit, Ordering::Relaxed);
+    }
+
+    // count an interrupt the handler has been entered for
+    pub(crate) fn entered(&self) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // count a spurious interrupt. Once they exceed the limit the interrupt line is disabled at the interrupt controller
+    pub(crate) fn spurious(&self) {
+        self.spurious.fetch_add(1, Ordering::Relaxed);
+        let limit = self.limit.load(Ordering::Relaxed);
+        if limit == 0 {
+            return;
+        }
+        let now = clock::now();
+        let window_start = self.window_start.load(Ordering::Relaxed);
+        // a clock set back before the window start starts a new window as well
+        if now < window_start || now - window_start >= WINDOW {
+            self.window_start.store(now, Ordering::Relaxed);
+            self.window_spurious.store(0, Ordering::Relaxed);
+        }
+        let count = self.window_spurious.fetch_add(1, Ordering::Relaxed) + 1;
+        if count > limit && !self.masked.swap(true, Ordering::AcqRel) {
+            set_irq_line(self.peripheral, false);
+            if let Some(hook) = self.hook.get() {
+                hook(self.peripheral);
+            }
+        }
+    }
+
+    // enable the interrupt line again if it has been disabled, clearing the masked state
+    pub(crate) fn resume(&self) {
+        if self.masked.swap(false, Ordering::AcqRel) {
+            self.window_spurious.store(0, Ordering::Relaxed);
+            set_irq_line(self.peripheral, true);
+        }
+    }
+
+    pub(crate) fn stats(&self) -> IrqStats {
+        IrqStats {
+            total: self.total.load(Ordering::Relaxed),
+            spurious: self.spurious.load(Ordering::Relaxed),
+            masked: self.masked.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// enable or disable the GPU interrupt of the Uart at the interrupt controller: IRQ 57 of the Uart0 is bit 25 of the
+// second bank, the AUX IRQ 29 of the Uart1 is bit 29 of the first bank. Writing a bit leaves the other interrupts of
+// the bank untouched
+fn set_irq_line(peripheral: Peripheral, enabled: bool) {
+    match (peripheral, enabled) {
+        (Peripheral::Uart0, true) => ENABLE_IRQS_2::Register.set(1 << 25),
+        (Peripheral::Uart0, false) => DISABLE_IRQS_2::Register.set(1 << 25),
+        (Peripheral::Uart1, true) => ENABLE_IRQS_1::Register.set(1 << 29),
+        (Peripheral::Uart1, false) => DISABLE_IRQS_1::Register.set(1 << 29),
+    }
+}
+
+// the enable and disable registers of the GPU interrupts of the interrupt controller
+define_mmio_register! [
+    ENABLE_IRQS_1<WriteOnly<u32>@(PERIPHERAL_BASE + 0xB210)>,
+    ENABLE_IRQS_2<WriteOnly<u32>@(PERIPHERAL_BASE + 0xB214)>,
+    DISABLE_IRQS_1<WriteOnly<u32>@(PERIPHERAL_BASE + 0xB21C)>,
+    DISABLE_IRQS_2<WriteOnly<u32>@(PERIPHERAL_BASE + 0xB220)>
+];
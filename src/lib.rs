@@ -7,6 +7,7 @@
 #![doc(html_root_url = "https://docs.rs/ruspiro-uart/0.3.0")]
 #![no_std]
 #![feature(llvm_asm)]
+#![deny(unsafe_op_in_unsafe_fn)]
 //! # UART API for Raspberry Pi
 //!
 //! This crate provides access to the Uart0 (PL011) and the Uart1 (miniUART) peripheral of the Raspberry Pi. It is quite
@@ -16,12 +17,12 @@
 //!
 //! # Example
 //!
-//! The proposed usage of the UART is to attach it to a generic console as an output channel instead of using it 
+//! The proposed usage of the UART is to attach it to a generic console as an output channel instead of using it
 //! directly. To do so, please refer to the [``ruspiro-console`` crate](https://crates.io/crates/ruspiro-console).
 //!
-//! But in case you would like to use the uart without the console abstraction it is recommended to wrap it into a 
-//! singleton to guaranty safe cross core access and ensure only one time initialization. In the example we pass a 
-//! fixed core clock rate to the initialization function. However, the real core clock rate could be optained with a 
+//! But in case you would like to use the uart without the console abstraction it is recommended to wrap it into a
+//! singleton to guaranty safe cross core access and ensure only one time initialization. In the example we pass a
+//! fixed core clock rate to the initialization function. However, the real core clock rate could be optained with a
 //! call to the mailbox property tag interface of the Raspberry Pi (see [`ruspiro-mailbox` crate](https://crates.io/crates/ruspiro-mailbox) for details.).
 //!
 //! ```ignore
@@ -42,6 +43,11 @@
 //! }
 //! ```
 
+pub mod prelude;
+
+#[macro_use]
+mod macros;
+
 pub mod uart0;
 #[doc(inline)]
 pub use uart0::*;
@@ -50,7 +56,908 @@ pub mod uart1;
 #[doc(inline)]
 pub use uart1::*;
 
-type UartResult<T> = Result<T, &'static str>;
+#[cfg(feature = "bench")]
+pub mod bench;
+
+pub mod raw;
+
+pub mod encoding;
+
+pub mod p2plink;
+
+pub mod command;
+
+pub mod timesync;
+
+pub mod softuart;
+
+pub mod router;
+
+pub mod diagnose;
+
+pub mod once;
+
+pub mod serial;
+
+pub mod negotiate;
+
+pub mod slowsend;
+
+pub mod pl011;
+
+pub mod soak;
+
+pub mod linecheck;
+
+pub mod clock;
+
+pub mod irqstats;
+
+pub mod telemetry;
+
+pub mod hal;
+
+#[cfg(feature = "messages")]
+pub mod message;
+
+mod crc;
+
+mod sealed;
+
+mod watchdog;
+
+mod staging;
+
+mod prefix;
+
+mod escape;
+
+mod pacing;
+
+mod throttle;
+
+mod teardown;
+
+#[cfg(feature = "async")]
+mod asynch;
+
+pub mod buffer;
+
+pub mod ringbuffer;
+
+#[cfg(feature = "early_log")]
+pub mod early;
+
+#[cfg(feature = "trace")]
+pub mod trace;
+
+#[cfg(feature = "tx_history")]
+pub mod history;
+
+#[cfg(feature = "fiq")]
+pub mod fiq;
+
+#[cfg(feature = "heartbeat")]
+pub mod heartbeat;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "hw-test")]
+pub mod hwtest;
+
+use core::fmt;
+use core::ops::BitOr;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use sealed::core_id;
+
+pub mod error;
+#[doc(inline)]
+pub use error::*;
+
+type UartResult<T> = Result<T, UartError>;
+
+// write the crate features that are active
+fn describe_features<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    write!(w, "  features:        ")?;
+    if cfg!(feature = "ruspiro_pi3") {
+        write!(w, " ruspiro_pi3")?;
+    }
+    if cfg!(feature = "bench") {
+        write!(w, " bench")?;
+    }
+    if cfg!(feature = "early_log") {
+        write!(w, " early_log")?;
+    }
+    if cfg!(feature = "trace") {
+        write!(w, " trace")?;
+    }
+    if cfg!(feature = "messages") {
+        write!(w, " messages")?;
+    }
+    if cfg!(feature = "tx_history") {
+        write!(w, " tx_history")?;
+    }
+    if cfg!(feature = "heapless") {
+        write!(w, " heapless")?;
+    }
+    if cfg!(feature = "heartbeat") {
+        write!(w, " heartbeat")?;
+    }
+    if cfg!(feature = "fiq") {
+        write!(w, " fiq")?;
+    }
+    if cfg!(feature = "ffi") {
+        write!(w, " ffi")?;
+    }
+    if cfg!(feature = "hw-test") {
+        write!(w, " hw-test")?;
+    }
+    if cfg!(feature = "async") {
+        write!(w, " async")?;
+    }
+    if cfg!(feature = "dma") {
+        write!(w, " dma")?;
+    }
+    writeln!(w)
+}
+
+// the number of cores of the Raspberry Pi
+const CORES: usize = 4;
+
+// the longest time in micro seconds a single console write may block, 0 if unlimited
+static CONSOLE_MAX_BLOCK: AtomicU64 = AtomicU64::new(0);
+
+/// Limit the time a single console write (``putc`` or ``puts``) to any Uart may block to the given duration in micro
+/// seconds, so printing can not stall an interrupt handler or a real-time task beyond a known bound. Output not sent
+/// within this time is dropped and counted in the ``console_dropped_bytes`` of the Uart. A duration of 0 removes the
+/// limit, which is the default.
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::*;
+/// # fn doc() {
+/// // never block for longer than 5ms on a print
+/// set_console_max_block(5_000);
+/// # }
+/// ```
+pub fn set_console_max_block(duration: u64) {
+    CONSOLE_MAX_BLOCK.store(duration, Ordering::Relaxed);
+}
+
+// the time a console write starting now need to be finished, if the blocking time is limited
+fn console_deadline() -> Option<u64> {
+    match CONSOLE_MAX_BLOCK.load(Ordering::Relaxed) {
+        0 => None,
+        duration => Some(clock::now() + duration),
+    }
+}
+
+// the time the transmitter may take to accept the next byte of a console write with the given deadline, ``None`` if
+// the deadline has passed. A console timeout of 0 does not limit the time
+fn console_byte_timeout(console_timeout: u64, deadline: u64) -> Option<u64> {
+    match deadline.saturating_sub(clock::now()) {
+        0 => None,
+        remaining if console_timeout == 0 => Some(remaining),
+        remaining => Some(remaining.min(console_timeout)),
+    }
+}
+
+// send the console output through ``fill``, which writes as much of the data as the transmitter accepts right away or
+// returns ``None`` if the transmitter can not be used. While the transmitter is busy the task is switched with
+// ``yield_now`` instead of spinning, until the console timeout or the deadline of the console write has passed.
+// Returns the number of bytes sent
+fn console_send_yielding(
+    data: &[u8],
+    console_timeout: u64,
+    deadline: Option<u64>,
+    yield_now: fn(),
+    mut fill: impl FnMut(&[u8]) -> Option<usize>,
+) -> usize {
+    let mut sent = 0;
+    let mut progress = clock::now();
+    while sent < data.len() {
+        match fill(&data[sent..]) {
+            None => break,
+            Some(0) => {
+                let now = clock::now();
+                if (console_timeout != 0 && now.saturating_sub(progress) > console_timeout)
+                    || matches!(deadline, Some(deadline) if now >= deadline)
+                {
+                    break;
+                }
+                watchdog::kick();
+                yield_now();
+            }
+            Some(count) => {
+                sent += count;
+                progress = clock::now();
+            }
+        }
+    }
+    sent
+}
+
+// the time in micro seconds the initialization waits for the peripheral to respond at each stage
+const INIT_TIMEOUT: u64 = 10_000;
+
+// wait until the condition is met, but fail with an initialization timeout for the given stage if this takes to long
+fn wait_for_init(stage: InitStage, condition: impl Fn() -> bool) -> UartResult<()> {
+    let start = clock::now();
+    while !condition() {
+        if clock::elapsed_since(start) > INIT_TIMEOUT {
+            return Err(UartError::InitializationTimeout(stage));
+        }
+        ruspiro_timer::sleepcycles(10);
+    }
+    Ok(())
+}
+
+// the time in micro seconds before a deadline up to which the watchdog is kicked while waiting for it. The final
+// approach is a tight loop so the deadline is met as close as possible
+const DEADLINE_APPROACH: u64 = 100;
+
+// wait until the clock reaches the timestamp, fails if it has already passed
+fn wait_until(timestamp: u64) -> UartResult<()> {
+    if clock::now() > timestamp {
+        return Err(UartError::SlotMissed);
+    }
+    while clock::now() + DEADLINE_APPROACH < timestamp {
+        watchdog::kick();
+    }
+    while clock::now() < timestamp {}
+    Ok(())
+}
+
+// the CPU cycles waited between two checks for received data while waiting with a timeout given in cycles
+const POLL_CYCLES: u64 = 1000;
+
+// the wait for data to arrive within a timeout
+struct TimeoutWait {
+    timeout: Timeout,
+    start: u64,
+    waited: u64,
+}
+
+impl TimeoutWait {
+    fn start(timeout: Timeout) -> Self {
+        TimeoutWait {
+            timeout,
+            start: clock::now(),
+            waited: 0,
+        }
+    }
+
+    // wait a short while for data to arrive, returns ``false`` once the timeout has passed
+    fn wait(&mut self) -> bool {
+        watchdog::kick();
+        match self.timeout {
+            Timeout::Cycles(cycles) if self.waited >= cycles => false,
+            Timeout::Micros(micros) => clock::elapsed_since(self.start) <= micros,
+            _ => {
+                ruspiro_timer::sleepcycles(POLL_CYCLES as u32);
+                self.waited += POLL_CYCLES;
+                true
+            }
+        }
+    }
+}
+
+// receive into the buffer until the delimiter has been received, which is stored as well. ``receive`` takes the next
+// byte without blocking. The whole line need to arrive within the timeout. Returns the number of bytes stored
+fn read_until(
+    delimiter: u8,
+    buffer: &mut [u8],
+    timeout: Timeout,
+    mut receive: impl FnMut() -> Option<UartResult<u8>>,
+) -> UartResult<usize> {
+    if buffer.is_empty() {
+        return Err(UartError::EmptyBuffer);
+    }
+    let mut wait = TimeoutWait::start(timeout);
+    let mut count = 0;
+    loop {
+        // the byte following a full buffer is left to be received
+        if count == buffer.len() {
+            return Err(UartError::BufferTooSmall);
+        }
+        match receive() {
+            Some(data) => {
+                let data = data?;
+                buffer[count] = data;
+                count += 1;
+                if data == delimiter {
+                    return Ok(count);
+                }
+            }
+            None if !wait.wait() => return Err(UartError::ReceiveDataTimeOut),
+            None => (),
+        }
+    }
+}
+
+// receive into the buffer until it is filled or the timeout has passed. ``receive`` takes the next byte without
+// blocking and ``on_error`` decides how to continue with a byte received with an error: ``Ok(Some(data))`` stores a
+// replacement, ``Ok(None)`` skips the byte and an error ends the receive. Returns the number of bytes stored
+fn receive_timeout(
+    buffer: &mut [u8],
+    timeout: Timeout,
+    mut receive: impl FnMut() -> Option<UartResult<u8>>,
+    mut on_error: impl FnMut(UartError) -> UartResult<Option<u8>>,
+) -> UartResult<usize> {
+    let mut wait = TimeoutWait::start(timeout);
+    let mut count = 0;
+    while count < buffer.len() {
+        match receive() {
+            Some(Ok(data)) => {
+                buffer[count] = data;
+                count += 1;
+            }
+            Some(Err(error)) => {
+                if let Some(data) = on_error(error)? {
+                    buffer[count] = data;
+                    count += 1;
+                }
+            }
+            None if !wait.wait() => break,
+            None => (),
+        }
+    }
+    Ok(count)
+}
+
+/// The receive status of a single byte received by an Uart peripheral. Protocols like LIN or DMX use a break or
+/// framing error as frame delimiter, so those are reported alongside the data instead of dropping the byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RxFlag {
+    /// the byte has been received without any error
+    Ok = 0,
+    /// the parity of the received byte does not match the configured parity
+    Parity = 1,
+    /// the received byte did not have a valid stop bit
+    Framing = 2,
+    /// the receive line was held low for longer than a full character time
+    Break = 3,
+}
+
+impl RxFlag {
+    // the error a receive function fails with for a byte received with this flag
+    pub(crate) fn error(self) -> Option<UartError> {
+        match self {
+            RxFlag::Ok => None,
+            RxFlag::Parity => Some(UartError::ParityError),
+            RxFlag::Framing => Some(UartError::FramingError),
+            RxFlag::Break => Some(UartError::BreakReceived),
+        }
+    }
+}
+
+/// The receive errors an Uart peripheral has latched since they were cleared the last time. Other than the [RxFlag]
+/// of a single byte this also covers data lost due to an overrun of the receive FIFO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LineErrors(u8);
+
+impl LineErrors {
+    /// a byte has been received without a valid stop bit
+    pub const FRAMING: LineErrors = LineErrors(1 << 0);
+    /// a byte has been received with a wrong parity
+    pub const PARITY: LineErrors = LineErrors(1 << 1);
+    /// a break has been received
+    pub const BREAK: LineErrors = LineErrors(1 << 2);
+    /// data has been received while the receive FIFO was full and got lost
+    pub const OVERRUN: LineErrors = LineErrors(1 << 3);
+
+    /// No error at all
+    pub const fn empty() -> Self {
+        LineErrors(0)
+    }
+
+    /// Decode the value of the receive status register (RSRECR) of a PL011, bits not assigned to an error are dropped
+    pub const fn from_bits_truncate(bits: u32) -> Self {
+        LineErrors((bits & 0xF) as u8)
+    }
+
+    /// The errors encoded as in the receive status register
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Check whether no error is contained
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Check whether all of the given errors are contained
+    pub const fn contains(self, other: LineErrors) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for LineErrors {
+    type Output = LineErrors;
+
+    fn bitor(self, other: LineErrors) -> LineErrors {
+        LineErrors(self.0 | other.0)
+    }
+}
+
+/// The errors that may be detected while receiving data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RxError {
+    /// data has been received while the receive FIFO was already full, so data got lost
+    Overrun,
+    /// the parity of a received byte does not match the configured parity
+    Parity,
+    /// a received byte did not have a valid stop bit
+    Framing,
+}
+
+/// The events reported by the interrupt handling of an Uart peripheral
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartEvent {
+    /// at least the given number of bytes is waiting in the receive FIFO
+    DataAvailable(usize),
+    /// the transmit FIFO has been drained
+    TxComplete,
+    /// an error occured while receiving data
+    Error(RxError),
+    /// the receive line was held low for longer than a full character time
+    Break,
+    /// the state of the modem status lines (e.g. CTS) has changed
+    ModemChange,
+}
+
+/// The parity bit added to each character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Parity {
+    /// no parity bit
+    None = 0,
+    /// the parity bit makes the number of set bits even
+    Even = 1,
+    /// the parity bit makes the number of set bits odd
+    Odd = 2,
+}
+
+/// The line configuration of an Uart, the format of each character sent and received
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::*;
+/// # use ruspiro_uart::serial::{DataBits, StopBits};
+/// # fn doc() {
+/// let config = UartConfig {
+///     data_bits: DataBits::Seven,
+///     parity: Parity::Even,
+///     stop_bits: StopBits::One,
+/// };
+/// let mut uart = Uart0::new();
+/// let _ = uart.initialize_with_config(3_000_000, 9_600, &config);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartConfig {
+    /// the number of data bits
+    pub data_bits: serial::DataBits,
+    /// the parity bit
+    pub parity: Parity,
+    /// the number of stop bits
+    pub stop_bits: serial::StopBits,
+}
+
+impl UartConfig {
+    /// 8 data bits, no parity and 1 stop bit, the configuration every Uart is initialized with
+    pub const EIGHT_N_ONE: UartConfig = UartConfig {
+        data_bits: serial::DataBits::Eight,
+        parity: Parity::None,
+        stop_bits: serial::StopBits::One,
+    };
+
+    // the number of data bits the miniUART need to be configured for and the parity it need to emulate. The miniUART
+    // supports 7 or 8 data bits and a single stop bit only, a parity is emulated in software using the 8th data bit
+    pub(crate) fn uart1_mode(&self) -> UartResult<(u32, Parity)> {
+        use serial::{DataBits, StopBits};
+        match (self.data_bits, self.parity, self.stop_bits) {
+            (DataBits::Eight, Parity::None, StopBits::One) => Ok((8, Parity::None)),
+            (DataBits::Seven, Parity::None, StopBits::One) => Ok((7, Parity::None)),
+            (DataBits::Seven, parity, StopBits::One) => Ok((8, parity)),
+            _ => Err(UartError::UnsupportedSettings),
+        }
+    }
+}
+
+impl Default for UartConfig {
+    fn default() -> Self {
+        UartConfig::EIGHT_N_ONE
+    }
+}
+
+/// The Uart peripheral a hook, a statistic or a hardware resource belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Peripheral {
+    /// the Uart0 (PL011)
+    Uart0,
+    /// the Uart1 (miniUART)
+    Uart1,
+}
+
+/// The time a receive function waits for the data to arrive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeout {
+    /// wait until all data has arrived
+    None,
+    /// wait about the given number of CPU cycles, checking for data every 1000 cycles
+    Cycles(u64),
+    /// wait the given number of micro seconds of the [clock]
+    Micros(u64),
+}
+
+/// How the console output of an Uart is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleMode {
+    /// the console output is sent before the write returns, this is the default
+    Sync,
+    /// the console output is queued and sent from the transmit interrupt, so the write returns right away
+    Buffered,
+}
+
+/// Whether the FIFOs of an Uart buffer the data sent and received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FifoMode {
+    /// the FIFOs are used, this is the default after initialization
+    Enabled,
+    /// each character is handled on it's own for the lowest latency
+    Disabled,
+}
+
+/// The prefix added to each line of the console output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefix {
+    /// no prefix
+    None,
+    /// the time of the [clock] in seconds with micro second resolution, e.g. ``[    12.345678] ``
+    TimestampMicros,
+    /// the id of the core writing the line, e.g. ``[core 1] ``
+    CoreId,
+    /// the time of the [clock] and the core id, e.g. ``[    12.345678 core 1] ``
+    Both,
+}
+
+/// The substitution of the non-printable bytes in the console output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escape {
+    /// the bytes are sent as they are
+    None,
+    /// the control characters in caret notation, e.g. ``^[`` for ``ESC`` and ``^?`` for ``DEL``
+    Caret,
+    /// the control characters as hex escape, e.g. ``\x1B`` for ``ESC``
+    Hex,
+}
+
+/// How a receive call handles a byte received with an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RxErrorAction {
+    /// drop the byte and receive the next one in it's place
+    Skip,
+    /// fail the receive call with the error
+    Abort,
+    /// store the given value in place of the byte
+    Replace(u8),
+}
+
+/// The errors a single receive call tolerates. Once more than ``max_retries`` bytes have been received with an error
+/// the call fails with the error, no matter which action has been chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// the number of bytes received with an error that are tolerated within a single call
+    pub max_retries: u32,
+    /// the action taken for each tolerated byte received with an error
+    pub on_error: RxErrorAction,
+}
+
+impl RetryPolicy {
+    /// The default policy failing on the first error
+    pub const ABORT: RetryPolicy = RetryPolicy {
+        max_retries: 0,
+        on_error: RxErrorAction::Abort,
+    };
+
+    // decide how to continue with a byte received with an error, ``errors`` counts the errors of the receive call so
+    // far. ``Ok(Some(data))`` stores a replacement, ``Ok(None)`` skips the byte and an error ends the receive call
+    pub(crate) fn handle(&self, errors: &mut u32, error: UartError) -> UartResult<Option<u8>> {
+        *errors += 1;
+        if *errors > self.max_retries {
+            return Err(error);
+        }
+        match self.on_error {
+            RxErrorAction::Skip => Ok(None),
+            RxErrorAction::Abort => Err(error),
+            RxErrorAction::Replace(data) => Ok(Some(data)),
+        }
+    }
+}
+
+/// The outcome of a buffered read, telling why the read returned along with the number of bytes copied into the buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOutcome {
+    /// the buffer has been filled completely
+    Complete(usize),
+    /// the peer stopped sending, so the buffer has been filled only partially
+    Partial(usize),
+    /// the deadline has been hit while waiting for more data, possibly in the middle of a frame
+    TimedOut(usize),
+}
+
+impl ReadOutcome {
+    /// The number of bytes copied into the buffer
+    pub fn count(&self) -> usize {
+        match *self {
+            ReadOutcome::Complete(count)
+            | ReadOutcome::Partial(count)
+            | ReadOutcome::TimedOut(count) => count,
+        }
+    }
+
+    /// Whether the buffer has been filled completely
+    pub fn is_complete(&self) -> bool {
+        matches!(self, ReadOutcome::Complete(_))
+    }
+}
+
+/// A step of the Uart initialization recorded in the [InitReport]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InitStep {
+    /// claiming the GPIO pins and switching them to the Uart
+    Pins = 1 << 0,
+    /// enabling the peripheral and waiting for an ongoing transfer to finish
+    Peripheral = 1 << 1,
+    /// clearing the FIFO's
+    Fifo = 1 << 2,
+    /// setting the baud rate divisor
+    BaudRate = 1 << 3,
+    /// setting the data bits, the parity and the stop bits of the [UartConfig]
+    LineConfig = 1 << 4,
+    /// setting up the interrupt mask
+    Interrupts = 1 << 5,
+}
+
+/// The steps completed by the last initialization of an Uart and the step it stopped at, if it failed. First boot
+/// bring-up on new hardware often fails with a timeout, which this tells apart e.g. for the FIFO and the pins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InitReport {
+    completed: u8,
+    failure: Option<(InitStep, UartError)>,
+}
+
+impl InitReport {
+    // a report without any step completed
+    pub(crate) const fn new() -> Self {
+        InitReport {
+            completed: 0,
+            failure: None,
+        }
+    }
+
+    // record the outcome of a step
+    pub(crate) fn run<T>(&mut self, step: InitStep, result: UartResult<T>) -> UartResult<T> {
+        match &result {
+            Ok(_) => self.completed |= step as u8,
+            Err(error) => self.failure = Some((step, *error)),
+        }
+        result
+    }
+
+    // record a step that can not fail
+    pub(crate) fn complete(&mut self, step: InitStep) {
+        self.completed |= step as u8;
+    }
+
+    /// Whether the step has been completed
+    pub fn is_completed(&self, step: InitStep) -> bool {
+        self.completed & step as u8 != 0
+    }
+
+    /// The step the initialization failed at, along with the error
+    pub fn failure(&self) -> Option<(InitStep, UartError)> {
+        self.failure
+    }
+
+    /// Whether the initialization has run without failing. A report of an Uart that has never been initialized is
+    /// not successful either.
+    pub fn is_success(&self) -> bool {
+        self.completed != 0 && self.failure.is_none()
+    }
+}
+
+/// The letter case of the hex digits above 9
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexCase {
+    /// send the digits as ``A`` to ``F``
+    Upper,
+    /// send the digits as ``a`` to ``f``
+    Lower,
+}
+
+/// The operational state of an Uart peripheral. Sending or receiving is only possible while the Uart is ``Active``,
+/// so data is never written into a half-configured peripheral, e.g. by another core while the baud rate is changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum UartState {
+    /// the Uart is not initialized
+    Idle = 0,
+    /// the Uart is initialized or it's baud rate is changed
+    Configuring = 1,
+    /// the Uart is ready to send and receive
+    Active = 2,
+    /// the Uart temporarily does not send or receive, e.g. while a break is sent
+    Suspended = 3,
+}
+
+// the state of an Uart that can be changed and checked from any core, and the number of senders currently writing to
+// the transmit FIFO. A sender announces itself before it checks the state and a re-configuration changes the state
+// before it waits for the senders to leave, so either the sender sees the re-configuration or the re-configuration
+// sees the sender
+pub(crate) struct StateCell {
+    state: AtomicU8,
+    senders: AtomicUsize,
+}
+
+impl StateCell {
+    pub(crate) const fn new() -> Self {
+        StateCell {
+            state: AtomicU8::new(UartState::Idle as u8),
+            senders: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn get(&self) -> UartState {
+        state_from(self.state.load(Ordering::SeqCst))
+    }
+
+    pub(crate) fn set(&self, state: UartState) {
+        self.state.store(state as u8, Ordering::SeqCst);
+    }
+
+    // register a sender writing to the transmit FIFO until the guard is dropped. While the Uart is re-configured or
+    // suspended the sender waits for it to become active again if ``wait`` is set, otherwise or if the Uart is not
+    // initialized ``None`` is returned. A sender running in the interrupt handler of the Uart must not wait, as it
+    // might have interrupted the re-configuration
+    pub(crate) fn enter_send(&self, wait: bool) -> Option<SendGuard<'_>> {
+        loop {
+            self.senders.fetch_add(1, Ordering::SeqCst);
+            let state = self.get();
+            if state == UartState::Active {
+                return Some(SendGuard(&self.senders));
+            }
+            self.senders.fetch_sub(1, Ordering::SeqCst);
+            if state == UartState::Idle || !wait {
+                return None;
+            }
+            watchdog::kick();
+            ruspiro_timer::sleepcycles(10);
+        }
+    }
+
+    // move into the state, e.g. to re-configure the Uart, and wait for the senders writing to the transmit FIFO to
+    // leave. Returns the previous state to be restored once done. If the senders do not leave in time the previous
+    // state is restored right away and the re-configuration fails
+    pub(crate) fn quiesce(&self, state: UartState) -> UartResult<UartState> {
+        let previous = state_from(self.state.swap(state as u8, Ordering::SeqCst));
+        let left = wait_for_init(InitStage::TransmitterIdle, || {
+            self.senders.load(Ordering::SeqCst) == 0
+        });
+        if left.is_err() {
+            self.set(previous);
+        }
+        left.map(|_| previous)
+    }
+
+    // whether the Uart can be used to send or receive data right now
+    pub(crate) fn is_active(&self) -> bool {
+        self.get() == UartState::Active
+    }
+
+    // the error an operation shall fail with in the current state, if any
+    pub(crate) fn check(&self) -> UartResult<()> {
+        match self.get() {
+            UartState::Active => Ok(()),
+            UartState::Idle => Err(UartError::NotInitialized),
+            UartState::Configuring | UartState::Suspended => Err(UartError::Busy),
+        }
+    }
+
+    // mark the end of a (re-)configuration depending on it's outcome
+    pub(crate) fn configured<T>(&self, result: UartResult<T>) -> UartResult<T> {
+        self.set(if result.is_ok() {
+            UartState::Active
+        } else {
+            UartState::Idle
+        });
+        result
+    }
+}
+
+// the state stored in a StateCell
+fn state_from(value: u8) -> UartState {
+    match value {
+        1 => UartState::Configuring,
+        2 => UartState::Active,
+        3 => UartState::Suspended,
+        _ => UartState::Idle,
+    }
+}
+
+// unregisters a sender from the StateCell when it is done writing to the transmit FIFO
+pub(crate) struct SendGuard<'a>(&'a AtomicUsize);
+
+impl Drop for SendGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// a flag for each core whether it executes the interrupt handler of an Uart. Code called back from the handler must
+// not wait for the code it has interrupted, e.g. for a lock it holds, as this will never continue
+pub(crate) struct IrqFlag([AtomicBool; 4]);
+
+impl IrqFlag {
+    pub(crate) const fn new() -> Self {
+        IrqFlag([
+            AtomicBool::new(false),
+            AtomicBool::new(false),
+            AtomicBool::new(false),
+            AtomicBool::new(false),
+        ])
+    }
+
+    // mark the current core as executing the interrupt handler until the guard is dropped
+    pub(crate) fn enter(&self) -> IrqGuard<'_> {
+        let flag = &self.0[core_id()];
+        IrqGuard {
+            flag,
+            nested: flag.swap(true, Ordering::Relaxed),
+        }
+    }
+
+    // whether the current core executes the interrupt handler
+    pub(crate) fn is_set(&self) -> bool {
+        self.0[core_id()].load(Ordering::Relaxed)
+    }
+}
+
+// clears the flag of an interrupt handler when it returns, unless the handler has been called from another one
+pub(crate) struct IrqGuard<'a> {
+    flag: &'a AtomicBool,
+    nested: bool,
+}
+
+impl Drop for IrqGuard<'_> {
+    fn drop(&mut self) {
+        if !self.nested {
+            self.flag.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+// the directions an Uart is initialized for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Both,
+    TxOnly,
+    RxOnly,
+}
+
+impl Direction {
+    // whether the transmitter is used
+    pub(crate) fn tx(self) -> bool {
+        self != Direction::RxOnly
+    }
+
+    // whether the receiver is used
+    pub(crate) fn rx(self) -> bool {
+        self != Direction::TxOnly
+    }
+}
 
 /// The different types of interrupts that can be raised from an Uart peripheral.
 #[repr(u8)]
@@ -0,0 +1,116 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Console Line Check
+//!
+//! Flaky cabling or a baud rate slightly off corrupts single characters of the console output, which easily goes
+//! unnoticed and produces confusing logs while debugging remotely. With the line check enabled a marker ``#XX`` is
+//! appended to each line of the console output, separated by a space and right before the line ending (``\r\n`` or
+//! ``\n``). ``XX`` are two upper case hex digits of the CRC-8 (polynomial 0x07, initial value 0x00, no reflection, no
+//! final XOR) over all bytes of the line in front of the separating space, including a line prefix if one is set.
+//!
+//! To verify a line on the host strip the line ending, split off the last 4 characters `` #XX`` and compare ``XX``
+//! with the CRC-8 of the remaining bytes. A line with a missing or wrong marker has been corrupted on it's way.
+//!
+//! ```text
+//! booting #98
+//! ```
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::*;
+//! # use ruspiro_console::ConsoleImpl;
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! uart.set_line_check(true);
+//! // sends "booting #98\r\n"
+//! uart.puts("booting\r\n");
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::crc::crc8;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// The checksum of a console line as sent in it's marker. ``line`` is the content of the line without the marker and
+/// the line ending.
+/// # Example
+/// ```
+/// # use ruspiro_uart::linecheck::line_checksum;
+/// assert_eq!(line_checksum(b"booting"), 0x98);
+/// ```
+pub fn line_checksum(line: &[u8]) -> u8 {
+    crc8(0, line)
+}
+
+// the line check state of a console, the checksum of the line sent so far
+pub(crate) struct LineCheck {
+    enabled: bool,
+    crc: AtomicU8,
+}
+
+impl LineCheck {
+    pub(crate) const fn new() -> Self {
+        LineCheck {
+            enabled: false,
+            crc: AtomicU8::new(0),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // enable or disable the marker, the next line checked starts with the next byte sent
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.crc.store(0, Ordering::Relaxed);
+    }
+
+    // send the console output, adding the marker at the end of each line. The checksum of a line is kept across calls
+    // until the line ending is sent
+    pub(crate) fn write(&self, data: &[u8], send: impl Fn(&[u8])) {
+        let mut rest = data;
+        while !rest.is_empty() {
+            let idx = match rest.iter().position(|byte| *byte == b'\n') {
+                Some(idx) => idx,
+                None => {
+                    self.update(rest);
+                    send(rest);
+                    return;
+                }
+            };
+            let end = if idx > 0 && rest[idx - 1] == b'\r' {
+                idx - 1
+            } else {
+                idx
+            };
+            let (line, ending) = rest[..=idx].split_at(end);
+            self.update(line);
+            send(line);
+            let crc = self.crc.swap(0, Ordering::Relaxed);
+            send(&[
+                b' ',
+                b'#',
+                HEX_DIGITS[(crc >> 4) as usize],
+                HEX_DIGITS[(crc & 0xF) as usize],
+            ]);
+            send(ending);
+            rest = &rest[idx + 1..];
+        }
+    }
+
+    // add the data to the checksum of the current line
+    fn update(&self, data: &[u8]) {
+        let crc = crc8(self.crc.load(Ordering::Relaxed), data);
+        self.crc.store(crc, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,103 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Output Macros
+//!
+//! Printing numbers with ``core::fmt`` pulls in quite some code, which might not fit into size constrained boot
+//! loaders. The macros provided here send a line with an optional label and a number directly as bytes using
+//! [Uart1::send_hex](crate::Uart1::send_hex) and [Uart1::send_dec](crate::Uart1::send_dec) instead. The value is
+//! converted into an ``u64``, so only unsigned integer types are printed properly.
+//!
+
+/// Send a line containing the value in it's hexadecimal representation, optionally prefixed by a label.
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::{uart_hex, Uart1};
+/// # fn doc() {
+/// # let mut uart = Uart1::new();
+/// # let _ = uart.initialize(250_000_000, 115_200);
+/// // sends "0x1000\r\n"
+/// uart_hex!(uart, 0x1000);
+/// // sends "kernel loaded at 0x80000\r\n"
+/// uart_hex!(uart, "kernel loaded at ", 0x80000);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! uart_hex {
+    ($uart:expr, $value:expr) => {{
+        let uart = &$uart;
+        uart.send_hex($value as u64);
+        uart.send_string("\r\n");
+    }};
+    ($uart:expr, $label:expr, $value:expr) => {{
+        let uart = &$uart;
+        uart.send_string($label);
+        uart.send_hex($value as u64);
+        uart.send_string("\r\n");
+    }};
+}
+
+/// Send a line containing the value in it's decimal representation, optionally prefixed by a label.
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::{uart_dec, Uart1};
+/// # fn doc() {
+/// # let mut uart = Uart1::new();
+/// # let _ = uart.initialize(250_000_000, 115_200);
+/// // sends "42\r\n"
+/// uart_dec!(uart, 42);
+/// // sends "blocks loaded: 128\r\n"
+/// uart_dec!(uart, "blocks loaded: ", 128);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! uart_dec {
+    ($uart:expr, $value:expr) => {{
+        let uart = &$uart;
+        uart.send_dec($value as u64);
+        uart.send_string("\r\n");
+    }};
+    ($uart:expr, $label:expr, $value:expr) => {{
+        let uart = &$uart;
+        uart.send_string($label);
+        uart.send_dec($value as u64);
+        uart.send_string("\r\n");
+    }};
+}
+
+// implement the helpers sending and receiving fixed width integers for an Uart. The Uart need to provide the
+// ``send_raw`` and ``receive_raw`` functions
+macro_rules! impl_binary_helpers {
+    () => {
+        impl_binary_helpers! {
+            send_u16_le, receive_u16_le, u16, to_le_bytes, from_le_bytes, "little endian";
+            send_u16_be, receive_u16_be, u16, to_be_bytes, from_be_bytes, "big endian";
+            send_u32_le, receive_u32_le, u32, to_le_bytes, from_le_bytes, "little endian";
+            send_u32_be, receive_u32_be, u32, to_be_bytes, from_be_bytes, "big endian";
+            send_u64_le, receive_u64_le, u64, to_le_bytes, from_le_bytes, "little endian";
+            send_u64_be, receive_u64_be, u64, to_be_bytes, from_be_bytes, "big endian";
+        }
+    };
+    ($($send:ident, $receive:ident, $ty:ty, $to_bytes:ident, $from_bytes:ident, $order:literal;)*) => {
+        $(
+            #[doc = concat!("Send the ``", stringify!($ty), "`` in ", $order, " byte order")]
+            pub fn $send(&self, value: $ty) {
+                self.send_raw(&value.$to_bytes());
+            }
+
+            #[doc = concat!(
+                "Receive an ``", stringify!($ty), "`` in ", $order,
+                " byte order, blocking until all of it's bytes have been received"
+            )]
+            pub fn $receive(&self) -> Result<$ty, UartError> {
+                let mut bytes = [0u8; core::mem::size_of::<$ty>()];
+                self.receive_raw(&mut bytes)
+                    .map(|_| <$ty>::$from_bytes(bytes))
+            }
+        )*
+    };
+}
@@ -0,0 +1,162 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Message Channel
+//!
+//! With the ``messages`` feature active, any type implementing the ``serde`` traits can be sent to and received from
+//! the other side of an Uart, e.g. an agent running on a host. The messages are serialized with ``postcard``, protected
+//! by a CRC-16 and framed with COBS, so each frame is terminated by a zero byte that does not appear anywhere else in
+//! the frame. This allows the receiver to re-synchronize on the next frame after any transmission error.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # use ruspiro_uart::message::MessageChannel;
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! uart.send_msg(&42u32).unwrap();
+//! let answer: u32 = uart.receive_msg(100_000).unwrap();
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::clock;
+use crate::crc::crc16;
+use crate::p2plink::Transport;
+use crate::UartError;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The maximum size of a serialized message
+pub const MAX_MESSAGE_SIZE: usize = 256;
+
+// the size of a frame before and after the COBS encoding. The encoding adds one byte for every 254 bytes
+const FRAME_SIZE: usize = MAX_MESSAGE_SIZE + 2;
+const ENCODED_SIZE: usize = FRAME_SIZE + FRAME_SIZE / 254 + 1;
+
+/// Sending and receiving typed messages. This is implemented for every [Transport], so for both Uarts.
+pub trait MessageChannel {
+    /// Serialize the message and send it. Fails with ``Err(UartError::SerializeError)`` if the message could not be
+    /// serialized, e.g. as it exceeds [MAX_MESSAGE_SIZE] bytes.
+    fn send_msg<T: Serialize>(&self, msg: &T) -> Result<(), UartError>;
+
+    /// Receive the next message, waiting at most ``timeout`` micro seconds for it to arrive. A timeout of 0 waits
+    /// until a message has been received. Fails with ``Err(UartError::ReceiveDataTimeOut)`` if no message has been
+    /// received in time and with ``Err(UartError::InvalidMessage)`` if the message has been corrupted or could not be
+    /// deserialized into the requested type.
+    fn receive_msg<T: DeserializeOwned>(&self, timeout: u64) -> Result<T, UartError>;
+}
+
+impl<U: Transport> MessageChannel for U {
+    fn send_msg<T: Serialize>(&self, msg: &T) -> Result<(), UartError> {
+        let mut frame = [0u8; FRAME_SIZE];
+        let size = postcard::to_slice(msg, &mut frame[..MAX_MESSAGE_SIZE])
+            .map_err(|_| UartError::SerializeError)?
+            .len();
+        let crc = crc16(0xFFFF, &frame[..size]);
+        frame[size..size + 2].copy_from_slice(&crc.to_be_bytes());
+
+        let mut encoded = [0u8; ENCODED_SIZE];
+        let length = cobs_encode(&frame[..size + 2], &mut encoded);
+        // the leading delimiter terminates any garbage the receiver might have seen before this frame
+        self.write(&[0]);
+        self.write(&encoded[..length]);
+        self.write(&[0]);
+        Ok(())
+    }
+
+    fn receive_msg<T: DeserializeOwned>(&self, timeout: u64) -> Result<T, UartError> {
+        let timeout = if timeout == 0 { u64::MAX } else { timeout };
+        let start = clock::now();
+        let mut encoded = [0u8; ENCODED_SIZE];
+        let mut length = 0;
+        let mut overflow = false;
+        loop {
+            let remaining = timeout.saturating_sub(clock::elapsed_since(start));
+            let byte = self
+                .read_byte(remaining)
+                .ok_or(UartError::ReceiveDataTimeOut)?;
+            if byte != 0 {
+                if length < ENCODED_SIZE {
+                    encoded[length] = byte;
+                    length += 1;
+                } else {
+                    overflow = true;
+                }
+                continue;
+            }
+            // empty frames are just delimiters
+            if length != 0 || overflow {
+                break;
+            }
+        }
+        if overflow {
+            return Err(UartError::InvalidMessage);
+        }
+
+        let mut frame = [0u8; FRAME_SIZE];
+        let size = cobs_decode(&encoded[..length], &mut frame).ok_or(UartError::InvalidMessage)?;
+        if size < 2 {
+            return Err(UartError::InvalidMessage);
+        }
+        let crc = u16::from_be_bytes([frame[size - 2], frame[size - 1]]);
+        if crc != crc16(0xFFFF, &frame[..size - 2]) {
+            return Err(UartError::InvalidMessage);
+        }
+        postcard::from_bytes(&frame[..size - 2]).map_err(|_| UartError::InvalidMessage)
+    }
+}
+
+// encode the data with COBS (consistent overhead byte stuffing), so it does no longer contain any zero byte. Returns
+// the size of the encoded data, not including the terminating zero
+fn cobs_encode(data: &[u8], encoded: &mut [u8]) -> usize {
+    let mut code_idx = 0;
+    let mut out = 1;
+    let mut code = 1u8;
+    for &byte in data {
+        if byte != 0 {
+            encoded[out] = byte;
+            out += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            encoded[code_idx] = code;
+            code_idx = out;
+            out += 1;
+            code = 1;
+        }
+    }
+    encoded[code_idx] = code;
+    out
+}
+
+// decode the COBS encoded data without the terminating zero. Returns the size of the decoded data or ``None`` if the
+// encoded data is invalid
+fn cobs_decode(encoded: &[u8], data: &mut [u8]) -> Option<usize> {
+    let mut idx = 0;
+    let mut out = 0;
+    while idx < encoded.len() {
+        let code = encoded[idx] as usize;
+        idx += 1;
+        if code == 0 || idx + code - 1 > encoded.len() || out + code - 1 > data.len() {
+            return None;
+        }
+        data[out..out + code - 1].copy_from_slice(&encoded[idx..idx + code - 1]);
+        out += code - 1;
+        idx += code - 1;
+        // each block not being the last and not being a maximum size block is followed by a zero in the data
+        if code < 0xFF && idx < encoded.len() {
+            if out == data.len() {
+                return None;
+            }
+            data[out] = 0;
+            out += 1;
+        }
+    }
+    Some(out)
+}
@@ -0,0 +1,154 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Baud Rate Negotiation
+//!
+//! The console typically starts at 115200 baud, as this is what every terminal expects. A host tool that knows about
+//! this negotiation may switch both ends to a higher rate, which speeds up log heavy sessions a lot. Without such a
+//! tool answering, the rate stays as it is.
+//!
+//! ## Protocol
+//!
+//! All rates are sent as 4 byte little endian values.
+//!
+//! 1. The device sends the probe ``ESC 'B' '?'`` followed by the number of candidate rates as a single byte and the
+//!    candidates in the order of preference.
+//! 2. The host answers within [ANSWER_TIMEOUT] with ``ESC 'B' '!'`` followed by the rate it selected from the
+//!    candidates. A host not answering in time or selecting another rate leaves the rate unchanged.
+//! 3. Both sides switch to the selected rate. The device waits [SWITCH_DELAY] for the host to switch and sends the
+//!    confirmation ``ESC 'B' '='`` at the new rate, which the host echoes.
+//! 4. If the device does not receive the echo within [CONFIRM_TIMEOUT] both sides return to the previous rate. The
+//!    host does so if it does not receive the confirmation within [SWITCH_DELAY] and [CONFIRM_TIMEOUT].
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! if let Ok(baud_rate) = uart.negotiate_baud(&[921_600, 460_800]) {
+//!     // the console now runs at baud_rate
+//! }
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::{clock, UartResult};
+use ruspiro_timer as timer;
+
+/// The probe the device starts the negotiation with
+pub const PROBE: [u8; 3] = [0x1B, b'B', b'?'];
+
+/// The answer of the host selecting a rate
+pub const SELECT: [u8; 3] = [0x1B, b'B', b'!'];
+
+/// The confirmation sent by the device at the new rate and echoed by the host
+pub const CONFIRM: [u8; 3] = [0x1B, b'B', b'='];
+
+/// The maximum number of candidate rates offered with a single probe
+pub const MAX_CANDIDATES: usize = 8;
+
+/// The time in micro seconds the host has to answer the probe
+pub const ANSWER_TIMEOUT: u64 = 100_000;
+
+/// The time in micro seconds the device waits for the host to switch the rate before it sends the confirmation
+pub const SWITCH_DELAY: u64 = 10_000;
+
+/// The time in micro seconds the host has to echo the confirmation
+pub const CONFIRM_TIMEOUT: u64 = 100_000;
+
+// run the negotiation offering the candidates. ``switch`` changes the rate of the device once all data has been sent.
+// Returns the rate in use afterwards. A byte received with an error while waiting for the host fails the negotiation
+// with this error, after returning to the previous rate if it has been switched already
+pub(crate) fn negotiate(
+    current: u32,
+    candidates: &[u32],
+    send: impl Fn(&[u8]),
+    mut receive: impl FnMut() -> Option<UartResult<u8>>,
+    mut switch: impl FnMut(u32) -> UartResult<()>,
+) -> UartResult<u32> {
+    let candidates = &candidates[..candidates.len().min(MAX_CANDIDATES)];
+    if candidates.is_empty() {
+        return Ok(current);
+    }
+    send(&PROBE);
+    send(&[candidates.len() as u8]);
+    for candidate in candidates {
+        send(&candidate.to_le_bytes());
+    }
+
+    if !expect(&SELECT, ANSWER_TIMEOUT, &mut receive)? {
+        return Ok(current);
+    }
+    let mut selected = [0u8; 4];
+    for byte in selected.iter_mut() {
+        match receive_timeout(ANSWER_TIMEOUT, &mut receive)? {
+            Some(value) => *byte = value,
+            None => return Ok(current),
+        }
+    }
+    let selected = u32::from_le_bytes(selected);
+    if !candidates.contains(&selected) {
+        return Ok(current);
+    }
+
+    if switch(selected).is_err() {
+        return Ok(current);
+    }
+    timer::sleep(SWITCH_DELAY);
+    // drop anything received while the rates did not match, including the errors this causes
+    while receive().is_some() {}
+    send(&CONFIRM);
+    match expect(&CONFIRM, CONFIRM_TIMEOUT, &mut receive) {
+        Ok(true) => Ok(selected),
+        confirmed => {
+            // there is nothing left to fall back to if even the switch back fails
+            let _ = switch(current);
+            confirmed.map(|_| current)
+        }
+    }
+}
+
+// wait for the pattern to be received within the timeout in micro seconds, skipping any other data. Fails with the
+// error of a byte received with an error
+fn expect(
+    pattern: &[u8],
+    timeout: u64,
+    receive: &mut impl FnMut() -> Option<UartResult<u8>>,
+) -> UartResult<bool> {
+    let start = clock::now();
+    let mut matched = 0;
+    while matched < pattern.len() {
+        let remaining = timeout.saturating_sub(clock::elapsed_since(start));
+        match receive_timeout(remaining, receive)? {
+            Some(byte) if byte == pattern[matched] => matched += 1,
+            Some(byte) if byte == pattern[0] => matched = 1,
+            Some(_) => matched = 0,
+            None => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+// wait at most the timeout in micro seconds for the next byte
+fn receive_timeout(
+    timeout: u64,
+    receive: &mut impl FnMut() -> Option<UartResult<u8>>,
+) -> UartResult<Option<u8>> {
+    let start = clock::now();
+    loop {
+        if let Some(byte) = receive() {
+            return byte.map(Some);
+        }
+        if clock::elapsed_since(start) > timeout {
+            return Ok(None);
+        }
+        crate::watchdog::kick();
+        timer::sleepcycles(10);
+    }
+}
@@ -0,0 +1,105 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Lazy Initialized Uarts
+//!
+//! Library crates like a panic handler might need an Uart without knowing whether the application has initialized it
+//! already. A [OnceUart0] or [OnceUart1] placed in a ``static`` hands out the Uart initialized on first use. If several
+//! cores request the Uart at the same time exactly one of them initializes it, while the others wait for the
+//! initialization to finish. If the initialization fails the next request tries again. If the initialization panics
+//! all requests fail with ``Err(UartError::Poisoned)`` from then on, provided the panic unwinds. As a panic on a bare
+//! metal target usually never returns, [OnceUart1::get_or_init_timeout] bounds the wait for another core instead.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::once::OnceUart1;
+//! # use ruspiro_console::ConsoleImpl;
+//! static UART: OnceUart1 = OnceUart1::new();
+//!
+//! # fn doc() {
+//! if let Ok(uart) = UART.get_or_init(250_000_000, 115_200) {
+//!     uart.puts("Hello from any core\r\n");
+//! }
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::sealed::InitCell;
+use crate::{Timeout, TimeoutWait, Uart0, Uart1, UartError};
+
+macro_rules! once_uart {
+    ($(#[$doc:meta])* $name:ident, $uart:ident) => {
+        $(#[$doc])*
+        pub struct $name {
+            uart: InitCell<$uart>,
+        }
+
+        impl $name {
+            /// Create the lazy initialized Uart
+            pub const fn new() -> Self {
+                $name {
+                    uart: InitCell::new($uart::new()),
+                }
+            }
+
+            /// Get the Uart, initializing it with the given clock and baud rate if this is the first request. The
+            /// rates of later requests are ignored. If another core is initializing the Uart at the same time this
+            /// waits until it has finished. The error of a failed initialization is passed to the requester that tried
+            /// it. Fails with ``Err(UartError::Poisoned)`` if an initialization has panicked.
+            pub fn get_or_init(&self, clock_rate: u32, baud_rate: u32) -> Result<&$uart, UartError> {
+                self.get_or_init_timeout(clock_rate, baud_rate, Timeout::None)
+            }
+
+            /// Get the Uart like ``get_or_init``, but wait at most the given timeout for another core initializing
+            /// the Uart at the same time. Fails with ``Err(UartError::Busy)`` once the timeout has passed, e.g. as the
+            /// initializing core panicked and never finishes.
+            pub fn get_or_init_timeout(
+                &self,
+                clock_rate: u32,
+                baud_rate: u32,
+                timeout: Timeout,
+            ) -> Result<&$uart, UartError> {
+                let mut wait = TimeoutWait::start(timeout);
+                self.uart.get_or_try_init(
+                    |uart| uart.initialize(clock_rate, baud_rate),
+                    || {
+                        if wait.wait() {
+                            Ok(())
+                        } else {
+                            Err(UartError::Busy)
+                        }
+                    },
+                    || UartError::Poisoned,
+                )
+            }
+
+            /// Get the Uart if it has been initialized already
+            pub fn get(&self) -> Option<&$uart> {
+                self.uart.get()
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+    };
+}
+
+once_uart!(
+    /// Lazy initialized Uart0 (PL011) to be placed in a ``static``
+    OnceUart0,
+    Uart0
+);
+
+once_uart!(
+    /// Lazy initialized Uart1 (miniUART) to be placed in a ``static``
+    OnceUart1,
+    Uart1
+);
@@ -0,0 +1,250 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Point To Point Link
+//!
+//! A simple and reliable messaging protocol to connect two boards with their Uarts back-to-back (TX to RX and vice
+//! versa). Each message is sent in a frame protected by a CRC and carrying a sequence number. The receiver
+//! acknowledges every frame received correctly and requests a retransmit of frames received with a wrong CRC. The
+//! sender retransmits a frame until it has been acknowledged or the number of retries is exhausted (stop-and-wait ARQ).
+//! Frames received twice, as the acknowledge got lost, are acknowledged again but delivered only once.
+//!
+//! The protocol is intended for request/response style communication: while a message is sent, messages sent by the
+//! other side at the same time are not accepted but retransmitted by the other side later on.
+//!
+//! A frame is made up of the following bytes:
+//! ```text
+//! | 0x7E | kind | sequence | length | payload ... | CRC-16 high | CRC-16 low |
+//! ```
+//! The CRC-16/CCITT is calculated over kind, sequence, length and payload.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::{Timeout, Uart1};
+//! # use ruspiro_uart::p2plink::Link;
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! let mut link = Link::new(&uart);
+//! link.send(b"ping").expect("other side did not acknowledge");
+//!
+//! let mut buffer = [0u8; 64];
+//! if let Ok(size) = link.receive(&mut buffer, Timeout::Micros(100_000)) {
+//!     // process the message in buffer[..size]
+//! }
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::clock;
+use crate::crc::crc16;
+use crate::{watchdog, RxFlag, Timeout, TimeoutWait, Uart0, Uart1, UartError};
+use ruspiro_timer as timer;
+
+/// The maximum payload size of a single message
+pub const MAX_PAYLOAD: usize = 255;
+
+// the byte marking the start of each frame
+const FRAME_START: u8 = 0x7E;
+
+// the kinds of frames
+const KIND_DATA: u8 = 0x01;
+const KIND_ACK: u8 = 0x02;
+const KIND_NAK: u8 = 0x03;
+
+/// The transport the link sends and receives it's frames with. This is implemented for both Uarts.
+pub trait Transport {
+    /// Send the data
+    fn write(&self, data: &[u8]);
+    /// Receive a single byte, waiting at most ``timeout`` micro seconds
+    fn read_byte(&self, timeout: u64) -> Option<u8>;
+}
+
+impl Transport for Uart0 {
+    fn write(&self, data: &[u8]) {
+        self.write_data(data);
+    }
+
+    fn read_byte(&self, timeout: u64) -> Option<u8> {
+        let start = clock::now();
+        let mut received = [(0u8, RxFlag::Ok); 1];
+        // bytes received with an error are passed on as well, the CRC of the frame will reveal the corruption
+        while self.read_tagged(&mut received) == 0 {
+            if clock::elapsed_since(start) > timeout {
+                return None;
+            }
+            watchdog::kick();
+            timer::sleepcycles(10);
+        }
+        Some(received[0].0)
+    }
+}
+
+impl Transport for Uart1 {
+    fn write(&self, data: &[u8]) {
+        self.send_data(data);
+    }
+
+    fn read_byte(&self, timeout: u64) -> Option<u8> {
+        let mut received = [0u8; 1];
+        self.try_receive_data(&mut received, Timeout::Micros(timeout))
+            .ok()
+            .map(|_| received[0])
+    }
+}
+
+/// A reliable point to point link using the given [Transport]
+pub struct Link<'a, T: Transport> {
+    transport: &'a T,
+    tx_sequence: u8,
+    rx_sequence: Option<u8>,
+    ack_timeout: u64,
+    retries: u8,
+}
+
+// the header of a received frame
+struct Frame {
+    kind: u8,
+    sequence: u8,
+    length: usize,
+}
+
+impl<'a, T: Transport> Link<'a, T> {
+    /// Create a new link on top of the given transport. The transport need to be initialized already. The link waits
+    /// 50ms for a frame to be acknowledged and retries sending a frame 5 times.
+    pub fn new(transport: &'a T) -> Self {
+        Link {
+            transport,
+            tx_sequence: 0,
+            rx_sequence: None,
+            ack_timeout: 50_000,
+            retries: 5,
+        }
+    }
+
+    /// Set the time in micro seconds to wait for a frame to be acknowledged before it is sent again. This need to
+    /// cover the time to transfer the largest frame at the baud rate in use.
+    pub fn set_ack_timeout(&mut self, timeout: u64) {
+        self.ack_timeout = timeout;
+    }
+
+    /// Set the number of times a frame is sent again if it has not been acknowledged
+    pub fn set_retries(&mut self, retries: u8) {
+        self.retries = retries;
+    }
+
+    /// Send the message and wait until the other side has acknowledged it. Fails with
+    /// ``Err(UartError::PayloadTooLarge)`` if the message exceeds [MAX_PAYLOAD] bytes and with
+    /// ``Err(UartError::NoAcknowledge)`` if the message has not been acknowledged after all retries.
+    pub fn send(&mut self, payload: &[u8]) -> Result<(), UartError> {
+        if payload.len() > MAX_PAYLOAD {
+            return Err(UartError::PayloadTooLarge);
+        }
+        let sequence = self.tx_sequence;
+        let mut buffer = [0u8; MAX_PAYLOAD];
+        for _ in 0..=self.retries {
+            self.send_frame(KIND_DATA, sequence, payload);
+            let mut wait = TimeoutWait::start(Timeout::Micros(self.ack_timeout));
+            loop {
+                match self.receive_frame(&mut buffer, &mut wait) {
+                    Some(Ok(frame)) if frame.kind == KIND_ACK && frame.sequence == sequence => {
+                        self.tx_sequence = sequence.wrapping_add(1);
+                        return Ok(());
+                    }
+                    Some(Ok(frame)) if frame.kind == KIND_NAK => break,
+                    // a corrupted frame might be the acknowledge, so request it again by sending the frame again
+                    Some(Err(_)) => break,
+                    Some(Ok(frame)) if frame.kind == KIND_DATA => {
+                        // the other side did not get our acknowledge for it's last message, messages sent at the
+                        // same time are not accepted
+                        if Some(frame.sequence) == self.rx_sequence {
+                            self.send_frame(KIND_ACK, frame.sequence, &[]);
+                        }
+                    }
+                    Some(Ok(_)) => (),
+                    None => break,
+                }
+            }
+        }
+        Err(UartError::NoAcknowledge)
+    }
+
+    /// Wait at most the given timeout for a message to be received, acknowledge it and copy the payload into the
+    /// buffer. Returns the size of the payload. Fails with ``Err(UartError::ReceiveDataTimeOut)`` if no message
+    /// has been received in time and with ``Err(UartError::BufferTooSmall)`` if the message does not fit into the
+    /// buffer. In the latter case the message is not acknowledged, so the other side will send it again.
+    pub fn receive(&mut self, buffer: &mut [u8], timeout: Timeout) -> Result<usize, UartError> {
+        let mut payload = [0u8; MAX_PAYLOAD];
+        let mut wait = TimeoutWait::start(timeout);
+        loop {
+            match self.receive_frame(&mut payload, &mut wait) {
+                Some(Ok(frame)) if frame.kind == KIND_DATA => {
+                    if frame.length > buffer.len() {
+                        return Err(UartError::BufferTooSmall);
+                    }
+                    self.send_frame(KIND_ACK, frame.sequence, &[]);
+                    // a message received again as our acknowledge got lost is only acknowledged
+                    if Some(frame.sequence) != self.rx_sequence {
+                        self.rx_sequence = Some(frame.sequence);
+                        buffer[..frame.length].copy_from_slice(&payload[..frame.length]);
+                        return Ok(frame.length);
+                    }
+                }
+                Some(Err(sequence)) => self.send_frame(KIND_NAK, sequence, &[]),
+                Some(Ok(_)) => (),
+                None => return Err(UartError::ReceiveDataTimeOut),
+            }
+        }
+    }
+
+    // send a single frame
+    fn send_frame(&self, kind: u8, sequence: u8, payload: &[u8]) {
+        let header = [kind, sequence, payload.len() as u8];
+        let crc = crc16(crc16(0xFFFF, &header), payload);
+        self.transport.write(&[FRAME_START]);
+        self.transport.write(&header);
+        self.transport.write(payload);
+        self.transport.write(&crc.to_be_bytes());
+    }
+
+    // wait for the next frame to be received until the timeout of ``wait`` has passed. Returns ``None`` if there was
+    // no complete frame received in time and ``Some(Err(sequence))`` if the frame was corrupted.
+    fn receive_frame(
+        &self,
+        payload: &mut [u8; MAX_PAYLOAD],
+        wait: &mut TimeoutWait,
+    ) -> Option<Result<Frame, u8>> {
+        let mut next_byte = || loop {
+            if let Some(byte) = self.transport.read_byte(0) {
+                return Some(byte);
+            }
+            if !wait.wait() {
+                return None;
+            }
+        };
+        // skip everything until the start of the next frame
+        while next_byte()? != FRAME_START {}
+        let mut header = [0u8; 3];
+        for entry in header.iter_mut() {
+            *entry = next_byte()?;
+        }
+        let length = header[2] as usize;
+        for entry in payload[..length].iter_mut() {
+            *entry = next_byte()?;
+        }
+        let crc = u16::from_be_bytes([next_byte()?, next_byte()?]);
+        if crc != crc16(crc16(0xFFFF, &header), &payload[..length]) {
+            return Some(Err(header[1]));
+        }
+        Some(Ok(Frame {
+            kind: header[0],
+            sequence: header[1],
+            length,
+        }))
+    }
+}
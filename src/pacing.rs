@@ -0,0 +1,79 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Console Pacing
+//!
+//! Slow devices like thermal printers or old terminals silently drop characters received while they are still busy,
+//! especially right after a carriage return. The console output is paced for them by waiting after each character, and
+//! additionally after each carriage return, once it has left the transmitter.
+//!
+
+use crate::clock;
+use ruspiro_timer as timer;
+
+// the delays in micro seconds after each character and after each carriage return
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Pacing {
+    pub(crate) char_delay: u64,
+    pub(crate) line_delay: u64,
+}
+
+impl Pacing {
+    pub(crate) const NONE: Pacing = Pacing {
+        char_delay: 0,
+        line_delay: 0,
+    };
+
+    // whether any delay is configured
+    pub(crate) fn is_active(&self) -> bool {
+        *self != Pacing::NONE
+    }
+
+    // send the data byte by byte and wait for each byte to be transmitted before the delay starts. ``flush`` waits for
+    // the transmitter to get idle for at most the given time in micro seconds, 0 meaning no limit, and returns
+    // ``false`` if it did not. The wait is bound by the console timeout and the deadline, if any. Stops once the
+    // deadline has passed or the transmitter did not get idle in time and returns the number of bytes passed to
+    // ``send``
+    pub(crate) fn send(
+        &self,
+        data: &[u8],
+        deadline: Option<u64>,
+        console_timeout: u64,
+        send: impl Fn(&[u8]),
+        flush: impl Fn(u64) -> bool,
+    ) -> usize {
+        for (count, byte) in data.iter().enumerate() {
+            if matches!(deadline, Some(deadline) if clock::now() >= deadline) {
+                return count;
+            }
+            send(core::slice::from_ref(byte));
+            let timeout = match deadline {
+                Some(deadline) => match crate::console_byte_timeout(console_timeout, deadline) {
+                    Some(timeout) => timeout,
+                    None => return count + 1,
+                },
+                None => console_timeout,
+            };
+            if !flush(timeout) {
+                return count + 1;
+            }
+            let delay = if *byte == b'\r' {
+                self.char_delay + self.line_delay
+            } else {
+                self.char_delay
+            };
+            let delay = match deadline {
+                Some(deadline) => delay.min(deadline.saturating_sub(clock::now())),
+                None => delay,
+            };
+            if delay != 0 {
+                timer::sleep(delay);
+            }
+        }
+        data.len()
+    }
+}
@@ -0,0 +1,242 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Additional PL011 Uarts
+//!
+//! The BCM2711 of the Raspberry Pi 4 provides the additional PL011 instances UART2 to UART5 next to the Uart0. The
+//! [Pl011] drives any PL011 instance given it's register base address, the base address of the GPIO block and the GPIO
+//! pins it is routed to. The base addresses and default pins of the BCM2711 instances are available through
+//! [Pl011Instance]. As the register blocks are accessed at the addresses given, creating a [Pl011] is ``unsafe``.
+//!
+//! The [Pl011] runs the same register level steps as the [Uart0](crate::Uart0), but covers the basic operation only.
+//! The interrupt handling, leases and the other extensions of the [Uart0](crate::Uart0) are not available. The pins are
+//! routed by writing the given GPIO block directly, as the GPIO crate addresses the GPIO block of the Raspberry Pi 3.
+//! The timeouts of the initialization are taken from the [clock](crate::clock), which need to be set to a clock of the
+//! board in use, e.g. with [set_clock](crate::clock::set_clock), if it is not a Raspberry Pi 3.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::pl011::*;
+//! # use ruspiro_console::ConsoleImpl;
+//! # fn doc() {
+//! // the peripherals of the Raspberry Pi 4 in low peripheral mode
+//! let instance = Pl011Instance::Uart2;
+//! let mut uart = unsafe {
+//!     Pl011::new(
+//!         0xFE00_0000 + instance.base_offset(),
+//!         0xFE00_0000 + GPIO_OFFSET,
+//!         instance.pins(),
+//!     )
+//! };
+//! let _ = uart.initialize(48_000_000, 115_200);
+//! uart.puts("Hello from UART2\r\n");
+//! # }
+//! ```
+
+use crate::sealed::MmioBlock;
+use crate::uart0::interface;
+use crate::{Direction, InitReport, RxFlag, Timeout, UartConfig, UartError};
+use ruspiro_console::ConsoleImpl;
+
+/// The offset of the GPIO block from the peripheral base address
+pub const GPIO_OFFSET: u32 = 0x0020_0000;
+
+/// The GPIO pins a PL011 is routed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pl011Pins {
+    /// the pin of the transmit line
+    pub tx: u32,
+    /// the pin of the receive line
+    pub rx: u32,
+    /// the alternative function (0 to 5) routing the pins to the PL011
+    pub alt: u8,
+}
+
+/// The additional PL011 instances of the BCM2711
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pl011Instance {
+    /// UART2, on GPIO0/1 with alt4
+    Uart2,
+    /// UART3, on GPIO4/5 with alt4
+    Uart3,
+    /// UART4, on GPIO8/9 with alt4
+    Uart4,
+    /// UART5, on GPIO12/13 with alt4
+    Uart5,
+}
+
+impl Pl011Instance {
+    /// The offset of the register block from the peripheral base address
+    pub const fn base_offset(self) -> u32 {
+        match self {
+            Pl011Instance::Uart2 => 0x0020_1400,
+            Pl011Instance::Uart3 => 0x0020_1600,
+            Pl011Instance::Uart4 => 0x0020_1800,
+            Pl011Instance::Uart5 => 0x0020_1A00,
+        }
+    }
+
+    /// The default GPIO pins of the instance
+    pub const fn pins(self) -> Pl011Pins {
+        let tx = match self {
+            Pl011Instance::Uart2 => 0,
+            Pl011Instance::Uart3 => 4,
+            Pl011Instance::Uart4 => 8,
+            Pl011Instance::Uart5 => 12,
+        };
+        Pl011Pins {
+            tx,
+            rx: tx + 1,
+            alt: 4,
+        }
+    }
+}
+
+/// A PL011 Uart at a given register base address
+pub struct Pl011 {
+    registers: MmioBlock,
+    gpio: MmioBlock,
+    pins: Pl011Pins,
+    initialized: bool,
+}
+
+impl Pl011 {
+    /// Get a new PL011 with it's registers at the given base address, routed to the given pins with the GPIO block at
+    /// the given base address. It need to be initialized before it can be used.
+    ///
+    /// # Safety
+    /// The base addresses need to be the addresses of the register block of a PL011 and of the GPIO block that are
+    /// mapped for the lifetime of the [Pl011], and no other driver may access this PL011 or the pins meanwhile.
+    pub const unsafe fn new(base_addr: u32, gpio_base: u32, pins: Pl011Pins) -> Self {
+        Pl011 {
+            // the caller guarantees the base addresses to be a mapped PL011 and GPIO block
+            registers: unsafe { MmioBlock::new(base_addr as usize) },
+            gpio: unsafe { MmioBlock::new(gpio_base as usize) },
+            pins,
+            initialized: false,
+        }
+    }
+
+    /// Initialize the PL011 with the given Uart clock rate and baud rate, using 8 data bits, no parity and 1 stop bit.
+    /// Fails with ``Err(UartError::GpioError)`` if the alternative function of the pins is not in the range 0 to 5,
+    /// and with ``Err(UartError::PinBusy)`` if a pin is routed to another peripheral.
+    pub fn initialize(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        if self.initialized {
+            return Err(UartError::AlreadyInitialized);
+        }
+        if baud_rate == 0 || baud_rate > clock_rate / 16 {
+            return Err(UartError::UnsupportedBaudRate);
+        }
+        interface::route_pins(&self.gpio, self.pins, Direction::Both)?;
+        let mut report = InitReport::new();
+        if let Err(error) = interface::setup(
+            &self.registers,
+            clock_rate,
+            baud_rate,
+            Direction::Both,
+            &UartConfig::EIGHT_N_ONE,
+            &mut report,
+        ) {
+            interface::unroute_pins(&self.gpio, self.pins, Direction::Both);
+            return Err(error);
+        }
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Send the data. If the PL011 is not initialized nothing is sent.
+    pub fn send_data(&self, data: &[u8]) {
+        if !self.initialized {
+            return;
+        }
+        for byte in data {
+            interface::write_blocking(&self.registers, *byte);
+        }
+    }
+
+    /// Send a string
+    pub fn send_string(&self, s: &str) {
+        self.send_data(s.as_bytes());
+    }
+
+    /// Wait until all data has been sent
+    pub fn flush(&self) {
+        if self.initialized {
+            interface::drain(&self.registers);
+        }
+    }
+
+    /// Receive the next byte together with the error state the PL011 reports along with it, if one is available,
+    /// without waiting for data to arrive
+    pub fn try_receive(&self) -> Option<(u8, RxFlag)> {
+        if self.initialized {
+            interface::read_tagged(&self.registers)
+        } else {
+            None
+        }
+    }
+
+    /// Receive data into the buffer, waiting at most the given timeout for the buffer to be filled. Returns
+    /// ``Ok(size)`` with the number of bytes received before the timeout has passed, or
+    /// ``Err(UartError::ReceiveDataTimeOut)`` if no data has been received at all. ``Timeout::Cycles(0)`` takes the
+    /// data currently available without waiting. Fails with ``Err(UartError::ParityError)``,
+    /// ``Err(UartError::FramingError)`` or ``Err(UartError::BreakReceived)`` for a byte received with the respective
+    /// error, the bytes received before it are lost.
+    pub fn try_receive_data(
+        &self,
+        buffer: &mut [u8],
+        timeout: Timeout,
+    ) -> Result<usize, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        if buffer.is_empty() {
+            return Err(UartError::EmptyBuffer);
+        }
+        let receive = || {
+            self.try_receive().map(|(data, flag)| match flag.error() {
+                Some(error) => Err(error),
+                None => Ok(data),
+            })
+        };
+        match crate::receive_timeout(buffer, timeout, receive, Err)? {
+            0 => Err(UartError::ReceiveDataTimeOut),
+            count => Ok(count),
+        }
+    }
+
+    /// Shut down the PL011 and switch it's pins back to inputs
+    pub fn deinit(&mut self) {
+        if self.initialized {
+            self.flush();
+            self.registers.write(CR, 0);
+            interface::unroute_pins(&self.gpio, self.pins, Direction::Both);
+            self.initialized = false;
+        }
+    }
+}
+
+// the control register of the PL011
+const CR: usize = 0x30;
+
+impl Drop for Pl011 {
+    fn drop(&mut self) {
+        // switch the GPIO pin's used by the PL011 back to inputs
+        self.deinit();
+    }
+}
+
+// to use the PL011 as a console to output strings implement the respective trait
+impl ConsoleImpl for Pl011 {
+    fn putc(&self, c: char) {
+        self.send_data(&[c as u8]);
+    }
+
+    fn puts(&self, s: &str) {
+        self.send_string(s);
+    }
+}
@@ -0,0 +1,120 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Console Line Prefix
+//!
+//! Adds the configured prefix to the start of each line of the console output, so the output of several cores can be
+//! ordered and timed on the host.
+//!
+
+use crate::{clock, Prefix, CORES};
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// the maximum length of a formatted prefix
+const PREFIX_LENGTH: usize = 32;
+
+// the maximum length of the prefix and the start of the line sent together
+const CHUNK_LENGTH: usize = 128;
+
+// Tracks per core whether the next byte of the console output of a core starts a new line. The cores write their
+// lines independently, so a core ending a line must not make another core prefix the middle of it's line.
+pub(crate) struct LineStart {
+    cores: [AtomicBool; CORES],
+}
+
+impl LineStart {
+    pub(crate) const fn new() -> Self {
+        LineStart {
+            cores: [
+                AtomicBool::new(true),
+                AtomicBool::new(true),
+                AtomicBool::new(true),
+                AtomicBool::new(true),
+            ],
+        }
+    }
+
+    // the flag of the core executing this code
+    fn current(&self) -> &AtomicBool {
+        &self.cores[crate::core_id() % CORES]
+    }
+}
+
+// split the console output into lines and send the prefix before the first byte of each line. The prefix is sent
+// together with the start of the line in one call of ``send``, so the output of another core can not end up between
+// them. ``line_start`` keeps track whether the next byte starts a new line across calls
+pub(crate) fn write_prefixed(
+    prefix: Prefix,
+    line_start: &LineStart,
+    data: &[u8],
+    send: impl Fn(&[u8]),
+) {
+    let line_start = line_start.current();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (line, remaining) = match rest.iter().position(|byte| *byte == b'\n') {
+            Some(idx) => rest.split_at(idx + 1),
+            None => (rest, &[][..]),
+        };
+        let mut unsent = line;
+        if line_start.swap(false, Ordering::Relaxed) {
+            let mut buffer = PrefixBuffer {
+                data: [0; CHUNK_LENGTH],
+                length: 0,
+            };
+            if format_prefix(prefix, &mut buffer).is_ok() {
+                let count = line.len().min(CHUNK_LENGTH - buffer.length);
+                buffer.data[buffer.length..buffer.length + count].copy_from_slice(&line[..count]);
+                send(&buffer.data[..buffer.length + count]);
+                unsent = &line[count..];
+            }
+        }
+        if !unsent.is_empty() {
+            send(unsent);
+        }
+        if line.last() == Some(&b'\n') {
+            line_start.store(true, Ordering::Relaxed);
+        }
+        rest = remaining;
+    }
+}
+
+// write the prefix for the current time and core
+fn format_prefix(prefix: Prefix, w: &mut impl Write) -> fmt::Result {
+    let now = clock::now();
+    match prefix {
+        Prefix::None => Ok(()),
+        Prefix::TimestampMicros => write!(w, "[{:>6}.{:06}] ", now / 1_000_000, now % 1_000_000),
+        Prefix::CoreId => write!(w, "[core {}] ", crate::core_id()),
+        Prefix::Both => write!(
+            w,
+            "[{:>6}.{:06} core {}] ",
+            now / 1_000_000,
+            now % 1_000_000,
+            crate::core_id()
+        ),
+    }
+}
+
+// fixed size buffer the prefix is formatted into, followed by the start of the line
+struct PrefixBuffer {
+    data: [u8; CHUNK_LENGTH],
+    length: usize,
+}
+
+impl Write for PrefixBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.length + s.len();
+        if end > PREFIX_LENGTH {
+            return Err(fmt::Error);
+        }
+        self.data[self.length..end].copy_from_slice(s.as_bytes());
+        self.length = end;
+        Ok(())
+    }
+}
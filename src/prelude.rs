@@ -0,0 +1,45 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Prelude
+//!
+//! Re-exports everything typically needed to work with the Uarts, so a single import is sufficient.
+//!
+//! # Example
+//! ```no_run
+//! use ruspiro_uart::prelude::*;
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! if uart.initialize(250_000_000, 115_200).is_ok() {
+//!     uart.set_encoding(Encoding::Latin1, b'?');
+//!     uart.enable_interrupts(InterruptType::Receive);
+//!     uart.puts("Hello Uart...\r\n");
+//! }
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+pub use crate::buffer::BufferStorage;
+pub use crate::diagnose::Finding;
+pub use crate::encoding::Encoding;
+pub use crate::error::{InitStage, UartError};
+pub use crate::hal::HalUart;
+pub use crate::irqstats::IrqStats;
+pub use crate::pl011::{Pl011, Pl011Instance, Pl011Pins};
+pub use crate::router::SeverityRouter;
+pub use crate::softuart::SoftUartTx;
+pub use crate::uart0::{IrqFlags, LeaseConfig, Purpose, Uart0, Uart0Lease};
+pub use crate::uart1::{RtsLevel, Uart1, Uart1Buffered, Uart1IrqSource, Uart1IrqStatus};
+#[cfg(feature = "async")]
+pub use crate::{uart0::Uart0Async, uart1::Uart1Async};
+pub use crate::{
+    ConsoleMode, Escape, FifoMode, HexCase, InitReport, InitStep, InterruptType, LineErrors,
+    Parity, Prefix, ReadOutcome, RetryPolicy, RxError, RxErrorAction, RxFlag, Timeout, UartConfig,
+    UartEvent, UartState,
+};
+pub use ruspiro_console::ConsoleImpl;
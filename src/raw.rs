@@ -0,0 +1,230 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Raw Register Access
+//!
+//! The MMIO register definitions of the Uart peripherals for advanced use cases the driver does not cover (yet), like
+//! a custom interrupt mask. Registers that can only be read without side effects are re-exported as they are. Writing
+//! to any other register may break the invariants the drivers rely on (e.g. the configured baud rate or the state of
+//! the FIFO's) and is therefore only possible through an ``unsafe`` accessor of the respective register. The same
+//! applies to the line status register of the Uart1, as reading it clears the receiver overrun flag the driver reports.
+//! The bit field definitions of those registers are always accessible.
+//!
+//! To take over an Uart for a while, e.g. to work around the quirks of a bluetooth controller, the driver instance is
+//! turned into a [RawUart0] or [RawUart1] with ``into_raw``. This keeps the peripheral configured and it's pins claimed,
+//! and records what the driver has configured. Passing it to ``from_raw`` hands the peripheral back to the driver
+//! without initializing it again.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::raw::uart0::*;
+//! # fn doc() {
+//! // check whether the transmit FIFO of the Uart0 is full
+//! let tx_full = UART0_FR::Register.read(UART0_FR::TXFF) == 1;
+//! // additionaly raise an interrupt on framing errors
+//! unsafe { imsc::register() }.write(imsc::INT_FE, 0x1);
+//! # }
+//! ```
+
+use ruspiro_register::{ReadOnly, ReadWrite};
+
+/// The Uart0 handed over to manual register control with [Uart0::into_raw](crate::Uart0::into_raw)
+#[derive(Debug, PartialEq, Eq)]
+pub struct RawUart0 {
+    pub(crate) tx_pin: Option<u32>,
+    pub(crate) rx_pin: Option<u32>,
+    pub(crate) divisor: u32,
+    pub(crate) clock_rate: u32,
+    pub(crate) baud_rate: u32,
+}
+
+impl RawUart0 {
+    /// The address of the register block of the Uart0
+    pub fn base_address(&self) -> usize {
+        crate::uart0::interface::base_address()
+    }
+
+    /// The GPIO pin claimed for the transmit line, ``None`` if the Uart0 only receives
+    pub fn tx_pin(&self) -> Option<u32> {
+        self.tx_pin
+    }
+
+    /// The GPIO pin claimed for the receive line, ``None`` if the Uart0 only transmits
+    pub fn rx_pin(&self) -> Option<u32> {
+        self.rx_pin
+    }
+
+    /// The baud rate divisor in 1/64, as the integer part in ``IBRD`` shifted by 6 bits plus the fractional part in
+    /// ``FBRD``
+    pub fn divisor(&self) -> u32 {
+        self.divisor
+    }
+
+    /// The clock rate the Uart0 has been initialized with
+    pub fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
+    /// The baud rate the Uart0 has been configured with
+    pub fn baud_rate(&self) -> u32 {
+        self.baud_rate
+    }
+}
+
+/// The Uart1 handed over to manual register control with [Uart1::into_raw](crate::Uart1::into_raw)
+#[derive(Debug, PartialEq, Eq)]
+pub struct RawUart1 {
+    pub(crate) tx_pin: Option<u32>,
+    pub(crate) rx_pin: Option<u32>,
+    pub(crate) flow_control: bool,
+    pub(crate) divisor: u32,
+    pub(crate) clock_rate: u32,
+    pub(crate) baud_rate: u32,
+}
+
+impl RawUart1 {
+    /// The address of the AUX block the Uart1 belongs to
+    pub fn base_address(&self) -> usize {
+        crate::uart1::interface::uart1_base_address()
+    }
+
+    /// The GPIO pin claimed for the transmit line, ``None`` if the Uart1 only receives
+    pub fn tx_pin(&self) -> Option<u32> {
+        self.tx_pin
+    }
+
+    /// The GPIO pin claimed for the receive line, ``None`` if the Uart1 only transmits
+    pub fn rx_pin(&self) -> Option<u32> {
+        self.rx_pin
+    }
+
+    /// The GPIO pins claimed for CTS and RTS, if the auto flow control is enabled
+    pub fn flow_control_pins(&self) -> Option<(u32, u32)> {
+        if self.flow_control {
+            Some((16, 17))
+        } else {
+            None
+        }
+    }
+
+    /// The value of the baud rate register
+    pub fn divisor(&self) -> u32 {
+        self.divisor
+    }
+
+    /// The core clock rate the Uart1 has been initialized with
+    pub fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
+    /// The baud rate the Uart1 has been configured with
+    pub fn baud_rate(&self) -> u32 {
+        self.baud_rate
+    }
+}
+
+// define a module for a writable register re-exporting it's fields while the register itself is only accessible
+// through an unsafe function
+macro_rules! unsafe_register {
+    ($($(#[$doc:meta])* $name:ident => $reg:ident $({ $($field:ident),* })?),* $(,)?) => {
+        $(
+            $(#[$doc])*
+            pub mod $name {
+                use super::ReadWrite;
+                $(pub use super::$reg::{$($field),*};)?
+
+                /// Access the register.
+                ///
+                /// # Safety
+                /// Writing to the register may break the invariants the Uart driver relies on. The caller need to
+                /// ensure the driver is not used while the register is modified and that the peripheral is left in a
+                /// state the driver is able to continue with.
+                pub unsafe fn register() -> ReadWrite<u32> {
+                    super::$reg::Register
+                }
+            }
+        )*
+    };
+}
+
+/// The registers of the Uart0 (PL011) peripheral
+pub mod uart0 {
+    use super::ReadWrite;
+    use crate::uart0::interface::*;
+    pub use crate::uart0::interface::{UART0_FR, UART0_MIS, UART0_RIS};
+
+    unsafe_register![
+        /// Data register
+        dr => UART0_DR,
+        /// Receive status / error clear register
+        rsrecr => UART0_RSRECR,
+        /// Integer baud rate divisor
+        ibrd => UART0_IBRD,
+        /// Fractional baud rate divisor
+        fbrd => UART0_FBRD,
+        /// Line control register
+        lcrh => UART0_LCRH { SPS, WLEN, FEN, STP2, EPS, PEN, BRK },
+        /// Control register
+        cr => UART0_CR {
+            CTSEN, RTSEN, OUT2, OUT1, RTS, DTR, RXE, TXE, LBE, UART_EN
+        },
+        /// Interrupt FIFO level select register
+        ifls => UART0_IFLS { RXIFSEL, TXIFSEL },
+        /// Interrupt mask set/clear register
+        imsc => UART0_IMSC {
+            INT_OE, INT_BE, INT_PE, INT_FE, INT_RT, INT_TX, INT_RX, INT_DSRM, INT_DCDM, INT_CTSM
+        },
+        /// Interrupt clear register
+        icr => UART0_ICR,
+        /// DMA control register
+        dmacr => UART0_DMACR { DMAONERR, TXDMAE, RXDMAE },
+    ];
+}
+
+/// The registers of the Uart1 (miniUART) peripheral and the AUX block it belongs to
+pub mod uart1 {
+    use super::{ReadOnly, ReadWrite};
+    use crate::uart1::interface::*;
+    pub use crate::uart1::interface::{AUX_IRQ, AUX_MU_MSR_REG, AUX_MU_STAT_REG};
+
+    /// Line status register
+    pub mod aux_mu_lsr {
+        use super::ReadOnly;
+        pub use super::AUX_MU_LSR_REG::{DATAREADY, RCVOVERRUN, TRANSEMPTY, TRANSIDLE};
+
+        /// Access the register.
+        ///
+        /// # Safety
+        /// Reading the register clears the receiver overrun flag. An overrun seen by the caller is therefore no
+        /// longer reported by the Uart1 driver as ``UartEvent::Error(RxError::Overrun)``. The caller need to ensure
+        /// the driver is not relying on the flag while the register is read.
+        pub unsafe fn register() -> ReadOnly<u32> {
+            super::AUX_MU_LSR_REG::Register
+        }
+    }
+
+    unsafe_register![
+        /// Auxiliary enables register, shared with the SPI1 and SPI2 peripherals
+        aux_enables => AUX_ENABLES { MINIUART_ENABLE, SPI1_ENABLE, SPI2_ENABLE },
+        /// Data register
+        aux_mu_io => AUX_MU_IO_REG,
+        /// Interrupt enable register
+        aux_mu_ier => AUX_MU_IER_REG { RX_ENABLE, TX_ENABLE, RCV_IRQ },
+        /// Interrupt identify register
+        aux_mu_iir => AUX_MU_IIR_REG { IRQPENDING, IRQID_FIFOCLR, FIFO_ENABLES },
+        /// Line control register
+        aux_mu_lcr => AUX_MU_LCR_REG { DATASIZE, BREAK, DLAB },
+        /// Modem control register
+        aux_mu_mcr => AUX_MU_MCR_REG,
+        /// Extra control register
+        aux_mu_cntl => AUX_MU_CNTL_REG {
+            RCV_ENABLE, TRANS_ENABLE, AUTO_FLOW_RTS, AUTO_FLOW_CTS, AUTO_RTS_LEVEL, RTS_ASSERT, CTS_ASSERT
+        },
+        /// Baud rate register
+        aux_mu_baud => AUX_MU_BAUD_REG,
+    ];
+}
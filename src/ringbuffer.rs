@@ -0,0 +1,18 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Ring Buffer
+//!
+//! A fixed size single producer single consumer ring buffer that is safe to be filled from within an interrupt
+//! handler while being drained from the regular program flow (or vice versa). The Uarts use it to pass received data
+//! and events out of their interrupt handlers, other drivers (e.g. SPI or I2C) may use it the same way. The positions
+//! wrap at twice the capacity to be able to distinguish a full from an empty buffer.
+//!
+
+#![forbid(unsafe_code)]
+
+pub use crate::sealed::SpscRing;
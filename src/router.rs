@@ -0,0 +1,155 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Severity Router
+//!
+//! A ``SeverityRouter`` takes the place of a single Uart as console and splits the output by severity onto two devices.
+//! Every line is sent to the primary device, while the lines matched as errors are sent to the secondary device as
+//! well. Operations get a low-noise channel carrying only the errors without any change to the application's logging.
+//! With the ``tx_history`` feature active the error lines are also kept in a history of their own, see
+//! [last_errors](crate::history::last_errors).
+//!
+//! The console hands the output over in pieces, e.g. a formatted line arrives as the literal parts and the formatted
+//! arguments. The matcher is therefore given the first piece of each line and decides for the whole line, which works
+//! as long as the severity is part of the literal text the line starts with.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::prelude::*;
+//! # use ruspiro_uart::router::SeverityRouter;
+//! # fn doc() {
+//! let mut primary = Uart1::new();
+//! let _ = primary.initialize(250_000_000, 115_200);
+//! let mut secondary = Uart0::new();
+//! let _ = secondary.initialize(48_000_000, 115_200);
+//!
+//! let router = SeverityRouter::new(primary, secondary);
+//! router.puts("INFO: all fine\r\n");
+//! // this line is sent on both Uarts
+//! router.puts("ERROR: sensor lost\r\n");
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use core::sync::atomic::{AtomicU8, Ordering};
+use ruspiro_console::ConsoleImpl;
+
+// the state of the current line
+const LINE_START: u8 = 0;
+const LINE_ROUTINE: u8 = 1;
+const LINE_ERROR: u8 = 2;
+
+/// Match the lines starting with ``ERROR``, the default matcher of a [SeverityRouter]
+pub fn error_prefix(line: &str) -> bool {
+    line.starts_with("ERROR")
+}
+
+/// The console splitting the output by severity onto a primary and a secondary device
+pub struct SeverityRouter<P: ConsoleImpl, S: ConsoleImpl> {
+    primary: P,
+    secondary: S,
+    matcher: fn(&str) -> bool,
+    line: AtomicU8,
+}
+
+impl<P: ConsoleImpl, S: ConsoleImpl> SeverityRouter<P, S> {
+    /// Create a router sending all output to the primary device and the lines starting with ``ERROR`` to the secondary
+    /// device as well. Both devices need to be initialized already.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::prelude::*;
+    /// # use ruspiro_uart::router::SeverityRouter;
+    /// # fn doc() {
+    /// let router = SeverityRouter::new(Uart1::new(), Uart0::new());
+    /// # }
+    /// ```
+    pub fn new(primary: P, secondary: S) -> Self {
+        SeverityRouter {
+            primary,
+            secondary,
+            matcher: error_prefix,
+            line: AtomicU8::new(LINE_START),
+        }
+    }
+
+    /// Set the function deciding whether a line is an error. It is given the first piece of output of each line.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::prelude::*;
+    /// # use ruspiro_uart::router::SeverityRouter;
+    /// # fn doc() {
+    /// let mut router = SeverityRouter::new(Uart1::new(), Uart0::new());
+    /// router.set_matcher(|line| line.starts_with("E:") || line.starts_with("FATAL"));
+    /// # }
+    /// ```
+    pub fn set_matcher(&mut self, matcher: fn(&str) -> bool) {
+        self.matcher = matcher;
+    }
+
+    /// The device receiving all output
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    /// The device receiving the error lines
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+
+    // route a piece of output ending at the latest with the end of a line
+    fn route(&self, piece: &str) {
+        let mut line = self.line.load(Ordering::Relaxed);
+        if line == LINE_START {
+            line = if (self.matcher)(piece) {
+                LINE_ERROR
+            } else {
+                LINE_ROUTINE
+            };
+        }
+        self.primary.puts(piece);
+        if line == LINE_ERROR {
+            self.secondary.puts(piece);
+            #[cfg(feature = "tx_history")]
+            crate::history::record_error(piece.as_bytes());
+        }
+        if piece.ends_with('\n') {
+            line = LINE_START;
+        }
+        self.line.store(line, Ordering::Relaxed);
+    }
+}
+
+// the devices release their resources on their own, a line cut off by dropping the router is terminated so the output
+// of the next console starts on a line of it's own
+impl<P: ConsoleImpl, S: ConsoleImpl> Drop for SeverityRouter<P, S> {
+    fn drop(&mut self) {
+        if self.line.load(Ordering::Relaxed) != LINE_START {
+            self.route("\r\n");
+        }
+    }
+}
+
+// to use the router as a console to output strings implement the respective trait
+impl<P: ConsoleImpl, S: ConsoleImpl> ConsoleImpl for SeverityRouter<P, S> {
+    fn putc(&self, c: char) {
+        let mut buffer = [0u8; 4];
+        self.route(c.encode_utf8(&mut buffer));
+    }
+
+    fn puts(&self, s: &str) {
+        let mut rest = s;
+        while let Some(end) = rest.find('\n') {
+            let (piece, tail) = rest.split_at(end + 1);
+            self.route(piece);
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            self.route(rest);
+        }
+    }
+}
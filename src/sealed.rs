@@ -0,0 +1,989 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Internal Unsafe Boundary
+//!
+//! All ``unsafe`` code of the crate that is used by the public modules lives in this module, so it can be audited in
+//! one place. The public modules forbid ``unsafe`` code altogether. Each item documents the invariant it relies on,
+//! the safe interface of the item upholds it. The remaining ``unsafe`` code is confined to the accessors of the ``raw``
+//! module, the C interface of the ``ffi`` module and the constructor of a ``Pl011`` at an arbitrary address, which are
+//! ``unsafe`` by intention. The core id is read here as well.
+//! The registers of the DMA controller and the cache maintenance needed for DMA transfers are accessed here as well.
+//! The ``AsBytes`` trait of the ``telemetry`` module is declared here, as it's implementations need to be ``unsafe``.
+//!
+
+#[cfg(feature = "early_log")]
+use crate::early::EARLY_LOG_SIZE;
+#[cfg(feature = "tx_history")]
+use crate::history::TxHistory;
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+#[cfg(feature = "async")]
+use core::task::Waker;
+
+// the id of the core executing this code. Reading the MPIDR register has no side effects and is allowed at any
+// exception level the crate runs at
+pub(crate) fn core_id() -> usize {
+    #[allow(unused_mut)]
+    let mut mpidr: u64 = 0;
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        llvm_asm!("mrs $0, mpidr_el1":"=r"(mpidr):::"volatile")
+    };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        let value: u32;
+        llvm_asm!("mrc p15, 0, $0, c0, c0, 5":"=r"(value):::"volatile");
+        mpidr = value as u64;
+    };
+    (mpidr & 0x3) as usize
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+// a function pointer that can be stored in a FnHook
+pub(crate) trait HookFn: private::Sealed + Copy {
+    // the address of the function
+    fn address(self) -> usize;
+    // the function at the address
+    //
+    // # Safety
+    // The address need to be taken from a function of the same type with ``address``
+    unsafe fn from_address(address: usize) -> Self;
+}
+
+impl private::Sealed for fn() {}
+
+impl HookFn for fn() {
+    fn address(self) -> usize {
+        self as usize
+    }
+
+    unsafe fn from_address(address: usize) -> Self {
+        unsafe { core::mem::transmute(address) }
+    }
+}
+
+impl private::Sealed for fn() -> u64 {}
+
+impl HookFn for fn() -> u64 {
+    fn address(self) -> usize {
+        self as usize
+    }
+
+    unsafe fn from_address(address: usize) -> Self {
+        unsafe { core::mem::transmute(address) }
+    }
+}
+
+#[cfg(feature = "trace")]
+impl private::Sealed for fn(crate::trace::TraceEvent) {}
+
+#[cfg(feature = "trace")]
+impl HookFn for fn(crate::trace::TraceEvent) {
+    fn address(self) -> usize {
+        self as usize
+    }
+
+    unsafe fn from_address(address: usize) -> Self {
+        unsafe { core::mem::transmute(address) }
+    }
+}
+
+#[cfg(feature = "fiq")]
+impl private::Sealed for fn(u8, crate::RxFlag) {}
+
+#[cfg(feature = "fiq")]
+impl HookFn for fn(u8, crate::RxFlag) {
+    fn address(self) -> usize {
+        self as usize
+    }
+
+    unsafe fn from_address(address: usize) -> Self {
+        unsafe { core::mem::transmute(address) }
+    }
+}
+
+#[cfg(feature = "heartbeat")]
+impl private::Sealed for crate::heartbeat::HeartbeatFn {}
+
+#[cfg(feature = "heartbeat")]
+impl HookFn for crate::heartbeat::HeartbeatFn {
+    fn address(self) -> usize {
+        self as usize
+    }
+
+    unsafe fn from_address(address: usize) -> Self {
+        unsafe { core::mem::transmute(address) }
+    }
+}
+
+#[cfg(feature = "ffi")]
+impl private::Sealed for crate::ffi::RxCallback {}
+
+#[cfg(feature = "ffi")]
+impl HookFn for crate::ffi::RxCallback {
+    fn address(self) -> usize {
+        self as usize
+    }
+
+    unsafe fn from_address(address: usize) -> Self {
+        unsafe { core::mem::transmute(address) }
+    }
+}
+
+#[cfg(feature = "dma")]
+impl private::Sealed for crate::uart0::FrameFn {}
+
+#[cfg(feature = "dma")]
+impl HookFn for crate::uart0::FrameFn {
+    fn address(self) -> usize {
+        self as usize
+    }
+
+    unsafe fn from_address(address: usize) -> Self {
+        unsafe { core::mem::transmute(address) }
+    }
+}
+
+impl private::Sealed for fn(crate::Peripheral) {}
+
+impl HookFn for fn(crate::Peripheral) {
+    fn address(self) -> usize {
+        self as usize
+    }
+
+    unsafe fn from_address(address: usize) -> Self {
+        unsafe { core::mem::transmute(address) }
+    }
+}
+
+impl private::Sealed for fn(crate::uart0::IrqFlags) {}
+
+impl HookFn for fn(crate::uart0::IrqFlags) {
+    fn address(self) -> usize {
+        self as usize
+    }
+
+    unsafe fn from_address(address: usize) -> Self {
+        unsafe { core::mem::transmute(address) }
+    }
+}
+
+impl private::Sealed for fn(crate::slowsend::SlowSend) {}
+
+impl HookFn for fn(crate::slowsend::SlowSend) {
+    fn address(self) -> usize {
+        self as usize
+    }
+
+    unsafe fn from_address(address: usize) -> Self {
+        unsafe { core::mem::transmute(address) }
+    }
+}
+
+// A hook function that can be registered and called from any core and from interrupt handlers. The function is stored
+// as it's address, 0 if there is no hook registered. Only addresses of functions of type F are ever stored.
+pub(crate) struct FnHook<F: HookFn> {
+    address: AtomicUsize,
+    _hook: core::marker::PhantomData<F>,
+}
+
+impl<F: HookFn> FnHook<F> {
+    pub(crate) const fn new() -> Self {
+        FnHook {
+            address: AtomicUsize::new(0),
+            _hook: core::marker::PhantomData,
+        }
+    }
+
+    // register the hook or remove it if ``None`` is given
+    pub(crate) fn set(&self, hook: Option<F>) {
+        self.address
+            .store(hook.map_or(0, HookFn::address), Ordering::Release);
+    }
+
+    // the registered hook
+    pub(crate) fn get(&self) -> Option<F> {
+        match self.address.load(Ordering::Acquire) {
+            0 => None,
+            // the address has been stored from a function of type F
+            address => Some(unsafe { F::from_address(address) }),
+        }
+    }
+}
+
+// A block of 32 bit MMIO registers at a base address that is only known at runtime
+pub(crate) struct MmioBlock {
+    base: usize,
+}
+
+impl MmioBlock {
+    // # Safety
+    // The base need to be the address of a register block of a peripheral that is mapped for the lifetime of the
+    // block, and each offset accessed need to be a register of this peripheral
+    pub(crate) const unsafe fn new(base: usize) -> Self {
+        MmioBlock { base }
+    }
+
+    // read the register at the offset
+    pub(crate) fn read(&self, offset: usize) -> u32 {
+        // the address is a register of a mapped peripheral as required by ``new``
+        unsafe { core::ptr::read_volatile((self.base + offset) as *const u32) }
+    }
+
+    // write the register at the offset
+    pub(crate) fn write(&self, offset: usize, value: u32) {
+        // the address is a register of a mapped peripheral as required by ``new``
+        unsafe { core::ptr::write_volatile((self.base + offset) as *mut u32, value) }
+    }
+}
+
+// the register block of the Uart0, which is always mapped
+pub(crate) fn uart0_registers() -> MmioBlock {
+    // the Uart0 is a PL011 at this address
+    unsafe { MmioBlock::new(crate::uart0::interface::base_address()) }
+}
+
+// the states of an InitCell
+const UNINITIALIZED: u8 = 0;
+const INITIALIZING: u8 = 1;
+const READY: u8 = 2;
+const POISONED: u8 = 3;
+
+// A value that is initialized once by the first caller while concurrent callers wait for the initialization to finish.
+// The value is only modified by the caller that moved the state from uninitialized to initializing, and only shared
+// once the state is ready. If the initialization panics the cell is poisoned and never handed out.
+pub(crate) struct InitCell<T> {
+    state: AtomicU8,
+    value: UnsafeCell<T>,
+}
+
+// the value is only shared once it is ready and never modified afterwards
+unsafe impl<T: Send + Sync> Sync for InitCell<T> {}
+
+impl<T> InitCell<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        InitCell {
+            state: AtomicU8::new(UNINITIALIZED),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    // initialize the value with ``init`` if this did not happen yet. If the initialization fails the cell stays
+    // uninitialized and the next caller tries again. While another caller initializes the value ``wait`` is called
+    // repeatedly, an error returned by it ends the wait. The error of ``poisoned`` is returned once an initialization
+    // has panicked
+    pub(crate) fn get_or_try_init<E>(
+        &self,
+        init: impl FnOnce(&mut T) -> Result<(), E>,
+        mut wait: impl FnMut() -> Result<(), E>,
+        poisoned: impl FnOnce() -> E,
+    ) -> Result<&T, E> {
+        loop {
+            match self.state.compare_exchange(
+                UNINITIALIZED,
+                INITIALIZING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    // the state is only left initializing by an initialization that panicked
+                    let guard = PoisonGuard(&self.state);
+                    // this caller is the only one accessing the value while initializing
+                    let result = init(unsafe { &mut *self.value.get() });
+                    core::mem::forget(guard);
+                    let state = if result.is_ok() { READY } else { UNINITIALIZED };
+                    self.state.store(state, Ordering::Release);
+                    return result.map(|_| unsafe { &*self.value.get() });
+                }
+                // the value is ready and never modified again
+                Err(READY) => return Ok(unsafe { &*self.value.get() }),
+                Err(POISONED) => return Err(poisoned()),
+                Err(_) => wait()?,
+            }
+        }
+    }
+
+    // the value if it has been initialized already
+    pub(crate) fn get(&self) -> Option<&T> {
+        if self.state.load(Ordering::Acquire) == READY {
+            // the value is ready and never modified again
+            Some(unsafe { &*self.value.get() })
+        } else {
+            None
+        }
+    }
+}
+
+// poisons the state of an InitCell when dropped while unwinding from a panicking initialization
+struct PoisonGuard<'a>(&'a AtomicU8);
+
+impl Drop for PoisonGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(POISONED, Ordering::Release);
+    }
+}
+
+// Buffer for the data to be handed over to the active transfer. The consumer is the active transfer, the producers
+// are the transfers started while it is active, either from an interrupt handler or from another core. Producers are
+// serialized with the ``pushing`` lock that holds the id of the pushing core (plus one). A producer waits for a push of
+// another core to finish, but gives up if it has interrupted a push of it's own core, as that push can not finish
+// before the producer returns.
+// The positions wrap at twice the buffer size to be able to distinguish a full from an empty buffer.
+pub(crate) struct TxHandover<const N: usize> {
+    default: UnsafeCell<[u8; N]>,
+    storage: UnsafeCell<Option<&'static mut [u8]>>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    pushing: AtomicUsize,
+}
+
+// the buffer content is only accessed in the way described above
+unsafe impl<const N: usize> Sync for TxHandover<N> {}
+
+// the value of the producer lock if no one is pushing
+const NOT_PUSHING: usize = 0;
+
+impl<const N: usize> TxHandover<N> {
+    pub(crate) const fn new() -> Self {
+        TxHandover {
+            default: UnsafeCell::new([0; N]),
+            storage: UnsafeCell::new(None),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            pushing: AtomicUsize::new(NOT_PUSHING),
+        }
+    }
+
+    // the number of bytes the buffer can hold
+    pub(crate) fn capacity(&self) -> usize {
+        // the storage is only replaced while there is no transfer active
+        unsafe {
+            match &*self.storage.get() {
+                Some(storage) => storage.len(),
+                None => N,
+            }
+        }
+    }
+
+    // the first byte of the memory currently used by the buffer. The bytes are only accessed through this pointer,
+    // each one either by the producer or by the consumer, depending on the head and tail position
+    fn data(&self) -> *mut u8 {
+        unsafe {
+            match &mut *self.storage.get() {
+                Some(storage) => storage.as_mut_ptr(),
+                None => (*self.default.get()).as_mut_ptr(),
+            }
+        }
+    }
+
+    // replace the memory used by the buffer, this must not happen while there is a transfer active
+    pub(crate) fn set_storage(&self, storage: &'static mut [u8]) {
+        unsafe { *self.storage.get() = Some(storage) };
+        self.head.store(0, Ordering::Release);
+        self.tail.store(0, Ordering::Release);
+    }
+
+    // store the data as a whole, returns false if there is not enough room for all of it or if the push of another
+    // producer on the same core has been interrupted
+    pub(crate) fn push(&self, data: &[u8]) -> bool {
+        if data.is_empty() {
+            return true;
+        }
+        let producer = core_id() + 1;
+        loop {
+            match self.pushing.compare_exchange(
+                NOT_PUSHING,
+                producer,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(owner) if owner == producer => return false,
+                Err(_) => (),
+            }
+        }
+        let size = self.capacity();
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        let used = (head + 2 * size - tail) % (2 * size).max(1);
+        let fits = size - used >= data.len();
+        if fits {
+            let buffer = self.data();
+            for (idx, byte) in data.iter().enumerate() {
+                // the bytes between head and tail belong to the producer holding the lock
+                unsafe { buffer.add((head + idx) % size).write_volatile(*byte) };
+            }
+            self.head
+                .store((head + data.len()) % (2 * size), Ordering::Release);
+        }
+        self.pushing.store(NOT_PUSHING, Ordering::Release);
+        fits
+    }
+
+    // take as much data as fits into the given chunk, returns the number of bytes taken
+    pub(crate) fn pop(&self, chunk: &mut [u8]) -> usize {
+        let size = self.capacity();
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        let count = ((head + 2 * size - tail) % (2 * size).max(1)).min(chunk.len());
+        let buffer = self.data();
+        for (idx, byte) in chunk.iter_mut().take(count).enumerate() {
+            // the bytes between tail and head belong to the consumer
+            *byte = unsafe { buffer.add((tail + idx) % size).read_volatile() };
+        }
+        if count != 0 {
+            self.tail
+                .store((tail + count) % (2 * size), Ordering::Release);
+        }
+        count
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+}
+
+// Single producer single consumer byte queue. The producer is the core the queue belongs to, the consumer is the core
+// currently draining the queues. The positions wrap at twice the queue size to be able to distinguish a full from an
+// empty queue.
+// The buffer is only accessed through a raw pointer, as producer and consumer access their parts of it at the same
+// time and no reference to the whole buffer may exist meanwhile.
+pub(crate) struct StagingQueue {
+    buffer: *mut u8,
+    size: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// the buffer is only accessed in the way described above
+unsafe impl Sync for StagingQueue {}
+unsafe impl Send for StagingQueue {}
+
+impl StagingQueue {
+    pub(crate) fn new(buffer: &'static mut [u8]) -> Self {
+        StagingQueue {
+            buffer: buffer.as_mut_ptr(),
+            size: buffer.len(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    // add all of the data to the queue, or nothing if it does not fit completely
+    pub(crate) fn push(&self, data: &[u8]) -> bool {
+        let size = self.size;
+        if data.len() > size {
+            return false;
+        }
+        if data.is_empty() {
+            return true;
+        }
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        let used = (head + 2 * size - tail) % (2 * size);
+        if size - used < data.len() {
+            return false;
+        }
+        // the producer only writes the free part of the buffer, which wraps around at most once
+        let start = head % size;
+        let first = data.len().min(size - start);
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), self.buffer.add(start), first);
+            ptr::copy_nonoverlapping(data[first..].as_ptr(), self.buffer, data.len() - first);
+        }
+        self.head
+            .store((head + data.len()) % (2 * size), Ordering::Release);
+        true
+    }
+
+    // take as much data from the queue as fits into the chunk. Returns the number of bytes taken
+    pub(crate) fn pop(&self, chunk: &mut [u8]) -> usize {
+        let size = self.size;
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if size == 0 || head == tail {
+            return 0;
+        }
+        let count = ((head + 2 * size - tail) % (2 * size)).min(chunk.len());
+        // the consumer only reads the used part of the buffer, which wraps around at most once
+        let start = tail % size;
+        let first = count.min(size - start);
+        unsafe {
+            ptr::copy_nonoverlapping(self.buffer.add(start), chunk.as_mut_ptr(), first);
+            ptr::copy_nonoverlapping(self.buffer, chunk[first..].as_mut_ptr(), count - first);
+        }
+        self.tail
+            .store((tail + count) % (2 * size), Ordering::Release);
+        count
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+}
+
+/// Single producer single consumer ring buffer with space for ``N`` entries. It is safe to be filled from within an
+/// interrupt handler while being drained from the regular program flow, or vice versa, without disabling interrupts.
+/// A producer (consumer) calling while another producer (consumer) is in the middle of a call, e.g. from an interrupt
+/// handler or another core, is turned away as if the buffer was full (empty), so the buffer never hands out an entry
+/// that is being written. Several producers or consumers therefore need to be serialized to not lose entries.
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::ringbuffer::SpscRing;
+/// static RX: SpscRing<u8, 64> = SpscRing::new();
+///
+/// fn irq_handler(received: u8) {
+///     // the byte is dropped if the buffer is full
+///     let _ = RX.push(received);
+/// }
+///
+/// # fn doc() {
+/// while let Some(byte) = RX.pop() {
+///     // process the byte
+/// }
+/// # }
+/// ```
+pub struct SpscRing<T: Copy, const N: usize> {
+    buffer: UnsafeCell<[MaybeUninit<T>; N]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    pushing: AtomicBool,
+    popping: AtomicBool,
+}
+
+// the entries are only accessed by the one producer and the one consumer admitted at a time, each owning their part
+// of the buffer. The producer only writes the entries between head and tail, the consumer only reads the entries the
+// producer has published with the head position
+unsafe impl<T: Copy + Send, const N: usize> Sync for SpscRing<T, N> {}
+
+impl<T: Copy, const N: usize> Default for SpscRing<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy, const N: usize> SpscRing<T, N> {
+    // a buffer without entries could never store one, so creating it fails to compile
+    const NOT_EMPTY: () = assert!(N > 0, "SpscRing needs a capacity of at least one entry");
+
+    /// Create a new empty ring buffer. The capacity ``N`` need to be at least 1, a buffer without space for an entry
+    /// fails to compile.
+    /// ```compile_fail
+    /// # use ruspiro_uart::ringbuffer::SpscRing;
+    /// static EMPTY: SpscRing<u8, 0> = SpscRing::new();
+    /// ```
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::NOT_EMPTY;
+        SpscRing {
+            buffer: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            pushing: AtomicBool::new(false),
+            popping: AtomicBool::new(false),
+        }
+    }
+
+    /// Add an entry to the buffer. Returns ``false`` if the buffer is full, or another producer is pushing right now,
+    /// and the entry could not be stored.
+    pub fn push(&self, value: T) -> bool {
+        if self.pushing.swap(true, Ordering::Acquire) {
+            return false;
+        }
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        let stored = (head + 2 * N - tail) % (2 * N) < N;
+        if stored {
+            unsafe { (*self.buffer.get())[head % N] = MaybeUninit::new(value) };
+            self.head.store((head + 1) % (2 * N), Ordering::Release);
+        }
+        self.pushing.store(false, Ordering::Release);
+        stored
+    }
+
+    /// Take the oldest entry from the buffer. Returns ``None`` if the buffer is empty, or another consumer is popping
+    /// right now.
+    pub fn pop(&self) -> Option<T> {
+        if self.popping.swap(true, Ordering::Acquire) {
+            return None;
+        }
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        let value = if head == tail {
+            None
+        } else {
+            let value = unsafe { (*self.buffer.get())[tail % N].assume_init() };
+            self.tail.store((tail + 1) % (2 * N), Ordering::Release);
+            Some(value)
+        };
+        self.popping.store(false, Ordering::Release);
+        value
+    }
+
+    /// Check whether there is no entry in the buffer
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+
+    /// The number of entries in the buffer
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        (head + 2 * N - tail) % (2 * N)
+    }
+
+    /// The number of entries the buffer can hold
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+/// Buffer capturing output until an Uart is ready to send it. Writers only ever append to the already buffered data.
+/// Writing to the buffer while it is replayed from another core might replay data that has been reserved but not yet
+/// written completely, so the initialization of the Uart should not race with output from other cores.
+#[cfg(feature = "early_log")]
+pub struct EarlyLogBuffer {
+    buffer: UnsafeCell<[u8; EARLY_LOG_SIZE]>,
+    len: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+// the buffer content is only written in the area reserved by the atomic length
+#[cfg(feature = "early_log")]
+unsafe impl Sync for EarlyLogBuffer {}
+
+#[cfg(feature = "early_log")]
+impl EarlyLogBuffer {
+    pub(crate) const fn new() -> Self {
+        EarlyLogBuffer {
+            buffer: UnsafeCell::new([0; EARLY_LOG_SIZE]),
+            len: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Append the data to the buffer. Data that does not fit into the buffer any more is dropped and counted.
+    pub fn write(&self, data: &[u8]) {
+        let mut start = self.len.load(Ordering::Relaxed);
+        let count = loop {
+            let count = data.len().min(EARLY_LOG_SIZE - start);
+            match self.len.compare_exchange_weak(
+                start,
+                start + count,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break count,
+                Err(current) => start = current,
+            }
+        };
+        // only the reserved range is written, no reference to the whole buffer is created as other writers fill their
+        // ranges at the same time
+        unsafe {
+            let buffer = self.buffer.get() as *mut u8;
+            ptr::copy_nonoverlapping(data.as_ptr(), buffer.add(start), count);
+        }
+        if count < data.len() {
+            self.dropped
+                .fetch_add(data.len() - count, Ordering::Relaxed);
+        }
+    }
+
+    /// The number of bytes currently buffered
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Check whether there is no data buffered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of bytes that have been dropped as the buffer was already full
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    // pass all buffered data to the given sender and clear the buffer
+    pub(crate) fn replay(&self, send: impl Fn(&[u8])) {
+        let buffer = self.buffer.get() as *const u8;
+        let mut sent = 0;
+        loop {
+            let len = self.len.load(Ordering::Acquire);
+            // only the range appended since the last round is borrowed, writers append behind it
+            send(unsafe { core::slice::from_raw_parts(buffer.add(sent), len - sent) });
+            sent = len;
+            // only clear the buffer if no further data has been appended while replaying
+            if self
+                .len
+                .compare_exchange(len, 0, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                break;
+            }
+        }
+    }
+}
+
+// The transmit history placed in a section that is not cleared at boot, so it survives a reset. The history only
+// holds atomics and checks whether it is valid before it is used, so any content found there is safe to read.
+#[cfg(feature = "tx_history")]
+#[link_section = ".noinit"]
+pub(crate) static TX_HISTORY: TxHistory = TxHistory::new();
+
+// The error lines routed by a severity router, kept the same way as the transmit history
+#[cfg(feature = "tx_history")]
+#[link_section = ".noinit"]
+pub(crate) static ERROR_HISTORY: TxHistory = TxHistory::new();
+
+// A waker registered by a future and woken from an interrupt handler. The waker is only accessed while holding the
+// lock. A wake that finds the lock held by the registration leaves the woken flag set, which the registration checks
+// once it has released the lock.
+#[cfg(feature = "async")]
+pub(crate) struct WakerCell {
+    lock: AtomicBool,
+    woken: AtomicBool,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// the waker is only accessed while holding the lock
+#[cfg(feature = "async")]
+unsafe impl Sync for WakerCell {}
+
+#[cfg(feature = "async")]
+impl WakerCell {
+    pub(crate) const fn new() -> Self {
+        WakerCell {
+            lock: AtomicBool::new(false),
+            woken: AtomicBool::new(false),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    // register the waker to be woken with the next call of ``wake``
+    pub(crate) fn register(&self, waker: &Waker) {
+        if self.lock.swap(true, Ordering::Acquire) {
+            // a wake is in progress, let the task poll again right away
+            waker.wake_by_ref();
+            return;
+        }
+        // the lock is held
+        let slot = unsafe { &mut *self.waker.get() };
+        match slot {
+            Some(current) if current.will_wake(waker) => (),
+            _ => *slot = Some(waker.clone()),
+        }
+        self.lock.store(false, Ordering::Release);
+        if self.woken.swap(false, Ordering::Acquire) {
+            waker.wake_by_ref();
+        }
+    }
+
+    // wake the registered waker, if any
+    pub(crate) fn wake(&self) {
+        self.woken.store(true, Ordering::Release);
+        if self.lock.swap(true, Ordering::Acquire) {
+            // the registration wakes it's waker once it has released the lock
+            return;
+        }
+        self.woken.store(false, Ordering::Relaxed);
+        // the lock is held
+        let waker = unsafe { (*self.waker.get()).take() };
+        self.lock.store(false, Ordering::Release);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+// the base address of the DMA controller, channel 0 to 14 have a register block of 0x100 bytes each from there on
+#[cfg(all(feature = "dma", feature = "ruspiro_pi3"))]
+const DMA_BASE: usize = 0x3F00_7000;
+
+// the register block of the DMA channel, ``None`` for channels other than 0 to 14
+#[cfg(feature = "dma")]
+pub(crate) fn dma_channel(channel: u8) -> Option<MmioBlock> {
+    if channel > 14 {
+        None
+    } else {
+        // the DMA controller is always mapped and the channel has a register block at this address
+        Some(unsafe { MmioBlock::new(DMA_BASE + channel as usize * 0x100) })
+    }
+}
+
+// the global enable register of the DMA channels
+#[cfg(feature = "dma")]
+pub(crate) fn dma_enable() -> MmioBlock {
+    // the DMA controller is always mapped and has the global enable register at this address
+    unsafe { MmioBlock::new(DMA_BASE + 0xFF0) }
+}
+
+// write the data cache lines covering the memory back to the RAM, so a DMA transfer reads the current content
+#[cfg(feature = "dma")]
+pub(crate) fn clean_dcache(start: usize, len: usize) {
+    // the cache line size of the Cortex-A53
+    const CACHE_LINE: usize = 64;
+    let mut line = start & !(CACHE_LINE - 1);
+    while line < start + len {
+        // cleaning a cache line does not change the content of the memory seen by the CPU
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            llvm_asm!("dc cvac, $0"::"r"(line)::"volatile")
+        };
+        #[cfg(target_arch = "arm")]
+        unsafe {
+            llvm_asm!("mcr p15, 0, $0, c7, c10, 1"::"r"(line)::"volatile")
+        };
+        line += CACHE_LINE;
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        llvm_asm!("dsb sy":::"memory":"volatile")
+    };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        llvm_asm!("dsb":::"memory":"volatile")
+    };
+}
+
+// write back and discard the data cache lines covering the memory, so the CPU reads what a DMA transfer has written
+// and no dirty line overwrites it later on
+#[cfg(feature = "dma")]
+pub(crate) fn invalidate_dcache(start: usize, len: usize) {
+    // the cache line size of the Cortex-A53
+    const CACHE_LINE: usize = 64;
+    let mut line = start & !(CACHE_LINE - 1);
+    while line < start + len {
+        // the line is written back before it is discarded, so no content written by the CPU gets lost
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            llvm_asm!("dc civac, $0"::"r"(line)::"volatile")
+        };
+        #[cfg(target_arch = "arm")]
+        unsafe {
+            llvm_asm!("mcr p15, 0, $0, c7, c14, 1"::"r"(line)::"volatile")
+        };
+        line += CACHE_LINE;
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        llvm_asm!("dsb sy":::"memory":"volatile")
+    };
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        llvm_asm!("dsb":::"memory":"volatile")
+    };
+}
+
+/// A type whose value can be sent as it's plain bytes in memory, e.g. as a telemetry record.
+///
+/// # Safety
+/// Every byte of the value need to be initialized, so the type must not contain any padding, and it's layout need to
+/// be fixed, e.g. with ``#[repr(C)]``. References and pointers are not allowed either, as their address is meaningless
+/// to the receiver.
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::telemetry::AsBytes;
+/// #[derive(Clone, Copy)]
+/// #[repr(C)]
+/// struct Sample {
+///     timestamp: u32,
+///     position: f32,
+/// }
+///
+/// // the two fields of 4 bytes each leave no padding
+/// unsafe impl AsBytes for Sample {}
+/// ```
+pub unsafe trait AsBytes: Copy {
+    /// The bytes of the value as it is laid out in memory
+    fn as_bytes(&self) -> &[u8] {
+        // the value is fully initialized as required by the trait, and the slice borrows it
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+// primitives and arrays of them have no padding
+unsafe impl AsBytes for u8 {}
+unsafe impl AsBytes for u16 {}
+unsafe impl AsBytes for u32 {}
+unsafe impl AsBytes for u64 {}
+unsafe impl AsBytes for i8 {}
+unsafe impl AsBytes for i16 {}
+unsafe impl AsBytes for i32 {}
+unsafe impl AsBytes for i64 {}
+unsafe impl AsBytes for f32 {}
+unsafe impl AsBytes for f64 {}
+unsafe impl<T: AsBytes, const N: usize> AsBytes for [T; N] {}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::TxHandover;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::vec::Vec;
+
+    // several producers hand over chunks while the consumer drains them, no chunk may be lost or torn apart
+    #[test]
+    fn handover_keeps_chunks_of_concurrent_producers() {
+        const PRODUCERS: u8 = 4;
+        const CHUNKS: usize = 500;
+        const CHUNK_SIZE: usize = 5;
+        static HANDOVER: TxHandover<16> = TxHandover::new();
+        static DONE: AtomicBool = AtomicBool::new(false);
+
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|producer| {
+                thread::spawn(move || {
+                    for _ in 0..CHUNKS {
+                        while !HANDOVER.push(&[producer; CHUNK_SIZE]) {
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+        let consumer = thread::spawn(|| {
+            let mut received = Vec::new();
+            let mut chunk = [0u8; 3];
+            loop {
+                let done = DONE.load(Ordering::Acquire);
+                let count = HANDOVER.pop(&mut chunk);
+                received.extend_from_slice(&chunk[..count]);
+                if count == 0 && done {
+                    return received;
+                }
+            }
+        });
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        DONE.store(true, Ordering::Release);
+        let received = consumer.join().unwrap();
+
+        assert_eq!(received.len(), PRODUCERS as usize * CHUNKS * CHUNK_SIZE);
+        for chunk in received.chunks(CHUNK_SIZE) {
+            assert!(chunk.iter().all(|byte| *byte == chunk[0]));
+        }
+        for producer in 0..PRODUCERS {
+            let count = received.iter().filter(|byte| **byte == producer).count();
+            assert_eq!(count, CHUNKS * CHUNK_SIZE);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Host Serial Vocabulary
+//!
+//! The line settings named the way common host side serial port crates (e.g. ``serialport``) name them. Code and
+//! configuration structures shared between a host tool and the firmware running on the Raspberry Pi can therefore be
+//! used on both sides verbatim. The settings convert into the configuration of the Uarts, settings a Uart does not
+//! support are rejected.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # use ruspiro_uart::serial::*;
+//! # fn doc() {
+//! let settings = SerialSettings {
+//!     baud_rate: 9_600,
+//!     data_bits: DataBits::Seven,
+//!     parity: Parity::Even,
+//!     stop_bits: StopBits::One,
+//!     flow_control: FlowControl::None,
+//! };
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize_serial(250_000_000, &settings);
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::{UartConfig, UartError};
+
+/// The number of data bits of each character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataBits {
+    /// 5 data bits
+    Five,
+    /// 6 data bits
+    Six,
+    /// 7 data bits
+    Seven,
+    /// 8 data bits
+    Eight,
+}
+
+/// The parity bit added to each character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    /// no parity bit
+    None,
+    /// the parity bit makes the number of set bits odd
+    Odd,
+    /// the parity bit makes the number of set bits even
+    Even,
+}
+
+/// The number of stop bits of each character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    /// 1 stop bit
+    One,
+    /// 2 stop bits
+    Two,
+}
+
+/// The flow control used on the line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowControl {
+    /// no flow control
+    None,
+    /// XON/XOFF flow control
+    Software,
+    /// RTS/CTS flow control
+    Hardware,
+}
+
+/// The complete line settings of a serial port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialSettings {
+    /// the baud rate
+    pub baud_rate: u32,
+    /// the number of data bits
+    pub data_bits: DataBits,
+    /// the parity bit
+    pub parity: Parity,
+    /// the number of stop bits
+    pub stop_bits: StopBits,
+    /// the flow control
+    pub flow_control: FlowControl,
+}
+
+impl From<Parity> for crate::Parity {
+    fn from(parity: Parity) -> Self {
+        match parity {
+            Parity::None => crate::Parity::None,
+            Parity::Odd => crate::Parity::Odd,
+            Parity::Even => crate::Parity::Even,
+        }
+    }
+}
+
+impl From<crate::Parity> for Parity {
+    fn from(parity: crate::Parity) -> Self {
+        match parity {
+            crate::Parity::None => Parity::None,
+            crate::Parity::Odd => Parity::Odd,
+            crate::Parity::Even => Parity::Even,
+        }
+    }
+}
+
+impl SerialSettings {
+    // the line configuration of these settings. None of the Uarts supports flow control
+    pub(crate) fn config(&self) -> Result<UartConfig, UartError> {
+        if self.flow_control != FlowControl::None {
+            return Err(UartError::UnsupportedSettings);
+        }
+        Ok(UartConfig {
+            data_bits: self.data_bits,
+            parity: self.parity.into(),
+            stop_bits: self.stop_bits,
+        })
+    }
+}
@@ -0,0 +1,99 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Slow Send Detection
+//!
+//! A send blocks as long as the transmitter does not accept further data, e.g. as the flow control got stuck or the
+//! terminal is gone. Instead of appearing frozen the system can be notified about such a send: a registered hook is
+//! called once for each send that blocks for longer than a threshold, with the state of the transmitter at that time.
+//! The hook may raise an alert or switch the console to another Uart. There is only one hook shared by all Uarts.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # use ruspiro_uart::slowsend::SlowSend;
+//! fn stuck(info: SlowSend) {
+//!     // switch the console to another output
+//! }
+//!
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! uart.set_slow_send_hook(100_000, Some(stuck));
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::clock;
+use crate::sealed::FnHook;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub use crate::Peripheral;
+
+/// The state of a send blocking for longer than the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlowSend {
+    /// the Uart the send is blocked on
+    pub peripheral: Peripheral,
+    /// the time in micro seconds the send has been blocked so far
+    pub blocked: u64,
+    /// the number of bytes of the send not yet accepted by the transmitter
+    pub pending: usize,
+    /// the raw transmitter status, the flag register (FR) of the Uart0 or the line status register (LSR) of the Uart1
+    pub status: u32,
+}
+
+// the registered hook and the time in micro seconds a send need to block before it is reported
+static HOOK: FnHook<fn(SlowSend)> = FnHook::new();
+static THRESHOLD: AtomicU64 = AtomicU64::new(0);
+
+// register the hook or remove it if ``None`` is given
+pub(crate) fn set_hook(threshold: u64, hook: Option<fn(SlowSend)>) {
+    THRESHOLD.store(threshold, Ordering::Relaxed);
+    HOOK.set(hook);
+}
+
+// Watches a single send. The time is only taken if a hook is registered at the start of the send
+pub(crate) struct SendWatch {
+    start: Option<u64>,
+}
+
+impl SendWatch {
+    pub(crate) fn start() -> Self {
+        SendWatch {
+            start: HOOK.get().map(|_| clock::now()),
+        }
+    }
+
+    // to be called while the send is blocked. Reports the send once it has been blocked for longer than the threshold
+    pub(crate) fn check(
+        &mut self,
+        peripheral: Peripheral,
+        pending: usize,
+        status: impl FnOnce() -> u32,
+    ) {
+        let start = match self.start {
+            Some(start) => start,
+            None => return,
+        };
+        let blocked = clock::elapsed_since(start);
+        if blocked <= THRESHOLD.load(Ordering::Relaxed) {
+            return;
+        }
+        // report only once per send
+        self.start = None;
+        if let Some(hook) = HOOK.get() {
+            hook(SlowSend {
+                peripheral,
+                blocked,
+                pending,
+                status: status(),
+            });
+        }
+    }
+}
@@ -0,0 +1,170 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Soak Test
+//!
+//! Validating a new board spin or a long cable run usually requires an external tester. With a jumper between the TX
+//! and the RX pin of an Uart the ``soak_test`` function validates the link on it's own. It sends the data of a
+//! [Pattern] in chunks fitting into the FIFO's, verifies each byte received against the byte sent and returns a
+//! [SoakReport] with the bit error rate and the highest throughput sustained over a measurement window.
+//!
+//! Bytes not received in time are counted as lost with all of their bits wrong, as a dropped byte would corrupt a
+//! real transmission as well. Bytes received with a parity, framing or break error are counted as receive errors in
+//! addition to their wrong bits.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # use ruspiro_uart::soak::Pattern;
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 921_600);
+//! // run the test for 10 seconds
+//! let report = uart.soak_test(10_000_000, Pattern::Random).unwrap();
+//! assert_eq!(report.bit_errors, 0);
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::clock;
+use crate::watchdog;
+use crate::RxFlag;
+
+// the number of bytes sent before the reception is verified, fits into the transmit FIFO of both Uarts
+const CHUNK_SIZE: usize = 8;
+
+// the time in micro seconds the throughput is measured over
+const WINDOW: u64 = 100_000;
+
+// the additional time in micro seconds given to a chunk to arrive
+const CHUNK_MARGIN: u64 = 1_000;
+
+/// The data sent during a soak test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// pseudo random data, exercising all bit transitions
+    Random,
+    /// the same byte over and over, e.g. ``0x55`` for the highest number of edges
+    Fixed(u8),
+    /// a byte counting up and wrapping around
+    Counter,
+}
+
+/// The result of a soak test
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SoakReport {
+    /// The number of bytes sent
+    pub bytes_sent: u64,
+    /// The number of bytes received, correct or not
+    pub bytes_received: u64,
+    /// The number of bytes not received in time
+    pub bytes_lost: u64,
+    /// The number of wrong bits, each lost byte adds 8
+    pub bit_errors: u64,
+    /// The number of bytes received with a parity, framing or break error
+    pub rx_errors: u64,
+    /// The highest number of bytes per second received correctly within a measurement window of 100ms
+    pub max_throughput: u32,
+}
+
+impl SoakReport {
+    /// The ratio of wrong bits to the bits sent
+    pub fn bit_error_rate(&self) -> f32 {
+        if self.bytes_sent == 0 {
+            0.0
+        } else {
+            self.bit_errors as f32 / (self.bytes_sent * 8) as f32
+        }
+    }
+}
+
+// the generator of the pattern data
+struct PatternGenerator {
+    pattern: Pattern,
+    state: u32,
+}
+
+impl PatternGenerator {
+    fn new(pattern: Pattern) -> Self {
+        PatternGenerator {
+            pattern,
+            state: 0x2545_F491,
+        }
+    }
+
+    fn next(&mut self) -> u8 {
+        match self.pattern {
+            Pattern::Random => {
+                // xorshift32
+                self.state ^= self.state << 13;
+                self.state ^= self.state >> 17;
+                self.state ^= self.state << 5;
+                (self.state >> 24) as u8
+            }
+            Pattern::Fixed(value) => value,
+            Pattern::Counter => {
+                let value = self.state as u8;
+                self.state = self.state.wrapping_add(1);
+                value
+            }
+        }
+    }
+}
+
+// run the soak test for the duration in micro seconds. The data is sent in chunks and the next chunk is only sent
+// once the previous one has been received or timed out, so late bytes are drained before each chunk
+pub(crate) fn run(
+    duration: u64,
+    pattern: Pattern,
+    char_time_ns: u32,
+    mut send: impl FnMut(&[u8]),
+    mut receive: impl FnMut() -> Option<(u8, RxFlag)>,
+) -> SoakReport {
+    let mut report = SoakReport::default();
+    let mut generator = PatternGenerator::new(pattern);
+    let chunk_timeout = CHUNK_SIZE as u64 * char_time_ns as u64 * 2 / 1000 + CHUNK_MARGIN;
+    let start = clock::now();
+    let mut window_start = start;
+    let mut window_bytes = 0u64;
+    while clock::elapsed_since(start) < duration {
+        while receive().is_some() {}
+        let mut chunk = [0u8; CHUNK_SIZE];
+        chunk.iter_mut().for_each(|byte| *byte = generator.next());
+        send(&chunk);
+        report.bytes_sent += CHUNK_SIZE as u64;
+
+        let deadline = clock::now() + chunk_timeout;
+        let mut received = 0;
+        while received < CHUNK_SIZE && clock::now() < deadline {
+            if let Some((byte, flag)) = receive() {
+                let errors = (byte ^ chunk[received]).count_ones() as u64;
+                if flag != RxFlag::Ok {
+                    report.rx_errors += 1;
+                } else if errors == 0 {
+                    window_bytes += 1;
+                }
+                report.bit_errors += errors;
+                received += 1;
+            }
+        }
+        report.bytes_received += received as u64;
+        report.bytes_lost += (CHUNK_SIZE - received) as u64;
+        report.bit_errors += (CHUNK_SIZE - received) as u64 * 8;
+
+        let now = clock::now();
+        let window = now.saturating_sub(window_start);
+        if window >= WINDOW {
+            let throughput = window_bytes * 1_000_000 / window;
+            report.max_throughput = report.max_throughput.max(throughput as u32);
+            window_start = now;
+            window_bytes = 0;
+        }
+        watchdog::kick();
+    }
+    report
+}
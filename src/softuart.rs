@@ -0,0 +1,176 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Software Uart Transmitter
+//!
+//! If both hardware Uarts are occupied, e.g. by a bluetooth module and a sensor, the ``SoftUartTx`` provides an
+//! additional debug output on any spare GPIO pin. The serial signal (8 data bits, no parity, 1 stop bit) is generated
+//! by toggling the pin based on the system timer, so the CPU is busy while the data is sent. As the timer has a
+//! resolution of a micro second the baud rate is limited to 115200.
+//!
+//! Interrupts occurring while a character is sent stretch the current bit and corrupt the character. Output that shall
+//! be received reliably should therefore be sent with interrupts disabled.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::softuart::SoftUartTx;
+//! # use ruspiro_console::ConsoleImpl;
+//! # fn doc() {
+//! let mut debug = SoftUartTx::new(21);
+//! let _ = debug.initialize(115_200);
+//! debug.puts("Hello from GPIO21\r\n");
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::clock;
+use crate::{watchdog, UartError};
+use ruspiro_console::ConsoleImpl;
+use ruspiro_gpio::GPIO;
+use ruspiro_register::define_mmio_register;
+
+/// The maximum baud rate supported by the software Uart
+pub const MAX_BAUD_RATE: u32 = 115_200;
+
+// Peripheral MMIO base address - depends on the right feature
+#[cfg(feature = "ruspiro_pi3")]
+const PERIPHERAL_BASE: u32 = 0x3F00_0000;
+
+// GPIO MMIO base address
+const GPIO_BASE: u32 = PERIPHERAL_BASE + 0x0020_0000;
+
+/// Software Uart transmitting on an arbitrary GPIO pin
+pub struct SoftUartTx {
+    pin: u32,
+    baud_rate: u32,
+    initialized: bool,
+}
+
+impl SoftUartTx {
+    /// Get a new software Uart transmitting on the given GPIO pin. It need to be initialized before it can be used.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::softuart::SoftUartTx;
+    /// # fn doc() {
+    /// let _debug = SoftUartTx::new(21);
+    /// # }
+    /// ```
+    pub const fn new(pin: u32) -> Self {
+        SoftUartTx {
+            pin,
+            baud_rate: 0,
+            initialized: false,
+        }
+    }
+
+    /// Initialize the software Uart with the given baud rate. This reserves the GPIO pin as output and sets the line
+    /// to idle (high). Fails with ``Err(UartError::UnsupportedBaudRate)`` if the baud rate exceeds [MAX_BAUD_RATE].
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::softuart::SoftUartTx;
+    /// # fn doc() {
+    /// let mut debug = SoftUartTx::new(21);
+    /// assert_eq!(debug.initialize(57_600), Ok(()));
+    /// # }
+    /// ```
+    pub fn initialize(&mut self, baud_rate: u32) -> Result<(), UartError> {
+        if baud_rate == 0 || baud_rate > MAX_BAUD_RATE {
+            return Err(UartError::UnsupportedBaudRate);
+        }
+        if self.initialized {
+            self.baud_rate = baud_rate;
+            return Ok(());
+        }
+        let pin = self.pin;
+        GPIO.take_for(|gpio| {
+            gpio.get_pin(pin).map(|pin| {
+                pin.into_output().high();
+            })
+        })
+        .map_err(UartError::GpioError)?;
+        self.set_line(true);
+        self.baud_rate = baud_rate;
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Send a single character. Characters not fitting into a single byte are truncated.
+    pub fn send_char(&self, c: char) {
+        self.send_data(&[c as u8]);
+    }
+
+    /// Send a string
+    pub fn send_string(&self, s: &str) {
+        self.send_data(s.as_bytes());
+    }
+
+    /// Send the data. If the software Uart is not initialized nothing is sent.
+    pub fn send_data(&self, data: &[u8]) {
+        if !self.initialized {
+            return;
+        }
+        for byte in data {
+            self.send_byte(*byte);
+            watchdog::kick();
+        }
+    }
+
+    // send a single frame. The edges are timed relative to the start bit, so the rounding of the bit time to full
+    // micro seconds does not add up over the frame
+    fn send_byte(&self, byte: u8) {
+        let baud_rate = self.baud_rate as u64;
+        // start bit, 8 data bits LSB first and the stop bit
+        let frame = ((byte as u16) << 1) | (1 << 9);
+        let start = clock::now();
+        for bit in 0..10u64 {
+            self.set_line(frame & (1 << bit) != 0);
+            let deadline = start + ((bit + 1) * 1_000_000 + baud_rate / 2) / baud_rate;
+            while clock::now() < deadline {}
+        }
+    }
+
+    // drive the line high or low
+    fn set_line(&self, high: bool) {
+        let mask = 1 << (self.pin % 32);
+        match (self.pin < 32, high) {
+            (true, true) => GPSET0::Register.set(mask),
+            (true, false) => GPCLR0::Register.set(mask),
+            (false, true) => GPSET1::Register.set(mask),
+            (false, false) => GPCLR1::Register.set(mask),
+        }
+    }
+}
+
+impl Drop for SoftUartTx {
+    fn drop(&mut self) {
+        // release the pin once this instance is dropped
+        if self.initialized {
+            let pin = self.pin;
+            GPIO.take_for(|gpio| gpio.free_pin(pin));
+        }
+    }
+}
+
+// to use the software Uart as a console to output strings implement the respective trait
+impl ConsoleImpl for SoftUartTx {
+    fn putc(&self, c: char) {
+        self.send_char(c);
+    }
+
+    fn puts(&self, s: &str) {
+        self.send_string(s);
+    }
+}
+
+// the GPIO registers setting and clearing the output level
+define_mmio_register! [
+    GPSET0<WriteOnly<u32>@(GPIO_BASE + 0x1C)>,
+    GPSET1<WriteOnly<u32>@(GPIO_BASE + 0x20)>,
+    GPCLR0<WriteOnly<u32>@(GPIO_BASE + 0x28)>,
+    GPCLR1<WriteOnly<u32>@(GPIO_BASE + 0x2C)>
+];
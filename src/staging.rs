@@ -0,0 +1,89 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Console Staging
+//!
+//! When several cores write heavily to the console, each of them waits for the others to finish sending their output,
+//! even if this is a long log line that takes quite some time to be sent. With console staging active, the output is
+//! put into a queue per core instead. The first core finding no one else draining those queues sends the queued
+//! output to the Uart, while all other cores continue right away. Each output is queued all-or-nothing, so the output
+//! of different cores is never interleaved. Output that does not fit into the queue of a core is dropped.
+//!
+//! The staging only pays off if the cores write to the console through a shared reference to the Uart, e.g. from a
+//! [OnceUart1](crate::once::OnceUart1). The console singleton of ``ruspiro-console`` takes a lock in front of each
+//! write, so the core draining the queues would hold it and all other cores would wait for it again.
+//!
+//! Each queue has a single producer, the core it belongs to. Writing to the console from an interrupt handler that
+//! interrupted a console output on the same core might therefore corrupt the queued output.
+//!
+
+#![forbid(unsafe_code)]
+
+use crate::sealed::StagingQueue;
+use crate::CORES;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// the per core queues and the flag whether one of the cores is currently draining them
+pub(crate) struct ConsoleStaging {
+    queues: [StagingQueue; CORES],
+    draining: AtomicBool,
+}
+
+impl ConsoleStaging {
+    // create the staging splitting the given memory evenly into the per core queues
+    pub(crate) fn new(storage: &'static mut [u8]) -> Self {
+        let size = storage.len() / CORES;
+        let (first, rest) = storage.split_at_mut(size);
+        let (second, rest) = rest.split_at_mut(size);
+        let (third, rest) = rest.split_at_mut(size);
+        let (fourth, _) = rest.split_at_mut(size);
+        ConsoleStaging {
+            queues: [
+                StagingQueue::new(first),
+                StagingQueue::new(second),
+                StagingQueue::new(third),
+                StagingQueue::new(fourth),
+            ],
+            draining: AtomicBool::new(false),
+        }
+    }
+
+    // queue the data of the current core and send all queued data if no other core is doing so already. Returns
+    // ``false`` if the data has been dropped as the queue of the current core is full
+    pub(crate) fn write(&self, data: &[u8], send: impl Fn(&[u8])) -> bool {
+        let queue = &self.queues[crate::core_id() % CORES];
+        let mut queued = queue.push(data);
+        self.drain(&send);
+        if !queued {
+            // the queue might have been drained in the meantime
+            queued = queue.push(data);
+            self.drain(&send);
+        }
+        queued
+    }
+
+    // send the data of all queues unless another core is doing this already
+    fn drain(&self, send: &impl Fn(&[u8])) {
+        let mut chunk = [0u8; 32];
+        while !self.draining.swap(true, Ordering::Acquire) {
+            for queue in &self.queues {
+                loop {
+                    let count = queue.pop(&mut chunk);
+                    if count == 0 {
+                        break;
+                    }
+                    send(&chunk[..count]);
+                }
+            }
+            self.draining.store(false, Ordering::Release);
+            // data might have been queued right before the draining flag has been cleared
+            if self.queues.iter().all(StagingQueue::is_empty) {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Teardown
+//!
+//! Deinitializing an Uart has to silence it's interrupts before anything else, otherwise an interrupt raised while
+//! tearing down could report events or touch pins that already belong to someone else. Each Uart provides the single
+//! steps, the order they run in is kept here in one place.
+//!
+
+// the steps of tearing down an Uart
+pub(crate) trait Teardown {
+    // mask and acknowledge the interrupts of the peripheral
+    fn disable_interrupts(&mut self);
+
+    // detach what might still access the peripheral from another interrupt, like the FIQ or the heartbeat
+    fn detach(&mut self);
+
+    // discard the events not processed yet
+    fn discard_events(&mut self);
+
+    // disable the peripheral
+    fn shutdown(&mut self);
+
+    // free the GPIO pins claimed
+    fn release_pins(&mut self);
+
+    // reset the settings outliving the instance to their defaults for the next initialization
+    fn reset(&mut self);
+}
+
+// tear down the Uart, the interrupts are disabled first and the pins are released only once the peripheral is shut
+// down
+pub(crate) fn tear_down<T: Teardown>(uart: &mut T) {
+    uart.disable_interrupts();
+    uart.detach();
+    uart.discard_events();
+    uart.shutdown();
+    uart.release_pins();
+    uart.reset();
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::{tear_down, Teardown};
+    use std::vec::Vec;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Step {
+        DisableInterrupts,
+        Detach,
+        DiscardEvents,
+        Shutdown,
+        ReleasePins,
+        Reset,
+    }
+
+    // records the steps and checks no interrupt can fire once the peripheral is gone or the pins are freed
+    struct Recorder {
+        steps: Vec<Step>,
+        interrupts: bool,
+        peripheral: bool,
+        pins: bool,
+    }
+
+    impl Recorder {
+        fn new() -> Self {
+            Recorder {
+                steps: Vec::new(),
+                interrupts: true,
+                peripheral: true,
+                pins: true,
+            }
+        }
+    }
+
+    impl Teardown for Recorder {
+        fn disable_interrupts(&mut self) {
+            self.steps.push(Step::DisableInterrupts);
+            self.interrupts = false;
+        }
+
+        fn detach(&mut self) {
+            self.steps.push(Step::Detach);
+        }
+
+        fn discard_events(&mut self) {
+            assert!(
+                !self.interrupts,
+                "events discarded while interrupts can still queue new ones"
+            );
+            self.steps.push(Step::DiscardEvents);
+        }
+
+        fn shutdown(&mut self) {
+            assert!(
+                !self.interrupts,
+                "peripheral shut down with interrupts enabled"
+            );
+            self.steps.push(Step::Shutdown);
+            self.peripheral = false;
+        }
+
+        fn release_pins(&mut self) {
+            assert!(!self.interrupts, "pins released with interrupts enabled");
+            assert!(
+                !self.peripheral,
+                "pins released while the peripheral still drives them"
+            );
+            self.steps.push(Step::ReleasePins);
+            self.pins = false;
+        }
+
+        fn reset(&mut self) {
+            self.steps.push(Step::Reset);
+        }
+    }
+
+    #[test]
+    fn interrupts_are_disabled_before_the_pins_are_released() {
+        let mut uart = Recorder::new();
+        tear_down(&mut uart);
+        assert_eq!(
+            uart.steps,
+            [
+                Step::DisableInterrupts,
+                Step::Detach,
+                Step::DiscardEvents,
+                Step::Shutdown,
+                Step::ReleasePins,
+                Step::Reset,
+            ]
+        );
+        assert!(!uart.pins);
+    }
+}
@@ -0,0 +1,116 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Binary Telemetry
+//!
+//! Control loops producing numeric data at a high rate waste most of the link formatting it as text. The
+//! [TelemetryWriter] sends each value as a fixed size binary record instead, framed so the host can find the records
+//! in the data stream, detect corrupted ones and tell how many got lost. Any type implementing [AsBytes] can be sent
+//! as a record. The script ``tools/telemetry.py`` decodes the records on the host.
+//!
+//! ## Frame Format
+//!
+//! | Offset  | Size     | Content                                                                               |
+//! |---------|----------|---------------------------------------------------------------------------------------|
+//! | 0       | 2        | the sync word [SYNC], ``0xA5 0x5A``                                                   |
+//! | 2       | 2        | the sequence number, little endian, counting up with each record and wrapping around  |
+//! | 4       | 1        | the size ``n`` of the record in bytes                                                 |
+//! | 5       | n        | the bytes of the record as laid out in the memory of the device (little endian)       |
+//! | 5 + n   | 2        | the CRC-16/CCITT (start 0xFFFF) of the sequence number, size and record, big endian   |
+//!
+//! As the sync word may appear within a record, the host verifies the CRC of each frame found and continues the search
+//! right after the sync word if it does not match. A gap in the sequence numbers tells the number of records lost.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # use ruspiro_uart::telemetry::TelemetryWriter;
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 921_600);
+//! let mut telemetry = TelemetryWriter::new(&uart);
+//! // a record of the timestamp and two readings
+//! telemetry.record(&[1_000u32, 42, 17]).unwrap();
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::crc::crc16;
+use crate::p2plink::Transport;
+use crate::UartError;
+
+pub use crate::sealed::AsBytes;
+
+/// The sync word starting each frame
+pub const SYNC: [u8; 2] = [0xA5, 0x5A];
+
+/// The maximum size of a single record in bytes
+pub const MAX_RECORD_SIZE: usize = 255;
+
+// the size of the sync word, sequence number and record size in front of the record and of the CRC after it
+const HEADER_SIZE: usize = 5;
+const CRC_SIZE: usize = 2;
+
+/// Sending fixed size binary records over an Uart, see the [module documentation](self) for the frame format.
+pub struct TelemetryWriter<'a, U: Transport> {
+    uart: &'a U,
+    sequence: u16,
+}
+
+impl<'a, U: Transport> TelemetryWriter<'a, U> {
+    /// Create a writer sending the records over the given Uart, the first record gets the sequence number 0.
+    pub fn new(uart: &'a U) -> Self {
+        TelemetryWriter { uart, sequence: 0 }
+    }
+
+    /// Send the value as a single record. Fails with ``Err(UartError::PayloadTooLarge)`` if the value exceeds
+    /// [MAX_RECORD_SIZE] bytes.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::Uart0;
+    /// # use ruspiro_uart::telemetry::{AsBytes, TelemetryWriter};
+    /// #[derive(Clone, Copy)]
+    /// #[repr(C)]
+    /// struct Sample {
+    ///     timestamp: u32,
+    ///     position: f32,
+    /// }
+    ///
+    /// // the two fields of 4 bytes each leave no padding
+    /// unsafe impl AsBytes for Sample {}
+    ///
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 921_600);
+    /// let mut telemetry = TelemetryWriter::new(&uart);
+    /// telemetry.record(&Sample { timestamp: 1_000, position: 0.5 }).unwrap();
+    /// # }
+    /// ```
+    pub fn record<T: AsBytes>(&mut self, value: &T) -> Result<(), UartError> {
+        let record = value.as_bytes();
+        if record.len() > MAX_RECORD_SIZE {
+            return Err(UartError::PayloadTooLarge);
+        }
+        let mut frame = [0u8; HEADER_SIZE + MAX_RECORD_SIZE + CRC_SIZE];
+        frame[..2].copy_from_slice(&SYNC);
+        frame[2..4].copy_from_slice(&self.sequence.to_le_bytes());
+        frame[4] = record.len() as u8;
+        let end = HEADER_SIZE + record.len();
+        frame[HEADER_SIZE..end].copy_from_slice(record);
+        let crc = crc16(0xFFFF, &frame[2..end]);
+        frame[end..end + CRC_SIZE].copy_from_slice(&crc.to_be_bytes());
+        self.uart.write(&frame[..end + CRC_SIZE]);
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(())
+    }
+
+    /// The sequence number the next record is sent with
+    pub fn sequence(&self) -> u16 {
+        self.sequence
+    }
+}
@@ -0,0 +1,66 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Console Throttle
+//!
+//! An accidental verbose debug dump sent synchronously keeps the core busy until the last byte has left the
+//! transmitter, starving a real-time loop running on the same core. The throttle grants the synchronous console output
+//! a time budget within each interval. Once the budget is used up the rest of the output is handed to the interrupt
+//! driven console queue, so it is still delivered while the core continues.
+//!
+
+use crate::clock;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+// the time budget in micro seconds within each interval and the time used in the current one
+pub(crate) struct ConsoleThrottle {
+    interval: u64,
+    budget: u64,
+    window_start: AtomicU64,
+    used: AtomicU64,
+}
+
+impl ConsoleThrottle {
+    pub(crate) const fn new() -> Self {
+        ConsoleThrottle {
+            interval: 0,
+            budget: 0,
+            window_start: AtomicU64::new(0),
+            used: AtomicU64::new(0),
+        }
+    }
+
+    // grant the given percentage of each interval, an interval of 0 disables the throttle
+    pub(crate) fn set(&mut self, interval: u64, percent: u8) {
+        self.interval = interval;
+        self.budget = interval * percent.min(100) as u64 / 100;
+        self.window_start.store(clock::now(), Ordering::Relaxed);
+        self.used.store(0, Ordering::Relaxed);
+    }
+
+    // whether the time spent on the console output is limited
+    pub(crate) fn is_active(&self) -> bool {
+        self.interval != 0
+    }
+
+    // the time left in the current interval at the given time, starting the next interval if the current one is over
+    // or the clock has been set back before its start
+    pub(crate) fn remaining(&self, now: u64) -> u64 {
+        let start = self.window_start.load(Ordering::Relaxed);
+        if now < start || now - start >= self.interval {
+            self.window_start.store(now, Ordering::Relaxed);
+            self.used.store(0, Ordering::Relaxed);
+        }
+        self.budget
+            .saturating_sub(self.used.load(Ordering::Relaxed))
+    }
+
+    // account the time spent sending console output
+    pub(crate) fn charge(&self, spent: u64) {
+        self.used.fetch_add(spent, Ordering::Relaxed);
+    }
+}
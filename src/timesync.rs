@@ -0,0 +1,113 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Time Synchronization
+//!
+//! Bare metal systems typically have no clue about the wall clock time. A host connected to an Uart can provide it
+//! with a tiny NTP like handshake. The device sends a request carrying it's local time ``t1``. The host answers with
+//! the time it has received the request ``t2`` and the time it sends the response ``t3``. The device notes the time
+//! it receives the response ``t4``. With those four timestamps the offset between the clocks can be calculated
+//! independent of the transfer time, as long as the transfer takes the same time in both directions.
+//!
+//! All timestamps are given in micro seconds as ``u64`` little endian. The device timestamps are taken from the system
+//! timer, the host timestamps are the micro seconds since the Unix epoch.
+//! ```text
+//! request:  | "TSYN" | t1 |
+//! response: | "TSYR" | t1 | t2 | t3 |
+//! ```
+//! The host echoes ``t1`` to allow the device to reject stale responses. A host side implementation in Python could
+//! look like this:
+//! ```text
+//! import serial, struct, time
+//! port = serial.Serial("/dev/ttyUSB0", 115200)
+//! while True:
+//!     port.read_until(b"TSYN")
+//!     t2 = time.time_ns() // 1000
+//!     t1 = port.read(8)
+//!     t3 = time.time_ns() // 1000
+//!     port.write(b"TSYR" + t1 + struct.pack("<QQ", t2, t3))
+//! ```
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::Uart1;
+//! # use ruspiro_uart::clock;
+//! # use ruspiro_uart::timesync::TimeSync;
+//! # fn doc() {
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! if let Ok(wall_clock) = uart.sync_time(500_000) {
+//!     // the offset to convert clock values into wall clock time
+//!     let offset = wall_clock - clock::now();
+//! }
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::clock;
+use crate::p2plink::Transport;
+use crate::UartError;
+
+// the magic bytes starting the request and the response
+const REQUEST: &[u8; 4] = b"TSYN";
+const RESPONSE: &[u8; 4] = b"TSYR";
+
+/// Synchronizing the time with a host. This is implemented for every [Transport], so for both Uarts.
+pub trait TimeSync {
+    /// Request the wall clock time from the host and wait at most ``timeout`` micro seconds for the response. Returns
+    /// the wall clock time in micro seconds since the Unix epoch at the moment the response has been received. Fails
+    /// with ``Err(UartError::ReceiveDataTimeOut)`` if the host did not answer in time and with
+    /// ``Err(UartError::InvalidMessage)`` if the response does not belong to the request.
+    fn sync_time(&self, timeout: u64) -> Result<u64, UartError>;
+}
+
+impl<U: Transport> TimeSync for U {
+    fn sync_time(&self, timeout: u64) -> Result<u64, UartError> {
+        let t1 = clock::now();
+        self.write(REQUEST);
+        self.write(&t1.to_le_bytes());
+
+        let next_byte = || {
+            let elapsed = clock::elapsed_since(t1);
+            if elapsed > timeout {
+                None
+            } else {
+                self.read_byte(timeout - elapsed)
+            }
+        };
+        // skip everything until the magic bytes of the response have been received
+        let mut matched = 0;
+        while matched < RESPONSE.len() {
+            let byte = next_byte().ok_or(UartError::ReceiveDataTimeOut)?;
+            matched = if byte == RESPONSE[matched] {
+                matched + 1
+            } else if byte == RESPONSE[0] {
+                1
+            } else {
+                0
+            };
+        }
+        let mut timestamps = [0u8; 24];
+        for byte in timestamps.iter_mut() {
+            *byte = next_byte().ok_or(UartError::ReceiveDataTimeOut)?;
+        }
+        let t4 = clock::now();
+
+        let timestamp = |idx: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&timestamps[idx * 8..idx * 8 + 8]);
+            u64::from_le_bytes(bytes) as i128
+        };
+        if timestamp(0) != t1 as i128 {
+            return Err(UartError::InvalidMessage);
+        }
+        let (t1, t2, t3, t4) = (t1 as i128, timestamp(1), timestamp(2), t4 as i128);
+        let offset = ((t2 - t1) + (t3 - t4)) / 2;
+        Ok((t4 + offset) as u64)
+    }
+}
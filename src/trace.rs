@@ -0,0 +1,106 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Tracing
+//!
+//! With the ``trace`` feature active the Uarts emit a begin and an end marker for each initialization, send, receive
+//! and interrupt handling to a registered trace sink. The sink may pass those markers on to a timeline tool to
+//! visualize where the time is spent. Without the feature no markers are emitted at all.
+//!
+//! # Example
+//! ```no_run
+//! # use ruspiro_uart::trace::{self, TraceEvent};
+//! # use ruspiro_uart::Uart1;
+//! fn sink(event: TraceEvent) {
+//!     // forward the event to the timeline tool
+//! }
+//!
+//! # fn doc() {
+//! trace::set_trace_sink(Some(sink));
+//! let mut uart = Uart1::new();
+//! let _ = uart.initialize(250_000_000, 115_200);
+//! # }
+//! ```
+
+#![forbid(unsafe_code)]
+
+use crate::clock;
+use crate::sealed::FnHook;
+
+/// The Uart peripheral emitting a trace event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TracePeripheral {
+    /// the Uart0 (PL011)
+    Uart0,
+    /// the Uart1 (miniUART)
+    Uart1,
+}
+
+/// The operations traced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceSpan {
+    /// the initialization of the peripheral
+    Initialize,
+    /// sending data
+    Send,
+    /// receiving data
+    Receive,
+    /// handling the interrupts of the peripheral
+    Interrupt,
+}
+
+/// A marker emitted at the begin or the end of a traced operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// the peripheral executing the operation
+    pub peripheral: TracePeripheral,
+    /// the operation
+    pub span: TraceSpan,
+    /// ``true`` if the operation begins, ``false`` if it ends
+    pub begin: bool,
+    /// the time of the event in micro seconds
+    pub timestamp: u64,
+}
+
+// the registered sink
+static SINK: FnHook<fn(TraceEvent)> = FnHook::new();
+
+/// Register the sink receiving the trace events, or remove it if ``None`` is given. The sink is called from within
+/// interrupt handlers as well, so it should return quickly.
+pub fn set_trace_sink(sink: Option<fn(TraceEvent)>) {
+    SINK.set(sink);
+}
+
+// pass the event to the registered sink
+fn emit(peripheral: TracePeripheral, span: TraceSpan, begin: bool) {
+    if let Some(sink) = SINK.get() {
+        sink(TraceEvent {
+            peripheral,
+            span,
+            begin,
+            timestamp: clock::now(),
+        });
+    }
+}
+
+// emit the begin marker of the operation. The end marker is emitted once the returned guard is dropped
+pub(crate) fn span(peripheral: TracePeripheral, span: TraceSpan) -> SpanGuard {
+    emit(peripheral, span, true);
+    SpanGuard { peripheral, span }
+}
+
+// emits the end marker of an operation when dropped
+pub(crate) struct SpanGuard {
+    peripheral: TracePeripheral,
+    span: TraceSpan,
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        emit(self.peripheral, self.span, false);
+    }
+}
@@ -0,0 +1,204 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Async Uart0
+//!
+//! The [Uart0Async] reads and writes through futures woken by the Uart0 (PL011) interrupt, so a task waiting for data
+//! to arrive or for space in the transmit FIFO does not block the executor. The futures only rely on the ``core``
+//! waker, so they run on the RusPiRo async executor as well as on any other ``no_std`` executor.
+//!
+
+#![forbid(unsafe_code)]
+
+use super::{interface, Uart0};
+use crate::asynch::poll_fn;
+use crate::sealed::WakerCell;
+use crate::{clock, UartError, UartEvent};
+use core::task::Poll;
+
+// the wakers of the tasks waiting for received data and for space in the transmit FIFO
+static RX_WAKER: WakerCell = WakerCell::new();
+static TX_WAKER: WakerCell = WakerCell::new();
+
+/// Uart0 (PL011) reading and writing through futures woken by the Uart0 interrupt
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::Uart0Async;
+/// # async fn doc() {
+/// let mut uart = Uart0Async::new();
+/// let _ = uart.initialize(3_000_000, 115_200);
+/// let mut buffer = [0u8; 16];
+/// if let Ok(count) = uart.read(&mut buffer).await {
+///     let _ = uart.write(&buffer[..count]).await;
+/// }
+/// # }
+/// ```
+pub struct Uart0Async {
+    uart: Uart0,
+    // the error of a byte received after data already handed out, reported by the next read
+    rx_error: Option<UartError>,
+}
+
+impl Default for Uart0Async {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Uart0Async {
+    /// Get a new async Uart0 instance, that needs to be initialized before it can be used.
+    pub const fn new() -> Self {
+        Uart0Async {
+            uart: Uart0::new(),
+            rx_error: None,
+        }
+    }
+
+    /// Initialize the Uart0 peripheral. The Uart0 interrupt need to call [Uart0Async::handle_interrupt] from now on.
+    pub fn initialize(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.uart.initialize(clock_rate, baud_rate)
+    }
+
+    /// The underlying Uart0, e.g. to configure it
+    pub fn uart(&self) -> &Uart0 {
+        &self.uart
+    }
+
+    /// Receive the data available into the buffer, waiting for at least one byte to arrive. Returns the number of
+    /// bytes received. A byte received with a wrong parity, without a valid stop bit or a break fails the read with
+    /// the corresponding error. If data was received before such a byte, this data is returned first and the error
+    /// is reported by the next read.
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, UartError> {
+        if buffer.is_empty() {
+            return Err(UartError::EmptyBuffer);
+        }
+        interface::check_state()?;
+        let rx_error = &mut self.rx_error;
+        poll_fn(|cx| {
+            RX_WAKER.register(cx.waker());
+            // the receive interrupts are enabled with the initialization, registering first ensures no data arriving
+            // after the check below is missed
+            match receive(buffer, rx_error) {
+                Ok(0) => Poll::Pending,
+                result => Poll::Ready(result),
+            }
+        })
+        .await
+    }
+
+    /// Receive the data available into the buffer like [Uart0Async::read], but give up if no byte arrived within the
+    /// timeout in micro seconds of the [clock](crate::clock). No timer wakes the waiting task, so while no data is
+    /// available the future asks the executor to poll it again, letting the other tasks run in between.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # async fn doc() {
+    /// # let mut uart = Uart0Async::new();
+    /// let mut buffer = [0u8; 16];
+    /// match uart.read_with_timeout(&mut buffer, 100_000).await {
+    ///     Ok(count) => (), // process the data received
+    ///     Err(UartError::ReceiveDataTimeOut) => (), // nothing arrived within 100ms
+    ///     Err(_) => (),
+    /// }
+    /// # }
+    /// ```
+    pub async fn read_with_timeout(
+        &mut self,
+        buffer: &mut [u8],
+        timeout: u64,
+    ) -> Result<usize, UartError> {
+        if buffer.is_empty() {
+            return Err(UartError::EmptyBuffer);
+        }
+        interface::check_state()?;
+        let deadline = clock::now().saturating_add(timeout);
+        let rx_error = &mut self.rx_error;
+        poll_fn(|cx| {
+            RX_WAKER.register(cx.waker());
+            match receive(buffer, rx_error) {
+                Ok(0) if clock::now() >= deadline => {
+                    Poll::Ready(Err(UartError::ReceiveDataTimeOut))
+                }
+                Ok(0) => {
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+                result => Poll::Ready(result),
+            }
+        })
+        .await
+    }
+
+    /// Send the data, waiting for space in the transmit FIFO whenever it is full. Completes once all data has been
+    /// handed to the transmitter.
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), UartError> {
+        interface::check_state()?;
+        let mut sent = 0;
+        poll_fn(|cx| {
+            sent += interface::fill_fifo(&data[sent..]);
+            if sent == data.len() {
+                return Poll::Ready(Ok(()));
+            }
+            TX_WAKER.register(cx.waker());
+            interface::set_tx_interrupt(true);
+            // the transmit interrupt is only raised when the FIFO level drops, which might have happened already
+            sent += interface::fill_fifo(&data[sent..]);
+            if sent == data.len() {
+                interface::set_tx_interrupt(false);
+                return Poll::Ready(Ok(()));
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Wake the tasks waiting for received data or for space in the transmit FIFO. This need to be called from the
+    /// interrupt handler of the Uart0 (PL011) interrupt. The pending interrupts are acknowledged.
+    /// # Example
+    /// ```ignore
+    /// # use ruspiro_uart::Uart0Async;
+    /// #[IrqHandler(Pl011)]
+    /// fn uart0_handler() {
+    ///     Uart0Async::handle_interrupt();
+    /// }
+    /// ```
+    pub fn handle_interrupt() {
+        let _irq = interface::enter_irq();
+        interface::handle_interrupt(|event| match event {
+            UartEvent::DataAvailable(_) | UartEvent::Break | UartEvent::Error(_) => RX_WAKER.wake(),
+            UartEvent::TxComplete => {
+                interface::set_tx_interrupt(false);
+                TX_WAKER.wake();
+            }
+            _ => (),
+        });
+    }
+}
+
+// take the data available without waiting. The error of a byte received after other data is kept to be reported by
+// the next call
+fn receive(buffer: &mut [u8], rx_error: &mut Option<UartError>) -> Result<usize, UartError> {
+    if let Some(error) = rx_error.take() {
+        return Err(error);
+    }
+    let mut count = 0;
+    for slot in buffer.iter_mut() {
+        match interface::read_byte_tagged() {
+            Some((data, flag)) => match flag.error() {
+                Some(error) if count == 0 => return Err(error),
+                Some(error) => {
+                    *rx_error = Some(error);
+                    break;
+                }
+                None => *slot = data,
+            },
+            None => break,
+        }
+        count += 1;
+    }
+    Ok(count)
+}
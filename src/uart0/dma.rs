@@ -0,0 +1,486 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Uart0 DMA Transmit
+//!
+//! Large amounts of data, e.g. log dumps or firmware images, are sent through a DMA channel paced by the transmit
+//! DREQ of the Uart0, instead of writing byte by byte into the transmit FIFO. The DMA controller only writes 32 bit
+//! words to a peripheral, while the data register of the Uart0 takes one byte per write. The data is therefore spread
+//! into words in two staging buffers. While the DMA channel sends one of them, the next chunk is prepared in the other.
+//! Receiving works the other way round, the interrupt of the DMA channel signals each chunk received, upon which the
+//! next chunk is started before the received one is copied out of it's staging buffer.
+//! Frames, e.g. HCI ACL packets or sensor bursts, are received by the DMA channel filling a frame buffer while the
+//! receive timeout interrupt of the Uart0 closes the frame once the line has been idle, so the exact frame length is
+//! known without a length field or terminator byte.
+//! The channel need to be one the firmware does not use itself, channels 0, 2, 3 and 6 are usually taken.
+//!
+
+#![forbid(unsafe_code)]
+
+use super::{interface, IrqFlags, Uart0};
+use crate::sealed::{self, FnHook, MmioBlock};
+use crate::{watchdog, ReadOutcome, Timeout, TimeoutWait, UartError, UartResult};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+
+// the bytes sent by a single DMA transfer
+const CHUNK_SIZE: usize = 256;
+
+// the bytes of the largest frame, enough for an HCI ACL packet
+const FRAME_SIZE: usize = 1024;
+
+/// The function called from the interrupt handlers with each frame received by [Uart0::receive_frames_dma], or with the
+/// error of a byte received with an error within the frame
+pub type FrameFn = fn(Result<&[u8], UartError>);
+
+// the address of the Uart0 data register and the offset of the RAM as seen by the DMA controller. The RAM is accessed
+// through the alias bypassing the L2 cache
+const UART0_DR_BUS: u32 = 0x7E20_1000;
+const RAM_BUS_OFFSET: u32 = 0xC000_0000;
+
+// the registers of a DMA channel
+const DMA_CS: usize = 0x00;
+const DMA_CONBLK_AD: usize = 0x04;
+const DMA_TXFR_LEN: usize = 0x14;
+const DMA_DEBUG: usize = 0x20;
+
+// the bits of the control and status register
+const CS_ACTIVE: u32 = 1 << 0;
+const CS_END: u32 = 1 << 1;
+const CS_INT: u32 = 1 << 2;
+const CS_ERROR: u32 = 1 << 8;
+const CS_RESET: u32 = 1 << 31;
+
+// the transfer information of the control block: raise the interrupt at the end of the transfer, wait for the write
+// response, increment the memory address and pace the access to the data register with the DREQ of the Uart0
+// transmitter (peripheral 12) or receiver (peripheral 14)
+const TI_INTEN: u32 = 1 << 0;
+const TI_WAIT_RESP: u32 = 1 << 3;
+const TI_DEST_INC: u32 = 1 << 4;
+const TI_DEST_DREQ: u32 = 1 << 6;
+const TI_SRC_INC: u32 = 1 << 8;
+const TI_SRC_DREQ: u32 = 1 << 10;
+const TI_PERMAP_UART_TX: u32 = 12 << 16;
+const TI_PERMAP_UART_RX: u32 = 14 << 16;
+
+// the parity error flag of a word read from the data register
+const DR_FE: u32 = 1 << 8;
+const DR_PE: u32 = 1 << 9;
+const DR_BE: u32 = 1 << 10;
+
+// the error flags of the debug register, cleared by writing them
+const DEBUG_ERRORS: u32 = 0b111;
+
+// no DMA channel set
+const NO_CHANNEL: u8 = 0xFF;
+
+// the DMA channel used for sending and receiving
+static CHANNEL: AtomicU8 = AtomicU8::new(NO_CHANNEL);
+
+// whether a transfer owns the DMA channel. It is claimed before the channel is checked and held until the last chunk
+// has completed, as the channel is briefly inactive in between the chunks
+static CLAIMED: AtomicBool = AtomicBool::new(false);
+
+// the flag raised by the interrupt of the DMA channel once a chunk has been received
+static RX_DONE: AtomicBool = AtomicBool::new(false);
+
+// whether frames are received and the function they are passed to
+static FRAMES: AtomicBool = AtomicBool::new(false);
+static FRAME_HOOK: FnHook<FrameFn> = FnHook::new();
+
+// the control block of a transfer, it need to be aligned to 32 bytes
+#[repr(C, align(32))]
+struct ControlBlock([AtomicU32; 8]);
+
+// the data of a transfer spread into one word per byte, aligned to the cache line size
+#[repr(C, align(64))]
+struct Chunk([AtomicU32; CHUNK_SIZE]);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const WORD: AtomicU32 = AtomicU32::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const CONTROL_BLOCK: ControlBlock = ControlBlock([WORD; 8]);
+#[allow(clippy::declare_interior_mutable_const)]
+const CHUNK: Chunk = Chunk([WORD; CHUNK_SIZE]);
+
+// the frame buffer spread into one word per byte like a chunk
+#[repr(C, align(64))]
+struct FrameBuffer([AtomicU32; FRAME_SIZE]);
+
+// the two staging buffers and their control blocks, and the frame buffer
+static CONTROL_BLOCKS: [ControlBlock; 2] = [CONTROL_BLOCK; 2];
+static CHUNKS: [Chunk; 2] = [CHUNK; 2];
+static FRAME: FrameBuffer = FrameBuffer([WORD; FRAME_SIZE]);
+
+impl Uart0 {
+    /// Set the DMA channel [Uart0::send_data_dma] and [Uart0::receive_data_dma] transfer the data through, or ``None``
+    /// to transfer it without DMA. Frames received with [Uart0::receive_frames_dma] are no longer received. Fails
+    /// with ``Err(UartError::InvalidDmaChannel)`` for a channel other than 0 to 14.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(48_000_000, 921_600);
+    /// uart.set_dma_channel(Some(5)).unwrap();
+    /// uart.send_data_dma(&[0x55; 4096]).unwrap();
+    /// # }
+    /// ```
+    pub fn set_dma_channel(&mut self, channel: Option<u8>) -> Result<(), UartError> {
+        stop_frames();
+        match channel {
+            Some(channel) if sealed::dma_channel(channel).is_none() => {
+                Err(UartError::InvalidDmaChannel)
+            }
+            Some(channel) => {
+                CHANNEL.store(channel, Ordering::Relaxed);
+                Ok(())
+            }
+            None => {
+                CHANNEL.store(NO_CHANNEL, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+    }
+
+    /// Receive frames through the DMA channel set with ``set_dma_channel``. The channel fills a frame buffer of up to
+    /// 1024 bytes and the receive timeout interrupt, raised once the line has been idle for 32 bit times, closes the
+    /// frame. The callback is called from the interrupt handler with each frame of it's exact length, or with the
+    /// error of a byte received with an error within the frame, and the next frame is received right away. A frame
+    /// filling the whole buffer is passed on once it is full. [Uart0::handle_interrupt] and
+    /// [Uart0::handle_dma_interrupt] need to be called from the respective interrupt handlers. The receive interrupt is
+    /// masked and the channel stays claimed until the frame reception is stopped with ``None``. Fails with
+    /// ``Err(UartError::NotInitialized)`` if the receiver can not be used, ``Err(UartError::InvalidDmaChannel)`` if no
+    /// channel is set and ``Err(UartError::Busy)`` while the channel is used by another transfer.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # use ruspiro_uart::UartError;
+    /// fn frame_received(frame: Result<&[u8], UartError>) {
+    ///     if let Ok(frame) = frame {
+    ///         // process the frame of frame.len() bytes
+    ///     }
+    /// }
+    ///
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(48_000_000, 921_600);
+    /// uart.set_dma_channel(Some(5)).unwrap();
+    /// uart.receive_frames_dma(Some(frame_received)).unwrap();
+    /// # }
+    /// ```
+    pub fn receive_frames_dma(&mut self, callback: Option<FrameFn>) -> Result<(), UartError> {
+        stop_frames();
+        let callback = match callback {
+            Some(callback) => callback,
+            None => return Ok(()),
+        };
+        if !interface::rx_ready() {
+            return Err(UartError::NotInitialized);
+        }
+        if sealed::dma_channel(CHANNEL.load(Ordering::Relaxed)).is_none() {
+            return Err(UartError::InvalidDmaChannel);
+        }
+        let claim = claim().ok_or(UartError::Busy)?;
+        FRAME_HOOK.set(Some(callback));
+        FRAMES.store(true, Ordering::Release);
+        interface::set_rx_dma(true);
+        start_frame(&claim.dma);
+        // the channel is released once the frame reception is stopped
+        core::mem::forget(claim);
+        interface::set_irq_mask(IrqFlags::RX.bits(), false);
+        interface::set_irq_mask(IrqFlags::RX_TIMEOUT.bits(), true);
+        Ok(())
+    }
+
+    /// Acknowledge the interrupt of the DMA channel set with ``set_dma_channel``, which signals the end of each chunk
+    /// received by [Uart0::receive_data_dma], or a full frame buffer of [Uart0::receive_frames_dma]. The function need
+    /// to be called from the handler of the interrupt of the DMA channel, which is interrupt 16 + channel.
+    /// # Example
+    /// ```ignore
+    /// # use ruspiro_uart::uart0::*;
+    /// #[IrqHandler(Dma5)]
+    /// fn dma_handler() {
+    ///     Uart0::handle_dma_interrupt();
+    /// }
+    /// ```
+    pub fn handle_dma_interrupt() {
+        if let Some(dma) = sealed::dma_channel(CHANNEL.load(Ordering::Relaxed)) {
+            if dma.read(DMA_CS) & CS_INT != 0 {
+                // the transfer has ended, so writing the flags does not pause the channel
+                dma.write(DMA_CS, CS_INT | CS_END);
+                if FRAMES.load(Ordering::Acquire) {
+                    // the frame filled the whole buffer
+                    deliver_frame(FRAME_SIZE, false);
+                    start_frame(&dma);
+                } else {
+                    RX_DONE.store(true, Ordering::Release);
+                }
+            }
+        }
+    }
+}
+
+// send the data through the DMA channel set. Returns ``Ok(false)`` without sending anything if there is no channel set,
+// the channel is busy with another transfer or the transmitter can not be used right now. The transfer is registered
+// as a sender until the last chunk has been sent, so a re-configuration waits for it like for the FIFO sends
+pub(super) fn send(data: &[u8]) -> UartResult<bool> {
+    let _sender = match interface::enter_send(false) {
+        Some(sender) => sender,
+        None => return Ok(false),
+    };
+    let claim = match claim() {
+        Some(claim) => claim,
+        None => return Ok(false),
+    };
+    let dma = &claim.dma;
+    interface::set_tx_dma(true);
+    let mut result = Ok(true);
+    for (index, data) in data.chunks(CHUNK_SIZE).enumerate() {
+        // the other staging buffer might still be sent
+        let chunk = &CHUNKS[index % 2];
+        for (word, byte) in chunk.0.iter().zip(data) {
+            word.store(*byte as u32, Ordering::Relaxed);
+        }
+        sealed::clean_dcache(chunk as *const Chunk as usize, data.len() * 4);
+        if let Err(error) = wait_idle(dma) {
+            result = Err(error);
+            break;
+        }
+        let info = TI_WAIT_RESP | TI_DEST_DREQ | TI_SRC_INC | TI_PERMAP_UART_TX;
+        start(
+            dma,
+            index,
+            info,
+            bus_address(chunk),
+            UART0_DR_BUS,
+            data.len(),
+        );
+    }
+    if result.is_ok() {
+        result = wait_idle(dma).map(|_| true);
+    }
+    interface::set_tx_dma(false);
+    result
+}
+
+// receive into the buffer through the DMA channel set, within the timeout. Returns ``Ok(None)``
+// without receiving anything if there is no channel set, the channel is busy with another transfer or the receiver can
+// not be used right now. The end of each chunk is signalled by the interrupt of the channel, upon which the next chunk
+// is started before the received one is copied into the buffer
+pub(super) fn receive(buffer: &mut [u8], timeout: Timeout) -> UartResult<Option<ReadOutcome>> {
+    if !interface::rx_ready() {
+        return Ok(None);
+    }
+    let claim = match claim() {
+        Some(claim) => claim,
+        None => return Ok(None),
+    };
+    let dma = &claim.dma;
+    let mut wait = TimeoutWait::start(timeout);
+    let total = buffer.len();
+    RX_DONE.store(false, Ordering::Relaxed);
+    interface::set_rx_dma(true);
+    start_rx(dma, 0, total.min(CHUNK_SIZE));
+    let mut result = Ok(ReadOutcome::Complete(total));
+    for (index, data) in buffer.chunks_mut(CHUNK_SIZE).enumerate() {
+        let mut count = data.len();
+        let mut timed_out = false;
+        while !RX_DONE.swap(false, Ordering::Acquire) {
+            if !wait.wait() {
+                // stop the transfer and keep the data received so far
+                count -= (dma.read(DMA_TXFR_LEN) as usize / 4).min(count);
+                dma.write(DMA_CS, CS_RESET);
+                timed_out = true;
+                break;
+            }
+        }
+        if dma.read(DMA_CS) & CS_ERROR != 0 {
+            dma.write(DMA_DEBUG, DEBUG_ERRORS);
+            dma.write(DMA_CS, CS_RESET);
+            result = Err(UartError::DmaError);
+            break;
+        }
+        let next = index * CHUNK_SIZE + data.len();
+        if !timed_out && next < total {
+            start_rx(dma, index + 1, (total - next).min(CHUNK_SIZE));
+        }
+        let chunk = &CHUNKS[index % 2];
+        sealed::invalidate_dcache(chunk as *const Chunk as usize, count * 4);
+        if let Err(error) = copy_received(&chunk.0, &mut data[..count]) {
+            dma.write(DMA_CS, CS_RESET);
+            result = Err(error);
+            break;
+        }
+        if timed_out {
+            result = Ok(ReadOutcome::TimedOut(index * CHUNK_SIZE + count));
+            break;
+        }
+    }
+    interface::set_rx_dma(false);
+    result.map(Some)
+}
+
+// the DMA channel owned by a transfer, released once dropped
+struct Claim {
+    dma: MmioBlock,
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        CLAIMED.store(false, Ordering::Release);
+    }
+}
+
+// stop receiving frames and release the DMA channel
+pub(super) fn stop_frames() {
+    if FRAMES.swap(false, Ordering::AcqRel) {
+        interface::set_irq_mask(IrqFlags::RX_TIMEOUT.bits(), false);
+        if let Some(dma) = sealed::dma_channel(CHANNEL.load(Ordering::Relaxed)) {
+            dma.write(DMA_CS, CS_RESET);
+        }
+        interface::set_rx_dma(false);
+        FRAME_HOOK.set(None);
+        CLAIMED.store(false, Ordering::Release);
+    }
+}
+
+// close the frame received so far at the receive timeout and start receiving the next one. Returns ``false`` if no
+// frames are received
+pub(super) fn close_frame() -> bool {
+    if !FRAMES.load(Ordering::Acquire) {
+        return false;
+    }
+    let dma = match sealed::dma_channel(CHANNEL.load(Ordering::Relaxed)) {
+        Some(dma) => dma,
+        None => return false,
+    };
+    // pause the channel, so the remaining length does not change any more before it is reset
+    dma.write(DMA_CS, 0);
+    let remaining = (dma.read(DMA_TXFR_LEN) as usize / 4).min(FRAME_SIZE);
+    dma.write(DMA_CS, CS_RESET);
+    deliver_frame(FRAME_SIZE - remaining, true);
+    start_frame(&dma);
+    true
+}
+
+// start receiving the next frame into the frame buffer
+fn start_frame(dma: &MmioBlock) {
+    // no cache line of the buffer may be written back while the DMA controller fills it
+    sealed::invalidate_dcache(&FRAME as *const FrameBuffer as usize, FRAME_SIZE * 4);
+    let info = TI_INTEN | TI_WAIT_RESP | TI_DEST_INC | TI_SRC_DREQ | TI_PERMAP_UART_RX;
+    start(dma, 0, info, UART0_DR_BUS, bus_address(&FRAME), FRAME_SIZE);
+}
+
+// pass the frame of the bytes the DMA channel has received to the callback. The bytes left below the DMA request
+// level in the receive FIFO are taken as well if ``drain`` is set
+fn deliver_frame(count: usize, drain: bool) {
+    sealed::invalidate_dcache(&FRAME as *const FrameBuffer as usize, count * 4);
+    let mut frame = [0u8; FRAME_SIZE];
+    let mut result = copy_received(&FRAME.0[..count], &mut frame[..count]).map(|_| count);
+    if drain {
+        while let Ok(len) = result {
+            if len == FRAME_SIZE {
+                break;
+            }
+            match interface::read_fifo_tagged() {
+                Some((byte, flag)) => {
+                    result = match flag.error() {
+                        Some(error) => Err(error),
+                        None => {
+                            frame[len] = byte;
+                            Ok(len + 1)
+                        }
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+    if let Some(hook) = FRAME_HOOK.get() {
+        hook(result.map(|len| &frame[..len]));
+    }
+}
+
+// take the DMA channel set for a transfer, if it is not busy with another one. Only the transfer owning the claim
+// checks and resets the channel, so no other core can reset a running transfer
+fn claim() -> Option<Claim> {
+    let channel = CHANNEL.load(Ordering::Relaxed);
+    let dma = sealed::dma_channel(channel)?;
+    if CLAIMED
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        return None;
+    }
+    let claim = Claim { dma };
+    if claim.dma.read(DMA_CS) & CS_ACTIVE != 0 {
+        // the channel is used by someone else
+        return None;
+    }
+    let enable = sealed::dma_enable();
+    enable.write(0, enable.read(0) | 1 << channel);
+    claim.dma.write(DMA_CS, CS_RESET);
+    Some(claim)
+}
+
+// start a transfer of the bytes with the control block belonging to the staging buffer of the chunk
+fn start(dma: &MmioBlock, index: usize, info: u32, source: u32, destination: u32, len: usize) {
+    let control_block = &CONTROL_BLOCKS[index % 2];
+    let block = [info, source, destination, (len * 4) as u32, 0, 0, 0, 0];
+    for (word, value) in control_block.0.iter().zip(block.iter()) {
+        word.store(*value, Ordering::Relaxed);
+    }
+    sealed::clean_dcache(control_block as *const ControlBlock as usize, 32);
+    dma.write(DMA_CONBLK_AD, bus_address(control_block));
+    dma.write(DMA_CS, CS_ACTIVE);
+}
+
+// start receiving the bytes of the chunk into it's staging buffer
+fn start_rx(dma: &MmioBlock, index: usize, len: usize) {
+    let chunk = &CHUNKS[index % 2];
+    // no cache line of the buffer may be written back while the DMA controller fills it
+    sealed::invalidate_dcache(chunk as *const Chunk as usize, len * 4);
+    let info = TI_INTEN | TI_WAIT_RESP | TI_DEST_INC | TI_SRC_DREQ | TI_PERMAP_UART_RX;
+    start(dma, index, info, UART0_DR_BUS, bus_address(chunk), len);
+}
+
+// copy the received bytes out of the staging buffer, failing on a byte received with an error
+fn copy_received(words: &[AtomicU32], data: &mut [u8]) -> UartResult<()> {
+    for (byte, word) in data.iter_mut().zip(words.iter()) {
+        let raw = word.load(Ordering::Relaxed);
+        if raw & DR_BE != 0 {
+            return Err(UartError::BreakReceived);
+        } else if raw & DR_FE != 0 {
+            return Err(UartError::FramingError);
+        } else if raw & DR_PE != 0 {
+            return Err(UartError::ParityError);
+        }
+        *byte = raw as u8;
+    }
+    Ok(())
+}
+
+// wait until the channel has finished the current transfer
+fn wait_idle(dma: &MmioBlock) -> UartResult<()> {
+    while dma.read(DMA_CS) & CS_ACTIVE != 0 {
+        watchdog::kick();
+    }
+    let cs = dma.read(DMA_CS);
+    // acknowledge the end of the transfer
+    dma.write(DMA_CS, cs & CS_END);
+    if cs & CS_ERROR != 0 {
+        dma.write(DMA_DEBUG, DEBUG_ERRORS);
+        Err(UartError::DmaError)
+    } else {
+        Ok(())
+    }
+}
+
+// the address of the value as seen by the DMA controller
+fn bus_address<T>(value: &T) -> u32 {
+    value as *const T as usize as u32 | RAM_BUS_OFFSET
+}
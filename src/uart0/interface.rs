@@ -8,11 +8,28 @@
 //! # Low-Level Uart0 interface implementation
 //!
 
+use crate::clock;
+use crate::diagnose::{self, Finding};
+use crate::irqstats::{IrqCounter, IrqStats};
+use crate::pl011::Pl011Pins;
+use crate::ringbuffer::SpscRing;
+use crate::sealed::{self, FnHook, MmioBlock};
+use crate::slowsend::SendWatch;
+use crate::uart0::{IrqFlags, BLUETOOTH_PINS};
+use crate::Peripheral;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use ruspiro_gpio::GPIO;
 use ruspiro_register::{define_mmio_register, RegisterFieldValue};
 use ruspiro_timer as timer;
 
-use crate::UartResult;
+use crate::serial::{DataBits, StopBits};
+#[cfg(feature = "trace")]
+use crate::trace::{self, TracePeripheral, TraceSpan};
+use crate::{
+    wait_for_init, watchdog, Direction, InitReport, InitStage, InitStep, IrqFlag, IrqGuard, Parity,
+    RxError, RxFlag, SendGuard, StateCell, UartConfig, UartError, UartEvent, UartResult, UartState,
+};
 
 // Peripheral MMIO base address - depends on the right feature
 #[cfg(feature = "ruspiro_pi3")]
@@ -21,77 +38,1050 @@ const PERIPHERAL_BASE: u32 = 0x3F00_0000;
 // UART0 MMIO base address
 const UART0_BASE: u32 = PERIPHERAL_BASE + 0x0020_1000;
 
+// the depth of the Uart0 transmit FIFO
+const UART0_FIFO_SIZE: usize = 16;
+
+// flag whether a transfer is currently active
+static TX_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+// the directions the Uart0 has been initialized for. The GPIO pin of a direction not used is not reserved
+static TX_ENABLED: AtomicBool = AtomicBool::new(false);
+static RX_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// the pins the Uart0 has been initialized with, packed as TX pin, RX pin and alternative function into one byte each
+static PINS: AtomicU32 = AtomicU32::new(pack_pins(BLUETOOTH_PINS));
+
+// the state of the Uart0, data is only sent or received while it is active
+static STATE: StateCell = StateCell::new();
+
+// the cores executing the interrupt handler of the Uart0
+static IN_IRQ: IrqFlag = IrqFlag::new();
+
+// the interrupts the handler has been entered for
+static IRQ_COUNTER: IrqCounter = IrqCounter::new(Peripheral::Uart0);
+
+// the hook notified of the pending interrupts by the interrupt handler
+static IRQ_HOOK: FnHook<fn(IrqFlags)> = FnHook::new();
+
+// the size of the receive buffer the poll service drains the receive FIFO into
+const RX_POLL_BUFFER_SIZE: usize = 256;
+
+// the receive buffer filled by the poll service and whether the service is attached. While attached all received data
+// is taken from this buffer instead of the receive FIFO
+static RX_POLLED: SpscRing<(u8, RxFlag), RX_POLL_BUFFER_SIZE> = SpscRing::new();
+static RX_POLL_SERVICE: AtomicBool = AtomicBool::new(false);
+
+// whether bytes received with a framing error are dropped as glitches, and the number of bytes dropped so far
+static GLITCH_FILTER: AtomicBool = AtomicBool::new(false);
+static GLITCHES: AtomicUsize = AtomicUsize::new(0);
+
 /// Initialize the Uart0 based on the given core rate and baud rate.
 /// For the time beeing the Uart0 will be bridged to the Raspberry Pi
 /// bluetooth chip.
+/// Each step waiting for the peripheral to respond is bound by a timeout. If only one direction is used only the
+/// corresponding pin is reserved. The steps completed are recorded in the report.
 /// TODO: enable the GPIO pins to be used to be passed from outside
 ///       Is there a way to do some compile time checks, that only valid pins
 ///       are passed?
-pub(crate) fn init(clock_rate: u32, baud_rate: u32) -> UartResult<()> {
+pub(crate) fn init(
+    clock_rate: u32,
+    baud_rate: u32,
+    direction: Direction,
+    pins: Pl011Pins,
+    config: &UartConfig,
+    report: &mut InitReport,
+) -> UartResult<()> {
+    #[cfg(feature = "trace")]
+    let _span = trace::span(TracePeripheral::Uart0, TraceSpan::Initialize);
+    STATE.set(UartState::Configuring);
+    STATE.configured(configure(
+        clock_rate, baud_rate, direction, pins, config, report,
+    ))
+}
+
+// claim the pins and configure the Uart0 registers
+fn configure(
+    clock_rate: u32,
+    baud_rate: u32,
+    direction: Direction,
+    pins: Pl011Pins,
+    config: &UartConfig,
+    report: &mut InitReport,
+) -> UartResult<()> {
+    report.run(InitStep::Pins, acquire_pins(direction, pins))?;
+    let setup = setup(
+        &sealed::uart0_registers(),
+        clock_rate,
+        baud_rate,
+        direction,
+        config,
+        report,
+    );
+    if setup.is_err() {
+        // leave the peripheral disabled and free the pins claimed again
+        shutdown();
+        release();
+        return setup;
+    }
+
+    UART0_IMSC::Register.write_value(
+        RegisterFieldValue::<u32>::new(UART0_IMSC::INT_RX, 0x1)
+            | RegisterFieldValue::<u32>::new(UART0_IMSC::INT_RT, 0x1)
+            | RegisterFieldValue::<u32>::new(UART0_IMSC::INT_OE, 0x1),
+    );
+    report.complete(InitStep::Interrupts);
+
+    // UART0 is now ready to be used
+    Ok(())
+}
+
+// switch the pin to the alternative function
+macro_rules! into_alt {
+    ($pin:ident, $alt:ident) => {
+        match $alt {
+            0 => {
+                $pin.into_alt_f0();
+            }
+            1 => {
+                $pin.into_alt_f1();
+            }
+            2 => {
+                $pin.into_alt_f2();
+            }
+            3 => {
+                $pin.into_alt_f3();
+            }
+            4 => {
+                $pin.into_alt_f4();
+            }
+            _ => {
+                $pin.into_alt_f5();
+            }
+        }
+    };
+}
+
+// the register offsets and bits of a PL011 register block
+const DR: usize = 0x00;
+const FR: usize = 0x18;
+const IBRD: usize = 0x24;
+const FBRD: usize = 0x28;
+const LCRH: usize = 0x2C;
+const CR: usize = 0x30;
+const IFLS: usize = 0x34;
+const IMSC: usize = 0x38;
+const ICR: usize = 0x44;
+const DMACR: usize = 0x48;
+
+const FR_BUSY: u32 = 1 << 3;
+const FR_RXFE: u32 = 1 << 4;
+const FR_TXFF: u32 = 1 << 5;
+const FR_TXFE: u32 = 1 << 7;
+const LCRH_PEN: u32 = 1 << 1;
+const LCRH_EPS: u32 = 1 << 2;
+const LCRH_STP2: u32 = 1 << 3;
+const LCRH_FEN: u32 = 1 << 4;
+const LCRH_WLEN_SHIFT: u32 = 5;
+const CR_UARTEN: u32 = 1 << 0;
+const CR_TXE: u32 = 1 << 8;
+const CR_RXE: u32 = 1 << 9;
+const IFLS_RXIFSEL_SHIFT: u32 = 3;
+
+// The steps below are shared by the Uart0 and the additional PL011 instances of the BCM2711, which only differ in the
+// base address of their register block and of the GPIO block their pins are routed with. The Uart0 runs them on it's
+// own register block.
+
+// reset the PL011 with the given register block and set it up for the line configuration with the FIFOs enabled and
+// all interrupts masked. The pending data is sent out while the PL011 is still enabled, a disabled PL011
+// does not drain it's FIFO. Then it is disabled and the FIFO flushed before re-configuring
+pub(crate) fn setup(
+    registers: &MmioBlock,
+    clock_rate: u32,
+    baud_rate: u32,
+    direction: Direction,
+    config: &UartConfig,
+    report: &mut InitReport,
+) -> UartResult<()> {
+    let (int_div, frac_div) = divisors(clock_rate, baud_rate);
+    let idle = wait_for_init(InitStage::TransmitterIdle, || {
+        registers.read(FR) & FR_BUSY == 0
+    });
+    report.run(InitStep::Peripheral, idle)?;
+    registers.write(CR, 0);
+    registers.write(LCRH, registers.read(LCRH) & !LCRH_FEN);
+    let cleared = wait_for_init(InitStage::FifoClear, || registers.read(FR) & FR_TXFE != 0);
+    report.run(InitStep::Fifo, cleared)?;
+    registers.write(IMSC, 0);
+    registers.write(ICR, 0x7FF);
+    registers.write(DMACR, 0);
+    registers.write(IBRD, int_div);
+    registers.write(FBRD, frac_div);
+    report.complete(InitStep::BaudRate);
+    let ifls = registers.read(IFLS) & !(0b111 << IFLS_RXIFSEL_SHIFT);
+    registers.write(
+        IFLS,
+        ifls | (Ifsel::Filled_1_8 as u32) << IFLS_RXIFSEL_SHIFT,
+    );
+    // writing the line control latches the divisors
+    registers.write(LCRH, line_control(config) | LCRH_FEN);
+    report.complete(InitStep::LineConfig);
+    let mut cr = CR_UARTEN;
+    if direction.tx() {
+        cr |= CR_TXE;
+    }
+    if direction.rx() {
+        cr |= CR_RXE;
+    }
+    registers.write(CR, cr);
+    Ok(())
+}
+
+// write a byte to the PL011 with the given register block once the transmit FIFO has room for it
+pub(crate) fn write_blocking(registers: &MmioBlock, data: u8) {
+    while registers.read(FR) & FR_TXFF != 0 {
+        watchdog::kick();
+    }
+    registers.write(DR, data as u32);
+}
+
+// wait until the PL011 with the given register block has sent out all data and is no longer busy
+pub(crate) fn drain(registers: &MmioBlock) {
+    while registers.read(FR) & FR_BUSY != 0 {
+        watchdog::kick();
+    }
+}
+
+// read one byte and it's error state from the receive FIFO of the PL011 with the given register block
+pub(crate) fn read_tagged(registers: &MmioBlock) -> Option<(u8, RxFlag)> {
+    if registers.read(FR) & FR_RXFE != 0 {
+        return None;
+    }
+    // the data and it's error flags need to be read with one access as reading pops the entry from the FIFO
+    let raw = registers.read(DR);
+    let flag = if raw & DR_BE != 0 {
+        RxFlag::Break
+    } else if raw & DR_FE != 0 {
+        RxFlag::Framing
+    } else if raw & DR_PE != 0 {
+        RxFlag::Parity
+    } else {
+        RxFlag::Ok
+    };
+    Some(((raw & 0xFF) as u8, flag))
+}
+
+// route the pins of the directions in use to the PL011 by writing the function select registers of the given GPIO
+// block directly. This is used for the PL011 instances the GPIO crate does not know the address of, so a pin already
+// switched to another alternative function is refused, while a pin used as input or output is taken over
+pub(crate) fn route_pins(
+    gpio: &MmioBlock,
+    pins: Pl011Pins,
+    direction: Direction,
+) -> UartResult<()> {
+    let Pl011Pins { tx, rx, alt } = pins;
+    if alt > 5 {
+        return Err(UartError::GpioError("invalid alternative function"));
+    }
+    let used = [(direction.tx(), tx), (direction.rx(), rx)];
+    for (_, pin) in used.iter().filter(|(used, _)| *used) {
+        diagnose::check_free(*pin, diagnose::function_at(gpio, *pin), alt as u32)?;
+    }
+    for (_, pin) in used.iter().filter(|(used, _)| *used) {
+        diagnose::select_function(gpio, *pin, diagnose::alt_function(alt as u32));
+    }
+    Ok(())
+}
+
+// switch the pins of the directions in use back to inputs
+pub(crate) fn unroute_pins(gpio: &MmioBlock, pins: Pl011Pins, direction: Direction) {
+    let used = [(direction.tx(), pins.tx), (direction.rx(), pins.rx)];
+    for (_, pin) in used.iter().filter(|(used, _)| *used) {
+        diagnose::select_function(gpio, *pin, 0b000);
+    }
+}
+
+// check and claim the pins of the directions in use within a single GPIO transaction, so no other core can switch or
+// claim a pin in between. The TX pin is released again if the RX pin can not be claimed
+fn claim_pins(pins: Pl011Pins, direction: Direction) -> UartResult<()> {
+    let Pl011Pins { tx, rx, alt } = pins;
+    if alt > 5 {
+        return Err(UartError::GpioError("invalid alternative function"));
+    }
     GPIO.take_for(|gpio| {
-        let _ = gpio.get_pin(32).map(|pin| pin.into_alt_f3());
-        let _ = gpio.get_pin(33).map(|pin| pin.into_alt_f3());
+        if direction.tx() {
+            diagnose::check_pin_free(tx, alt as u32)?;
+        }
+        if direction.rx() {
+            diagnose::check_pin_free(rx, alt as u32)?;
+        }
+        if direction.tx() {
+            gpio.get_pin(tx)
+                .map(|pin| into_alt!(pin, alt))
+                .map_err(UartError::GpioError)?;
+        }
+        if direction.rx() {
+            let rx = gpio.get_pin(rx).map(|pin| into_alt!(pin, alt));
+            if rx.is_err() && direction.tx() {
+                gpio.free_pin(tx);
+            }
+            rx.map_err(UartError::GpioError)?;
+        }
         Ok(())
     })
-    .and_then(|_| {
-        let baud16: u32 = baud_rate * 16;
-        let int_div: u32 = clock_rate / baud16;
-        let frac_div2 = (clock_rate % baud16) * 8 / baud_rate;
-        let frac_div = (frac_div2 / 2) + (frac_div2 % 2);
-
-        // configure UART0
-        UART0_CR::Register.set(0);
-        UART0_IMSC::Register.set(0x0);
-        UART0_ICR::Register.set(0x7FF);
+}
+
+// check and claim the pins of the directions in use within a single GPIO transaction, so no other core can switch or
+// claim a pin in between
+fn acquire_pins(direction: Direction, pins: Pl011Pins) -> UartResult<()> {
+    claim_pins(pins, direction).map(|_| {
+        PINS.store(pack_pins(pins), Ordering::Relaxed);
+        TX_ENABLED.store(direction.tx(), Ordering::Relaxed);
+        RX_ENABLED.store(direction.rx(), Ordering::Relaxed);
+    })
+}
+
+// pack the pins into a single value, so they can be kept in an atomic
+const fn pack_pins(pins: Pl011Pins) -> u32 {
+    pins.tx | (pins.rx << 8) | ((pins.alt as u32) << 16)
+}
+
+// the pins the Uart0 has been initialized with
+pub(crate) fn pins() -> Pl011Pins {
+    let packed = PINS.load(Ordering::Relaxed);
+    Pl011Pins {
+        tx: packed & 0xFF,
+        rx: (packed >> 8) & 0xFF,
+        alt: (packed >> 16) as u8,
+    }
+}
+
+// the integer and fractional baud rate divisor
+pub(crate) fn divisors(clock_rate: u32, baud_rate: u32) -> (u32, u32) {
+    let baud16: u32 = baud_rate * 16;
+    let int_div: u32 = clock_rate / baud16;
+    let frac_div2 = (clock_rate % baud16) * 8 / baud_rate;
+    let frac_div = (frac_div2 / 2) + (frac_div2 % 2);
+    (int_div, frac_div)
+}
+
+// change the baud rate once all pending data has been sent. The divisors are latched by writing the line control
+// register, which may only happen while the Uart0 is disabled. No further data is accepted for sending while waiting
+// for this
+pub(crate) fn set_baud_rate(clock_rate: u32, baud_rate: u32) -> UartResult<()> {
+    let previous = STATE.quiesce(UartState::Configuring)?;
+    let idle = wait_for_init(InitStage::TransmitterIdle, || {
+        UART0_FR::Register.read(UART0_FR::BUSY) == 0
+    });
+    if idle.is_ok() {
+        let (int_div, frac_div) = divisors(clock_rate, baud_rate);
+        let cr = UART0_CR::Register.get();
+        UART0_CR::Register.write(UART0_CR::UART_EN, 0x0);
         UART0_IBRD::Register.set(int_div);
         UART0_FBRD::Register.set(frac_div);
-        UART0_IFLS::Register.write(UART0_IFLS::RXIFSEL, Ifsel::Filled_1_8 as u32);
-        UART0_LCRH::Register.write_value(
-            RegisterFieldValue::<u32>::new(UART0_LCRH::WLEN, Wlen::DataLen8 as u32)
-                | RegisterFieldValue::<u32>::new(UART0_LCRH::FEN, 0x1),
-        );
-        UART0_CR::Register.write_value(
-            RegisterFieldValue::<u32>::new(UART0_CR::UART_EN, 0x1)
-                | RegisterFieldValue::<u32>::new(UART0_CR::TXE, 0x1)
-                | RegisterFieldValue::<u32>::new(UART0_CR::RXE, 0x1),
-        );
-
-        UART0_IMSC::Register.write_value(
-            RegisterFieldValue::<u32>::new(UART0_IMSC::INT_RX, 0x1)
-                | RegisterFieldValue::<u32>::new(UART0_IMSC::INT_RT, 0x1)
-                | RegisterFieldValue::<u32>::new(UART0_IMSC::INT_OE, 0x1),
-        );
-
-        // UART0 is now ready to be used
-        Ok(())
-    })
+        UART0_LCRH::Register.set(UART0_LCRH::Register.get());
+        UART0_CR::Register.set(cr);
+    }
+    // the Uart0 keeps running with the previous baud rate if it did not get idle
+    STATE.set(previous);
+    idle
+}
+
+// hand the Uart0 over to manual register control. It stays suspended for the driver until it is taken back with
+// ``resume_raw``
+pub(crate) fn suspend_raw() -> UartResult<()> {
+    STATE.quiesce(UartState::Suspended).map(|_| ())
+}
+
+// take the Uart0 back from manual register control, restoring the divisor recorded when it has been handed over if it
+// has been changed in the meantime
+pub(crate) fn resume_raw(recorded: u32) {
+    if divisor() != recorded {
+        let cr = UART0_CR::Register.get();
+        UART0_CR::Register.write(UART0_CR::UART_EN, 0x0);
+        UART0_IBRD::Register.set(recorded >> 6);
+        UART0_FBRD::Register.set(recorded & 0x3F);
+        UART0_LCRH::Register.set(UART0_LCRH::Register.get());
+        UART0_CR::Register.set(cr);
+    }
+    STATE.set(UartState::Active);
+}
+
+// the current state of the Uart0
+pub(crate) fn state() -> UartState {
+    STATE.get()
+}
+
+// fail with the error matching the current state if the Uart0 can not be used right now
+pub(crate) fn check_state() -> UartResult<()> {
+    STATE.check()
+}
+
+// mark the current core as executing the interrupt handler of the Uart0 until the guard is dropped
+pub(crate) fn enter_irq() -> IrqGuard<'static> {
+    IN_IRQ.enter()
+}
+
+// whether the current core executes the interrupt handler of the Uart0
+pub(crate) fn in_irq() -> bool {
+    IN_IRQ.is_set()
+}
+
+// whether data can be written to the transmit FIFO, as the transmitter is used and not re-configured
+pub(crate) fn tx_ready() -> bool {
+    TX_ENABLED.load(Ordering::Relaxed) && STATE.is_active()
+}
+
+// register a sender writing to the transmit FIFO until the guard is dropped, waiting for a re-configuration to finish
+// if ``wait`` is set and the caller is not the interrupt handler. Returns ``None`` if nothing can be sent right now
+pub(crate) fn enter_send(wait: bool) -> Option<SendGuard<'static>> {
+    if TX_ENABLED.load(Ordering::Relaxed) {
+        STATE.enter_send(wait && !in_irq())
+    } else {
+        None
+    }
+}
+
+// whether data can be read from the receive FIFO, as the receiver is used and not re-configured
+#[cfg(feature = "dma")]
+pub(crate) fn rx_ready() -> bool {
+    RX_ENABLED.load(Ordering::Relaxed) && STATE.is_active()
+}
+
+// mask all interrupts of the Uart0 and acknowledge the pending ones
+pub(crate) fn disable_interrupts() {
+    UART0_IMSC::Register.set(0x0);
+    UART0_ICR::Register.set(0x7FF);
+}
+
+// unmask or mask the transmit interrupt
+#[cfg(feature = "async")]
+pub(crate) fn set_tx_interrupt(enabled: bool) {
+    UART0_IMSC::Register.write(UART0_IMSC::INT_TX, enabled as u32);
+}
+
+// disable the Uart0 peripheral
+pub(crate) fn shutdown() {
+    STATE.set(UartState::Idle);
+    UART0_DMACR::Register.set(0x0);
+    UART0_CR::Register.set(0x0);
 }
 
 pub(crate) fn release() {
+    let pins = pins();
     GPIO.take_for(|gpio| {
-        gpio.free_pin(32);
-        gpio.free_pin(33);
+        if TX_ENABLED.swap(false, Ordering::Relaxed) {
+            gpio.free_pin(pins.tx);
+        }
+        if RX_ENABLED.swap(false, Ordering::Relaxed) {
+            gpio.free_pin(pins.rx);
+        }
     });
+    PINS.store(pack_pins(BLUETOOTH_PINS), Ordering::Relaxed);
+}
+
+// write byte data to the Uart0
+// The data register only takes 8 bits per access, so there is no way to push more than one byte with a single write.
+// But if the transmit FIFO is empty it is known to accept a whole FIFO worth of data without checking the flags
+// before each and every byte.
+pub(crate) fn write_data(data: &[u8]) {
+    #[cfg(feature = "trace")]
+    let _span = trace::span(TracePeripheral::Uart0, TraceSpan::Send);
+    // wait for a re-configuration to finish instead of dropping the data
+    let _sender = match enter_send(true) {
+        Some(sender) => sender,
+        None => return,
+    };
+    // a transfer interrupting this one is written in between, only the non-blocking sends wait for it to finish
+    let owner = !TX_ACTIVE.swap(true, Ordering::Acquire);
+    let mut remaining = data;
+    let mut watch = SendWatch::start();
+    while !remaining.is_empty() {
+        let free = if UART0_FR::Register.read(UART0_FR::TXFE) == 1 {
+            fifo_depth()
+        } else if UART0_FR::Register.read(UART0_FR::TXFF) == 0 {
+            1
+        } else {
+            // wait until Uart0 is ready to accept writes
+            watch.check(Peripheral::Uart0, remaining.len(), || {
+                UART0_FR::Register.get()
+            });
+            watchdog::kick();
+            timer::sleepcycles(10);
+            continue;
+        };
+        let (chunk, rest) = remaining.split_at(free.min(remaining.len()));
+        for byte in chunk {
+            #[cfg(feature = "tx_history")]
+            crate::history::record(*byte);
+            UART0_DR::Register.set(*byte as u32);
+        }
+        remaining = rest;
+    }
+    if owner {
+        TX_ACTIVE.store(false, Ordering::Release);
+    }
+}
+
+// write as many bytes of the data as the transmit FIFO accepts without waiting, as a transfer of it's own. Returns
+// ``None`` without writing anything if another transfer is active, e.g. the one interrupted by this call
+pub(crate) fn try_send(data: &[u8]) -> Option<usize> {
+    if TX_ACTIVE.swap(true, Ordering::Acquire) {
+        return None;
+    }
+    let count = fill_fifo(data);
+    TX_ACTIVE.store(false, Ordering::Release);
+    Some(count)
+}
+
+// write as many bytes to the transmit FIFO as it accepts without waiting. Returns the number of bytes written
+pub(crate) fn fill_fifo(data: &[u8]) -> usize {
+    let _sender = match enter_send(false) {
+        Some(sender) => sender,
+        None => return 0,
+    };
+    let mut count = 0;
+    for byte in data {
+        if UART0_FR::Register.read(UART0_FR::TXFF) == 1 {
+            break;
+        }
+        #[cfg(feature = "tx_history")]
+        crate::history::record(*byte);
+        UART0_DR::Register.set(*byte as u32);
+        count += 1;
+    }
+    count
+}
+
+// wait until the Uart0 has sent out all data and is no longer busy
+pub(crate) fn flush() {
+    drain(&sealed::uart0_registers());
 }
 
+// wait until the Uart0 has sent out all data, but give up once the timeout in micro seconds has passed. A timeout of
+// 0 does not limit the time. Returns ``false`` if the transmitter did not get idle in time
+pub(crate) fn flush_timeout(timeout: u64) -> bool {
+    let start = clock::now();
+    while UART0_FR::Register.read(UART0_FR::BUSY) == 1 {
+        if timeout != 0 && clock::elapsed_since(start) > timeout {
+            return false;
+        }
+        watchdog::kick();
+        timer::sleepcycles(10);
+    }
+    true
+}
+
+#[cfg(feature = "bench")]
 pub(crate) fn write_byte(data: u8) {
-    // wait until Uart0 is ready to accept writes
+    #[cfg(feature = "tx_history")]
+    crate::history::record(data);
+    write_blocking(&sealed::uart0_registers(), data);
+}
+
+// write a byte to the Uart0 but give up if the transmit FIFO stays full for more than the given timeout in micro
+// seconds. Returns ``false`` if the byte could not be written
+pub(crate) fn write_byte_timeout(data: u8, timeout: u64) -> bool {
+    let _sender = match enter_send(false) {
+        Some(sender) => sender,
+        None => return false,
+    };
+    let start = clock::now();
     while UART0_FR::Register.read(UART0_FR::TXFF) == 1 {
+        if clock::elapsed_since(start) > timeout {
+            return false;
+        }
+        watchdog::kick();
         timer::sleepcycles(10);
     }
+    #[cfg(feature = "tx_history")]
+    crate::history::record(data);
     UART0_DR::Register.set(data as u32);
+    true
 }
 
 pub(crate) fn read_byte() -> Option<u8> {
+    #[cfg(feature = "trace")]
+    let _span = trace::span(TracePeripheral::Uart0, TraceSpan::Receive);
     /*if UART0_FR::Register.read(UART0_FR::RXFE) == 1 {
         None
     } else {
         Some((UART0_DR::Register.get() & 0xFF) as u8)
     }*/
-    while UART0_FR::Register.read(UART0_FR::RXFE) == 1 {
+    loop {
+        if let Some((data, _)) = rx_pop() {
+            return Some(data);
+        }
+        watchdog::kick();
         timer::sleepcycles(10);
     }
-    Some((UART0_DR::Register.get() & 0xFF) as u8)
+}
+
+// read one byte from the Uart0 receive FIFO without blocking and tag it with the error state the PL011 reports along
+// with each received byte
+pub(crate) fn read_byte_tagged() -> Option<(u8, RxFlag)> {
+    #[cfg(feature = "trace")]
+    let _span = trace::span(TracePeripheral::Uart0, TraceSpan::Receive);
+    rx_pop()
+}
+
+// take the next received byte from the receive FIFO, or from the receive buffer if the poll service is attached
+fn rx_pop() -> Option<(u8, RxFlag)> {
+    if RX_POLL_SERVICE.load(Ordering::Acquire) {
+        filter_glitches(|| RX_POLLED.pop())
+    } else {
+        read_fifo_filtered()
+    }
+}
+
+// read the next byte and it's error state from the receive FIFO, dropping the glitches if the filter is enabled
+pub(crate) fn read_fifo_filtered() -> Option<(u8, RxFlag)> {
+    filter_glitches(read_fifo_tagged)
+}
+
+// take the next byte from the source that is not dropped as glitch
+fn filter_glitches(mut next: impl FnMut() -> Option<(u8, RxFlag)>) -> Option<(u8, RxFlag)> {
+    loop {
+        let tagged = next();
+        match tagged {
+            Some((_, RxFlag::Framing)) if GLITCH_FILTER.load(Ordering::Relaxed) => {
+                GLITCHES.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => return tagged,
+        }
+    }
+}
+
+// enable or disable dropping bytes received with a framing error
+pub(crate) fn set_glitch_filter(enabled: bool) {
+    GLITCH_FILTER.store(enabled, Ordering::Relaxed);
+}
+
+// the number of bytes dropped as glitches
+pub(crate) fn glitches() -> usize {
+    GLITCHES.load(Ordering::Relaxed)
+}
+
+// attach or detach the poll service. Data left in the receive buffer when detaching is discarded
+pub(crate) fn set_poll_service(attached: bool) {
+    RX_POLL_SERVICE.store(attached, Ordering::Release);
+    if !attached {
+        while RX_POLLED.pop().is_some() {}
+    }
+}
+
+// drain the receive FIFO into the receive buffer if the poll service is attached. Reports the data added to the
+// buffer and the data lost as the buffer overflowed
+pub(crate) fn poll_service(report: impl Fn(UartEvent)) {
+    if !RX_POLL_SERVICE.load(Ordering::Acquire) {
+        return;
+    }
+    let mut count = 0;
+    let mut overflow = false;
+    while let Some(tagged) = read_fifo_tagged() {
+        if RX_POLLED.push(tagged) {
+            count += 1;
+        } else {
+            overflow = true;
+        }
+    }
+    if overflow {
+        report(UartEvent::Error(RxError::Overrun));
+    }
+    if count != 0 {
+        report(UartEvent::DataAvailable(count));
+    }
+}
+
+// the longest period of the poll service in micro seconds that does not let the receive FIFO overflow
+pub(crate) fn max_poll_period(clock_rate: u32) -> u64 {
+    bit_time_ns(clock_rate) as u64 * frame_bits() as u64 * fifo_depth() as u64 / 1000
+}
+
+// the number of bytes the transmit and receive path hold. With the FIFOs disabled they are single byte holding
+// registers
+fn fifo_depth() -> usize {
+    if UART0_LCRH::Register.read(UART0_LCRH::FEN) == 1 {
+        UART0_FIFO_SIZE
+    } else {
+        1
+    }
+}
+
+// enable or disable the FIFOs. The line control may only be changed while the Uart0 is disabled, so the pending data
+// is sent out before the peripheral is disabled for the change and enabled again afterwards. No further data is
+// accepted for sending while waiting for this. Data waiting in the receive FIFO is lost
+pub(crate) fn set_fifo_enabled(enabled: bool) -> UartResult<()> {
+    let previous = STATE.quiesce(UartState::Configuring)?;
+    let idle = wait_for_init(InitStage::TransmitterIdle, || {
+        UART0_FR::Register.read(UART0_FR::BUSY) == 0
+    });
+    if idle.is_ok() {
+        let cr = UART0_CR::Register.get();
+        UART0_CR::Register.write(UART0_CR::UART_EN, 0x0);
+        UART0_LCRH::Register.write(UART0_LCRH::FEN, enabled as u32);
+        UART0_CR::Register.set(cr);
+    }
+    // the FIFO mode is kept if the Uart0 did not get idle
+    STATE.set(previous);
+    idle
+}
+
+// enable or disable the internal loopback of the transmitted data to the receiver
+#[cfg(feature = "bench")]
+pub(crate) fn set_loopback(enabled: bool) {
+    UART0_CR::Register.write(UART0_CR::LBE, enabled as u32);
+}
+
+// enable or disable the DMA requests of the receive and transmit FIFO and whether a receive error stops the receive
+// requests
+pub(crate) fn set_dma(rx: bool, tx: bool, on_err: bool) {
+    UART0_DMACR::Register.write_value(
+        RegisterFieldValue::<u32>::new(UART0_DMACR::RXDMAE, rx as u32)
+            | RegisterFieldValue::<u32>::new(UART0_DMACR::TXDMAE, tx as u32)
+            | RegisterFieldValue::<u32>::new(UART0_DMACR::DMAONERR, on_err as u32),
+    );
+}
+
+// enable or disable the DMA requests of the transmit FIFO only
+#[cfg(feature = "dma")]
+pub(crate) fn set_tx_dma(enabled: bool) {
+    UART0_DMACR::Register.write(UART0_DMACR::TXDMAE, enabled as u32);
+}
+
+// enable or disable the DMA requests of the receive FIFO only
+#[cfg(feature = "dma")]
+pub(crate) fn set_rx_dma(enabled: bool) {
+    UART0_DMACR::Register.write(UART0_DMACR::RXDMAE, enabled as u32);
+}
+
+// switch the baud rate, the line configuration and the hardware flow control with a single re-configuration once the
+// senders currently writing to the transmit FIFO are done and all pending data has been sent
+pub(crate) fn reconfigure(
+    clock_rate: u32,
+    baud_rate: u32,
+    config: &UartConfig,
+    flow_control: bool,
+) -> UartResult<()> {
+    let previous = STATE.quiesce(UartState::Configuring)?;
+    let idle = wait_for_init(InitStage::TransmitterIdle, || {
+        UART0_FR::Register.read(UART0_FR::BUSY) == 0
+    });
+    if idle.is_ok() {
+        let (int_div, frac_div) = divisors(clock_rate, baud_rate);
+        let enabled = UART0_CR::Register.read(UART0_CR::UART_EN);
+        UART0_CR::Register.write(UART0_CR::UART_EN, 0x0);
+        UART0_IBRD::Register.set(int_div);
+        UART0_FBRD::Register.set(frac_div);
+        // writing the line control latches the divisors
+        write_line_config(config);
+        UART0_CR::Register.write(UART0_CR::CTSEN, flow_control as u32);
+        UART0_CR::Register.write(UART0_CR::RTSEN, flow_control as u32);
+        UART0_CR::Register.write(UART0_CR::UART_EN, enabled);
+    }
+    // the Uart0 keeps running with the previous configuration if it did not get idle
+    STATE.set(previous);
+    idle
+}
+
+// write the line control for the configuration, keeping the FIFO setting. The Uart0 need to be disabled
+fn write_line_config(config: &UartConfig) {
+    let registers = sealed::uart0_registers();
+    registers.write(LCRH, line_control(config) | registers.read(LCRH) & LCRH_FEN);
+}
+
+// the line control bits of a PL011 for the configuration, without the FIFO setting
+fn line_control(config: &UartConfig) -> u32 {
+    let wlen = match config.data_bits {
+        DataBits::Five => Wlen::DataLen5,
+        DataBits::Six => Wlen::DataLen6,
+        DataBits::Seven => Wlen::DataLen7,
+        DataBits::Eight => Wlen::DataLen8,
+    };
+    let mut lcrh = (wlen as u32) << LCRH_WLEN_SHIFT;
+    if config.parity != Parity::None {
+        lcrh |= LCRH_PEN;
+    }
+    if config.parity == Parity::Even {
+        lcrh |= LCRH_EPS;
+    }
+    if config.stop_bits == StopBits::Two {
+        lcrh |= LCRH_STP2;
+    }
+    lcrh
+}
+
+// read one byte and it's error state from the receive FIFO
+pub(crate) fn read_fifo_tagged() -> Option<(u8, RxFlag)> {
+    read_tagged(&sealed::uart0_registers())
+}
+
+// read the receive errors latched in the receive status register and clear them
+pub(crate) fn take_line_errors() -> u32 {
+    let errors = UART0_RSRECR::Register.get();
+    // any write clears all errors
+    UART0_RSRECR::Register.set(0);
+    errors
+}
+
+// error flags reported in the data register along with each received byte
+const DR_FE: u32 = 1 << 8;
+const DR_PE: u32 = 1 << 9;
+const DR_BE: u32 = 1 << 10;
+
+// the duration of a single bit on the line in nano seconds as configured with the baud rate divisor
+pub(crate) fn bit_time_ns(clock_rate: u32) -> u32 {
+    (divisor() as u64 * 1_000_000_000 / (4 * clock_rate as u64)) as u32
+}
+
+// the baud rate divisor, given in 1/64 of the 16 clock cycles sampling a single bit
+pub(crate) fn divisor() -> u32 {
+    64 * (UART0_IBRD::Register.get() & 0xFFFF) + (UART0_FBRD::Register.get() & 0x3F)
+}
+
+// the address of the register block of the Uart0
+pub(crate) fn base_address() -> usize {
+    UART0_BASE as usize
+}
+
+// the GPIO pins claimed for the transmit and the receive line
+pub(crate) fn claimed_pins() -> (Option<u32>, Option<u32>) {
+    let pins = pins();
+    let tx = if TX_ENABLED.load(Ordering::Relaxed) {
+        Some(pins.tx)
+    } else {
+        None
+    };
+    let rx = if RX_ENABLED.load(Ordering::Relaxed) {
+        Some(pins.rx)
+    } else {
+        None
+    };
+    (tx, rx)
+}
+
+// the number of bits on the line for a single character: start bit, data bits, parity bit and stop bits
+pub(crate) fn frame_bits() -> u32 {
+    let lcrh = |field| UART0_LCRH::Register.read(field);
+    1 + (lcrh(UART0_LCRH::WLEN) + 5) + lcrh(UART0_LCRH::PEN) + (lcrh(UART0_LCRH::STP2) + 1)
+}
+
+// check the peripheral and pin configuration for the usual causes of garbled data and report each problem found
+pub(crate) fn diagnose(clock_rate: u32, baud_rate: u32, mut report: impl FnMut(Finding)) {
+    if UART0_CR::Register.read(UART0_CR::UART_EN) == 0 {
+        report(Finding::PeripheralDisabled);
+    }
+    let tx = TX_ENABLED.load(Ordering::Relaxed);
+    let rx = RX_ENABLED.load(Ordering::Relaxed);
+    if tx && UART0_CR::Register.read(UART0_CR::TXE) == 0 {
+        report(Finding::TransmitterDisabled);
+    }
+    if rx && UART0_CR::Register.read(UART0_CR::RXE) == 0 {
+        report(Finding::ReceiverDisabled);
+    }
+    let Pl011Pins {
+        tx: tx_pin,
+        rx: rx_pin,
+        alt,
+    } = pins();
+    let pins = [(tx, tx_pin), (rx, rx_pin)];
+    for (_, pin) in pins.iter().filter(|(used, _)| *used) {
+        if let Some(finding) = diagnose::check_pin(*pin, alt as u32) {
+            report(finding);
+        }
+    }
+    let data_bits = UART0_LCRH::Register.read(UART0_LCRH::WLEN) + 5;
+    if data_bits != 8 {
+        report(Finding::DataSize(data_bits));
+    }
+    if UART0_LCRH::Register.read(UART0_LCRH::FEN) == 0 {
+        report(Finding::FifoDisabled);
+    }
+    let divisor = 64 * (UART0_IBRD::Register.get() & 0xFFFF) as u64
+        + (UART0_FBRD::Register.get() & 0x3F) as u64;
+    let actual = clock_rate as u64 * 4 / divisor.max(1);
+    if let Some(finding) = diagnose::check_baud_rate(baud_rate, actual as u32) {
+        report(finding);
+    }
+}
+
+// write the configuration of the Uart0 peripheral as read from it's registers
+pub(crate) fn describe<W: fmt::Write>(w: &mut W, clock_rate: u32) -> fmt::Result {
+    let ibrd = UART0_IBRD::Register.get() & 0xFFFF;
+    let fbrd = UART0_FBRD::Register.get() & 0x3F;
+    let lcrh = |field| UART0_LCRH::Register.read(field);
+    let fifo_level = ["1/8", "1/4", "1/2", "3/4", "7/8"];
+    let on_off = |value| if value == 1 { "on" } else { "off" };
+    let pins = pins();
+    writeln!(
+        w,
+        "  pins:             TX GPIO{}, RX GPIO{} (alt{})",
+        pins.tx, pins.rx, pins.alt
+    )?;
+    writeln!(
+        w,
+        "  baud rate:        {} (divisor {} + {}/64)",
+        (clock_rate as u64 * 4 / (64 * ibrd as u64 + fbrd as u64).max(1)),
+        ibrd,
+        fbrd
+    )?;
+    writeln!(w, "  data bits:        {}", lcrh(UART0_LCRH::WLEN) + 5)?;
+    writeln!(
+        w,
+        "  parity:           {}",
+        match (
+            lcrh(UART0_LCRH::PEN),
+            lcrh(UART0_LCRH::EPS),
+            lcrh(UART0_LCRH::SPS)
+        ) {
+            (0, _, _) => "none",
+            (_, 0, 0) => "odd",
+            (_, _, 0) => "even",
+            (_, 0, _) => "mark",
+            _ => "space",
+        }
+    )?;
+    writeln!(w, "  stop bits:        {}", lcrh(UART0_LCRH::STP2) + 1)?;
+    writeln!(
+        w,
+        "  flow control:     RTS {}, CTS {}",
+        on_off(UART0_CR::Register.read(UART0_CR::RTSEN)),
+        on_off(UART0_CR::Register.read(UART0_CR::CTSEN))
+    )?;
+    writeln!(
+        w,
+        "  fifo:             {}, RX level {}, TX level {}",
+        if lcrh(UART0_LCRH::FEN) == 1 {
+            "enabled"
+        } else {
+            "disabled"
+        },
+        fifo_level[(UART0_IFLS::Register.read(UART0_IFLS::RXIFSEL) as usize).min(4)],
+        fifo_level[(UART0_IFLS::Register.read(UART0_IFLS::TXIFSEL) as usize).min(4)]
+    )?;
+    writeln!(
+        w,
+        "  dma requests:     RX {}, TX {}, stop on error {}",
+        on_off(UART0_DMACR::Register.read(UART0_DMACR::RXDMAE)),
+        on_off(UART0_DMACR::Register.read(UART0_DMACR::TXDMAE)),
+        on_off(UART0_DMACR::Register.read(UART0_DMACR::DMAONERR))
+    )?;
+    writeln!(
+        w,
+        "  interrupt mask:   {:#05X}",
+        UART0_IMSC::Register.get() & 0x7FF
+    )?;
+    let stats = IRQ_COUNTER.stats();
+    writeln!(
+        w,
+        "  interrupts seen:  {} ({} spurious{})",
+        stats.total,
+        stats.spurious,
+        if stats.masked { ", masked" } else { "" }
+    )
+}
+
+// the interrupt statistics of the Uart0
+pub(crate) fn irq_stats() -> IrqStats {
+    IRQ_COUNTER.stats()
+}
+
+// set the number of spurious interrupts per second tolerated before the interrupt line is disabled
+pub(crate) fn set_spurious_irq_limit(limit: u32, hook: Option<fn(Peripheral)>) {
+    IRQ_COUNTER.set_limit(limit, hook);
+}
+
+// enable the interrupt line disabled due to spurious interrupts
+pub(crate) fn resume_interrupts() {
+    IRQ_COUNTER.resume();
+}
+
+// the raw interrupt status
+pub(crate) fn raw_irq_status() -> u32 {
+    UART0_RIS::Register.get()
+}
+
+// the masked interrupt status
+pub(crate) fn masked_irq_status() -> u32 {
+    UART0_MIS::Register.get()
+}
+
+// acknowledge the given interrupts
+pub(crate) fn clear_irq(flags: u32) {
+    UART0_ICR::Register.set(flags);
+}
+
+// unmask or mask the given interrupts, leaving the others as they are
+pub(crate) fn set_irq_mask(flags: u32, enabled: bool) {
+    let mask = UART0_IMSC::Register.get();
+    if enabled {
+        UART0_IMSC::Register.set(mask | flags);
+    } else {
+        UART0_IMSC::Register.set(mask & !flags);
+    }
+}
+
+// set the hook notified of the pending interrupts by the interrupt handler
+pub(crate) fn set_irq_hook(hook: Option<fn(IrqFlags)>) {
+    IRQ_HOOK.set(hook);
+}
+
+// decode the pending interrupts of the Uart0, report them as events and acknowledge them
+pub(crate) fn handle_interrupt(report: impl Fn(UartEvent)) {
+    #[cfg(feature = "trace")]
+    let _span = trace::span(TracePeripheral::Uart0, TraceSpan::Interrupt);
+    IRQ_COUNTER.entered();
+    let mis = UART0_MIS::Register.get();
+    if mis == 0 {
+        IRQ_COUNTER.spurious();
+        return;
+    }
+    if let Some(hook) = IRQ_HOOK.get() {
+        hook(IrqFlags::from_bits_truncate(mis));
+    }
+    let pending = |field| UART0_MIS::Register.read(field) == 1;
+    if pending(UART0_MIS::INT_OE) {
+        report(UartEvent::Error(RxError::Overrun));
+    }
+    if pending(UART0_MIS::INT_PE) {
+        report(UartEvent::Error(RxError::Parity));
+    }
+    if pending(UART0_MIS::INT_BE) {
+        report(UartEvent::Break);
+    } else if pending(UART0_MIS::INT_FE) {
+        report(UartEvent::Error(RxError::Framing));
+    }
+    if pending(UART0_MIS::INT_RX) {
+        // the receive interrupt is raised once the configured FIFO level is reached, or for each single byte if the
+        // FIFOs are disabled
+        if fifo_depth() == 1 {
+            report(UartEvent::DataAvailable(1));
+        } else {
+            let level = [2, 4, 8, 12, 14];
+            let rxifsel = UART0_IFLS::Register.read(UART0_IFLS::RXIFSEL) as usize;
+            report(UartEvent::DataAvailable(level[rxifsel.min(4)]));
+        }
+    } else if pending(UART0_MIS::INT_RT) && !rx_frame_closed() {
+        report(UartEvent::DataAvailable(1));
+    }
+    if pending(UART0_MIS::INT_TX) {
+        report(UartEvent::TxComplete);
+    }
+    if pending(UART0_MIS::INT_CTSM) || pending(UART0_MIS::INT_DSRM) || pending(UART0_MIS::INT_DCDM)
+    {
+        report(UartEvent::ModemChange);
+    }
+    UART0_ICR::Register.set(mis);
+}
+
+// close the frame received through DMA at the receive timeout, if frames are received that way
+#[cfg(feature = "dma")]
+fn rx_frame_closed() -> bool {
+    super::dma::close_frame()
+}
+
+#[cfg(not(feature = "dma"))]
+fn rx_frame_closed() -> bool {
+    false
 }
 
 #[allow(dead_code, non_camel_case_types)]
@@ -114,7 +1104,7 @@ enum Wlen {
 define_mmio_register![
     UART0_DR<ReadWrite<u32>@(UART0_BASE + 0x00)>,
     UART0_RSRECR<ReadWrite<u32>@(UART0_BASE + 0x04)>,
-    UART0_FR<ReadWrite<u32>@(UART0_BASE + 0x18)> {
+    UART0_FR<ReadOnly<u32>@(UART0_BASE + 0x18)> {
         TXFE    OFFSET(7),
         RXFF    OFFSET(6),
         TXFF    OFFSET(5),
@@ -158,9 +1148,25 @@ define_mmio_register![
         INT_RX      OFFSET(4), // receive FiFo reached water mark
         INT_DSRM    OFFSET(3),
         INT_DCDM    OFFSET(2),
-        INT_CTSM    OFFSET(1)     
+        INT_CTSM    OFFSET(1)
     },
-    UART0_RIS<ReadWrite<u32>@(UART0_BASE + 0x3C)>,
-    UART0_MIS<ReadWrite<u32>@(UART0_BASE + 0x40)>,
-    UART0_ICR<ReadWrite<u32>@(UART0_BASE + 0x44)>
+    UART0_RIS<ReadOnly<u32>@(UART0_BASE + 0x3C)>,
+    UART0_MIS<ReadOnly<u32>@(UART0_BASE + 0x40)> {
+        INT_OE      OFFSET(10),
+        INT_BE      OFFSET(9),
+        INT_PE      OFFSET(8),
+        INT_FE      OFFSET(7),
+        INT_RT      OFFSET(6),
+        INT_TX      OFFSET(5),
+        INT_RX      OFFSET(4),
+        INT_DSRM    OFFSET(3),
+        INT_DCDM    OFFSET(2),
+        INT_CTSM    OFFSET(1)
+    },
+    UART0_ICR<ReadWrite<u32>@(UART0_BASE + 0x44)>,
+    UART0_DMACR<ReadWrite<u32>@(UART0_BASE + 0x48)> {
+        DMAONERR    OFFSET(2), // disable the receive DMA request on a receive error interrupt
+        TXDMAE      OFFSET(1),
+        RXDMAE      OFFSET(0)
+    }
 ];
@@ -0,0 +1,222 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Uart0 Interrupt Status
+//!
+//! Users dispatching the interrupts of the Uart0 (PL011) on their own, instead of using [Uart0::handle_interrupt],
+//! can read the raw and masked interrupt status and acknowledge interrupts through the typed [IrqFlags]. Each
+//! interrupt source can be unmasked on it's own, and a hook registered with [Uart0::set_irq_hook] is told which of
+//! them are pending each time [Uart0::handle_interrupt] runs.
+//!
+
+#![forbid(unsafe_code)]
+
+use super::{interface, Uart0};
+use crate::irqstats::IrqStats;
+use crate::Peripheral;
+use core::ops::BitOr;
+
+/// A set of Uart0 interrupts, as found in the raw (RIS) and masked (MIS) interrupt status or the interrupt clear
+/// (ICR) register
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IrqFlags(u32);
+
+impl IrqFlags {
+    /// clear to send modem status changed
+    pub const CTS: IrqFlags = IrqFlags(1 << 1);
+    /// data carrier detect modem status changed
+    pub const DCD: IrqFlags = IrqFlags(1 << 2);
+    /// data set ready modem status changed
+    pub const DSR: IrqFlags = IrqFlags(1 << 3);
+    /// the receive FIFO reached the configured level
+    pub const RX: IrqFlags = IrqFlags(1 << 4);
+    /// the transmit FIFO dropped to the configured level
+    pub const TX: IrqFlags = IrqFlags(1 << 5);
+    /// data has been waiting in the receive FIFO for 32 bit times
+    pub const RX_TIMEOUT: IrqFlags = IrqFlags(1 << 6);
+    /// a byte has been received without a valid stop bit
+    pub const FRAMING: IrqFlags = IrqFlags(1 << 7);
+    /// a byte has been received with a wrong parity
+    pub const PARITY: IrqFlags = IrqFlags(1 << 8);
+    /// a break has been received
+    pub const BREAK: IrqFlags = IrqFlags(1 << 9);
+    /// data has been received while the receive FIFO was full
+    pub const OVERRUN: IrqFlags = IrqFlags(1 << 10);
+
+    /// No interrupt at all
+    pub const fn empty() -> Self {
+        IrqFlags(0)
+    }
+
+    /// All interrupts of the Uart0
+    pub const fn all() -> Self {
+        IrqFlags(0x7FE)
+    }
+
+    /// Decode the value of an interrupt register, bits not assigned to an interrupt are dropped
+    pub const fn from_bits_truncate(bits: u32) -> Self {
+        IrqFlags(bits & Self::all().0)
+    }
+
+    /// The value of the interrupt register
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Check whether no interrupt is contained
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Check whether all of the given interrupts are contained
+    pub const fn contains(self, other: IrqFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for IrqFlags {
+    type Output = IrqFlags;
+
+    fn bitor(self, other: IrqFlags) -> IrqFlags {
+        IrqFlags(self.0 | other.0)
+    }
+}
+
+impl Uart0 {
+    /// The interrupts raised by the Uart0, regardless whether they are masked. Returns no interrupts if the Uart0 is
+    /// not initialized.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// if uart.raw_irq_status().contains(IrqFlags::BREAK) {
+    ///     // the line is held low
+    /// }
+    /// # }
+    /// ```
+    pub fn raw_irq_status(&self) -> IrqFlags {
+        if self.initialized {
+            IrqFlags::from_bits_truncate(interface::raw_irq_status())
+        } else {
+            IrqFlags::empty()
+        }
+    }
+
+    /// The interrupts raised by the Uart0 that are not masked, and therefore trigger the interrupt line. Returns no
+    /// interrupts if the Uart0 is not initialized.
+    pub fn masked_irq_status(&self) -> IrqFlags {
+        if self.initialized {
+            IrqFlags::from_bits_truncate(interface::masked_irq_status())
+        } else {
+            IrqFlags::empty()
+        }
+    }
+
+    /// Acknowledge the given interrupts. Interrupts raised for data in the FIFOs are raised again as long as the
+    /// reason persists.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// let pending = uart.masked_irq_status();
+    /// // dispatch the pending interrupts ...
+    /// uart.clear_irq(pending);
+    /// # }
+    /// ```
+    pub fn clear_irq(&self, flags: IrqFlags) {
+        if self.initialized {
+            interface::clear_irq(flags.bits());
+        }
+    }
+
+    /// Unmask the given interrupts, so they trigger the interrupt line of the Uart0. The other interrupts stay as they
+    /// are. The receive, receive timeout and overrun interrupts are unmasked by the initialization.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.enable_interrupts(IrqFlags::BREAK | IrqFlags::FRAMING | IrqFlags::PARITY);
+    /// # }
+    /// ```
+    pub fn enable_interrupts(&self, flags: IrqFlags) {
+        if self.initialized {
+            interface::set_irq_mask(flags.bits(), true);
+        }
+    }
+
+    /// Mask the given interrupts, so they no longer trigger the interrupt line of the Uart0. They are still raised
+    /// in the [Uart0::raw_irq_status].
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.disable_interrupts(IrqFlags::RX_TIMEOUT);
+    /// # }
+    /// ```
+    pub fn disable_interrupts(&self, flags: IrqFlags) {
+        if self.initialized {
+            interface::set_irq_mask(flags.bits(), false);
+        }
+    }
+
+    /// Register a hook called from within [Uart0::handle_interrupt] with the pending interrupts, before they are
+    /// reported as events and acknowledged. ``None`` removes the hook.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// fn pending(flags: IrqFlags) {
+    ///     if flags.contains(IrqFlags::OVERRUN) {
+    ///         // the application did not keep up with the data received
+    ///     }
+    /// }
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.set_irq_hook(Some(pending));
+    /// # }
+    /// ```
+    pub fn set_irq_hook(&mut self, hook: Option<fn(IrqFlags)>) {
+        interface::set_irq_hook(hook);
+    }
+
+    /// The number of interrupts the interrupt handler has been entered for, and how many of them have been spurious
+    /// without any pending cause. See the [irqstats](crate::irqstats) module for details.
+    pub fn irq_stats(&self) -> IrqStats {
+        interface::irq_stats()
+    }
+
+    /// Disable the interrupt line of the Uart0 at the interrupt controller once the interrupt handler has been entered
+    /// spuriously more than ``limit`` times within a second, and notify the hook from within the interrupt handler. A
+    /// limit of 0 removes the limit, which is the default. See the [irqstats](crate::irqstats) module for details.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::Peripheral;
+    /// # fn storm(_: Peripheral) {}
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.set_spurious_irq_limit(1_000, Some(storm));
+    /// # }
+    /// ```
+    pub fn set_spurious_irq_limit(&mut self, limit: u32, hook: Option<fn(Peripheral)>) {
+        interface::set_spurious_irq_limit(limit, hook);
+    }
+
+    /// Enable the interrupt line of the Uart0 again that has been disabled due to spurious interrupts exceeding the limit
+    pub fn resume_interrupts(&self) {
+        interface::resume_interrupts();
+    }
+}
@@ -0,0 +1,256 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Shared Uart0
+//!
+//! The Uart0 (PL011) is connected to the bluetooth module, but may carry console traffic as well while the module is
+//! only poked occasionally. A [Uart0Lease] serializes those users. Each lease waits for the previous one to be
+//! released, which sends out all data of the previous user, and switches the baud rate, the line configuration and the
+//! hardware flow control if the previous lease has been taken for another [Purpose].
+//!
+//! The console output written through the [ConsoleImpl](ruspiro_console::ConsoleImpl) of the Uart0 takes the Uart0
+//! for [Purpose::Console] as well, so it never reaches the device of another lease holder. It waits for the lease of
+//! another purpose to be released, bounded by the console timeout if one is set.
+//!
+
+#![forbid(unsafe_code)]
+
+use super::{interface, Uart0};
+use crate::{watchdog, UartConfig, UartError};
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use ruspiro_timer as timer;
+
+/// The users sharing the Uart0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Purpose {
+    /// the HCI transport of the bluetooth module
+    Hci = 0,
+    /// the debug console
+    Console = 1,
+}
+
+/// The configuration the Uart0 is switched to when leased for a [Purpose]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeaseConfig {
+    /// the baud rate, 0 to use the baud rate the Uart0 has been initialized with
+    pub baud_rate: u32,
+    /// the format of the characters
+    pub line: UartConfig,
+    /// whether the RTS and CTS lines are handled by the Uart0. The pins need to be routed to the Uart0 already, e.g.
+    /// GPIO30 and GPIO31 with alt3 for the bluetooth module
+    pub flow_control: bool,
+}
+
+// the purpose holding the lease in the lowest byte and the number of users sharing it above, FREE if there is none.
+// Only the console output shares a lease, an explicit lease is always exclusive
+const FREE: u32 = 0xFF;
+static HOLDER: AtomicU32 = AtomicU32::new(FREE);
+
+// the purpose the Uart0 has been configured for last, NONE if it need to be configured again
+const NONE: u8 = u8::MAX;
+static CONFIGURED: AtomicU8 = AtomicU8::new(NONE);
+
+/// Exclusive access to the Uart0 for a single [Purpose], taken with [Uart0::lease]. Once the lease is dropped all data
+/// written has been sent and the Uart0 is handed to the next user.
+pub struct Uart0Lease<'a> {
+    uart: &'a Uart0,
+}
+
+impl Deref for Uart0Lease<'_> {
+    type Target = Uart0;
+
+    fn deref(&self) -> &Uart0 {
+        self.uart
+    }
+}
+
+impl Drop for Uart0Lease<'_> {
+    fn drop(&mut self) {
+        interface::flush();
+        leave();
+    }
+}
+
+// the Uart0 taken for a single console write, released when dropped
+pub(crate) struct ConsoleLease;
+
+impl Drop for ConsoleLease {
+    fn drop(&mut self) {
+        leave();
+    }
+}
+
+impl Uart0 {
+    /// Set the baud rate the Uart0 is switched to when leased for the given purpose, keeping the line configuration
+    /// and flow control set for it. Without a rate set the baud rate the Uart0 has been initialized with is used.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(48_000_000, 115_200);
+    /// uart.set_lease_baud_rate(Purpose::Hci, 921_600);
+    /// # }
+    /// ```
+    pub fn set_lease_baud_rate(&mut self, purpose: Purpose, baud_rate: u32) {
+        let config = self.lease_config(purpose);
+        self.lease_configs[purpose as usize] = Some(LeaseConfig {
+            baud_rate,
+            ..config
+        });
+    }
+
+    /// Set the configuration the Uart0 is switched to when leased for the given purpose. Without a configuration set
+    /// the baud rate and line configuration the Uart0 has been initialized with are used, without flow control.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(48_000_000, 115_200);
+    /// uart.set_lease_config(
+    ///     Purpose::Hci,
+    ///     LeaseConfig {
+    ///         baud_rate: 921_600,
+    ///         line: UartConfig::EIGHT_N_ONE,
+    ///         flow_control: true,
+    ///     },
+    /// );
+    /// # }
+    /// ```
+    pub fn set_lease_config(&mut self, purpose: Purpose, config: LeaseConfig) {
+        self.lease_configs[purpose as usize] = Some(config);
+    }
+
+    /// Take the Uart0 for the given purpose, waiting until the current lease has been released. If the previous lease
+    /// has been taken for another purpose the configuration of this purpose is applied. Data received for the previous
+    /// purpose but not yet read is kept. While the lease is held the console output waits for it to be released, so
+    /// the holder must not write to the console of this Uart0 unless a console timeout is set.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 115_200);
+    /// let hci = uart.lease(Purpose::Hci).unwrap();
+    /// // HCI Reset
+    /// hci.write_data(&[0x01, 0x03, 0x0C, 0x00]);
+    /// # }
+    /// ```
+    pub fn lease(&self, purpose: Purpose) -> Result<Uart0Lease<'_>, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        while HOLDER
+            .compare_exchange(
+                FREE,
+                holder(purpose, 1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            watchdog::kick();
+            timer::sleepcycles(10);
+        }
+        let lease = Uart0Lease { uart: self };
+        self.configure_for(purpose)?;
+        Ok(lease)
+    }
+
+    // the configuration applied when leased for the purpose
+    fn lease_config(&self, purpose: Purpose) -> LeaseConfig {
+        self.lease_configs[purpose as usize].unwrap_or(LeaseConfig {
+            baud_rate: 0,
+            line: self.line_config,
+            flow_control: false,
+        })
+    }
+
+    // apply the configuration of the purpose unless it is already in use
+    fn configure_for(&self, purpose: Purpose) -> Result<(), UartError> {
+        if CONFIGURED.load(Ordering::Acquire) != purpose as u8 {
+            let config = self.lease_config(purpose);
+            let baud_rate = match config.baud_rate {
+                0 => self.baud_rate,
+                baud_rate => baud_rate,
+            };
+            interface::reconfigure(
+                self.clock_rate,
+                baud_rate,
+                &config.line,
+                config.flow_control,
+            )?;
+            CONFIGURED.store(purpose as u8, Ordering::Release);
+        }
+        Ok(())
+    }
+}
+
+// take the Uart0 for a console write, sharing the lease with the other console writes but not with an explicit lease.
+// Waits for the lease of another purpose to be released until ``give_up`` returns true. Returns ``None`` if the Uart0
+// could not be taken or switched to the console configuration
+pub(crate) fn enter_console(
+    uart: &Uart0,
+    mut give_up: impl FnMut() -> bool,
+) -> Option<ConsoleLease> {
+    loop {
+        let current = HOLDER.load(Ordering::Relaxed);
+        let configured = CONFIGURED.load(Ordering::Acquire) == Purpose::Console as u8;
+        // the interrupt handler can not wait for the re-configuration to the console configuration
+        let next = if current == FREE && (configured || !interface::in_irq()) {
+            Some(holder(Purpose::Console, 1))
+        } else if current & 0xFF == Purpose::Console as u32 && configured {
+            // join the console writes in progress once they have applied the console configuration
+            Some(current + 0x100)
+        } else {
+            None
+        };
+        if let Some(next) = next {
+            if HOLDER
+                .compare_exchange(current, next, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                let lease = ConsoleLease;
+                return uart.configure_for(Purpose::Console).ok().map(|_| lease);
+            }
+        } else if give_up() {
+            return None;
+        } else {
+            watchdog::kick();
+            timer::sleepcycles(10);
+        }
+    }
+}
+
+// the value of HOLDER for the purpose shared by the number of users
+const fn holder(purpose: Purpose, users: u32) -> u32 {
+    purpose as u32 | (users << 8)
+}
+
+// release the lease of one user, freeing it once the last user has left
+fn leave() {
+    let mut current = HOLDER.load(Ordering::Relaxed);
+    loop {
+        let next = if current >> 8 <= 1 {
+            FREE
+        } else {
+            current - 0x100
+        };
+        match HOLDER.compare_exchange(current, next, Ordering::Release, Ordering::Relaxed) {
+            Ok(_) => break,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+// forget the purpose the Uart0 has been configured for, as it has been (re-)initialized or re-configured
+pub(crate) fn reset() {
+    CONFIGURED.store(NONE, Ordering::Relaxed);
+}
@@ -11,24 +11,139 @@
 //! communication bridge to other peripherals like the buit in bluetooth low energy chip.
 //!
 
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+use crate::buffer::BufferStorage;
+use crate::command::{CommandDispatcher, CommandHandler};
+use crate::diagnose::Finding;
+use crate::encoding::{self, Encoding};
+use crate::escape;
+#[cfg(feature = "fiq")]
+use crate::fiq;
+#[cfg(feature = "heartbeat")]
+use crate::heartbeat::{self, HeartbeatFn};
+use crate::linecheck::LineCheck;
+use crate::pacing::Pacing;
+use crate::pl011::Pl011Pins;
+use crate::prefix::{self, LineStart};
+use crate::raw::RawUart0;
+use crate::ringbuffer::SpscRing;
+use crate::sealed::InitCell;
+use crate::serial::SerialSettings;
+use crate::slowsend::{self, SlowSend};
+use crate::soak::{self, Pattern, SoakReport};
+use crate::staging::ConsoleStaging;
+use crate::teardown::{self, Teardown};
+#[cfg(any(feature = "fiq", feature = "heartbeat"))]
+use crate::Peripheral;
+use crate::{
+    clock, watchdog, Direction, Escape, FifoMode, InitReport, LineErrors, Prefix, ReadOutcome,
+    RetryPolicy, RxFlag, Timeout, UartConfig, UartError, UartEvent, UartState,
+};
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 use ruspiro_console::*;
+use ruspiro_timer as timer;
+
+pub(crate) mod interface;
+
+mod irq;
+pub use irq::IrqFlags;
+
+mod lease;
+pub use lease::{LeaseConfig, Purpose, Uart0Lease};
+
+#[cfg(feature = "dma")]
+mod dma;
+#[cfg(feature = "dma")]
+pub use dma::FrameFn;
+
+#[cfg(feature = "async")]
+mod asynch;
+#[cfg(feature = "async")]
+pub use asynch::Uart0Async;
+
+/// The pins the Uart0 is routed to by [Uart0::initialize], the bridge to the on-board bluetooth chip
+pub const BLUETOOTH_PINS: Pl011Pins = Pl011Pins {
+    tx: 32,
+    rx: 33,
+    alt: 3,
+};
+
+/// The pins of the serial console on the pin header, GPIO14 and GPIO15 with alt0
+pub const CONSOLE_PINS: Pl011Pins = Pl011Pins {
+    tx: 14,
+    rx: 15,
+    alt: 0,
+};
 
-mod interface;
+// the events raised by the interrupt handler waiting to be processed
+const EVENT_QUEUE_SIZE: usize = 16;
+static EVENTS: SpscRing<UartEvent, EVENT_QUEUE_SIZE> = SpscRing::new();
 
 /// Uart0 peripheral representation
 pub struct Uart0 {
     initialized: bool,
+    clock_rate: u32,
+    baud_rate: u32,
+    console_timeout: u64,
+    console_yield: Option<fn()>,
+    console_dropped: AtomicUsize,
+    encoding: Encoding,
+    fallback: u8,
+    commands: CommandDispatcher,
+    staging: InitCell<Option<ConsoleStaging>>,
+    prefix: Prefix,
+    line_start: LineStart,
+    escape: AtomicU8,
+    line_check: LineCheck,
+    lease_configs: [Option<LeaseConfig>; 2],
+    line_config: UartConfig,
+    pacing: Pacing,
+    init_report: InitReport,
+    retry_policy: RetryPolicy,
+}
+
+impl Default for Uart0 {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Uart0 {
     /// get a new Uart0 instance
     pub const fn new() -> Self {
-        Uart0 { initialized: false }
+        Uart0 {
+            initialized: false,
+            clock_rate: 0,
+            baud_rate: 0,
+            console_timeout: 0,
+            console_yield: None,
+            console_dropped: AtomicUsize::new(0),
+            encoding: Encoding::Utf8,
+            fallback: b'?',
+            commands: CommandDispatcher::new(),
+            staging: InitCell::new(None),
+            prefix: Prefix::None,
+            line_start: LineStart::new(),
+            escape: AtomicU8::new(Escape::None as u8),
+            line_check: LineCheck::new(),
+            lease_configs: [None; 2],
+            line_config: UartConfig::EIGHT_N_ONE,
+            pacing: Pacing::NONE,
+            init_report: InitReport::new(),
+            retry_policy: RetryPolicy::ABORT,
+        }
     }
 
     /// Initialize the Uart0 peripheral for usage. It takes the UART clock rate and the
     /// baud rate to configure correct communication speed. Please not that in the current version the initialization
-    /// of the Uart0 will use the GPIO pins 32 and 33 to configure the bridge to the on-board bluetooth low energy chip.
+    /// of the Uart0 will use the GPIO pins 32 and 33 to configure the bridge to the on-board bluetooth low energy chip,
+    /// use [Uart0::initialize_with_pins] to route it to other pins.
+    /// If the peripheral does not respond while being initialized an ``Err(UartError::InitializationTimeout(stage))``
+    /// is returned naming the stage of the initialization that failed.
     ///
     /// # Example
     /// ```no_run
@@ -38,14 +153,227 @@ impl Uart0 {
     /// assert_eq!(uart.initialize(3_000_000, 115_200), Ok(()));
     /// # }
     /// ```
-    pub fn initialize(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), &'static str> {
-        interface::init(clock_rate, baud_rate).map(|_| {
+    pub fn initialize(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.initialize_for(
+            clock_rate,
+            baud_rate,
+            Direction::Both,
+            BLUETOOTH_PINS,
+            &UartConfig::EIGHT_N_ONE,
+        )
+    }
+
+    /// Initialize the Uart0 peripheral for sending only. Only the TX pin GPIO32 is reserved and the receiver stays
+    /// disabled, so the RX pin GPIO33 can be used otherwise.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// assert_eq!(uart.initialize_tx_only(3_000_000, 115_200), Ok(()));
+    /// # }
+    /// ```
+    pub fn initialize_tx_only(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.initialize_for(
+            clock_rate,
+            baud_rate,
+            Direction::TxOnly,
+            BLUETOOTH_PINS,
+            &UartConfig::EIGHT_N_ONE,
+        )
+    }
+
+    /// Initialize the Uart0 peripheral for receiving only. Only the RX pin GPIO33 is reserved and the transmitter
+    /// stays disabled, so the TX pin GPIO32 can be used otherwise. Any data sent is dropped.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// assert_eq!(uart.initialize_rx_only(3_000_000, 9_600), Ok(()));
+    /// # }
+    /// ```
+    pub fn initialize_rx_only(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.initialize_for(
+            clock_rate,
+            baud_rate,
+            Direction::RxOnly,
+            BLUETOOTH_PINS,
+            &UartConfig::EIGHT_N_ONE,
+        )
+    }
+
+    /// Initialize the Uart0 peripheral like [Uart0::initialize], but with the given line configuration. The PL011
+    /// supports 5 to 8 data bits, an odd or even parity and 1 or 2 stop bits in any combination. The configuration is
+    /// applied before the Uart0 is enabled, so no character is sent or received with the default 8N1 setting.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::serial::{DataBits, StopBits};
+    /// # fn doc() {
+    /// let config = UartConfig {
+    ///     data_bits: DataBits::Eight,
+    ///     parity: Parity::Even,
+    ///     stop_bits: StopBits::Two,
+    /// };
+    /// let mut uart = Uart0::new();
+    /// assert_eq!(uart.initialize_with_config(3_000_000, 115_200, &config), Ok(()));
+    /// # }
+    /// ```
+    pub fn initialize_with_config(
+        &mut self,
+        clock_rate: u32,
+        baud_rate: u32,
+        config: &UartConfig,
+    ) -> Result<(), UartError> {
+        self.initialize_for(
+            clock_rate,
+            baud_rate,
+            Direction::Both,
+            BLUETOOTH_PINS,
+            config,
+        )
+    }
+
+    /// Initialize the Uart0 peripheral with line settings given in the vocabulary of host side serial port crates.
+    /// The settings supported are the ones of [Uart0::initialize_with_config] without flow control, other settings
+    /// fail with ``Err(UartError::UnsupportedSettings)``.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # use ruspiro_uart::serial::*;
+    /// # fn doc() {
+    /// let settings = SerialSettings {
+    ///     baud_rate: 115_200,
+    ///     data_bits: DataBits::Eight,
+    ///     parity: Parity::Even,
+    ///     stop_bits: StopBits::Two,
+    ///     flow_control: FlowControl::None,
+    /// };
+    /// let mut uart = Uart0::new();
+    /// assert_eq!(uart.initialize_serial(48_000_000, &settings), Ok(()));
+    /// # }
+    /// ```
+    pub fn initialize_serial(
+        &mut self,
+        clock_rate: u32,
+        settings: &SerialSettings,
+    ) -> Result<(), UartError> {
+        self.initialize_with_config(clock_rate, settings.baud_rate, &settings.config()?)
+    }
+
+    /// The steps completed by the last initialization of this instance, and the step it failed at. This tells which
+    /// step has timed out if an initialization fails with ``Err(UartError::InitializationTimeout(_))``.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// if uart.initialize(48_000_000, 115_200).is_err() {
+    ///     if let Some((step, error)) = uart.last_init_report().failure() {
+    ///         println!("initialization failed at {:?}: {}", step, error);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn last_init_report(&self) -> InitReport {
+        self.init_report
+    }
+
+    /// Initialize the Uart0 peripheral like [Uart0::initialize], but route it to the given GPIO pins using the
+    /// alternative function ``alt_fn`` (0 to 5) instead of the bridge to the bluetooth chip. This allows the Uart0 to
+    /// drive a normal serial console on GPIO14/15 (alt0, see [CONSOLE_PINS]). Fails with ``Err(UartError::GpioError)``
+    /// if a pin can not be claimed or the alternative function is invalid, and with ``Err(UartError::PinBusy)`` if a
+    /// pin is switched to an alternative function of another peripheral, e.g. PCM audio.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// assert_eq!(uart.initialize_with_pins(3_000_000, 115_200, 14, 15, 0), Ok(()));
+    /// # }
+    /// ```
+    pub fn initialize_with_pins(
+        &mut self,
+        clock_rate: u32,
+        baud_rate: u32,
+        tx_pin: u32,
+        rx_pin: u32,
+        alt_fn: u8,
+    ) -> Result<(), UartError> {
+        let pins = Pl011Pins {
+            tx: tx_pin,
+            rx: rx_pin,
+            alt: alt_fn,
+        };
+        self.initialize_for(
+            clock_rate,
+            baud_rate,
+            Direction::Both,
+            pins,
+            &UartConfig::EIGHT_N_ONE,
+        )
+    }
+
+    /// Initialize the Uart0 peripheral routed to the given GPIO pins like [Uart0::initialize_with_pins], with the
+    /// given line configuration like [Uart0::initialize_with_config].
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::uart0::CONSOLE_PINS;
+    /// # use ruspiro_uart::serial::{DataBits, StopBits};
+    /// # fn doc() {
+    /// let config = UartConfig {
+    ///     data_bits: DataBits::Seven,
+    ///     parity: Parity::Even,
+    ///     stop_bits: StopBits::One,
+    /// };
+    /// let mut uart = Uart0::new();
+    /// assert_eq!(uart.initialize_with_pins_and_config(48_000_000, 115_200, CONSOLE_PINS, &config), Ok(()));
+    /// # }
+    /// ```
+    pub fn initialize_with_pins_and_config(
+        &mut self,
+        clock_rate: u32,
+        baud_rate: u32,
+        pins: Pl011Pins,
+        config: &UartConfig,
+    ) -> Result<(), UartError> {
+        self.initialize_for(clock_rate, baud_rate, Direction::Both, pins, config)
+    }
+
+    // initialize the peripheral for the given directions, pins and line configuration
+    fn initialize_for(
+        &mut self,
+        clock_rate: u32,
+        baud_rate: u32,
+        direction: Direction,
+        pins: Pl011Pins,
+        config: &UartConfig,
+    ) -> Result<(), UartError> {
+        self.init_report = InitReport::new();
+        interface::init(
+            clock_rate,
+            baud_rate,
+            direction,
+            pins,
+            config,
+            &mut self.init_report,
+        )
+        .map(|_| {
             self.initialized = true;
+            self.clock_rate = clock_rate;
+            self.baud_rate = baud_rate;
+            self.line_config = *config;
+            lease::reset();
+            #[cfg(feature = "early_log")]
+            crate::early::EARLY_LOG.replay(interface::write_data);
         })
     }
 
     /// Write the byte buffer to the Uart0 transmit buffer/fifo which inturn will send the data to any connected device. In the current setup
-    /// this is the BLE chip.
+    /// this is the BLE chip. Called from within [Uart0::handle_interrupt], e.g. by a handler of the received data, only
+    /// the data the transmit FIFO accepts right away is written, as waiting for the transmitter might never end.
     /// # Example
     /// ```no_run
     /// # use ruspiro_uart::uart0::*;
@@ -57,6 +385,140 @@ impl Uart0 {
     /// # }
     /// ```
     pub fn write_data(&self, data: &[u8]) {
+        if !self.initialized {
+            return;
+        }
+        if interface::in_irq() {
+            interface::fill_fifo(data);
+        } else {
+            interface::write_data(data);
+        }
+    }
+
+    /// Send large amounts of data through the DMA channel set with ``set_dma_channel``, paced by the transmitter, see
+    /// the ``dma`` feature. Returns once all data has been handed to the transmit FIFO. Without the ``dma`` feature,
+    /// without a channel set or while the channel is busy with another transfer the data is written like
+    /// [Uart0::write_data] does. The data need to be placed in memory the DMA controller can access. Fails with
+    /// ``Err(UartError::DmaError)`` if the DMA controller reports an error, in which case an unknown part of the data
+    /// has been sent.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 921_600);
+    /// let dump = [0u8; 8192];
+    /// uart.send_data_dma(&dump).unwrap();
+    /// # }
+    /// ```
+    pub fn send_data_dma(&self, data: &[u8]) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        #[cfg(feature = "dma")]
+        {
+            if dma::send(data)? {
+                return Ok(());
+            }
+        }
+        self.write_data(data);
+        Ok(())
+    }
+
+    /// Receive into the buffer through the DMA channel set with ``set_dma_channel``, paced by the receiver, see the
+    /// ``dma`` feature. The end of each chunk of 256 bytes is signalled by the interrupt of the DMA channel, which need
+    /// to call ``Uart0::handle_dma_interrupt``. Returns ``ReadOutcome::Complete`` once the buffer is filled, or
+    /// ``ReadOutcome::TimedOut`` with the number of bytes received once the timeout has passed.
+    /// Without the ``dma`` feature, without a channel set or while the channel is busy with another transfer the data
+    /// is read from the receive FIFO instead. The receive interrupt handling and the poll service must not take data
+    /// from the receive FIFO meanwhile. Fails with ``Err(UartError::ParityError)``, ``Err(UartError::FramingError)`` or
+    /// ``Err(UartError::BreakReceived)`` on a byte received with the respective error and with
+    /// ``Err(UartError::DmaError)`` if the DMA controller reports an error.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # use ruspiro_uart::Timeout;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 921_600);
+    /// let mut image = [0u8; 16384];
+    /// let outcome = uart.receive_data_dma(&mut image, Timeout::Micros(10_000_000)).unwrap();
+    /// if !outcome.is_complete() {
+    ///     println!("only {} bytes received", outcome.count());
+    /// }
+    /// # }
+    /// ```
+    pub fn receive_data_dma(
+        &self,
+        buffer: &mut [u8],
+        timeout: Timeout,
+    ) -> Result<ReadOutcome, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        #[cfg(feature = "dma")]
+        {
+            if let Some(outcome) = dma::receive(buffer, timeout)? {
+                return Ok(outcome);
+            }
+        }
+        interface::check_state()?;
+        let count = crate::receive_timeout(buffer, timeout, Self::try_receive_byte, Err)?;
+        if count < buffer.len() {
+            Ok(ReadOutcome::TimedOut(count))
+        } else {
+            Ok(ReadOutcome::Complete(count))
+        }
+    }
+
+    /// Write as many bytes of the buffer as the transmit FIFO accepts right now, without waiting for the transmitter.
+    /// Returns the number of bytes written, which is 0 if the FIFO is full. This allows cooperative schedulers to
+    /// poll instead of blocking. Fails with ``Err(UartError::Busy)`` while the Uart0 is re-configured, e.g. by another
+    /// core changing the baud rate, and with ``Err(UartError::WouldBlock)`` while another transfer is sending, e.g. the
+    /// one interrupted by this call.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// let data = [1, 15, 20, 10];
+    /// let mut sent = 0;
+    /// while sent < data.len() {
+    ///     if let Ok(count) = uart.try_send_data(&data[sent..]) {
+    ///         sent += count;
+    ///     }
+    ///     // do something else while the FIFO drains
+    /// }
+    /// # }
+    /// ```
+    pub fn try_send_data(&self, data: &[u8]) -> Result<usize, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::check_state()?;
+        interface::try_send(data).ok_or(UartError::WouldBlock)
+    }
+
+    /// The current state of the Uart0, for diagnostic purposes. Data is only sent or received while the Uart0 is
+    /// ``UartState::Active``.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # use ruspiro_uart::UartState;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// assert_eq!(uart.state(), UartState::Active);
+    /// # }
+    /// ```
+    pub fn state(&self) -> UartState {
+        interface::state()
+    }
+
+    // write the data polling the flag register before each byte, only used to compare the throughput in benchmarks
+    #[cfg(feature = "bench")]
+    pub(crate) fn write_data_bytewise(&self, data: &[u8]) {
         if self.initialized {
             for byte in data {
                 interface::write_byte(*byte);
@@ -64,6 +526,134 @@ impl Uart0 {
         }
     }
 
+    /// Wait until all data written has been sent out on the line.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 115_200);
+    /// uart.write_data(b"going to sleep\r\n");
+    /// uart.flush();
+    /// # }
+    /// ```
+    pub fn flush(&self) {
+        if self.initialized {
+            interface::flush();
+        }
+    }
+
+    /// Change the baud rate at runtime without a full re-initialization, e.g. to switch a boot loader to a higher
+    /// speed once the handshake at 115200 baud is done. The clock rate is given again, as the core clock feeding the
+    /// Uart0 might have changed as well. The Uart0 waits until all data written has been sent out, is disabled while
+    /// the divisors are re-programmed and continues with the new baud rate, the data already received is kept.
+    ///
+    /// Fails with ``Err(UartError::NotInitialized)`` if the Uart0 is not initialized and with
+    /// ``Err(UartError::UnsupportedBaudRate)`` if the baud rate can not be configured with the clock rate. The baud
+    /// rates of the [Uart0Lease]s that do not set their own one follow this change.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 115_200);
+    /// uart.set_baud_rate(48_000_000, 921_600).unwrap();
+    /// # }
+    /// ```
+    pub fn set_baud_rate(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.apply_baud_rate(clock_rate, baud_rate)?;
+        self.clock_rate = clock_rate;
+        self.baud_rate = baud_rate;
+        Ok(())
+    }
+
+    // switch to the baud rate, or back to the configured one if none is given, only used in benchmarks
+    #[cfg(feature = "bench")]
+    pub(crate) fn switch_baud_rate(&self, baud_rate: Option<u32>) -> Result<(), UartError> {
+        self.apply_baud_rate(self.clock_rate, baud_rate.unwrap_or(self.baud_rate))
+    }
+
+    // program the baud rate into the peripheral without changing the one configured for this instance
+    fn apply_baud_rate(&self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        if baud_rate == 0 || baud_rate as u64 * 16 > clock_rate as u64 {
+            return Err(UartError::UnsupportedBaudRate);
+        }
+        interface::set_baud_rate(clock_rate, baud_rate)?;
+        // a lease need to apply it's baud rate again
+        lease::reset();
+        Ok(())
+    }
+
+    // route the transmitted data back to the receiver instead of the line, only used in benchmarks
+    #[cfg(feature = "bench")]
+    pub(crate) fn set_loopback(&self, enabled: bool) {
+        if self.initialized {
+            interface::set_loopback(enabled);
+        }
+    }
+
+    /// Set the time in micro seconds the console output is allowed to wait for the transmit FIFO to accept the next
+    /// byte. If the FIFO stays full for longer the remaining output of the current console write is dropped and counted
+    /// instead of blocking forever. A timeout of ``0`` (the default) means the console output blocks until all data
+    /// has been written.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.set_console_timeout(5_000);
+    /// # }
+    /// ```
+    pub fn set_console_timeout(&mut self, timeout: u64) {
+        self.console_timeout = timeout;
+    }
+
+    /// Register the function switching to another task of a co-operative scheduler, e.g. the ``yield_now`` of an
+    /// RTOS. While the console output waits for the transmitter to accept more data the function is called instead
+    /// of spinning, so other tasks run while a long line is sent. The console timeout still applies. The tasks
+    /// switched to must not write to this console, as the console stays locked by the waiting write. ``None``
+    /// removes the function, which is the default.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn yield_now() {}
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(48_000_000, 115_200);
+    /// uart.set_console_yield(Some(yield_now));
+    /// # }
+    /// ```
+    pub fn set_console_yield(&mut self, yield_now: Option<fn()>) {
+        self.console_yield = yield_now;
+    }
+
+    /// Get the number of bytes that have been dropped from the console output as the transmit FIFO did not accept them
+    /// within the configured console timeout.
+    pub fn console_dropped_bytes(&self) -> usize {
+        self.console_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Set the character encoding used to write strings and characters as console output. Characters not available
+    /// in the encoding are replaced with the ``fallback`` character. The default is to write strings as UTF-8.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # use ruspiro_uart::encoding::Encoding;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.set_encoding(Encoding::Cp437, b'?');
+    /// # }
+    /// ```
+    pub fn set_encoding(&mut self, encoding: Encoding, fallback: u8) {
+        self.encoding = encoding;
+        self.fallback = fallback;
+    }
+
     /// Read one byte from the Uart0 receive buffer/Fifo if available.
     /// # Example
     /// ```no_run
@@ -77,30 +667,1074 @@ impl Uart0 {
     /// # }
     /// ```
     pub fn read_data(&self) -> Option<u8> {
-        if self.initialized {
+        if self.initialized && interface::check_state().is_ok() {
             interface::read_byte()
         } else {
             None
         }
     }
-}
 
-/// When the Uart0 is dropped it should release the GPIO pins that have been aquired.
-impl Drop for Uart0 {
-    fn drop(&mut self) {
-        // release the GPIO pin's occupied by the Uart0
-        interface::release();
+    /// Receive data from the Uart0 into the buffer, blocking until it has been filled or the timeout has passed.
+    /// Returns ``Ok(size)`` once the buffer has been filled. Fails with ``Err(UartError::ReceiveDataTimeOut)`` if the
+    /// timeout has passed before and with ``Err(UartError::ParityError)``, ``Err(UartError::FramingError)`` or
+    /// ``Err(UartError::BreakReceived)`` for a byte received with the respective error, unless the retry policy set
+    /// with [Uart0::set_retry_policy] tolerates it.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # use ruspiro_uart::Timeout;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 115_200);
+    /// let mut buffer = [0u8; 8];
+    /// let size = uart.receive_data(&mut buffer, Timeout::None).expect("unable to receive data");
+    /// # }
+    /// ```
+    pub fn receive_data(&self, buffer: &mut [u8], timeout: Timeout) -> Result<usize, UartError> {
+        let count = self.receive_tagged(buffer, timeout)?;
+        if count < buffer.len() {
+            Err(UartError::ReceiveDataTimeOut)
+        } else {
+            Ok(count)
+        }
     }
-}
 
-/// to use the Uart0 as a console to output strings implement the respective trait
-impl ConsoleImpl for Uart0 {
-    fn putc(&self, c: char) {
-        let data: [u8; 1] = [c as u8];
-        self.write_data(&data);
+    /// Try to receive data from the Uart0 into the buffer, waiting at most the given timeout for the buffer to be
+    /// filled. Returns ``Ok(size)`` with the number of bytes received before the timeout has passed, or
+    /// ``Err(UartError::ReceiveDataTimeOut)`` if no data has been received at all. Bytes received with an error are
+    /// handled like [Uart0::receive_data] does.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # use ruspiro_uart::Timeout;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 115_200);
+    /// let mut buffer = [0u8; 8];
+    /// if let Ok(size) = uart.try_receive_data(&mut buffer, Timeout::Micros(1_000)) {
+    ///     uart.write_data(&buffer[..size]);
+    /// }
+    /// # }
+    /// ```
+    pub fn try_receive_data(
+        &self,
+        buffer: &mut [u8],
+        timeout: Timeout,
+    ) -> Result<usize, UartError> {
+        match self.receive_tagged(buffer, timeout)? {
+            0 => Err(UartError::ReceiveDataTimeOut),
+            count => Ok(count),
+        }
     }
 
-    fn puts(&self, s: &str) {
-        self.write_data(s.as_bytes());
+    /// Set the policy how [Uart0::receive_data] and [Uart0::try_receive_data] handle bytes received with an error,
+    /// e.g. with a wrong parity or without a valid stop bit. This allows long transfers to tolerate isolated glitches.
+    /// By default the receive call fails on the first error.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 115_200);
+    /// uart.set_retry_policy(RetryPolicy {
+    ///     max_retries: 3,
+    ///     on_error: RxErrorAction::Skip,
+    /// });
+    /// # }
+    /// ```
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    // fill the buffer with data received until the timeout has passed, handling the bytes received with an error as
+    // the retry policy demands. Returns the number of bytes received
+    fn receive_tagged(&self, buffer: &mut [u8], timeout: Timeout) -> Result<usize, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        if buffer.is_empty() {
+            return Err(UartError::EmptyBuffer);
+        }
+        interface::check_state()?;
+        let mut errors = 0;
+        crate::receive_timeout(buffer, timeout, Self::try_receive_byte, |error| {
+            self.retry_policy.handle(&mut errors, error)
+        })
+    }
+
+    // take the next received byte without blocking, a byte received with a wrong parity, without a valid stop bit or
+    // a break is reported as error
+    fn try_receive_byte() -> Option<Result<u8, UartError>> {
+        interface::read_byte_tagged().map(|(data, flag)| match flag.error() {
+            Some(error) => Err(error),
+            None => Ok(data),
+        })
+    }
+
+    /// Receive a line terminated by ``\n`` into the buffer, including the line feed and any carriage return before it,
+    /// so interactive shells or modem drivers need not poll for each byte. The whole line need to arrive within the
+    /// timeout. Returns the number of bytes stored. Fails with
+    /// ``Err(UartError::ReceiveDataTimeOut)`` once the timeout has passed, dropping the partial line, with
+    /// ``Err(UartError::BufferTooSmall)`` once the buffer is full before the line is complete, leaving the rest of the
+    /// line to be received, and with ``Err(UartError::ParityError)``, ``Err(UartError::FramingError)`` or
+    /// ``Err(UartError::BreakReceived)`` for a byte received with the respective error.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # use ruspiro_uart::Timeout;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 115_200);
+    /// let mut line = [0u8; 80];
+    /// let len = uart.read_line(&mut line, Timeout::Micros(1_000_000)).expect("unable to receive a line");
+    /// # }
+    /// ```
+    pub fn read_line(&self, buffer: &mut [u8], timeout: Timeout) -> Result<usize, UartError> {
+        self.read_until(b'\n', buffer, timeout)
+    }
+
+    /// Receive data like [Uart0::read_line], but terminated by the given delimiter, e.g. ``\r`` for the responses of
+    /// AT command modems.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # use ruspiro_uart::Timeout;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 115_200);
+    /// let mut response = [0u8; 32];
+    /// let len = uart.read_until(b'\r', &mut response, Timeout::Micros(500_000)).expect("no response");
+    /// # }
+    /// ```
+    pub fn read_until(
+        &self,
+        delimiter: u8,
+        buffer: &mut [u8],
+        timeout: Timeout,
+    ) -> Result<usize, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::check_state()?;
+        crate::read_until(delimiter, buffer, timeout, Self::try_receive_byte)
+    }
+
+    impl_binary_helpers!();
+
+    // send the bytes of a fixed width integer
+    fn send_raw(&self, bytes: &[u8]) {
+        self.write_data(bytes);
+    }
+
+    // receive the bytes of a fixed width integer
+    fn receive_raw(&self, bytes: &mut [u8]) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::check_state()?;
+        for byte in bytes.iter_mut() {
+            *byte = interface::read_byte().ok_or(UartError::ReceiveDataTimeOut)?;
+        }
+        Ok(())
+    }
+
+    /// Drop bytes received with a framing error. On long unshielded cables noise on the idle line is taken as a start
+    /// bit and shows up as a random byte, which in most cases lacks a valid stop bit. With the filter enabled those
+    /// bytes never reach any receive function and are counted in [Uart0::suppressed_glitches] instead. Breaks are
+    /// still reported, as protocols like LIN or DMX rely on them.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.set_glitch_filter(true);
+    /// # }
+    /// ```
+    pub fn set_glitch_filter(&mut self, enabled: bool) {
+        interface::set_glitch_filter(enabled);
+    }
+
+    /// The number of bytes dropped by the glitch filter so far
+    pub fn suppressed_glitches(&self) -> usize {
+        interface::glitches()
+    }
+
+    /// Configure the DMA handshake of the Uart0. With ``rx`` or ``tx`` set the peripheral requests a DMA transfer as
+    /// soon as the receive FIFO reaches it's interrupt level or the transmit FIFO drops below it. With ``on_err`` set
+    /// the receive requests stop on a receive error until the error interrupt is cleared. Apart from
+    /// [Uart0::send_data_dma] the DMA channel need to be set up by the caller with the data register as source or
+    /// destination. Fails with ``Err(UartError::NotInitialized)`` if the Uart0 is not initialized.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 115_200);
+    /// // let a DMA channel drain the receive FIFO
+    /// assert_eq!(uart.configure_dma(true, false, true), Ok(()));
+    /// # }
+    /// ```
+    pub fn configure_dma(&mut self, rx: bool, tx: bool, on_err: bool) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::set_dma(rx, tx, on_err);
+        Ok(())
+    }
+
+    /// Read the data currently available in the Uart0 receive FIFO into the given buffer without blocking. Each byte is
+    /// tagged with the error state the peripheral has detected while receiving it. Returns the number of entries
+    /// filled.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::RxFlag;
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// let mut buffer = [(0u8, RxFlag::Ok); 16];
+    /// let count = uart.read_tagged(&mut buffer);
+    /// for (data, flag) in &buffer[..count] {
+    ///     if *flag == RxFlag::Break {
+    ///         // a new frame starts
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn read_tagged(&self, buffer: &mut [(u8, RxFlag)]) -> usize {
+        if !self.initialized {
+            return 0;
+        }
+        let mut count = 0;
+        for entry in buffer.iter_mut() {
+            match interface::read_byte_tagged() {
+                Some(tagged) => *entry = tagged,
+                None => break,
+            }
+            count += 1;
+        }
+        count
+    }
+
+    /// Get the receive errors the Uart0 has detected since the last call, including data lost as the receive FIFO
+    /// overflowed, and clear them. Reading the data with [Uart0::read_tagged] tells which byte is affected by a
+    /// framing, parity or break error, while an overrun is only reported here. Returns no errors if the Uart0 is not
+    /// initialized.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::LineErrors;
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 115_200);
+    /// let errors = uart.line_status();
+    /// if errors.contains(LineErrors::OVERRUN) {
+    ///     // data got lost, request the frame again
+    /// }
+    /// # }
+    /// ```
+    pub fn line_status(&self) -> LineErrors {
+        if !self.initialized {
+            return LineErrors::empty();
+        }
+        LineErrors::from_bits_truncate(interface::take_line_errors())
+    }
+
+    /// Hand the Uart0 over to manual register control, see the [raw](crate::raw) module. The peripheral keeps running
+    /// and it's pins stay claimed, as does the interrupt handling. The settings of this instance, e.g. the encoding,
+    /// are not kept. The driver treats the Uart0 as suspended until it is taken back with [Uart0::from_raw], so other
+    /// users of the driver do not send or receive in the meantime. Fails with ``Err(UartError::NotInitialized)`` if
+    /// the Uart0 is not initialized, and with ``Err(UartError::InitializationTimeout(_))`` if a send of another core
+    /// does not finish, the Uart0 is shut down like on dropping it then.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(48_000_000, 115_200);
+    /// let raw = uart.into_raw().unwrap();
+    /// // access the registers of the Uart0 at raw.base_address()
+    /// let uart = Uart0::from_raw(raw);
+    /// uart.write_data(b"back again");
+    /// # }
+    /// ```
+    pub fn into_raw(mut self) -> Result<RawUart0, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::suspend_raw()?;
+        let (tx_pin, rx_pin) = interface::claimed_pins();
+        let raw = RawUart0 {
+            tx_pin,
+            rx_pin,
+            divisor: interface::divisor(),
+            clock_rate: self.clock_rate,
+            baud_rate: self.baud_rate,
+        };
+        // the peripheral is not shut down once this instance is dropped
+        self.initialized = false;
+        Ok(raw)
+    }
+
+    /// Take the Uart0 back from manual register control without initializing it again. The registers need to be left
+    /// the way the driver has configured them, e.g. the enabled directions. The baud rate divisor recorded by
+    /// [Uart0::into_raw] is restored if it has been changed.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart0::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(48_000_000, 115_200);
+    /// let raw = uart.into_raw().unwrap();
+    /// let uart = Uart0::from_raw(raw);
+    /// # }
+    /// ```
+    pub fn from_raw(raw: RawUart0) -> Self {
+        interface::resume_raw(raw.divisor);
+        let mut uart = Uart0::new();
+        uart.initialized = true;
+        uart.clock_rate = raw.clock_rate;
+        uart.baud_rate = raw.baud_rate;
+        uart
+    }
+
+    /// Shut down the Uart0 peripheral and release the GPIO pins it occupies. The interrupts are disabled first, so no
+    /// interrupt raised while tearing down the peripheral can report events afterwards. Events not yet processed are
+    /// discarded. This is also done when the Uart0 is dropped. The Uart0 need to be initialized again to be used
+    /// afterwards.
+    pub fn deinit(&mut self) {
+        if self.initialized {
+            teardown::tear_down(&mut Uart0Teardown);
+            self.initialized = false;
+        }
+    }
+
+    /// Handle the interrupts raised by the Uart0. This need to be called from the interrupt handler of the
+    /// Uart0 (PL011) interrupt, for example implemented with the
+    /// [``ruspiro-interrupt`` crate](https://crates.io/crates/ruspiro-interrupt). It does not require access to the
+    /// Uart0 instance. The pending interrupts are acknowledged and queued as [UartEvent]s to be processed with
+    /// [Uart0::events]. Console output written from within the handler is not waited for, only what the transmit FIFO
+    /// accepts right away is sent and the rest is counted in [Uart0::console_dropped_bytes]. This only covers the locks
+    /// of the Uart0 itself: a ``print!`` or ``println!`` from within an interrupt handler still deadlocks if the
+    /// interrupted code holds the lock of the global console, so interrupt handlers should use [Uart0::write_data]
+    /// instead.
+    /// # Example
+    /// ```ignore
+    /// # use ruspiro_uart::uart0::*;
+    /// #[IrqHandler(Pl011)]
+    /// fn uart0_handler() {
+    ///     Uart0::handle_interrupt();
+    /// }
+    /// ```
+    pub fn handle_interrupt() {
+        let _irq = interface::enter_irq();
+        interface::handle_interrupt(|event| {
+            // if the application does not process the events fast enough the new ones are dropped
+            let _ = EVENTS.push(event);
+        });
+    }
+
+    /// Get an iterator over the [UartEvent]s raised since the last call. Each event is only returned once.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// for event in uart.events() {
+    ///     match event {
+    ///         UartEvent::DataAvailable(_) => {
+    ///             let _ = uart.read_data();
+    ///         }
+    ///         UartEvent::Error(error) => println!("receive error {:?}", error),
+    ///         _ => (),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn events(&self) -> impl Iterator<Item = UartEvent> {
+        core::iter::from_fn(|| EVENTS.pop())
+    }
+
+    /// Register a hook that is called periodically while blocking in long running sends or receives, to feed a
+    /// hardware watchdog that would otherwise reset the system in the middle of a large transfer. There is only one
+    /// watchdog in the system, so the hook is shared by all Uarts. Passing ``None`` removes the hook.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn feed_watchdog() {}
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// uart.set_watchdog_kick(Some(feed_watchdog));
+    /// # }
+    /// ```
+    pub fn set_watchdog_kick(&mut self, kick: Option<fn()>) {
+        watchdog::set_kick(kick);
+    }
+
+    /// Register a hook that is called once for each send blocking for longer than ``threshold`` micro seconds, e.g. as
+    /// the terminal is gone, with the state of the transmitter. See the [slowsend] module for details. There is only
+    /// one hook shared by all Uarts. Passing ``None`` removes the hook.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::slowsend::SlowSend;
+    /// # fn stuck(_: SlowSend) {}
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// uart.set_slow_send_hook(100_000, Some(stuck));
+    /// # }
+    /// ```
+    pub fn set_slow_send_hook(&mut self, threshold: u64, hook: Option<fn(SlowSend)>) {
+        slowsend::set_hook(threshold, hook);
+    }
+
+    /// The duration of a single bit on the line in nano seconds. This is calculated from the baud rate divisor actually
+    /// configured in the peripheral, which might slightly differ from the requested baud rate.
+    pub fn bit_time_ns(&self) -> Result<u32, UartError> {
+        if self.initialized {
+            Ok(interface::bit_time_ns(self.clock_rate))
+        } else {
+            Err(UartError::NotInitialized)
+        }
+    }
+
+    /// The duration of a single character on the line in nano seconds, including the start, parity and stop bits.
+    pub fn char_time_ns(&self) -> Result<u32, UartError> {
+        self.bit_time_ns()
+            .map(|bit_time| bit_time * interface::frame_bits())
+    }
+
+    /// Wait for the time it takes to transfer ``chars`` characters on the line. Protocols like Modbus RTU or DMX use
+    /// such idle times to separate frames.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 19_200);
+    /// // Modbus RTU requires a silent interval of 3.5 characters between frames, so wait 4
+    /// uart.delay_chars(4).unwrap();
+    /// # }
+    /// ```
+    pub fn delay_chars(&self, chars: u32) -> Result<(), UartError> {
+        self.char_time_ns().map(|char_time| {
+            let duration = char_time as u64 * chars as u64;
+            // add a micro second to never wait shorter than required due to the truncation
+            timer::sleep(duration / 1000 + 1);
+        })
+    }
+
+    /// Start sending the data at the given time of the [clock](crate::clock) in micro seconds, e.g. in the time slot a node owns on a
+    /// TDMA style RS-485 bus. All data still pending is sent out first, then this waits until the timestamp is
+    /// reached. The start bit of the first byte follows within [Uart0::send_at_latency_ns], plus the latency of
+    /// interrupts occurring right at the timestamp. Fails with ``Err(UartError::SlotMissed)`` without sending
+    /// anything if the timestamp has already passed.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 500_000);
+    /// # let slot_start = 1_000_000;
+    /// let start = slot_start - uart.send_at_latency_ns().unwrap() as u64 / 1000;
+    /// uart.send_at(start, b"node 3 reporting").unwrap();
+    /// # }
+    /// ```
+    pub fn send_at(&self, timestamp: u64, data: &[u8]) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::flush();
+        crate::wait_until(timestamp)?;
+        interface::write_data(data);
+        Ok(())
+    }
+
+    /// Validate the link with a jumper between the TX and the RX pin. The data of the pattern is sent for the duration
+    /// in micro seconds and each byte received is verified against the byte sent. The report contains the bit error
+    /// rate and the highest throughput sustained. Anything else received meanwhile is discarded.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::soak::Pattern;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// let report = uart.soak_test(1_000_000, Pattern::Fixed(0x55)).unwrap();
+    /// assert!(report.bit_error_rate() < 1e-6);
+    /// # }
+    /// ```
+    pub fn soak_test(&self, duration: u64, pattern: Pattern) -> Result<SoakReport, UartError> {
+        let char_time = self.char_time_ns()?;
+        Ok(soak::run(
+            duration,
+            pattern,
+            char_time,
+            interface::write_data,
+            interface::read_byte_tagged,
+        ))
+    }
+
+    /// Route the receive interrupt of the Uart0 as FIQ to the given core for the lowest latency. Each byte received is
+    /// passed to the handler together with it's error state from [fiq::handle_fiq], or stored for [fiq::read] if no
+    /// handler is given. This replaces
+    /// any other FIQ source. Fails with ``Err(UartError::InvalidCore)`` if the core id is not in the range 0 to 3.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.route_rx_to_fiq(1, None).unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "fiq")]
+    pub fn route_rx_to_fiq(
+        &mut self,
+        core: usize,
+        handler: Option<fn(u8, RxFlag)>,
+    ) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        // the Raspberry Pi has 4 cores
+        if core > 3 {
+            return Err(UartError::InvalidCore);
+        }
+        fiq::route(Peripheral::Uart0, core, handler);
+        Ok(())
+    }
+
+    /// Stop routing the receive interrupt of the Uart0 as FIQ.
+    #[cfg(feature = "fiq")]
+    pub fn release_fiq(&mut self) {
+        fiq::release(Peripheral::Uart0);
+    }
+
+    /// The fixed latency in nano seconds between the timestamp passed to [Uart0::send_at] and the start bit of the
+    /// first byte on the line. The transmitter starts with the next tick of the baud rate generator, which takes up to
+    /// a bit time, and the system timer has a resolution of a micro second.
+    pub fn send_at_latency_ns(&self) -> Result<u32, UartError> {
+        self.bit_time_ns().map(|bit_time| bit_time + 1_000)
+    }
+
+    /// Enable or disable the FIFOs of the Uart0. Protocols that need to react on each single character, e.g. answer an
+    /// XOFF within one character time, disable them, so every received byte raises the receive interrupt right away
+    /// and data written is not queued behind a full transmit FIFO. The data still to be sent is sent out before the
+    /// mode is changed, data waiting in the receive FIFO is lost. Initializing the Uart0 enables the FIFOs again.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.set_fifo_mode(FifoMode::Disabled).unwrap();
+    /// # }
+    /// ```
+    pub fn set_fifo_mode(&mut self, mode: FifoMode) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::set_fifo_enabled(mode == FifoMode::Enabled)
+    }
+
+    /// Run the receive path without interrupts. Once attached, [Uart0::poll_service] need to be called periodically,
+    /// e.g. from a timer interrupt handler, at least every ``period`` micro seconds. Each call drains the receive FIFO
+    /// into a receive buffer and reports the data added as [UartEvent::DataAvailable]. All receive functions take
+    /// their data from this buffer while the service is attached. Fails with ``Err(UartError::PollPeriodTooLong)`` if
+    /// the 16 byte receive FIFO would overflow within the period at the baud rate in use.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.attach_poll_service(500).unwrap();
+    /// // from now on Uart0::poll_service() is called every 500us by a timer interrupt handler
+    /// # }
+    /// ```
+    pub fn attach_poll_service(&mut self, period: u64) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        if period > interface::max_poll_period(self.clock_rate) {
+            return Err(UartError::PollPeriodTooLong);
+        }
+        interface::set_poll_service(true);
+        Ok(())
+    }
+
+    /// Detach the poll service, so the receive functions read from the receive FIFO again. Data left in the receive
+    /// buffer is discarded.
+    pub fn detach_poll_service(&mut self) {
+        interface::set_poll_service(false);
+    }
+
+    /// Drain the receive FIFO into the receive buffer of the poll service. This need to be called periodically once the
+    /// service has been attached with [Uart0::attach_poll_service]. It does not require access to the Uart0 instance.
+    /// The received data is reported as [UartEvent] to be processed with [Uart0::events], data lost as the receive
+    /// buffer is full is reported as ``UartEvent::Error(RxError::Overrun)``. If the service is not attached this does
+    /// nothing.
+    /// # Example
+    /// ```ignore
+    /// # use ruspiro_uart::uart0::*;
+    /// #[IrqHandler(ArmTimer)]
+    /// fn timer_handler() {
+    ///     Uart0::poll_service();
+    /// }
+    /// ```
+    pub fn poll_service() {
+        interface::poll_service(|event| {
+            // if the application does not process the events fast enough the new ones are dropped
+            let _ = EVENTS.push(event);
+        });
+    }
+
+    /// Register a command with it's description shown by the built-in ``help`` command. Received lines starting with
+    /// the command name are dispatched to the ``handler`` by [Uart0::process_commands], passing the remaining
+    /// arguments of the line. Registering a command with the same name again replaces the previous one.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.register_command("led", "switch the LED on or off: led <on|off>", |args| {
+    ///     let _on = args == "on";
+    /// });
+    /// # }
+    /// ```
+    pub fn register_command(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        handler: CommandHandler,
+    ) {
+        self.commands.register(name, help, handler);
+    }
+
+    /// Set the handler called with the whole line for received lines that do not match any registered command. Without
+    /// a fallback handler an unknown command is reported back to the sender.
+    pub fn set_command_fallback(&mut self, fallback: Option<CommandHandler>) {
+        self.commands.set_fallback(fallback);
+    }
+
+    /// Process the received data without blocking and dispatch each complete line to the registered commands. This
+    /// need to be called regularly, e.g. from the main loop or once a [UartEvent::DataAvailable] has been raised.
+    /// Returns the number of commands dispatched.
+    pub fn process_commands(&mut self) -> usize {
+        if !self.initialized {
+            return 0;
+        }
+        let mut dispatched = 0;
+        while let Some((byte, _)) = interface::read_byte_tagged() {
+            if self.commands.feed(byte, interface::write_data) {
+                dispatched += 1;
+            }
+        }
+        dispatched
+    }
+
+    /// Stage the console output in a queue per core, so no core has to wait for another core sending a long log line.
+    /// The first core finding no other core sending the queued output sends it, all other cores continue right away.
+    /// The memory of the given storage is split evenly between the cores. Output that does not fit into the queue of
+    /// a core is dropped and counted in [Uart0::console_dropped_bytes]. Console output from an interrupt handler that
+    /// interrupted a console output on the same core might corrupt the queued output.
+    ///
+    /// The staging can be set up on a shared reference, as only the first call takes effect and later calls are
+    /// ignored. It only pays off when the cores write to a shared ``Uart0`` like the one of a ``OnceUart0``, because the
+    /// console singleton keeps the console locked while one core writes to it.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::buffer::BufferStorage;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.set_console_staging(BufferStorage::Alloc(4 * 1024));
+    /// # }
+    /// ```
+    pub fn set_console_staging(&self, storage: BufferStorage) {
+        let _ = self.staging.get_or_try_init(
+            |staging| {
+                *staging = Some(ConsoleStaging::new(storage.into_slice()));
+                Ok::<(), ()>(())
+            },
+            || Ok(()),
+            || (),
+        );
+    }
+
+    /// Send a status frame every ``period`` micro seconds on the Uart0, filled by the given function. The frames are sent
+    /// from [heartbeat::service], which need to be called regularly from a timer interrupt handler. This replaces a
+    /// heartbeat enabled on another Uart.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::heartbeat::HEARTBEAT_FRAME_SIZE;
+    /// fn status(frame: &mut heapless::Vec<u8, HEARTBEAT_FRAME_SIZE>) {
+    ///     let _ = frame.extend_from_slice(b"[alive]\r\n");
+    /// }
+    ///
+    /// # fn doc() {
+    /// # let mut uart = Uart0::new();
+    /// # let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.enable_heartbeat(5_000_000, status).unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "heartbeat")]
+    pub fn enable_heartbeat(&mut self, period: u64, f: HeartbeatFn) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        heartbeat::enable(Peripheral::Uart0, period, f);
+        Ok(())
+    }
+
+    /// Stop sending the status frames on the Uart0.
+    #[cfg(feature = "heartbeat")]
+    pub fn disable_heartbeat(&mut self) {
+        heartbeat::disable(Peripheral::Uart0);
+    }
+
+    /// Wait the given time in milli seconds after each carriage return of the console output, so slow devices like
+    /// thermal printers finish moving the carriage before the next character arrives. The delay starts once the
+    /// carriage return has left the transmitter, a delay of 0 disables it.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_console::ConsoleImpl;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 9_600);
+    /// uart.set_line_delay(50);
+    /// uart.set_char_delay(500);
+    /// uart.puts("receipt\r\n");
+    /// # }
+    /// ```
+    pub fn set_line_delay(&mut self, ms: u32) {
+        self.pacing.line_delay = ms as u64 * 1000;
+    }
+
+    /// Wait the given time in micro seconds after each character of the console output once it has left the
+    /// transmitter, a delay of 0 disables it.
+    pub fn set_char_delay(&mut self, us: u32) {
+        self.pacing.char_delay = us as u64;
+    }
+
+    /// Add a prefix to each line of the console output, so the output of several cores can be ordered and timed on
+    /// the host without changing every print call. A new line starts after each ``\n``. Only the output written
+    /// through the [ConsoleImpl] is prefixed.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_console::ConsoleImpl;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.set_log_prefix(Prefix::Both);
+    /// // sends "[    12.345678 core 0] booting\r\n"
+    /// uart.puts("booting\r\n");
+    /// # }
+    /// ```
+    pub fn set_log_prefix(&mut self, prefix: Prefix) {
+        self.prefix = prefix;
+    }
+
+    /// Replace the non-printable bytes of the console output, so binary data printed by accident does not corrupt the
+    /// terminal. Tabs, line endings and bytes above ``0x7F`` are always sent as they are. Use [Escape::None] to switch
+    /// back to the raw output, e.g. while a file transfer shares the console. Only the output written through the
+    /// [ConsoleImpl] is escaped.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_console::ConsoleImpl;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(48_000_000, 115_200);
+    /// uart.set_console_escape(Escape::Caret);
+    /// // sends "^[[2J\r\n"
+    /// uart.puts("\x1B[2J\r\n");
+    /// # }
+    /// ```
+    pub fn set_console_escape(&self, escape: Escape) {
+        self.escape.store(escape as u8, Ordering::Relaxed);
+    }
+
+    /// Append a checksum marker `` #XX`` to each line of the console output, so lines corrupted by flaky cabling can
+    /// be detected on the host. See [linecheck](crate::linecheck) for the format. Only the output written through the
+    /// [ConsoleImpl] is checked.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_console::ConsoleImpl;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 115_200);
+    /// uart.set_line_check(true);
+    /// // sends "booting #98\r\n"
+    /// uart.puts("booting\r\n");
+    /// # }
+    /// ```
+    pub fn set_line_check(&mut self, enabled: bool) {
+        self.line_check.set_enabled(enabled);
+    }
+
+    /// Check the configuration of the peripheral and it's pins for the common causes of garbled data, like a baud
+    /// rate divisor not matching the requested baud rate or a pin claimed by another function. Returns the problems
+    /// found, an empty list if everything looks fine.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 115_200);
+    /// for finding in uart.diagnose() {
+    ///     // report the finding through some other channel
+    /// }
+    /// # }
+    /// ```
+    pub fn diagnose(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        if self.initialized {
+            interface::diagnose(self.clock_rate, self.baud_rate, |finding| {
+                findings.push(finding)
+            });
+        } else {
+            findings.push(Finding::NotInitialized);
+        }
+        findings
+    }
+
+    /// Write a human readable dump of the active configuration to ``w``. The line settings are read back from the
+    /// peripheral registers, so the dump shows what the hardware actually uses rather than what has been requested.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use core::fmt::Write;
+    /// # fn doc() {
+    /// let mut uart = Uart0::new();
+    /// let _ = uart.initialize(3_000_000, 115_200);
+    /// let mut dump = String::new();
+    /// uart.describe(&mut dump).unwrap();
+    /// uart.write_data(dump.as_bytes());
+    /// # }
+    /// ```
+    pub fn describe<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "Uart0 (PL011)")?;
+        writeln!(w, "  initialized:      {}", self.initialized)?;
+        if self.initialized {
+            writeln!(w, "  clock rate:       {} Hz", self.clock_rate)?;
+            writeln!(w, "  requested baud:   {}", self.baud_rate)?;
+            interface::describe(w, self.clock_rate)?;
+        }
+        writeln!(w, "  encoding:         {:?}", self.encoding)?;
+        writeln!(w, "  console timeout:  {} us", self.console_timeout)?;
+        writeln!(w, "  event queue:      {} entries", EVENT_QUEUE_SIZE)?;
+        #[cfg(feature = "early_log")]
+        writeln!(
+            w,
+            "  early log:        {} bytes",
+            crate::early::EARLY_LOG_SIZE
+        )?;
+        crate::describe_features(w)
+    }
+}
+
+/// When the Uart0 is dropped it should release the GPIO pins that have been aquired.
+// the steps tearing down the Uart0
+struct Uart0Teardown;
+
+impl Teardown for Uart0Teardown {
+    fn disable_interrupts(&mut self) {
+        interface::disable_interrupts();
+    }
+
+    fn detach(&mut self) {
+        #[cfg(feature = "dma")]
+        dma::stop_frames();
+        #[cfg(feature = "fiq")]
+        fiq::release(Peripheral::Uart0);
+        #[cfg(feature = "heartbeat")]
+        heartbeat::disable(Peripheral::Uart0);
+    }
+
+    fn discard_events(&mut self) {
+        while EVENTS.pop().is_some() {}
+    }
+
+    fn shutdown(&mut self) {
+        interface::shutdown();
+    }
+
+    fn release_pins(&mut self) {
+        interface::release();
+    }
+
+    fn reset(&mut self) {
+        interface::set_poll_service(false);
+        interface::set_glitch_filter(false);
+    }
+}
+
+impl Drop for Uart0 {
+    fn drop(&mut self) {
+        // release the GPIO pin's occupied by the Uart0
+        self.deinit();
+    }
+}
+
+impl Uart0 {
+    // write data as console output respecting the configured console timeout
+    fn console_write(&self, data: &[u8]) {
+        let deadline = crate::console_deadline();
+        let escape = escape::load(&self.escape);
+        if escape == Escape::None {
+            self.console_prefixed(data, deadline);
+        } else {
+            escape::write_escaped(escape, data, |chunk| self.console_prefixed(chunk, deadline));
+        }
+    }
+
+    // add the prefix to each line of the console output if configured
+    fn console_prefixed(&self, data: &[u8], deadline: Option<u64>) {
+        if self.prefix == Prefix::None {
+            self.console_checked(data, deadline);
+        } else {
+            prefix::write_prefixed(self.prefix, &self.line_start, data, |chunk| {
+                self.console_checked(chunk, deadline)
+            });
+        }
+    }
+
+    // add the line check marker to the console output if enabled
+    fn console_checked(&self, data: &[u8], deadline: Option<u64>) {
+        if self.line_check.is_enabled() {
+            self.line_check
+                .write(data, |chunk| self.console_output(chunk, deadline));
+        } else {
+            self.console_output(data, deadline);
+        }
+    }
+
+    // pass the console output to the staging queues or send it right away
+    fn console_output(&self, data: &[u8], deadline: Option<u64>) {
+        if !self.initialized {
+            #[cfg(feature = "early_log")]
+            crate::early::EARLY_LOG.write(data);
+            return;
+        }
+        // take the Uart0 for the console, so the output does not reach the device of another lease holder
+        let start = clock::now();
+        let give_up = || {
+            interface::in_irq()
+                || (self.console_timeout != 0 && clock::elapsed_since(start) > self.console_timeout)
+                || matches!(deadline, Some(deadline) if clock::now() >= deadline)
+        };
+        let _lease = match lease::enter_console(self, give_up) {
+            Some(lease) => lease,
+            None => {
+                self.console_dropped
+                    .fetch_add(data.len(), Ordering::Relaxed);
+                return;
+            }
+        };
+        if interface::in_irq() {
+            // the interrupted code might be writing to the staging queue or waiting for the transmitter itself
+            let sent = interface::fill_fifo(data);
+            self.console_dropped
+                .fetch_add(data.len() - sent, Ordering::Relaxed);
+            return;
+        }
+        match self.staging.get().and_then(Option::as_ref) {
+            Some(staging) => {
+                if !staging.write(data, |chunk| self.console_write_direct(chunk, deadline)) {
+                    self.console_dropped
+                        .fetch_add(data.len(), Ordering::Relaxed);
+                }
+            }
+            None => self.console_write_direct(data, deadline),
+        }
+    }
+
+    // send the console output right away, paced if any delay is configured
+    fn console_write_direct(&self, data: &[u8], deadline: Option<u64>) {
+        if self.pacing.is_active() {
+            let passed = self.pacing.send(
+                data,
+                deadline,
+                self.console_timeout,
+                |byte| self.console_write_blocking(byte, deadline),
+                interface::flush_timeout,
+            );
+            self.console_dropped
+                .fetch_add(data.len() - passed, Ordering::Relaxed);
+        } else {
+            self.console_write_blocking(data, deadline);
+        }
+    }
+
+    // send the console output respecting the configured console timeout and the deadline of the console write
+    fn console_write_blocking(&self, data: &[u8], deadline: Option<u64>) {
+        let sent =
+            match (deadline, self.console_yield) {
+                (_, Some(yield_now)) => {
+                    let sent = crate::console_send_yielding(
+                        data,
+                        self.console_timeout,
+                        deadline,
+                        yield_now,
+                        |chunk| {
+                            if interface::tx_ready() {
+                                Some(interface::fill_fifo(chunk))
+                            } else {
+                                None
+                            }
+                        },
+                    );
+                    Some(sent).filter(|sent| *sent < data.len())
+                }
+                (None, None) if self.console_timeout == 0 => {
+                    self.write_data(data);
+                    return;
+                }
+                (None, None) => data
+                    .iter()
+                    .position(|byte| !interface::write_byte_timeout(*byte, self.console_timeout)),
+                (Some(deadline), None) => data.iter().position(|byte| {
+                    match crate::console_byte_timeout(self.console_timeout, deadline) {
+                        Some(timeout) => !interface::write_byte_timeout(*byte, timeout),
+                        None => true,
+                    }
+                }),
+            };
+        if let Some(sent) = sent {
+            self.console_dropped
+                .fetch_add(data.len() - sent, Ordering::Relaxed);
+        }
+    }
+}
+
+/// to use the Uart0 as a console to output strings implement the respective trait
+impl ConsoleImpl for Uart0 {
+    fn putc(&self, c: char) {
+        let data: [u8; 1] = if self.encoding == Encoding::Utf8 {
+            [c as u8]
+        } else {
+            [self.encoding.encode(c, self.fallback)]
+        };
+        self.console_write(&data);
+    }
+
+    fn puts(&self, s: &str) {
+        if self.encoding == Encoding::Utf8 {
+            self.console_write(s.as_bytes());
+        } else {
+            encoding::encode_str(s, self.encoding, self.fallback, |data| {
+                self.console_write(data)
+            });
+        }
     }
 }
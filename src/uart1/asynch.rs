@@ -0,0 +1,169 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Async Uart1
+//!
+//! The [Uart1Async] reads and writes through futures woken by the Aux interrupt, so a task waiting for data to arrive
+//! or for space in the transmit FIFO does not block the executor. The futures only rely on the ``core`` waker, so they
+//! run on the RusPiRo async executor as well as on any other ``no_std`` executor.
+//!
+
+#![forbid(unsafe_code)]
+
+use super::{interface, Uart1};
+use crate::asynch::poll_fn;
+use crate::sealed::WakerCell;
+use crate::{clock, InterruptType, UartError, UartEvent};
+use core::task::Poll;
+
+// the wakers of the tasks waiting for received data and for space in the transmit FIFO
+static RX_WAKER: WakerCell = WakerCell::new();
+static TX_WAKER: WakerCell = WakerCell::new();
+
+/// Uart1 (miniUART) reading and writing through futures woken by the Aux interrupt
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::Uart1Async;
+/// # async fn doc() {
+/// let mut uart = Uart1Async::new();
+/// let _ = uart.initialize(250_000_000, 115_200);
+/// let mut buffer = [0u8; 16];
+/// if let Ok(count) = uart.read(&mut buffer).await {
+///     let _ = uart.write(&buffer[..count]).await;
+/// }
+/// # }
+/// ```
+pub struct Uart1Async {
+    uart: Uart1,
+}
+
+impl Default for Uart1Async {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Uart1Async {
+    /// Get a new async Uart1 instance, that needs to be initialized before it can be used.
+    pub const fn new() -> Self {
+        Uart1Async { uart: Uart1::new() }
+    }
+
+    /// Initialize the Uart1 peripheral. The Aux interrupt need to call [Uart1Async::handle_interrupt] from now on.
+    pub fn initialize(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.uart.initialize(clock_rate, baud_rate)
+    }
+
+    /// The underlying Uart1, e.g. to configure it
+    pub fn uart(&self) -> &Uart1 {
+        &self.uart
+    }
+
+    /// Receive the data available into the buffer, waiting for at least one byte to arrive. Bytes received with a
+    /// wrong parity are skipped. Returns the number of bytes received.
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, UartError> {
+        if buffer.is_empty() {
+            return Err(UartError::EmptyBuffer);
+        }
+        interface::uart1_check_state()?;
+        let uart = &self.uart;
+        poll_fn(|cx| {
+            let count = receive(buffer);
+            if count != 0 {
+                return Poll::Ready(Ok(count));
+            }
+            RX_WAKER.register(cx.waker());
+            // the miniUART raises the interrupt right away if data has arrived in the meantime
+            uart.enable_interrupts(InterruptType::Receive);
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Receive the data available into the buffer like [Uart1Async::read], but give up if no byte arrived within the
+    /// timeout in micro seconds of the [clock](crate::clock). No timer wakes the waiting task, so while no data is
+    /// available the future asks the executor to poll it again.
+    pub async fn read_with_timeout(
+        &mut self,
+        buffer: &mut [u8],
+        timeout: u64,
+    ) -> Result<usize, UartError> {
+        if buffer.is_empty() {
+            return Err(UartError::EmptyBuffer);
+        }
+        interface::uart1_check_state()?;
+        let deadline = clock::now().saturating_add(timeout);
+        poll_fn(|cx| {
+            let count = receive(buffer);
+            if count != 0 {
+                Poll::Ready(Ok(count))
+            } else if clock::now() >= deadline {
+                Poll::Ready(Err(UartError::ReceiveDataTimeOut))
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Send the data, waiting for space in the transmit FIFO whenever it is full. Completes once all data has been
+    /// handed to the transmitter.
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), UartError> {
+        interface::uart1_check_state()?;
+        let uart = &self.uart;
+        let mut sent = 0;
+        poll_fn(|cx| {
+            let mut remaining = data[sent..].iter();
+            sent += interface::uart1_fill_fifo(|| remaining.next().copied());
+            if sent == data.len() {
+                return Poll::Ready(Ok(()));
+            }
+            TX_WAKER.register(cx.waker());
+            uart.enable_interrupts(InterruptType::Transmit);
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Wake the tasks waiting for received data or for space in the transmit FIFO. This need to be called from the
+    /// interrupt handler of the Aux interrupt. The interrupt type raised is disabled until a task waits for it again.
+    /// Returns ``false`` if the miniUART was not a source of the Aux interrupt.
+    /// # Example
+    /// ```ignore
+    /// # use ruspiro_uart::Uart1Async;
+    /// #[IrqHandler(Aux, Uart1)]
+    /// fn uart1_handler() {
+    ///     Uart1Async::handle_interrupt();
+    /// }
+    /// ```
+    pub fn handle_interrupt() -> bool {
+        let _irq = interface::uart1_enter_irq();
+        let pending = interface::uart1_irq_snapshot();
+        interface::uart1_handle_interrupt(pending, |event| match event {
+            UartEvent::DataAvailable(_) => RX_WAKER.wake(),
+            UartEvent::TxComplete => TX_WAKER.wake(),
+            _ => (),
+        })
+    }
+}
+
+// take the data available without waiting, skipping bytes received with a wrong parity
+fn receive(buffer: &mut [u8]) -> usize {
+    let mut count = 0;
+    while count < buffer.len() {
+        match interface::uart1_try_receive() {
+            Some(Ok(data)) => {
+                buffer[count] = data;
+                count += 1;
+            }
+            Some(Err(_)) => (),
+            None => break,
+        }
+    }
+    count
+}
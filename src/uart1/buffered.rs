@@ -0,0 +1,214 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Interrupt Driven Buffered Uart1
+//!
+//! The [Uart1Buffered] sends and receives through buffers filled and drained by the Aux interrupt, so neither sending
+//! nor receiving blocks while the transmitter is busy or no data has arrived yet. The buffer sizes and the receive
+//! timeout are given as const generic parameters, so the memory and latency trade-offs are fixed at compile time and
+//! visible in the type.
+//!
+//! Receiving reports with a [ReadOutcome] why it returned: the buffer is full, the peer stopped sending, or the
+//! receive timeout has been hit while data was still arriving.
+
+#![forbid(unsafe_code)]
+
+use super::{interface, Uart1};
+use crate::clock;
+use crate::ringbuffer::SpscRing;
+use crate::{InterruptType, ReadOutcome, Timeout, TimeoutWait, UartError};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The default time in micro seconds [Uart1Buffered::receive_data] waits for data to arrive
+pub const DEFAULT_RX_TIMEOUT: u64 = 1_000;
+
+// the number of character times without data after which the peer is considered to have stopped sending
+const IDLE_CHARS: u64 = 4;
+
+/// Uart1 (miniUART) sending and receiving through buffers of ``RX`` and ``TX`` bytes served by the Aux interrupt.
+/// Receiving waits at most ``RX_TIMEOUT`` micro seconds for data to arrive.
+/// # Example
+/// ```no_run
+/// # use ruspiro_uart::Uart1Buffered;
+/// # fn doc() {
+/// let mut uart: Uart1Buffered<256, 1024> = Uart1Buffered::new();
+/// let _ = uart.initialize(250_000_000, 115_200);
+/// uart.send_data(b"Hello buffered Uart\r\n");
+/// # }
+/// ```
+pub struct Uart1Buffered<
+    const RX: usize,
+    const TX: usize,
+    const RX_TIMEOUT: u64 = DEFAULT_RX_TIMEOUT,
+> {
+    uart: Uart1,
+    rx: SpscRing<u8, RX>,
+    tx: SpscRing<u8, TX>,
+    rx_dropped: AtomicUsize,
+}
+
+impl<const RX: usize, const TX: usize, const RX_TIMEOUT: u64> Default
+    for Uart1Buffered<RX, TX, RX_TIMEOUT>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const RX: usize, const TX: usize, const RX_TIMEOUT: u64> Uart1Buffered<RX, TX, RX_TIMEOUT> {
+    /// Get a new buffered Uart1 instance, that needs to be initialized before it can be used.
+    pub const fn new() -> Self {
+        Uart1Buffered {
+            uart: Uart1::new(),
+            rx: SpscRing::new(),
+            tx: SpscRing::new(),
+            rx_dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Initialize the Uart1 peripheral and enable the receive interrupt. The Aux interrupt need to call
+    /// [Uart1Buffered::handle_interrupt] from now on.
+    pub fn initialize(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.uart.initialize(clock_rate, baud_rate)?;
+        self.uart.enable_interrupts(InterruptType::Receive);
+        Ok(())
+    }
+
+    /// The underlying Uart1, e.g. to configure it. Sending or receiving through it bypasses the buffers.
+    pub fn uart(&self) -> &Uart1 {
+        &self.uart
+    }
+
+    /// Queue the data to be sent by the interrupt handler. Returns the number of bytes queued, which is less than the
+    /// size of the data if the transmit buffer is full.
+    pub fn send_data(&self, data: &[u8]) -> usize {
+        let queued = data.iter().take_while(|byte| self.tx.push(**byte)).count();
+        if queued != 0 {
+            self.uart.enable_interrupts(InterruptType::Transmit);
+        }
+        queued
+    }
+
+    /// Take the received data from the receive buffer. Waits at most ``RX_TIMEOUT`` micro seconds for the buffer to
+    /// be filled, see [Uart1Buffered::receive_data_timeout] for a timeout given per call. Returns
+    /// [ReadOutcome::Complete] once it is filled, [ReadOutcome::Partial] once no data has arrived for 4 character times
+    /// after the last byte and [ReadOutcome::TimedOut] if the timeout has been hit before, which counts 0 bytes if no
+    /// data has arrived at all. It does not return on the first byte received, nor does it fail with
+    /// ``Err(UartError::ReceiveDataTimeOut)`` if no data has arrived.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart: Uart1Buffered<256, 1024> = Uart1Buffered::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// let mut frame = [0u8; 16];
+    /// match uart.receive_data(&mut frame) {
+    ///     Ok(ReadOutcome::Complete(_)) => (), // process the frame
+    ///     Ok(ReadOutcome::Partial(count)) => (), // the peer sent a short frame of count bytes
+    ///     Ok(ReadOutcome::TimedOut(count)) => (), // the rest of the frame might still arrive
+    ///     Err(_) => (),
+    /// }
+    /// # }
+    /// ```
+    pub fn receive_data(&self, buffer: &mut [u8]) -> Result<ReadOutcome, UartError> {
+        self.receive_data_timeout(buffer, Timeout::Micros(RX_TIMEOUT))
+    }
+
+    /// Take the received data from the receive buffer like [Uart1Buffered::receive_data], but wait at most the given
+    /// timeout instead of ``RX_TIMEOUT`` for the buffer to be filled.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart: Uart1Buffered<256, 1024> = Uart1Buffered::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// let mut frame = [0u8; 16];
+    /// if let Ok(ReadOutcome::Complete(_)) = uart.receive_data_timeout(&mut frame, Timeout::Micros(50_000)) {
+    ///     // process the frame
+    /// }
+    /// # }
+    /// ```
+    pub fn receive_data_timeout(
+        &self,
+        buffer: &mut [u8],
+        timeout: Timeout,
+    ) -> Result<ReadOutcome, UartError> {
+        if buffer.is_empty() {
+            return Err(UartError::EmptyBuffer);
+        }
+        let idle_timeout = self.uart.char_time_ns()? as u64 * IDLE_CHARS / 1000;
+        let mut wait = TimeoutWait::start(timeout);
+        let mut last_data = clock::now();
+        let mut count = 0;
+        loop {
+            let received = count;
+            while count < buffer.len() {
+                match self.rx.pop() {
+                    Some(byte) => buffer[count] = byte,
+                    None => break,
+                }
+                count += 1;
+            }
+            if count == buffer.len() {
+                return Ok(ReadOutcome::Complete(count));
+            }
+            let now = clock::now();
+            if count != received {
+                last_data = now;
+            } else if count != 0 && now.saturating_sub(last_data) > idle_timeout {
+                return Ok(ReadOutcome::Partial(count));
+            }
+            if !wait.wait() {
+                return Ok(ReadOutcome::TimedOut(count));
+            }
+        }
+    }
+
+    /// The number of received bytes that have been dropped as the receive buffer was full
+    pub fn rx_dropped_bytes(&self) -> usize {
+        self.rx_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Serve the receive and transmit buffers. This need to be called from the interrupt handler of the Aux
+    /// interrupt. The transmit interrupt is disabled once all queued data has been handed to the peripheral. Returns
+    /// ``false`` if the miniUART was not a source of the Aux interrupt.
+    /// # Example
+    /// ```ignore
+    /// # use ruspiro_uart::Uart1Buffered;
+    /// static UART: Singleton<Uart1Buffered<256, 1024>> = Singleton::new(Uart1Buffered::new());
+    ///
+    /// #[IrqHandler(Aux, Uart1)]
+    /// fn uart1_handler() {
+    ///     UART.use_for(|uart| uart.handle_interrupt());
+    /// }
+    /// ```
+    pub fn handle_interrupt(&self) -> bool {
+        if !interface::uart1_pending_aux_irq() {
+            return false;
+        }
+        let _irq = interface::uart1_enter_irq();
+        while let Some(received) = interface::uart1_try_receive() {
+            // bytes with a wrong parity are dropped as well
+            let stored = match received {
+                Ok(byte) => self.rx.push(byte),
+                Err(_) => false,
+            };
+            if !stored {
+                self.rx_dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        interface::uart1_fill_fifo(|| self.tx.pop());
+        if self.tx.is_empty() {
+            self.uart.disable_interrupts(InterruptType::Transmit);
+            // data might have been queued right before the interrupt has been disabled
+            if !self.tx.is_empty() {
+                self.uart.enable_interrupts(InterruptType::Transmit);
+            }
+        }
+        true
+    }
+}
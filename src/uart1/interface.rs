@@ -12,59 +12,442 @@ use ruspiro_gpio::GPIO;
 use ruspiro_register::{define_mmio_register, RegisterFieldValue};
 use ruspiro_timer as timer;
 
-use crate::InterruptType;
+use crate::clock;
+use crate::diagnose::{self, Finding};
+use crate::irqstats::{IrqCounter, IrqStats};
+use crate::ringbuffer::SpscRing;
+use crate::sealed::TxHandover;
+use crate::slowsend::SendWatch;
+#[cfg(feature = "trace")]
+use crate::trace::{self, TracePeripheral, TraceSpan};
+use crate::Peripheral;
+use crate::{
+    wait_for_init, watchdog, Direction, InitReport, InitStage, InitStep, InterruptType, IrqFlag,
+    IrqGuard, Parity, RxError, RxFlag, SendGuard, StateCell, UartError, UartEvent, UartResult,
+    UartState,
+};
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 
 // Peripheral MMIO base address - depends on the right feature
 #[cfg(feature = "ruspiro_pi3")]
 const PERIPHERAL_BASE: u32 = 0x3F00_0000;
 
+// ARM local peripherals MMIO base address
+#[cfg(feature = "ruspiro_pi3")]
+const ARM_LOCAL_BASE: u32 = 0x4000_0000;
+
 // AUX MMIO base address
 const AUX_BASE: u32 = PERIPHERAL_BASE + 0x0021_5000;
 
+// the depth of the miniUART transmit and receive FIFO
+const AUX_MU_FIFO_SIZE: usize = 8;
+
+// the size of the default buffer taking the data of transfers that interrupted an ongoing transfer
+const TX_HANDOVER_SIZE: usize = 256;
+
+// flag whether a transfer is currently active
+static TX_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+// data handed over to the active transfer by transfers that have interrupted it
+static TX_HANDOVER: TxHandover<TX_HANDOVER_SIZE> = TxHandover::new();
+
+// the parity emulated in software using the most significant of the 8 data bits
+static PARITY: AtomicU8 = AtomicU8::new(Parity::None as u8);
+
+// the directions the UART1 has been initialized for. The GPIO pin of a direction not used is not reserved
+static TX_ENABLED: AtomicBool = AtomicBool::new(false);
+static RX_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// whether the CTS and RTS pins are reserved for the auto flow control
+static FLOW_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// the state of the UART1, data is only sent or received while it is active
+static STATE: StateCell = StateCell::new();
+
+// the cores executing the interrupt handler of the UART1
+static IN_IRQ: IrqFlag = IrqFlag::new();
+
+// the interrupts the handler has been entered for
+static IRQ_COUNTER: IrqCounter = IrqCounter::new(Peripheral::Uart1);
+
+// the number of bytes queued in the transmit FIFO at most. A single byte keeps the latency of data sent low
+static TX_FIFO_DEPTH: AtomicUsize = AtomicUsize::new(AUX_MU_FIFO_SIZE);
+
+// the receive FIFO level that need to be reached before received data is reported by the interrupt handler
+static RX_WATERMARK: AtomicUsize = AtomicUsize::new(1);
+
+// the time in micro seconds without further data being received after which the received data is reported even if the
+// watermark has not been reached
+static RX_IDLE_TIMEOUT: AtomicU32 = AtomicU32::new(0);
+
+// whether the receive interrupt is held back as the watermark has not been reached yet, the receive FIFO level seen
+// last and the time it has been seen
+static RX_WAIT_ARMED: AtomicBool = AtomicBool::new(false);
+static RX_WAIT_LEVEL: AtomicUsize = AtomicUsize::new(0);
+static RX_WAIT_SINCE: AtomicU64 = AtomicU64::new(0);
+
+// the size of the receive buffer the poll service drains the receive FIFO into
+const RX_POLL_BUFFER_SIZE: usize = 256;
+
+// the receive buffer filled by the poll service or the receive interrupt and which of them fills it. As the buffer
+// takes a single producer only one of them can be active at a time. While one is active all received data is taken
+// from this buffer instead of the receive FIFO
+static RX_POLLED: SpscRing<u8, RX_POLL_BUFFER_SIZE> = SpscRing::new();
+static RX_BUFFER_MODE: AtomicU8 = AtomicU8::new(RX_UNBUFFERED);
+
+// the producers of the receive buffer
+const RX_UNBUFFERED: u8 = 0;
+const RX_POLL_SERVICE: u8 = 1;
+const RX_IRQ_BUFFERED: u8 = 2;
+
+// use the given memory to hand over data between interrupting and interrupted transfers
+pub(crate) fn uart1_set_tx_handover_storage(storage: &'static mut [u8]) {
+    TX_HANDOVER.set_storage(storage);
+}
+
 // initialize the UART1 peripheral of the Raspberry Pi3. This will reserve 2 GPIO pins for UART1 usage.
-// Those pins actually are GPIO14 and 15.
-pub(crate) fn uart1_init(clock_rate: u32, baud_rate: u32) -> Result<(), &'static str> {
+// Those pins actually are GPIO14 and 15. If only one direction is used only the corresponding pin is reserved.
+// Each step waiting for the peripheral to respond is bound by a timeout as the peripheral might not respond at all if
+// the firmware has not enabled it.
+pub(crate) fn uart1_init(
+    clock_rate: u32,
+    baud_rate: u32,
+    direction: Direction,
+    data_bits: u32,
+    report: &mut InitReport,
+) -> UartResult<()> {
+    #[cfg(feature = "trace")]
+    let _span = trace::span(TracePeripheral::Uart1, TraceSpan::Initialize);
+    STATE.set(UartState::Configuring);
+    let pins = uart1_acquire_pins(direction);
+    let result = report.run(InitStep::Pins, pins).and_then(|_| {
+        let setup = uart1_setup(clock_rate, baud_rate, direction, data_bits, report);
+        if setup.is_err() {
+            // leave the miniUART disabled and free the pins claimed again
+            uart1_shutdown();
+            uart1_release();
+        }
+        setup
+    });
+    STATE.configured(result)
+}
+
+// enable the miniUART and set up it's registers for the line configuration with the FIFOs enabled and all interrupts
+// disabled
+fn uart1_setup(
+    clock_rate: u32,
+    baud_rate: u32,
+    direction: Direction,
+    data_bits: u32,
+    report: &mut InitReport,
+) -> UartResult<()> {
+    AUX_ENABLES::Register.write(AUX_ENABLES::MINIUART_ENABLE, 0x1); // enable mini UART
+    let ready = wait_for_init(InitStage::AuxEnable, || {
+        AUX_ENABLES::Register.read(AUX_ENABLES::MINIUART_ENABLE) == 1
+    })
+    .and_then(|_| {
+        AUX_MU_IER_REG::Register.set(0x0); // disable interrupts
+                                           // let an ongoing transfer finish before the transmitter get's disabled
+        wait_for_init(InitStage::TransmitterIdle, || {
+            AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::TRANSIDLE) == 1
+        })
+    });
+    report.run(InitStep::Peripheral, ready)?;
+    AUX_MU_CNTL_REG::Register.set(0x0); // disable transmitter and receiver (to set new baud rate)
+    uart1_set_data_bits(data_bits); // set the data transfer mode
+    report.complete(InitStep::LineConfig);
+    AUX_MU_MCR_REG::Register.set(0x0); // set UART_RTS line to high (ready to send)
+    AUX_MU_IER_REG::Register.set(0x0); // disable interrupts
+    report.complete(InitStep::Interrupts);
+    AUX_MU_IIR_REG::Register //.set(0xC6);
+        .write_value(
+            RegisterFieldValue::<u32>::new(AUX_MU_IIR_REG::IRQID_FIFOCLR, 0b11)
+                | RegisterFieldValue::<u32>::new(AUX_MU_IIR_REG::FIFO_ENABLES, 0b11),
+        ); // clear recieve/transmit FIFO, set FIFO as always enabled
+    let cleared = wait_for_init(InitStage::FifoClear, || {
+        AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::DATAREADY) == 0
+            && AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::TRANSEMPTY) == 1
+    });
+    report.run(InitStep::Fifo, cleared)?;
+    AUX_MU_BAUD_REG::Register.set(uart1_baud_divisor(clock_rate, baud_rate)); // set the baud rate based on the core clock rate
+    report.complete(InitStep::BaudRate);
+
+    AUX_MU_CNTL_REG::Register //.set(0x3);
+        .write_value(
+            RegisterFieldValue::<u32>::new(AUX_MU_CNTL_REG::RCV_ENABLE, direction.rx() as u32)
+                | RegisterFieldValue::<u32>::new(
+                    AUX_MU_CNTL_REG::TRANS_ENABLE,
+                    direction.tx() as u32,
+                ),
+        ); // enable receiver and transmitter
+    Ok(())
+}
+
+// take over the UART1 peripheral as it has been configured by the firmware or a boot loader. The baud rate divisor and
+// data size are kept, so the remote side does not see any garbage while the line is re-configured. Pending transmit
+// data is sent out and left over receive data is discarded. Returns the baud rate derived from the divisor in use
+pub(crate) fn uart1_takeover(clock_rate: u32) -> UartResult<u32> {
+    #[cfg(feature = "trace")]
+    let _span = trace::span(TracePeripheral::Uart1, TraceSpan::Initialize);
+    if AUX_ENABLES::Register.read(AUX_ENABLES::MINIUART_ENABLE) == 0
+        || AUX_MU_CNTL_REG::Register.read(AUX_MU_CNTL_REG::TRANS_ENABLE) == 0
+    {
+        return Err(UartError::NotConfigured);
+    }
+    STATE.set(UartState::Configuring);
+    let result = uart1_acquire_pins(Direction::Both).and_then(|_| {
+        let adopted = uart1_adopt(clock_rate);
+        if adopted.is_err() {
+            // the miniUART stays as the firmware has configured it, only the pins claimed are freed again
+            uart1_release();
+        }
+        adopted
+    });
+    STATE.configured(result)
+}
+
+// let the pending transmit data go out and discard the left over receive data of the miniUART configured by the
+// firmware, returns the baud rate derived from the divisor in use
+fn uart1_adopt(clock_rate: u32) -> UartResult<u32> {
+    AUX_MU_IER_REG::Register.set(0x0); // disable interrupts
+    wait_for_init(InitStage::TransmitterIdle, || {
+        AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::TRANSIDLE) == 1
+    })?;
+    AUX_MU_IIR_REG::Register.write_value(
+        RegisterFieldValue::<u32>::new(AUX_MU_IIR_REG::IRQID_FIFOCLR, 0b01)
+            | RegisterFieldValue::<u32>::new(AUX_MU_IIR_REG::FIFO_ENABLES, 0b11),
+    ); // clear the receive FIFO only
+    wait_for_init(InitStage::FifoClear, || {
+        AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::DATAREADY) == 0
+    })?;
+    AUX_MU_CNTL_REG::Register.write(AUX_MU_CNTL_REG::RCV_ENABLE, 0x1); // the firmware might only use the transmitter
+    let divisor = AUX_MU_BAUD_REG::Register.get() & 0xFFFF;
+    Ok(uart1_actual_baud(clock_rate, divisor))
+}
+
+// configure the GPIO pins 14 and 15 for the use with the UART1 peripheral, only the pin of the direction used is
+// configured
+fn uart1_acquire_pins(direction: Direction) -> UartResult<()> {
+    // both pins are checked and claimed within a single GPIO transaction, so no other core can switch or claim a pin
+    // in between
     GPIO.take_for(|gpio| {
-        let tx = gpio
-            .get_pin(14)
-            .map(|pin| pin.into_alt_f5().into_pud_disabled());
-        let ty = gpio
-            .get_pin(15)
-            .map(|pin| pin.into_alt_f5().into_pud_disabled());
-        // returns OK only if both pins could be setup correctly
-        //maybe_tx.and(maybe_ty)
-        Ok((tx, ty))
+        if direction.tx() {
+            diagnose::check_pin_free(14, 5)?;
+        }
+        if direction.rx() {
+            diagnose::check_pin_free(15, 5)?;
+        }
+        if direction.tx() {
+            gpio.get_pin(14)
+                .map(|pin| {
+                    pin.into_alt_f5().into_pud_disabled();
+                })
+                .map_err(UartError::GpioError)?;
+        }
+        if direction.rx() {
+            let rx = gpio.get_pin(15).map(|pin| {
+                pin.into_alt_f5().into_pud_disabled();
+            });
+            if rx.is_err() && direction.tx() {
+                // release the TX pin again, so the Uart1 is never left with only one of the pins configured
+                gpio.free_pin(14);
+            }
+            rx.map_err(UartError::GpioError)?;
+        }
+        Ok(())
     })
     .map(|_| {
-        AUX_ENABLES::Register.write(AUX_ENABLES::MINIUART_ENABLE, 0x1); // enable mini UART
-        AUX_MU_IER_REG::Register.set(0x0); // disable interrupts
-        AUX_MU_CNTL_REG::Register.set(0x0); // disable transmitter and receiver (to set new baud rate)
-        AUX_MU_LCR_REG::Register.write(AUX_MU_LCR_REG::DATASIZE, 0x3); // set 8bit data transfer mode
-        AUX_MU_MCR_REG::Register.set(0x0); // set UART_RTS line to high (ready to send)
-        AUX_MU_IER_REG::Register.set(0x0); // disable interrupts
-        AUX_MU_IIR_REG::Register //.set(0xC6);
-            .write_value(
-                RegisterFieldValue::<u32>::new(AUX_MU_IIR_REG::IRQID_FIFOCLR, 0b11)
-                    | RegisterFieldValue::<u32>::new(AUX_MU_IIR_REG::FIFO_ENABLES, 0b11),
-            ); // clear recieve/transmit FIFO, set FIFO as always enabled
-        AUX_MU_BAUD_REG::Register.set(clock_rate / (8 * baud_rate) - 1); // set the baud rate based on the core clock rate
-
-        AUX_MU_CNTL_REG::Register //.set(0x3);
-            .write_value(
-                RegisterFieldValue::<u32>::new(AUX_MU_CNTL_REG::RCV_ENABLE, 0x1)
-                    | RegisterFieldValue::<u32>::new(AUX_MU_CNTL_REG::TRANS_ENABLE, 0x1),
-            ); // enable receiver and transmitter
+        TX_ENABLED.store(direction.tx(), Ordering::Relaxed);
+        RX_ENABLED.store(direction.rx(), Ordering::Relaxed);
     })
 }
 
+// the baud rate divisor producing the baud rate closest to the requested one at the given clock rate
+pub(crate) fn uart1_baud_divisor(clock_rate: u32, baud_rate: u32) -> u32 {
+    let divisor = (clock_rate as u64 + 4 * baud_rate as u64) / (8 * baud_rate as u64);
+    divisor.max(1) as u32 - 1
+}
+
+// the baud rate the given divisor produces at the given clock rate
+pub(crate) fn uart1_actual_baud(clock_rate: u32, divisor: u32) -> u32 {
+    clock_rate / (8 * (divisor + 1))
+}
+
+// change the baud rate once the senders currently writing to the transmit FIFO are done and the transmitter has sent
+// out all pending data. No further data is accepted for sending while waiting for this. Must not be called from the
+// interrupt handler of the UART1, as it might have interrupted a sender
+pub(crate) fn uart1_set_baud_rate(clock_rate: u32, baud_rate: u32) -> UartResult<()> {
+    let previous = STATE.quiesce(UartState::Configuring)?;
+    let idle = wait_for_init(InitStage::TransmitterIdle, || {
+        AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::TRANSIDLE) == 1
+    });
+    if idle.is_ok() {
+        // stop the receiver and transmitter so no character is sampled with half of the new divisor
+        let cntl = AUX_MU_CNTL_REG::Register.get();
+        AUX_MU_CNTL_REG::Register.set(0x0);
+        AUX_MU_BAUD_REG::Register.set(uart1_baud_divisor(clock_rate, baud_rate));
+        AUX_MU_CNTL_REG::Register.set(cntl);
+    }
+    // the UART1 keeps running with the previous baud rate if it did not get idle
+    STATE.set(previous);
+    idle
+}
+
+// disable the receiver and transmitter of the UART1 peripheral and the miniUART within the AUX block
+pub(crate) fn uart1_shutdown() {
+    STATE.set(UartState::Idle);
+    AUX_MU_CNTL_REG::Register.set(0x0);
+    AUX_ENABLES::Register.write(AUX_ENABLES::MINIUART_ENABLE, 0x0);
+}
+
 // release the UART1 peripheral, this will also free the pins reserved for UART1 till now
 pub(crate) fn uart1_release() {
     GPIO.take_for(|gpio| {
-        gpio.free_pin(14);
-        gpio.free_pin(15);
+        if TX_ENABLED.swap(false, Ordering::Relaxed) {
+            gpio.free_pin(14);
+        }
+        if RX_ENABLED.swap(false, Ordering::Relaxed) {
+            gpio.free_pin(15);
+        }
+        if FLOW_ENABLED.swap(false, Ordering::Relaxed) {
+            gpio.free_pin(16);
+            gpio.free_pin(17);
+        }
     });
 }
 
+// the baud rate divisor of the UART1
+pub(crate) fn uart1_divisor() -> u32 {
+    AUX_MU_BAUD_REG::Register.get() & 0xFFFF
+}
+
+// the address of the AUX block the UART1 belongs to
+pub(crate) fn uart1_base_address() -> usize {
+    AUX_BASE as usize
+}
+
+// the GPIO pins claimed for the transmit and the receive line, and whether the flow control pins are claimed
+pub(crate) fn uart1_claimed_pins() -> (Option<u32>, Option<u32>, bool) {
+    let tx = if TX_ENABLED.load(Ordering::Relaxed) {
+        Some(14)
+    } else {
+        None
+    };
+    let rx = if RX_ENABLED.load(Ordering::Relaxed) {
+        Some(15)
+    } else {
+        None
+    };
+    (tx, rx, FLOW_ENABLED.load(Ordering::Relaxed))
+}
+
+// configure the GPIO pins 16 (CTS) and 17 (RTS) and let the UART1 handle them on it's own. The RTS line is de-asserted
+// at the given level of the receive FIFO. Both lines are active low
+pub(crate) fn uart1_enable_auto_flow_control(level: u32) -> UartResult<()> {
+    if !FLOW_ENABLED.load(Ordering::Relaxed) {
+        GPIO.take_for(|gpio| {
+            diagnose::check_pin_free(16, 5)?;
+            diagnose::check_pin_free(17, 5)?;
+            gpio.get_pin(16)
+                .map(|pin| {
+                    pin.into_alt_f5().into_pud_disabled();
+                })
+                .map_err(UartError::GpioError)?;
+            let rts = gpio.get_pin(17).map(|pin| {
+                pin.into_alt_f5().into_pud_disabled();
+            });
+            if rts.is_err() {
+                gpio.free_pin(16);
+            }
+            rts.map(|_| ()).map_err(UartError::GpioError)
+        })?;
+        FLOW_ENABLED.store(true, Ordering::Relaxed);
+    }
+    AUX_MU_CNTL_REG::Register.write_value(
+        RegisterFieldValue::<u32>::new(
+            AUX_MU_CNTL_REG::RCV_ENABLE,
+            RX_ENABLED.load(Ordering::Relaxed) as u32,
+        ) | RegisterFieldValue::<u32>::new(
+            AUX_MU_CNTL_REG::TRANS_ENABLE,
+            TX_ENABLED.load(Ordering::Relaxed) as u32,
+        ) | RegisterFieldValue::<u32>::new(AUX_MU_CNTL_REG::AUTO_RTS_LEVEL, level)
+            | RegisterFieldValue::<u32>::new(AUX_MU_CNTL_REG::RTS_ASSERT, 0x1)
+            | RegisterFieldValue::<u32>::new(AUX_MU_CNTL_REG::CTS_ASSERT, 0x1)
+            | RegisterFieldValue::<u32>::new(AUX_MU_CNTL_REG::AUTO_FLOW_RTS, 0x1)
+            | RegisterFieldValue::<u32>::new(AUX_MU_CNTL_REG::AUTO_FLOW_CTS, 0x1),
+    );
+    Ok(())
+}
+
+// the interrupt statistics of the UART1
+pub(crate) fn uart1_irq_stats() -> IrqStats {
+    IRQ_COUNTER.stats()
+}
+
+// set the number of spurious interrupts per second tolerated before the interrupt line is disabled
+pub(crate) fn uart1_set_spurious_irq_limit(limit: u32, hook: Option<fn(Peripheral)>) {
+    IRQ_COUNTER.set_limit(limit, hook);
+}
+
+// enable the interrupt line disabled due to spurious interrupts
+pub(crate) fn uart1_resume_interrupts() {
+    IRQ_COUNTER.resume();
+}
+
+// hand the UART1 over to manual register control. It stays suspended for the driver until it is taken back with
+// ``uart1_resume_raw``
+pub(crate) fn uart1_suspend_raw() -> UartResult<()> {
+    STATE.quiesce(UartState::Suspended).map(|_| ())
+}
+
+// take the UART1 back from manual register control, restoring the divisor recorded when it has been handed over if it
+// has been changed in the meantime
+pub(crate) fn uart1_resume_raw(recorded: u32) {
+    if uart1_divisor() != recorded {
+        let cntl = AUX_MU_CNTL_REG::Register.get();
+        AUX_MU_CNTL_REG::Register.set(0x0);
+        AUX_MU_BAUD_REG::Register.set(recorded);
+        AUX_MU_CNTL_REG::Register.set(cntl);
+    }
+    STATE.set(UartState::Active);
+}
+
+// the current state of the UART1
+pub(crate) fn uart1_state() -> UartState {
+    STATE.get()
+}
+
+// fail with the error matching the current state if the UART1 can not be used right now
+pub(crate) fn uart1_check_state() -> UartResult<()> {
+    STATE.check()
+}
+
+// mark the current core as executing the interrupt handler of the UART1 until the guard is dropped
+pub(crate) fn uart1_enter_irq() -> IrqGuard<'static> {
+    IN_IRQ.enter()
+}
+
+// whether the current core executes the interrupt handler of the UART1
+pub(crate) fn uart1_in_irq() -> bool {
+    IN_IRQ.is_set()
+}
+
+// whether data can be written to the transmit FIFO, as the transmitter is used and not re-configured
+pub(crate) fn uart1_tx_ready() -> bool {
+    TX_ENABLED.load(Ordering::Relaxed) && STATE.is_active()
+}
+
+// register a sender writing to the transmit FIFO until the guard is dropped, waiting for a re-configuration to finish
+// if ``wait`` is set and the caller is not the interrupt handler. Returns ``None`` if nothing can be sent right now
+fn uart1_enter_send(wait: bool) -> Option<SendGuard<'static>> {
+    if TX_ENABLED.load(Ordering::Relaxed) {
+        STATE.enter_send(wait && !uart1_in_irq())
+    } else {
+        None
+    }
+}
+
 // send a character string to the UART1 peripheral
 pub(crate) fn uart1_send_char(c: char) {
     let data: [u8; 1] = [c as u8];
@@ -77,31 +460,375 @@ pub(crate) fn uart1_send_string(s: &str) {
 }
 
 // send byte data to the UART1 peripheral
+// If this interrupts an ongoing transfer (e.g. when called from an interrupt handler) the data is handed over to the
+// interrupted transfer, which sends it once it's own data has been sent completely. This ensures that the data of
+// each call is sent without being interleaved with data of other calls. Only if the data does not fit into the
+// handover buffer it is sent immediately.
 pub(crate) fn uart1_send_data(data: &[u8]) {
+    #[cfg(feature = "trace")]
+    let _span = trace::span(TracePeripheral::Uart1, TraceSpan::Send);
+    // wait for a re-configuration to finish instead of dropping the data
+    let _sender = match uart1_enter_send(true) {
+        Some(sender) => sender,
+        None => return,
+    };
+    if TX_ACTIVE.swap(true, Ordering::Acquire) {
+        if !TX_HANDOVER.push(data) {
+            uart1_write_fifo(data);
+        }
+        return;
+    }
+
+    uart1_write_fifo(data);
+    uart1_end_transfer();
+}
+
+// write as many bytes of the data as the transmit FIFO accepts without waiting, as a transfer of it's own. Returns
+// ``None`` without writing anything if another transfer is active, e.g. the one interrupted by this call. Data handed
+// over by a transfer interrupting this one is sent before this returns
+pub(crate) fn uart1_try_send(data: &[u8]) -> Option<usize> {
+    let _sender = match uart1_enter_send(false) {
+        Some(sender) => sender,
+        None => return Some(0),
+    };
+    if TX_ACTIVE.swap(true, Ordering::Acquire) {
+        return None;
+    }
+    let mut bytes = data.iter().copied();
+    let count = uart1_fill_fifo(|| bytes.next());
+    uart1_end_transfer();
+    Some(count)
+}
+
+// mark the active transfer as finished once everything that has been handed over while it was active has been sent
+fn uart1_end_transfer() {
+    loop {
+        // send everything that has been handed over while the transfer was active
+        let mut chunk = [0u8; AUX_MU_FIFO_SIZE];
+        loop {
+            let count = TX_HANDOVER.pop(&mut chunk);
+            if count == 0 {
+                break;
+            }
+            uart1_write_fifo(&chunk[..count]);
+        }
+        TX_ACTIVE.store(false, Ordering::Release);
+        // data might have been handed over right before the transfer has been marked as inactive. If no one else has
+        // taken over the transfer in the meantime it's up to us to send this data
+        if TX_HANDOVER.is_empty() || TX_ACTIVE.swap(true, Ordering::Acquire) {
+            break;
+        }
+    }
+}
+
+// write the data to the transmit FIFO
+// The IO register only takes 8 bits per access, so there is no way to push more than one byte with a single write.
+// Instead the transmit FIFO fill level is read once and as many bytes are written as there is space available in
+// the FIFO, instead of polling the line status before each and every byte.
+fn uart1_write_fifo(data: &[u8]) {
+    let mut remaining = data;
+    let mut watch = SendWatch::start();
+    while !remaining.is_empty() {
+        let free = TX_FIFO_DEPTH.load(Ordering::Relaxed).saturating_sub(
+            AUX_MU_STAT_REG::Register.read(AUX_MU_STAT_REG::TX_FIFO_LEVEL) as usize,
+        );
+        if free == 0 {
+            // wait for the transmitter to make room in the FIFO
+            watch.check(Peripheral::Uart1, remaining.len(), || {
+                AUX_MU_LSR_REG::Register.get()
+            });
+            watchdog::kick();
+            timer::sleepcycles(10);
+            continue;
+        }
+        let (chunk, rest) = remaining.split_at(free.min(remaining.len()));
+        for byte in chunk {
+            #[cfg(feature = "tx_history")]
+            crate::history::record(*byte);
+            AUX_MU_IO_REG::Register.set(uart1_add_parity(*byte) as u32);
+        }
+        remaining = rest;
+    }
+}
+
+// write the data provided by ``next`` to the transmit FIFO until the FIFO is full or there is no more data, without
+// waiting for the transmitter. Returns the number of bytes written
+pub(crate) fn uart1_fill_fifo(mut next: impl FnMut() -> Option<u8>) -> usize {
+    let _sender = match uart1_enter_send(false) {
+        Some(sender) => sender,
+        None => return 0,
+    };
+    let free = TX_FIFO_DEPTH
+        .load(Ordering::Relaxed)
+        .saturating_sub(AUX_MU_STAT_REG::Register.read(AUX_MU_STAT_REG::TX_FIFO_LEVEL) as usize);
+    let mut count = 0;
+    while count < free {
+        match next() {
+            Some(byte) => {
+                #[cfg(feature = "tx_history")]
+                crate::history::record(byte);
+                AUX_MU_IO_REG::Register.set(uart1_add_parity(byte) as u32);
+            }
+            None => break,
+        }
+        count += 1;
+    }
+    count
+}
+
+// send byte data to the UART1 peripheral polling the line status before each byte. This is the way the data was sent
+// before the FIFO level has been taken into account and is kept to be able to benchmark both variants
+#[cfg(feature = "bench")]
+pub(crate) fn uart1_send_data_bytewise(data: &[u8]) {
     for byte in data {
         // wait for the transmitter to be empty
         while AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::TRANSEMPTY) == 0 {
+            watchdog::kick();
             timer::sleepcycles(10);
         }
-        AUX_MU_IO_REG::Register.set(*byte as u32);
+        #[cfg(feature = "tx_history")]
+        crate::history::record(*byte);
+        AUX_MU_IO_REG::Register.set(uart1_add_parity(*byte) as u32);
     }
 }
 
-// wait to receive 1 byte from uart and return it
-// if timeout is > 0 return timeout error if nothing was available for this many time
-// timeout is given in multiples of 1000 CPU cycles
-pub(crate) fn uart1_receive_data(timeout: u32) -> Result<u8, &'static str> {
-    let mut count = 0;
-    while AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::DATAREADY) == 0
-        && (timeout == 0 || count < timeout)
-    {
-        timer::sleepcycles(1000);
-        count += 1;
+// wait until the transmitter has sent out all data and is idle
+pub(crate) fn uart1_flush() {
+    while AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::TRANSIDLE) == 0 {
+        watchdog::kick();
+        timer::sleepcycles(10);
+    }
+}
+
+// wait until the Uart1 has sent out all data, but give up once the timeout in micro seconds has passed. A timeout of
+// 0 does not limit the time. Returns ``false`` if the transmitter did not get idle in time
+pub(crate) fn uart1_flush_timeout(timeout: u64) -> bool {
+    let start = clock::now();
+    while AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::TRANSIDLE) == 0 {
+        if timeout != 0 && clock::elapsed_since(start) > timeout {
+            return false;
+        }
+        watchdog::kick();
+        timer::sleepcycles(10);
     }
-    if timeout != 0 && count >= timeout {
-        Err("Timeout")
+    true
+}
+
+// hold the TX line low for the given time in micro seconds to signal a break. The transmitter need to be idle, otherwise
+// the character currently sent would be corrupted, so all pending data is sent out first
+pub(crate) fn uart1_send_break(duration: u64) {
+    if !uart1_tx_ready() {
+        return;
+    }
+    let previous = match STATE.quiesce(UartState::Suspended) {
+        Ok(previous) => previous,
+        Err(_) => return,
+    };
+    while AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::TRANSIDLE) == 0 {
+        watchdog::kick();
+        timer::sleepcycles(10);
+    }
+    AUX_MU_LCR_REG::Register.write(AUX_MU_LCR_REG::BREAK, 0x1);
+    timer::sleep(duration);
+    AUX_MU_LCR_REG::Register.write(AUX_MU_LCR_REG::BREAK, 0x0);
+    STATE.set(previous);
+}
+
+// send byte data to the UART1 peripheral, but give up as soon as the transmitter has not been ready to accept the
+// next byte for more than the given timeout in micro seconds. Returns the number of bytes actually sent
+pub(crate) fn uart1_send_data_timeout(data: &[u8], timeout: u64) -> usize {
+    #[cfg(feature = "trace")]
+    let _span = trace::span(TracePeripheral::Uart1, TraceSpan::Send);
+    let _sender = match uart1_enter_send(false) {
+        Some(sender) => sender,
+        None => return 0,
+    };
+    for (sent, byte) in data.iter().enumerate() {
+        let start = clock::now();
+        // wait for the transmitter to be empty, but not forever
+        while AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::TRANSEMPTY) == 0 {
+            if clock::elapsed_since(start) > timeout {
+                return sent;
+            }
+            watchdog::kick();
+            timer::sleepcycles(10);
+        }
+        #[cfg(feature = "tx_history")]
+        crate::history::record(*byte);
+        AUX_MU_IO_REG::Register.set(uart1_add_parity(*byte) as u32);
+    }
+    data.len()
+}
+
+// receive 1 byte from uart if there is one available, without waiting for data to arrive
+pub(crate) fn uart1_try_receive() -> Option<UartResult<u8>> {
+    uart1_rx_pop().map(uart1_check_parity)
+}
+
+// receive 1 byte like ``uart1_try_receive``, but tag it with the result of the parity check instead of failing
+pub(crate) fn uart1_try_receive_tagged() -> Option<(u8, RxFlag)> {
+    uart1_rx_pop().map(uart1_tag_parity)
+}
+
+// check the parity of the byte received. A byte with a wrong parity is passed without the parity bit like a correct one
+fn uart1_tag_parity(data: u8) -> (u8, RxFlag) {
+    match uart1_check_parity(data) {
+        Ok(data) => (data, RxFlag::Ok),
+        Err(_) => (data & 0x7F, RxFlag::Parity),
+    }
+}
+
+// take the next received byte from the receive FIFO, or from the receive buffer if the poll service or the interrupt
+// fill it
+fn uart1_rx_pop() -> Option<u8> {
+    if uart1_rx_buffered() {
+        RX_POLLED.pop()
+    } else {
+        uart1_read_fifo()
+    }
+}
+
+// whether the received data is taken from the receive buffer
+pub(crate) fn uart1_rx_buffered() -> bool {
+    RX_BUFFER_MODE.load(Ordering::Acquire) != RX_UNBUFFERED
+}
+
+// let the receive interrupt fill the receive buffer or not. Fails with ``Err(UartError::RxBufferInUse)`` if the poll
+// service is attached
+pub(crate) fn uart1_set_rx_irq_buffered(enabled: bool) -> UartResult<()> {
+    uart1_set_rx_buffer_mode(RX_IRQ_BUFFERED, enabled)
+}
+
+// make the given producer fill the receive buffer or stop it from doing so. Enabling fails if the other producer is
+// active, disabling a producer that is not active does nothing. Data left in the receive buffer when disabling is
+// discarded
+fn uart1_set_rx_buffer_mode(mode: u8, enabled: bool) -> UartResult<()> {
+    let (current, new) = if enabled {
+        (RX_UNBUFFERED, mode)
+    } else {
+        (mode, RX_UNBUFFERED)
+    };
+    match RX_BUFFER_MODE.compare_exchange(current, new, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) if !enabled => {
+            while RX_POLLED.pop().is_some() {}
+            Ok(())
+        }
+        Err(active) if enabled && active != mode => Err(UartError::RxBufferInUse),
+        _ => Ok(()),
+    }
+}
+
+// the number of bytes in the receive buffer
+pub(crate) fn uart1_rx_available() -> usize {
+    if uart1_rx_buffered() {
+        RX_POLLED.len()
+    } else {
+        0
+    }
+}
+
+// take the next received byte from the receive FIFO
+pub(crate) fn uart1_read_fifo() -> Option<u8> {
+    if AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::DATAREADY) == 0 {
+        None
     } else {
-        Ok((AUX_MU_IO_REG::Register.get() & 0xFF) as u8)
+        Some((AUX_MU_IO_REG::Register.get() & 0xFF) as u8)
+    }
+}
+
+// read one byte from the receive FIFO and tag it with the result of the parity check
+#[cfg(feature = "fiq")]
+pub(crate) fn uart1_read_fifo_tagged() -> Option<(u8, RxFlag)> {
+    uart1_read_fifo().map(uart1_tag_parity)
+}
+
+// attach or detach the poll service. Fails with ``Err(UartError::RxBufferInUse)`` if the receive interrupt fills the
+// receive buffer
+pub(crate) fn uart1_set_poll_service(attached: bool) -> UartResult<()> {
+    uart1_set_rx_buffer_mode(RX_POLL_SERVICE, attached)
+}
+
+// drain the receive FIFO into the receive buffer if the poll service is attached. Reports the data lost as the FIFO
+// overflowed
+pub(crate) fn uart1_poll_service(report: impl Fn(UartEvent)) {
+    if RX_BUFFER_MODE.load(Ordering::Acquire) != RX_POLL_SERVICE {
+        return;
+    }
+    if AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::RCVOVERRUN) == 1 {
+        report(UartEvent::Error(RxError::Overrun));
+    }
+    uart1_drain_rx(report);
+}
+
+// drain the receive FIFO into the receive buffer. Reports the data added to the buffer and the data lost as the buffer
+// overflowed
+fn uart1_drain_rx(report: impl Fn(UartEvent)) {
+    let mut count = 0;
+    let mut overflow = false;
+    while AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::DATAREADY) == 1 {
+        if RX_POLLED.push((AUX_MU_IO_REG::Register.get() & 0xFF) as u8) {
+            count += 1;
+        } else {
+            overflow = true;
+        }
+    }
+    if overflow {
+        report(UartEvent::Error(RxError::Overrun));
+    }
+    if count != 0 {
+        report(UartEvent::DataAvailable(count));
+    }
+}
+
+// the longest period of the poll service in micro seconds that does not let the receive FIFO overflow
+pub(crate) fn uart1_max_poll_period(clock_rate: u32) -> u64 {
+    uart1_bit_time_ns(clock_rate) as u64 * uart1_frame_bits() as u64 * AUX_MU_FIFO_SIZE as u64
+        / 1000
+}
+
+// set the parity emulated in software. The miniUART does not support a parity bit, so a 7 bit character with parity
+// is sent and received as 8 bit character with the parity in the most significant bit
+pub(crate) fn uart1_set_parity(parity: Parity) {
+    PARITY.store(parity as u8, Ordering::Relaxed);
+}
+
+// switch the miniUART to 7 or 8 data bits
+pub(crate) fn uart1_set_data_bits(data_bits: u32) {
+    let size = if data_bits == 7 { 0x0 } else { 0x3 };
+    AUX_MU_LCR_REG::Register.write(AUX_MU_LCR_REG::DATASIZE, size);
+}
+
+// the parity currently emulated
+#[cfg(feature = "hw-test")]
+pub(crate) fn uart1_parity() -> Parity {
+    match PARITY.load(Ordering::Relaxed) {
+        p if p == Parity::Even as u8 => Parity::Even,
+        p if p == Parity::Odd as u8 => Parity::Odd,
+        _ => Parity::None,
+    }
+}
+
+// calculate the parity bit of the lower 7 bits of the data
+fn uart1_parity_bit(data: u8) -> Option<u8> {
+    let odd_bits = ((data & 0x7F).count_ones() & 1) as u8;
+    match PARITY.load(Ordering::Relaxed) {
+        p if p == Parity::Even as u8 => Some(odd_bits),
+        p if p == Parity::Odd as u8 => Some(odd_bits ^ 1),
+        _ => None,
+    }
+}
+
+// replace the most significant bit of the data with the parity bit if the parity emulation is active
+fn uart1_add_parity(data: u8) -> u8 {
+    uart1_parity_bit(data).map_or(data, |parity| (data & 0x7F) | (parity << 7))
+}
+
+// verify and strip the parity bit of the received data if the parity emulation is active
+fn uart1_check_parity(data: u8) -> UartResult<u8> {
+    match uart1_parity_bit(data) {
+        Some(parity) if parity != data >> 7 => Err(UartError::ParityError),
+        Some(_) => Ok(data & 0x7F),
+        None => Ok(data),
     }
 }
 
@@ -143,14 +870,279 @@ pub(crate) fn uart1_disable_interrupts(i_type: InterruptType) {
     }
 }
 
-pub(crate) fn uart1_get_interrupt_status() -> u32 {
-    AUX_MU_IIR_REG::Register.read(AUX_MU_IIR_REG::IRQPENDING)
-        | (AUX_MU_IIR_REG::Register.read(AUX_MU_IIR_REG::IRQID_FIFOCLR) << 1)
+// the duration of a single bit on the line in nano seconds as configured with the baud rate divisor
+pub(crate) fn uart1_bit_time_ns(clock_rate: u32) -> u32 {
+    let divisor = AUX_MU_BAUD_REG::Register.get() & 0xFFFF;
+    (8 * (divisor as u64 + 1) * 1_000_000_000 / clock_rate as u64) as u32
+}
+
+// the number of bits on the line for a single character: start bit, data bits and the stop bit. The miniUART does
+// not support parity or a second stop bit
+pub(crate) fn uart1_frame_bits() -> u32 {
+    if AUX_MU_LCR_REG::Register.read(AUX_MU_LCR_REG::DATASIZE) == 0b11 {
+        10
+    } else {
+        9
+    }
+}
+
+// check the peripheral and pin configuration for the usual causes of garbled data and report each problem found
+pub(crate) fn uart1_diagnose(clock_rate: u32, baud_rate: u32, mut report: impl FnMut(Finding)) {
+    if AUX_ENABLES::Register.read(AUX_ENABLES::MINIUART_ENABLE) == 0 {
+        report(Finding::PeripheralDisabled);
+    }
+    let tx = TX_ENABLED.load(Ordering::Relaxed);
+    let rx = RX_ENABLED.load(Ordering::Relaxed);
+    if tx && AUX_MU_CNTL_REG::Register.read(AUX_MU_CNTL_REG::TRANS_ENABLE) == 0 {
+        report(Finding::TransmitterDisabled);
+    }
+    if rx && AUX_MU_CNTL_REG::Register.read(AUX_MU_CNTL_REG::RCV_ENABLE) == 0 {
+        report(Finding::ReceiverDisabled);
+    }
+    let pins = [(tx, 14), (rx, 15)];
+    for (_, pin) in pins.iter().filter(|(used, _)| *used) {
+        if let Some(finding) = diagnose::check_pin(*pin, 5) {
+            report(finding);
+        }
+    }
+    if AUX_MU_LCR_REG::Register.read(AUX_MU_LCR_REG::DATASIZE) != 0b11 {
+        report(Finding::DataSize(7));
+    }
+    if AUX_MU_IIR_REG::Register.read(AUX_MU_IIR_REG::FIFO_ENABLES) != 0b11 {
+        report(Finding::FifoDisabled);
+    }
+    let divisor = AUX_MU_BAUD_REG::Register.get() & 0xFFFF;
+    if let Some(finding) =
+        diagnose::check_baud_rate(baud_rate, uart1_actual_baud(clock_rate, divisor))
+    {
+        report(finding);
+    }
+}
+
+// write the configuration of the UART1 peripheral as read from it's registers
+pub(crate) fn uart1_describe<W: fmt::Write>(w: &mut W, clock_rate: u32) -> fmt::Result {
+    let divisor = AUX_MU_BAUD_REG::Register.get() & 0xFFFF;
+    let on_off = |value| if value == 1 { "on" } else { "off" };
+    if FLOW_ENABLED.load(Ordering::Relaxed) {
+        writeln!(
+            w,
+            "  pins:             TX GPIO14, RX GPIO15, CTS GPIO16, RTS GPIO17 (alt5)"
+        )?;
+    } else {
+        writeln!(w, "  pins:             TX GPIO14, RX GPIO15 (alt5)")?;
+    }
+    writeln!(
+        w,
+        "  baud rate:        {} (divisor {})",
+        clock_rate / (8 * (divisor + 1)),
+        divisor
+    )?;
+    let parity = PARITY.load(Ordering::Relaxed);
+    let data_bits = if AUX_MU_LCR_REG::Register.read(AUX_MU_LCR_REG::DATASIZE) == 0b11 {
+        8
+    } else {
+        7
+    };
+    if parity == Parity::None as u8 {
+        writeln!(w, "  data bits:        {}", data_bits)?;
+        writeln!(w, "  parity:           none")?;
+    } else {
+        writeln!(w, "  data bits:        {}", data_bits - 1)?;
+        writeln!(
+            w,
+            "  parity:           {} (software)",
+            if parity == Parity::Even as u8 {
+                "even"
+            } else {
+                "odd"
+            }
+        )?;
+    }
+    writeln!(w, "  stop bits:        1")?;
+    writeln!(
+        w,
+        "  flow control:     RTS {}, CTS {}",
+        on_off(AUX_MU_CNTL_REG::Register.read(AUX_MU_CNTL_REG::AUTO_FLOW_RTS)),
+        on_off(AUX_MU_CNTL_REG::Register.read(AUX_MU_CNTL_REG::AUTO_FLOW_CTS))
+    )?;
+    writeln!(
+        w,
+        "  interrupts:       RX {}, TX {}",
+        on_off(AUX_MU_IER_REG::Register.read(AUX_MU_IER_REG::RX_ENABLE)),
+        on_off(AUX_MU_IER_REG::Register.read(AUX_MU_IER_REG::TX_ENABLE))
+    )?;
+    let stats = IRQ_COUNTER.stats();
+    writeln!(
+        w,
+        "  interrupts seen:  {} ({} spurious{})",
+        stats.total,
+        stats.spurious,
+        if stats.masked { ", masked" } else { "" }
+    )?;
+    writeln!(
+        w,
+        "  fifo level:       TX {}/{}, RX {}/{}",
+        AUX_MU_STAT_REG::Register.read(AUX_MU_STAT_REG::TX_FIFO_LEVEL),
+        AUX_MU_FIFO_SIZE,
+        AUX_MU_STAT_REG::Register.read(AUX_MU_STAT_REG::RX_FIFO_LEVEL),
+        AUX_MU_FIFO_SIZE
+    )?;
+    writeln!(
+        w,
+        "  rx watermark:     {} bytes, idle timeout {} us",
+        RX_WATERMARK.load(Ordering::Relaxed),
+        RX_IDLE_TIMEOUT.load(Ordering::Relaxed)
+    )?;
+    writeln!(w, "  tx handover:      {} bytes", TX_HANDOVER.capacity())
+}
+
+// route the GPU interrupts to the given core. The AUX interrupt can not be routed on it's own, so this applies to all
+// GPU interrupts
+pub(crate) fn uart1_route_irq(core: usize) {
+    GPU_INT_ROUTING::Register.write(GPU_INT_ROUTING::IRQ_CORE, core as u32);
+}
+
+// check whether the miniUART is a source of the pending AUX interrupt shared with SPI1 and SPI2
+pub(crate) fn uart1_pending_aux_irq() -> bool {
+    AUX_IRQ::Register.read(AUX_IRQ::MINIUART_IRQ) == 1
+}
+
+// the pending state of the AUX interrupt taken when the interrupt handler is entered, before the handler removes a
+// cause on it's own, e.g. by refilling the transmit FIFO or draining the receive FIFO
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct IrqSnapshot {
+    // the miniUART is a source of the AUX interrupt
+    uart1: bool,
+    // any of the sources of the AUX interrupt is pending
+    aux: bool,
+    // the miniUART has an interrupt pending
+    cause: bool,
+}
+
+impl IrqSnapshot {
+    // whether the miniUART is a source of the AUX interrupt
+    pub(crate) fn uart1(&self) -> bool {
+        self.uart1
+    }
+}
+
+// take the pending state of the AUX interrupt on entry of the interrupt handler
+pub(crate) fn uart1_irq_snapshot() -> IrqSnapshot {
+    IrqSnapshot {
+        uart1: uart1_pending_aux_irq(),
+        aux: AUX_IRQ::Register.get() & 0b111 != 0,
+        cause: AUX_MU_IIR_REG::Register.read(AUX_MU_IIR_REG::IRQPENDING) == 0,
+    }
+}
+
+// decode the pending interrupts of the Uart1 and report them as events. As the miniUART keeps the interrupt raised as
+// long as the reason persists (data in the receive FIFO, transmit FIFO empty) the reported interrupt is disabled,
+// which acknowledges it. Returns ``false`` if the miniUART was not a source of the AUX interrupt. The AUX interrupt
+// raised without any of it's sources pending, or with the miniUART pending without a cause, counts as spurious. This
+// is decided on the state taken on entry of the handler, as a cause removed by the handler itself is not spurious
+pub(crate) fn uart1_handle_interrupt(pending: IrqSnapshot, report: impl Fn(UartEvent)) -> bool {
+    #[cfg(feature = "trace")]
+    let _span = trace::span(TracePeripheral::Uart1, TraceSpan::Interrupt);
+    if !pending.uart1 {
+        if !pending.aux {
+            IRQ_COUNTER.entered();
+            IRQ_COUNTER.spurious();
+        }
+        return false;
+    }
+    IRQ_COUNTER.entered();
+    uart1_check_rx_watermark(&report);
+    if AUX_MU_LSR_REG::Register.read(AUX_MU_LSR_REG::RCVOVERRUN) == 1 {
+        report(UartEvent::Error(RxError::Overrun));
+    } else if !pending.cause {
+        IRQ_COUNTER.spurious();
+        return true;
+    }
+    // the interrupt pending bit is cleared if an interrupt is pending
+    while AUX_MU_IIR_REG::Register.read(AUX_MU_IIR_REG::IRQPENDING) == 0 {
+        match AUX_MU_IIR_REG::Register.read(AUX_MU_IIR_REG::IRQID_FIFOCLR) {
+            0b01 => {
+                AUX_MU_IER_REG::Register.write(AUX_MU_IER_REG::TX_ENABLE, 0x0);
+                report(UartEvent::TxComplete);
+            }
+            0b10 if RX_BUFFER_MODE.load(Ordering::Acquire) == RX_IRQ_BUFFERED => {
+                // draining the receive FIFO clears the interrupt, so it stays enabled
+                uart1_drain_rx(&report);
+            }
+            0b10 => {
+                AUX_MU_IER_REG::Register.write(AUX_MU_IER_REG::RX_ENABLE, 0x0);
+                uart1_rx_watermark(&report, true);
+            }
+            _ => break,
+        }
+    }
+    true
+}
+
+// set the emulated receive FIFO level interrupt. The level is limited to the size of the receive FIFO
+// The FIFOs of the miniUART can not be disabled. Instead the transmit path queues a single byte only and the receive
+// interrupt reports every single byte, as it does without a watermark.
+pub(crate) fn uart1_set_fifo_enabled(enabled: bool) {
+    let depth = if enabled { AUX_MU_FIFO_SIZE } else { 1 };
+    TX_FIFO_DEPTH.store(depth, Ordering::Relaxed);
+    if !enabled {
+        uart1_set_rx_watermark(1, 0);
+    }
+}
+
+pub(crate) fn uart1_set_rx_watermark(level: usize, idle_timeout: u32) {
+    RX_WATERMARK.store(level.clamp(1, AUX_MU_FIFO_SIZE), Ordering::Relaxed);
+    RX_IDLE_TIMEOUT.store(idle_timeout, Ordering::Relaxed);
+    RX_WAIT_ARMED.store(false, Ordering::Release);
+}
+
+// check the receive FIFO level again if the receive interrupt is held back for the watermark, called on each AUX
+// interrupt and from the timer tick
+pub(crate) fn uart1_check_rx_watermark(report: &impl Fn(UartEvent)) {
+    if RX_WAIT_ARMED.load(Ordering::Acquire) {
+        uart1_rx_watermark(report, false);
+    }
+}
+
+// The miniUART does not provide a receive FIFO level interrupt like the PL011 does but raises the receive interrupt as
+// soon as there is a single byte available. This is emulated without waiting in the interrupt handler: as long as the
+// receive FIFO is below the watermark the receive interrupt stays disabled and the level is checked again by the next
+// interrupt or timer tick. The received data is reported once the watermark has been reached or no further data has
+// been received within the idle timeout. ``raised`` tells whether the receive interrupt is the reason of the check
+fn uart1_rx_watermark(report: &impl Fn(UartEvent), raised: bool) {
+    let watermark = RX_WATERMARK.load(Ordering::Relaxed);
+    let idle_timeout = RX_IDLE_TIMEOUT.load(Ordering::Relaxed) as u64;
+    let level = AUX_MU_STAT_REG::Register.read(AUX_MU_STAT_REG::RX_FIFO_LEVEL) as usize;
+    let now = clock::now();
+    let armed = RX_WAIT_ARMED.load(Ordering::Acquire);
+    if level >= watermark
+        || (armed
+            && level == RX_WAIT_LEVEL.load(Ordering::Relaxed)
+            && now.saturating_sub(RX_WAIT_SINCE.load(Ordering::Relaxed)) > idle_timeout)
+    {
+        // the interrupt and the timer tick might check at the same time, only one of them reports the data
+        if RX_WAIT_ARMED.swap(false, Ordering::AcqRel) || raised {
+            report(UartEvent::DataAvailable(level));
+        }
+    } else if !armed || level != RX_WAIT_LEVEL.load(Ordering::Relaxed) {
+        RX_WAIT_LEVEL.store(level, Ordering::Relaxed);
+        RX_WAIT_SINCE.store(now, Ordering::Relaxed);
+        RX_WAIT_ARMED.store(true, Ordering::Release);
+    }
+}
+
+// the raw value of the interrupt identify register
+pub(crate) fn uart1_iir() -> u32 {
+    AUX_MU_IIR_REG::Register.get() & 0xFF
 }
 
 // specify the AUX registers
 define_mmio_register! [
-    AUX_IRQ<ReadOnly<u32>@(AUX_BASE + 0x00)>,
+    AUX_IRQ<ReadOnly<u32>@(AUX_BASE + 0x00)> {
+        MINIUART_IRQ OFFSET(0),
+        SPI1_IRQ OFFSET(1),
+        SPI2_IRQ OFFSET(2)
+    },
     AUX_ENABLES<ReadWrite<u32>@(AUX_BASE + 0x04)> {
         MINIUART_ENABLE OFFSET(0),
         SPI1_ENABLE OFFSET(1),
@@ -179,7 +1171,7 @@ define_mmio_register! [
         TRANSEMPTY OFFSET(5),
         TRANSIDLE  OFFSET(6)
     },
-    AUX_MU_MSR_REG<ReadWrite<u32>@(AUX_BASE + 0x58)>,
+    AUX_MU_MSR_REG<ReadOnly<u32>@(AUX_BASE + 0x58)>,
     AUX_MU_CNTL_REG<ReadWrite<u32>@(AUX_BASE + 0x60)> {
         RCV_ENABLE OFFSET(0),
         TRANS_ENABLE OFFSET(1),
@@ -190,6 +1182,27 @@ define_mmio_register! [
         CTS_ASSERT OFFSET(7)
 
     },
-    AUX_MU_STAT_REG<ReadWrite<u32>@(AUX_BASE + 0x64)>,
+    AUX_MU_STAT_REG<ReadOnly<u32>@(AUX_BASE + 0x64)> {
+        SYMBOL_AVAILABLE OFFSET(0),
+        SPACE_AVAILABLE OFFSET(1),
+        RX_IDLE OFFSET(2),
+        TX_IDLE OFFSET(3),
+        RX_OVERRUN OFFSET(4),
+        TX_FIFO_FULL OFFSET(5),
+        RTS_STATUS OFFSET(6),
+        CTS_LINE OFFSET(7),
+        TX_FIFO_EMPTY OFFSET(8),
+        TX_DONE OFFSET(9),
+        RX_FIFO_LEVEL OFFSET(16) BITS(4),
+        TX_FIFO_LEVEL OFFSET(24) BITS(4)
+    },
     AUX_MU_BAUD_REG<ReadWrite<u32>@(AUX_BASE + 0x68)>
 ];
+
+// the routing of the GPU interrupts in the ARM local peripherals
+define_mmio_register! [
+    GPU_INT_ROUTING<ReadWrite<u32>@(ARM_LOCAL_BASE + 0x0C)> {
+        IRQ_CORE OFFSET(0) BITS(2),
+        FIQ_CORE OFFSET(2) BITS(2)
+    }
+];
@@ -0,0 +1,177 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Uart1 Interrupt Status
+//!
+//! The interrupt identify register (IIR) of the miniUART flags a pending interrupt with a cleared bit 0 and names it's
+//! source in bits 1 and 2, while bits 6 and 7 always read as set as the FIFOs are always enabled. The
+//! [Uart1IrqStatus] decodes this layout, so users dispatching the interrupts of the Uart1 on their own, instead of using
+//! [Uart1::handle_interrupt], do not need to deal with the inverted bit.
+//!
+
+#![forbid(unsafe_code)]
+
+use super::{interface, Uart1};
+use crate::irqstats::IrqStats;
+use crate::Peripheral;
+
+/// The source of a pending Uart1 interrupt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Uart1IrqSource {
+    /// the transmit FIFO is empty
+    TransmitEmpty,
+    /// the receive FIFO holds at least one byte
+    ReceiveData,
+}
+
+/// The decoded interrupt status of the Uart1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uart1IrqStatus {
+    /// the source of the pending interrupt, ``None`` if no interrupt is pending
+    pub pending: Option<Uart1IrqSource>,
+    /// whether the FIFOs are enabled
+    pub fifos_enabled: bool,
+}
+
+impl Uart1IrqStatus {
+    /// Decode the value of the interrupt identify register (AUX_MU_IIR_REG)
+    /// # Example
+    /// ```
+    /// # use ruspiro_uart::uart1::*;
+    /// // the value after reset, no interrupt pending
+    /// let status = Uart1IrqStatus::from_iir(0xC1);
+    /// assert_eq!(status.pending, None);
+    /// assert!(status.fifos_enabled);
+    /// // the transmit FIFO is empty
+    /// assert_eq!(Uart1IrqStatus::from_iir(0xC2).pending, Some(Uart1IrqSource::TransmitEmpty));
+    /// // data has been received
+    /// assert_eq!(Uart1IrqStatus::from_iir(0xC4).pending, Some(Uart1IrqSource::ReceiveData));
+    /// ```
+    pub const fn from_iir(iir: u32) -> Self {
+        let pending = if iir & 0b1 != 0 {
+            None
+        } else {
+            match (iir >> 1) & 0b11 {
+                0b01 => Some(Uart1IrqSource::TransmitEmpty),
+                0b10 => Some(Uart1IrqSource::ReceiveData),
+                _ => None,
+            }
+        };
+        Uart1IrqStatus {
+            pending,
+            fifos_enabled: (iir >> 6) & 0b11 == 0b11,
+        }
+    }
+}
+
+impl Uart1 {
+    /// The decoded interrupt status of the Uart1. Fails with ``Err(UartError::NotInitialized)`` if the Uart1 is not
+    /// initialized.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// if uart.interrupt_status().unwrap().pending == Some(Uart1IrqSource::TransmitEmpty) {
+    ///     // send the next chunk of data
+    /// }
+    /// # }
+    /// ```
+    pub fn interrupt_status(&self) -> Result<Uart1IrqStatus, crate::UartError> {
+        if self.initialized {
+            Ok(Uart1IrqStatus::from_iir(interface::uart1_iir()))
+        } else {
+            Err(crate::UartError::NotInitialized)
+        }
+    }
+
+    /// The number of interrupts the interrupt handler has been entered for, and how many of them have been spurious
+    /// without any pending cause. See the [irqstats](crate::irqstats) module for details.
+    pub fn irq_stats(&self) -> IrqStats {
+        interface::uart1_irq_stats()
+    }
+
+    /// Disable the interrupt line of the Uart1 at the interrupt controller once the interrupt handler has been entered
+    /// spuriously more than ``limit`` times within a second, and notify the hook from within the interrupt handler. A
+    /// limit of 0 removes the limit, which is the default. See the [irqstats](crate::irqstats) module for details.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::Peripheral;
+    /// # fn storm(_: Peripheral) {}
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.set_spurious_irq_limit(1_000, Some(storm));
+    /// # }
+    /// ```
+    pub fn set_spurious_irq_limit(&mut self, limit: u32, hook: Option<fn(Peripheral)>) {
+        interface::uart1_set_spurious_irq_limit(limit, hook);
+    }
+
+    /// Enable the interrupt line of the Uart1 again that has been disabled due to spurious interrupts exceeding the limit
+    pub fn resume_interrupts(&self) {
+        interface::uart1_resume_interrupts();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Uart1IrqSource, Uart1IrqStatus};
+
+    #[test]
+    fn decodes_every_interrupt_id() {
+        // bit 0 is cleared while an interrupt is pending, bits 1 and 2 identify it
+        let expected = [
+            (0b000, None),
+            (0b001, None),
+            (0b010, Some(Uart1IrqSource::TransmitEmpty)),
+            (0b011, None),
+            (0b100, Some(Uart1IrqSource::ReceiveData)),
+            (0b101, None),
+            // the reserved id is not reported as a source
+            (0b110, None),
+            (0b111, None),
+        ];
+        for (iir, pending) in expected.iter() {
+            assert_eq!(
+                Uart1IrqStatus::from_iir(*iir).pending,
+                *pending,
+                "IIR {:#05b}",
+                iir
+            );
+        }
+    }
+
+    #[test]
+    fn no_interrupt_pending_ignores_the_id() {
+        for id in 0..4 {
+            assert_eq!(Uart1IrqStatus::from_iir(0xC1 | id << 1).pending, None);
+        }
+    }
+
+    #[test]
+    fn decodes_the_fifo_enables() {
+        assert!(Uart1IrqStatus::from_iir(0xC1).fifos_enabled);
+        assert!(!Uart1IrqStatus::from_iir(0x81).fifos_enabled);
+        assert!(!Uart1IrqStatus::from_iir(0x41).fifos_enabled);
+        assert!(!Uart1IrqStatus::from_iir(0x01).fifos_enabled);
+    }
+
+    #[test]
+    fn ignores_the_unused_bits() {
+        assert_eq!(
+            Uart1IrqStatus::from_iir(0xFFFF_FF3C),
+            Uart1IrqStatus::from_iir(0x04)
+        );
+        assert_eq!(
+            Uart1IrqStatus::from_iir(0xC4 | 0x38).pending,
+            Some(Uart1IrqSource::ReceiveData)
+        );
+    }
+}
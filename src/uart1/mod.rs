@@ -18,15 +18,118 @@
 //! of the actual device. Please refer to the [``ruspiro-console`` crate](https://crates.io/crates/ruspiro-console).
 //!
 
+#![forbid(unsafe_code)]
+
 extern crate alloc;
-use crate::InterruptType;
+use crate::buffer::BufferStorage;
+use crate::command::{CommandDispatcher, CommandHandler};
+use crate::diagnose::{self, Finding};
+use crate::encoding::{self, Encoding};
+use crate::escape;
+#[cfg(feature = "fiq")]
+use crate::fiq;
+#[cfg(feature = "heartbeat")]
+use crate::heartbeat::{self, HeartbeatFn};
+#[cfg(feature = "hw-test")]
+use crate::hwtest::{self, HwTestReport};
+use crate::linecheck::LineCheck;
+use crate::negotiate;
+use crate::pacing::Pacing;
+use crate::prefix::{self, LineStart};
+use crate::raw::RawUart1;
+use crate::ringbuffer::SpscRing;
+use crate::sealed::InitCell;
+use crate::serial::SerialSettings;
+use crate::slowsend::{self, SlowSend};
+use crate::soak::{self, Pattern, SoakReport};
+use crate::staging::ConsoleStaging;
+use crate::teardown::{self, Teardown};
+use crate::throttle::ConsoleThrottle;
+#[cfg(feature = "trace")]
+use crate::trace::{self, TracePeripheral, TraceSpan};
+#[cfg(any(feature = "fiq", feature = "heartbeat"))]
+use crate::Peripheral;
+#[cfg(feature = "fiq")]
+use crate::RxFlag;
+use crate::{
+    clock, watchdog, ConsoleMode, Direction, Escape, FifoMode, HexCase, InitReport, InterruptType,
+    Parity, Prefix, ReadOutcome, RetryPolicy, Timeout, UartConfig, UartError, UartEvent, UartState,
+};
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{fence, AtomicBool, AtomicU8, AtomicUsize, Ordering};
 use ruspiro_console::ConsoleImpl;
+use ruspiro_timer as timer;
+
+pub(crate) mod interface;
+use interface::IrqSnapshot;
+
+mod buffered;
+pub use buffered::{Uart1Buffered, DEFAULT_RX_TIMEOUT};
+
+mod irq;
+pub use irq::{Uart1IrqSource, Uart1IrqStatus};
 
-mod interface;
+#[cfg(feature = "async")]
+mod asynch;
+#[cfg(feature = "async")]
+pub use asynch::Uart1Async;
+
+// the events raised by the interrupt handler waiting to be processed
+const EVENT_QUEUE_SIZE: usize = 16;
+static EVENTS: SpscRing<UartEvent, EVENT_QUEUE_SIZE> = SpscRing::new();
+
+// the console output queued to be sent by the interrupt handler while the console is buffered or the throttled output
+// exceeded it's share, and the flags whether a core is currently queueing output or sending the queued output, as the
+// queue only supports a single producer and a single consumer
+const CONSOLE_BUFFER_SIZE: usize = 1024;
+static CONSOLE_BUFFERED: AtomicBool = AtomicBool::new(false);
+static CONSOLE_THROTTLED: AtomicBool = AtomicBool::new(false);
+static CONSOLE_QUEUE: SpscRing<u8, CONSOLE_BUFFER_SIZE> = SpscRing::new();
+static CONSOLE_QUEUE_LOCK: AtomicBool = AtomicBool::new(false);
+static CONSOLE_DRAINING: AtomicBool = AtomicBool::new(false);
+
+/// The fill level of the receive FIFO at which the miniUART de-asserts RTS with auto flow control, given as the empty
+/// spaces left in the 8 byte FIFO
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum RtsLevel {
+    /// de-assert RTS once 4 spaces are left
+    FourFree = 3,
+    /// de-assert RTS once 3 spaces are left
+    ThreeFree = 0,
+    /// de-assert RTS once 2 spaces are left
+    TwoFree = 1,
+    /// de-assert RTS once 1 space is left
+    OneFree = 2,
+}
 
 /// Uart1 (miniUART) peripheral representation
 pub struct Uart1 {
     initialized: bool,
+    clock_rate: u32,
+    baud_rate: u32,
+    console_timeout: u64,
+    console_yield: Option<fn()>,
+    console_dropped: AtomicUsize,
+    encoding: Encoding,
+    fallback: u8,
+    commands: CommandDispatcher,
+    staging: InitCell<Option<ConsoleStaging>>,
+    prefix: Prefix,
+    line_start: LineStart,
+    escape: AtomicU8,
+    line_check: LineCheck,
+    retry_policy: RetryPolicy,
+    pacing: Pacing,
+    throttle: ConsoleThrottle,
+    init_report: InitReport,
+}
+
+impl Default for Uart1 {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Uart1 {
@@ -39,11 +142,33 @@ impl Uart1 {
     /// # }
     /// ```
     pub const fn new() -> Self {
-        Uart1 { initialized: false }
+        Uart1 {
+            initialized: false,
+            clock_rate: 0,
+            baud_rate: 0,
+            console_timeout: 0,
+            console_yield: None,
+            console_dropped: AtomicUsize::new(0),
+            encoding: Encoding::Utf8,
+            fallback: b'?',
+            commands: CommandDispatcher::new(),
+            staging: InitCell::new(None),
+            prefix: Prefix::None,
+            line_start: LineStart::new(),
+            escape: AtomicU8::new(Escape::None as u8),
+            line_check: LineCheck::new(),
+            retry_policy: RetryPolicy::ABORT,
+            pacing: Pacing::NONE,
+            throttle: ConsoleThrottle::new(),
+            init_report: InitReport::new(),
+        }
     }
 
     /// Initialize the Uart1 peripheral for usage. It takes the core clock rate and the
-    /// baud rate to configure correct communication speed.
+    /// baud rate to configure correct communication speed. If the peripheral does not respond while being
+    /// initialized (e.g. as the firmware has not enabled it) an ``Err(UartError::InitializationTimeout(stage))`` is
+    /// returned naming the stage of the initialization that failed. If GPIO14 or GPIO15 are switched to an alternative
+    /// function of another peripheral the mux setting is left untouched and ``Err(UartError::PinBusy)`` is returned.
     /// # Example
     /// ```no_run
     /// # use ruspiro_uart::uart1::*;
@@ -53,9 +178,221 @@ impl Uart1 {
     /// # }
     /// ```
     ///
-    pub fn initialize(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), &'static str> {
-        interface::uart1_init(clock_rate, baud_rate).map(|_| {
+    pub fn initialize(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.initialize_for(
+            clock_rate,
+            baud_rate,
+            Direction::Both,
+            &UartConfig::EIGHT_N_ONE,
+        )
+    }
+
+    /// Initialize the Uart1 peripheral for sending only. Only the TX pin GPIO14 is reserved and the receiver stays
+    /// disabled, so the RX pin GPIO15 can be used otherwise.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// assert_eq!(uart.initialize_tx_only(250_000_000, 115_200), Ok(()));
+    /// # }
+    /// ```
+    pub fn initialize_tx_only(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.initialize_for(
+            clock_rate,
+            baud_rate,
+            Direction::TxOnly,
+            &UartConfig::EIGHT_N_ONE,
+        )
+    }
+
+    /// Initialize the Uart1 peripheral for receiving only. Only the RX pin GPIO15 is reserved and the transmitter
+    /// stays disabled, so the TX pin GPIO14 can be used otherwise. Any data sent is dropped.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// assert_eq!(uart.initialize_rx_only(250_000_000, 9_600), Ok(()));
+    /// # }
+    /// ```
+    pub fn initialize_rx_only(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.initialize_for(
+            clock_rate,
+            baud_rate,
+            Direction::RxOnly,
+            &UartConfig::EIGHT_N_ONE,
+        )
+    }
+
+    // initialize the peripheral for the given directions and line configuration
+    fn initialize_for(
+        &mut self,
+        clock_rate: u32,
+        baud_rate: u32,
+        direction: Direction,
+        config: &UartConfig,
+    ) -> Result<(), UartError> {
+        let (data_bits, parity) = config.uart1_mode()?;
+        self.init_report = InitReport::new();
+        interface::uart1_init(
+            clock_rate,
+            baud_rate,
+            direction,
+            data_bits,
+            &mut self.init_report,
+        )
+        .map(|_| {
+            interface::uart1_set_parity(parity);
+            self.initialized = true;
+            self.clock_rate = clock_rate;
+            self.baud_rate = baud_rate;
+            #[cfg(feature = "early_log")]
+            crate::early::EARLY_LOG.replay(interface::uart1_send_data);
+        })
+    }
+
+    /// Initialize the Uart1 peripheral with line settings given in the vocabulary of host side serial port crates.
+    /// The settings supported are the ones of [Uart1::initialize_with_config] without flow control, other settings
+    /// fail with ``Err(UartError::UnsupportedSettings)``.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # use ruspiro_uart::serial::*;
+    /// # fn doc() {
+    /// let settings = SerialSettings {
+    ///     baud_rate: 115_200,
+    ///     data_bits: DataBits::Eight,
+    ///     parity: Parity::None,
+    ///     stop_bits: StopBits::One,
+    ///     flow_control: FlowControl::None,
+    /// };
+    /// let mut uart = Uart1::new();
+    /// assert_eq!(uart.initialize_serial(250_000_000, &settings), Ok(()));
+    /// # }
+    /// ```
+    pub fn initialize_serial(
+        &mut self,
+        clock_rate: u32,
+        settings: &SerialSettings,
+    ) -> Result<(), UartError> {
+        self.initialize_with_config(clock_rate, settings.baud_rate, &settings.config()?)
+    }
+
+    /// Initialize the Uart1 peripheral with the given line configuration. The miniUART supports 8 data bits without
+    /// parity, 7 data bits without parity or 7 data bits with an odd or even parity emulated in software (see
+    /// [Uart1::set_parity]), always with one stop bit. Other configurations fail with
+    /// ``Err(UartError::UnsupportedSettings)`` without touching the peripheral. The configuration is applied before the
+    /// receiver and transmitter are enabled.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::serial::{DataBits, StopBits};
+    /// # fn doc() {
+    /// let config = UartConfig {
+    ///     data_bits: DataBits::Seven,
+    ///     parity: Parity::None,
+    ///     stop_bits: StopBits::One,
+    /// };
+    /// let mut uart = Uart1::new();
+    /// assert_eq!(uart.initialize_with_config(250_000_000, 115_200, &config), Ok(()));
+    /// # }
+    /// ```
+    pub fn initialize_with_config(
+        &mut self,
+        clock_rate: u32,
+        baud_rate: u32,
+        config: &UartConfig,
+    ) -> Result<(), UartError> {
+        self.initialize_for(clock_rate, baud_rate, Direction::Both, config)
+    }
+
+    /// The steps completed by the last initialization of this instance, and the step it failed at. This tells which
+    /// step has timed out if an initialization fails with ``Err(UartError::InitializationTimeout(_))``.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// if uart.initialize(250_000_000, 115_200).is_err() {
+    ///     if let Some((step, error)) = uart.last_init_report().failure() {
+    ///         println!("initialization failed at {:?}: {}", step, error);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn last_init_report(&self) -> InitReport {
+        self.init_report
+    }
+
+    /// Negotiate a higher baud rate with a host tool, see the [negotiate] module for the protocol. The candidates are
+    /// offered in the order of preference, rates the baud rate divisor can not produce at the clock rate in use within
+    /// [diagnose::MAX_BAUD_DEVIATION] percent are skipped. Returns the baud rate in use afterwards, which is the current
+    /// one if no host tool answered. Fails with ``Err(UartError::ParityError)`` if the answer of the host is received
+    /// with a wrong parity, in which case the current rate is kept as well.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// let baud_rate = uart.negotiate_baud(&[921_600, 460_800]).unwrap();
+    /// # }
+    /// ```
+    pub fn negotiate_baud(&mut self, candidates: &[u32]) -> Result<u32, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        let clock_rate = self.clock_rate;
+        let mut supported = [0u32; negotiate::MAX_CANDIDATES];
+        let mut count = 0;
+        for candidate in candidates {
+            if count < supported.len() && *candidate != 0 && *candidate <= clock_rate / 8 {
+                let divisor = interface::uart1_baud_divisor(clock_rate, *candidate);
+                let actual = interface::uart1_actual_baud(clock_rate, divisor);
+                if divisor <= 0xFFFF && diagnose::check_baud_rate(*candidate, actual).is_none() {
+                    supported[count] = *candidate;
+                    count += 1;
+                }
+            }
+        }
+        self.baud_rate = negotiate::negotiate(
+            self.baud_rate,
+            &supported[..count],
+            interface::uart1_send_data,
+            interface::uart1_try_receive,
+            |baud_rate| interface::uart1_set_baud_rate(clock_rate, baud_rate),
+        )?;
+        Ok(self.baud_rate)
+    }
+
+    /// Take over the Uart1 peripheral as it has already been configured by the firmware or a boot loader like U-Boot.
+    /// Instead of re-programming the peripheral, which causes a burst of garbage characters on the remote side when
+    /// happening in the middle of an output stream, the baud rate and data size in use are kept. Data still in the
+    /// transmit FIFO is sent out and left over data in the receive FIFO is discarded. If the peripheral has not been
+    /// configured yet ``Err(UartError::NotConfigured)`` is returned and the Uart1 need to be initialized with
+    /// [Uart1::initialize] instead.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # use ruspiro_uart::UartError;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// if uart.takeover(250_000_000) == Err(UartError::NotConfigured) {
+    ///     let _ = uart.initialize(250_000_000, 115_200);
+    /// }
+    /// # }
+    /// ```
+    pub fn takeover(&mut self, clock_rate: u32) -> Result<(), UartError> {
+        if self.initialized {
+            return Err(UartError::AlreadyInitialized);
+        }
+        interface::uart1_takeover(clock_rate).map(|baud_rate| {
             self.initialized = true;
+            self.clock_rate = clock_rate;
+            self.baud_rate = baud_rate;
+            #[cfg(feature = "early_log")]
+            crate::early::EARLY_LOG.replay(interface::uart1_send_data);
         })
     }
 
@@ -72,7 +409,11 @@ impl Uart1 {
     ///
     pub fn send_char(&self, c: char) {
         if self.initialized {
-            interface::uart1_send_char(c);
+            if self.encoding == Encoding::Utf8 {
+                interface::uart1_send_char(c);
+            } else {
+                interface::uart1_send_data(&[self.encoding.encode(c, self.fallback)]);
+            }
         }
     }
 
@@ -89,11 +430,38 @@ impl Uart1 {
     ///
     pub fn send_string(&self, s: &str) {
         if self.initialized {
-            interface::uart1_send_string(s);
+            if self.encoding == Encoding::Utf8 {
+                interface::uart1_send_string(s);
+            } else {
+                encoding::encode_str(s, self.encoding, self.fallback, interface::uart1_send_data);
+            }
         }
     }
 
-    /// Send a byte buffer to the uart peripheral
+    /// Set the character encoding used to send strings and characters. Characters not available in the encoding are
+    /// replaced with the ``fallback`` character. The default is to send strings as UTF-8.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # use ruspiro_uart::encoding::Encoding;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.set_encoding(Encoding::Latin1, b'?');
+    /// # }
+    /// ```
+    pub fn set_encoding(&mut self, encoding: Encoding, fallback: u8) {
+        self.encoding = encoding;
+        self.fallback = fallback;
+    }
+
+    /// Send a byte buffer to the uart peripheral. The data of each call is sent as a whole, even if the call
+    /// interrupts an ongoing transfer (e.g. from within an interrupt handler). In this case the data is handed over to
+    /// the interrupted transfer and sent right after the interrupted transfer has completed. This is guarantied as long
+    /// as the data fits into the handover buffer (256 bytes by default, see [Uart1::set_tx_handover_buffer]). Larger
+    /// data will be sent immediately and will therefore be interleaved with the data of the interrupted transfer.
+    /// Called from within [Uart1::handle_interrupt], e.g. by a handler of the received data, only the data the
+    /// transmit FIFO accepts right away is sent, as waiting for the transmitter might never end.
     /// # Example
     /// ```no_run
     /// # use ruspiro_uart::uart1::*;
@@ -104,11 +472,153 @@ impl Uart1 {
     /// # }
     /// ```
     pub fn send_data(&self, d: &[u8]) {
-        if self.initialized {
+        if !self.initialized {
+            return;
+        }
+        if interface::uart1_in_irq() {
+            let mut bytes = d.iter().copied();
+            interface::uart1_fill_fifo(|| bytes.next());
+        } else {
             interface::uart1_send_data(d);
         }
     }
 
+    /// Write as many bytes of the buffer as the transmit FIFO accepts right now, without waiting for the transmitter.
+    /// Returns the number of bytes written, which is 0 if the FIFO is full. This allows cooperative schedulers to
+    /// poll instead of blocking. Fails with ``Err(UartError::Busy)`` while the Uart is re-configured, e.g. by another
+    /// core changing the baud rate, or sends a break, and with ``Err(UartError::WouldBlock)`` while another transfer
+    /// is sending, e.g. the one interrupted by this call.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// let data = b"SomeData";
+    /// let mut sent = 0;
+    /// while sent < data.len() {
+    ///     if let Ok(count) = uart.try_send_data(&data[sent..]) {
+    ///         sent += count;
+    ///     }
+    ///     // do something else while the FIFO drains
+    /// }
+    /// # }
+    /// ```
+    pub fn try_send_data(&self, d: &[u8]) -> Result<usize, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::uart1_check_state()?;
+        interface::uart1_try_send(d).ok_or(UartError::WouldBlock)
+    }
+
+    /// The current state of the Uart, for diagnostic purposes. Data is only sent or received while the Uart is
+    /// ``UartState::Active``.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # use ruspiro_uart::UartState;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// assert_eq!(uart.state(), UartState::Active);
+    /// # }
+    /// ```
+    pub fn state(&self) -> UartState {
+        interface::uart1_state()
+    }
+
+    // send the data polling the line status before each byte, only used to compare the throughput in benchmarks
+    #[cfg(feature = "bench")]
+    pub(crate) fn send_data_bytewise(&self, d: &[u8]) {
+        if self.initialized {
+            interface::uart1_send_data_bytewise(d);
+        }
+    }
+
+    /// Wait until all data sent has left the transmitter.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.send_string("going to sleep\r\n");
+    /// uart.flush();
+    /// # }
+    /// ```
+    pub fn flush(&self) {
+        if self.initialized {
+            interface::uart1_flush();
+        }
+    }
+
+    /// Change the baud rate at runtime without a full re-initialization, e.g. to switch a boot loader to a higher
+    /// speed once the handshake at 115200 baud is done. The clock rate is given again, as the Uart1 runs from the core
+    /// clock that might have changed as well. The Uart1 waits until all data sent has left the transmitter, stops the
+    /// receiver and transmitter while ``AUX_MU_BAUD`` is re-programmed and continues with the new baud rate, the data
+    /// already received is kept.
+    ///
+    /// Fails with ``Err(UartError::NotInitialized)`` if the Uart1 is not initialized and with
+    /// ``Err(UartError::UnsupportedBaudRate)`` if the baud rate can not be configured with the clock rate.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.set_baud_rate(250_000_000, 921_600).unwrap();
+    /// # }
+    /// ```
+    pub fn set_baud_rate(&mut self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        self.apply_baud_rate(clock_rate, baud_rate)?;
+        self.clock_rate = clock_rate;
+        self.baud_rate = baud_rate;
+        Ok(())
+    }
+
+    // switch to the baud rate, or back to the configured one if none is given, only used in benchmarks
+    #[cfg(feature = "bench")]
+    pub(crate) fn switch_baud_rate(&self, baud_rate: Option<u32>) -> Result<(), UartError> {
+        self.apply_baud_rate(self.clock_rate, baud_rate.unwrap_or(self.baud_rate))
+    }
+
+    // program the baud rate into the peripheral without changing the one configured for this instance
+    fn apply_baud_rate(&self, clock_rate: u32, baud_rate: u32) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        if baud_rate == 0 || baud_rate as u64 * 8 > clock_rate as u64 {
+            return Err(UartError::UnsupportedBaudRate);
+        }
+        interface::uart1_set_baud_rate(clock_rate, baud_rate)
+    }
+
+    /// Set the memory used to hand over the data of a send call interrupting an ongoing transfer (see
+    /// [Uart1::send_data]). By default an internal buffer of 256 bytes is used. The size of the buffer limits the
+    /// amount of data that can be sent from an interrupt handler without interleaving with the interrupted transfer.
+    /// The buffer can only be replaced while the Uart1 is not initialized.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # use ruspiro_uart::buffer::BufferStorage;
+    /// # fn doc() {
+    /// static mut HANDOVER: [u8; 1024] = [0; 1024];
+    ///
+    /// let mut uart = Uart1::new();
+    /// uart.set_tx_handover_buffer(BufferStorage::Static(unsafe { &mut HANDOVER }))
+    ///     .expect("Uart1 already initialized");
+    /// # }
+    /// ```
+    pub fn set_tx_handover_buffer(&mut self, storage: BufferStorage) -> Result<(), UartError> {
+        if self.initialized {
+            Err(UartError::AlreadyInitialized)
+        } else {
+            interface::uart1_set_tx_handover_storage(storage.into_slice());
+            Ok(())
+        }
+    }
+
     /// convert a given u64 into it's hex representation and send to uart
     /// # Example
     /// ```no_run
@@ -120,167 +630,1620 @@ impl Uart1 {
     /// # }
     /// ```
     pub fn send_hex(&self, value: u64) {
-        if value == 0 {
-            self.send_string("0x0");
-            return;
-        }
-        const HEXCHAR: &[u8] = b"0123456789ABCDEF";
+        self.send_hex_width(value, 0, HexCase::Upper);
+    }
+
+    /// convert a given u64 into it's hex representation with at least ``width`` digits, padded with leading zeros,
+    /// and send to uart. The digits above 9 are sent in the given letter case.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// // sends "0x00c0ffee"
+    /// uart.send_hex_width(0xC0FFEE, 8, HexCase::Lower);
+    /// # }
+    /// ```
+    pub fn send_hex_width(&self, value: u64, width: usize, case: HexCase) {
+        let digits: &[u8; 16] = match case {
+            HexCase::Upper => b"0123456789ABCDEF",
+            HexCase::Lower => b"0123456789abcdef",
+        };
+        // "0x" followed by up to 16 digits
+        let mut hex: [u8; 18] = [b'0'; 18];
         let mut tmp = value;
-        let mut hex: [u8; 16] = [0; 16];
-        let mut idx = 0;
-        while tmp != 0 {
-            hex[idx] = HEXCHAR[(tmp & 0xF) as usize];
+        let mut idx = hex.len();
+        let first = hex.len() - width.min(16);
+        while tmp != 0 || idx > first || idx == hex.len() {
+            idx -= 1;
+            hex[idx] = digits[(tmp & 0xF) as usize];
             tmp >>= 4;
-            idx += 1;
         }
+        hex[idx - 2] = b'0';
+        hex[idx - 1] = b'x';
+        self.send_data(&hex[idx - 2..]);
+    }
 
-        self.send_string("0x");
-        for i in 0..16 {
-            if hex[15 - i] != 0 {
-                self.send_char(hex[15 - i] as char);
+    /// convert a given u64 into it's decimal representation and send to uart
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.send_dec(12345);
+    /// # }
+    /// ```
+    pub fn send_dec(&self, value: u64) {
+        // u64::MAX has 20 decimal digits
+        let mut dec: [u8; 20] = [0; 20];
+        let mut tmp = value;
+        let mut idx = dec.len();
+        loop {
+            idx -= 1;
+            dec[idx] = b'0' + (tmp % 10) as u8;
+            tmp /= 10;
+            if tmp == 0 {
+                break;
             }
         }
+        self.send_data(&dec[idx..]);
     }
 
-    /// Try to recieve data from the Uart of the given size
-    /// If the requested size could be read it returns a ``Ok(data: Vec<u8>)`` containing the data
-    /// otherwise an ``Err(msg: &str)``.
+    /// Try to recieve data from the Uart into the buffer, waiting at most the given timeout for the buffer to be
+    /// filled. Returns ``Ok(size)`` with the number of bytes received before the timeout has passed, or
+    /// ``Err(UartError::ReceiveDataTimeOut)`` if no data has been received at all.
     ///
     /// # Example
     /// ```no_run
     /// # use ruspiro_uart::uart1::*;
+    /// # use ruspiro_uart::Timeout;
     /// # fn doc() {
     /// # let mut uart = Uart1::new();
     /// # let _ = uart.initialize(250_000_000, 115_200);
     /// let mut buffer: [u8; 8] = [0; 8];
-    /// let rx_size = uart.try_receive_data(&mut buffer).expect("unable to receive data");
+    /// let rx_size = uart
+    ///     .try_receive_data(&mut buffer, Timeout::Micros(1_000))
+    ///     .expect("unable to receive data");
     /// # }
     /// ```
-    pub fn try_receive_data(&self, buffer: &mut [u8]) -> Result<usize, &'static str> {
-        if self.initialized {
-            if buffer.is_empty() {
-                Err("buffer size expected to be at least 1")
-            } else {
-                for c in 0..buffer.len() {
-                    buffer[c] = interface::uart1_receive_data(1000)?;
-                }
-                Ok(buffer.len())
-            }
-        } else {
-            // if Uart is not initialized return 0 size vector or error? For now -> error
-            Err("Uart not initialized")
+    pub fn try_receive_data(
+        &self,
+        buffer: &mut [u8],
+        timeout: Timeout,
+    ) -> Result<usize, UartError> {
+        match self.receive_with_policy(buffer, timeout)? {
+            0 => Err(UartError::ReceiveDataTimeOut),
+            count => Ok(count),
         }
     }
 
     /// Recieve data from the Uart of the given size, blocking the current execution until the
-    /// requested amount if data has been received.
+    /// requested amount if data has been received or the timeout has passed.
     /// If the requested size could be read it returns a ``Ok(size: usize)`` containing the data
-    /// otherwise an ``Err(msg: &str)``.
+    /// otherwise an ``Err(UartError)``, which is ``Err(UartError::ReceiveDataTimeOut)`` if the timeout has passed.
     ///
     /// # Example
     /// ```no_run
     /// # use ruspiro_uart::uart1::*;
+    /// # use ruspiro_uart::Timeout;
     /// # fn doc() {
     /// # let mut uart = Uart1::new();
     /// # let _ = uart.initialize(250_000_000, 115_200);
     /// let mut buffer: [u8; 8] = [0; 8];
-    /// let rx_size = uart.receive_data(&mut buffer).expect("unable to receive data");
+    /// let rx_size = uart.receive_data(&mut buffer, Timeout::None).expect("unable to receive data");
     /// # }
     /// ```
-    pub fn receive_data(&self, buffer: &mut [u8]) -> Result<usize, &'static str> {
-        if self.initialized {
-            if buffer.is_empty() {
-                Err("buffer size expected to be at least 1")
-            } else {
-                for c in 0..buffer.len() {
-                    buffer[c] = interface::uart1_receive_data(0)?;
-                }
-                Ok(buffer.len())
-            }
+    pub fn receive_data(&self, buffer: &mut [u8], timeout: Timeout) -> Result<usize, UartError> {
+        let count = self.receive_with_policy(buffer, timeout)?;
+        if count < buffer.len() {
+            Err(UartError::ReceiveDataTimeOut)
         } else {
-            // if Uart is not initialized return 0 size vector or error? For now -> error
-            Err("Uart not initialized")
+            Ok(count)
         }
     }
 
-    /// Enable Interrupts to be triggered by the miniUart. The ``i_type`` specifies the interrupts
-    /// that shall be triggered. To receive/handle the interrupts a corresponding interrupt handler need to be
-    /// implemented, for example by using the [``ruspiro-interrupt`` crate](https://crates.io/crates/ruspiro-interrupt).
+    /// Receive ``N`` bytes like [Uart1::receive_data], but return them in a ``heapless::Vec`` instead of filling a
+    /// buffer passed in. This gives callers without an allocator the received data by value.
     /// # Example
     /// ```no_run
-    /// # use ruspiro_uart::InterruptType;
     /// # use ruspiro_uart::uart1::*;
     /// # fn doc() {
     /// # let mut uart = Uart1::new();
     /// # let _ = uart.initialize(250_000_000, 115_200);
-    /// // enable the interrupt to be triggered when data is recieved by the miniUart
-    /// uart.enable_interrupts(InterruptType::Receive);
+    /// let header = uart.receive_into_heapless::<4>().expect("unable to receive data");
     /// # }
     /// ```
-    pub fn enable_interrupts(&self, i_type: InterruptType) {
-        if self.initialized {
-            interface::uart1_enable_interrupts(i_type);
-        }
+    #[cfg(feature = "heapless")]
+    pub fn receive_into_heapless<const N: usize>(&self) -> Result<heapless::Vec<u8, N>, UartError> {
+        let mut buffer = [0u8; N];
+        let count = self.receive_data(&mut buffer, Timeout::None)?;
+        let mut data = heapless::Vec::new();
+        // the vector has the capacity of the buffer
+        let _ = data.extend_from_slice(&buffer[..count]);
+        Ok(data)
     }
 
-    /// Disable Interrupts from beeing triggered by the miniUart. The ``i_type`` specifies the interrupts
-    /// that shall disbabled.
+    /// Receive a line terminated by ``\n`` into a ``heapless::String``, blocking until the line is complete. Carriage
+    /// returns and the line feed are not stored. Fails with ``Err(UartError::BufferTooSmall)`` once the line exceeds
+    /// ``N`` bytes, leaving the rest of the line to be received, and with ``Err(UartError::InvalidMessage)`` if the
+    /// line is not valid UTF-8.
     /// # Example
     /// ```no_run
-    /// # use ruspiro_uart::InterruptType;
     /// # use ruspiro_uart::uart1::*;
     /// # fn doc() {
     /// # let mut uart = Uart1::new();
     /// # let _ = uart.initialize(250_000_000, 115_200);
-    /// // disable the interrupt to be triggered when data is recieved by the miniUart
-    /// uart.disable_interrupts(InterruptType::Receive);
+    /// let command = uart.read_line_heapless::<64>().expect("unable to receive a line");
     /// # }
     /// ```
-    pub fn disable_interrupts(&self, i_type: InterruptType) {
-        if self.initialized {
-            interface::uart1_disable_interrupts(i_type);
+    #[cfg(feature = "heapless")]
+    pub fn read_line_heapless<const N: usize>(&self) -> Result<heapless::String<N>, UartError> {
+        let mut line = [0u8; N];
+        let mut len = 0;
+        loop {
+            let mut byte = [0u8; 1];
+            self.receive_data(&mut byte, Timeout::None)?;
+            match byte[0] {
+                b'\n' => break,
+                b'\r' => (),
+                data if len < N => {
+                    line[len] = data;
+                    len += 1;
+                }
+                _ => return Err(UartError::BufferTooSmall),
+            }
         }
+        let text = core::str::from_utf8(&line[..len]).map_err(|_| UartError::InvalidMessage)?;
+        let mut string = heapless::String::new();
+        // the string has the capacity of the line buffer
+        let _ = string.push_str(text);
+        Ok(string)
     }
 
-    /// Read the current interrupt status.
-    /// Bit 0 -> is set to 0 if an interrupt is pending
-    /// Bit [1:2] -> 01 = transmit register is empty
-    ///              10 = recieve register holds valid data
+    /// Receive a line terminated by ``\n`` into the buffer, including the line feed and any carriage return before it,
+    /// so interactive shells or modem drivers need not poll for each byte. The whole line need to arrive within the
+    /// timeout. Returns the number of bytes stored. Fails with
+    /// ``Err(UartError::ReceiveDataTimeOut)`` once the timeout has passed, dropping the partial line, with
+    /// ``Err(UartError::BufferTooSmall)`` once the buffer is full before the line is complete, leaving the rest of the
+    /// line to be received, and with ``Err(UartError::ParityError)`` for a byte received with a wrong parity. The
+    /// retry policy of [Uart1::receive_data] is not applied.
     /// # Example
     /// ```no_run
     /// # use ruspiro_uart::uart1::*;
+    /// # use ruspiro_uart::Timeout;
     /// # fn doc() {
     /// # let mut uart = Uart1::new();
     /// # let _ = uart.initialize(250_000_000, 115_200);
-    /// let irq_status = uart.get_interrupt_status();
-    /// if (irq_status & 0b010) != 0 {
-    ///     println!("transmit register empty raised");
-    /// }
+    /// let mut line = [0u8; 80];
+    /// let len = uart.read_line(&mut line, Timeout::Micros(1_000_000)).expect("unable to receive a line");
     /// # }
     /// ```
-    pub fn get_interrupt_status(&self) -> u32 {
-        if self.initialized {
-            interface::uart1_get_interrupt_status()
-        } else {
-            0
+    pub fn read_line(&self, buffer: &mut [u8], timeout: Timeout) -> Result<usize, UartError> {
+        self.read_until(b'\n', buffer, timeout)
+    }
+
+    /// Receive data like [Uart1::read_line], but terminated by the given delimiter, e.g. ``\r`` for the responses of
+    /// AT command modems.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # use ruspiro_uart::Timeout;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// let mut response = [0u8; 32];
+    /// let len = uart.read_until(b'\r', &mut response, Timeout::Micros(500_000)).expect("no response");
+    /// # }
+    /// ```
+    pub fn read_until(
+        &self,
+        delimiter: u8,
+        buffer: &mut [u8],
+        timeout: Timeout,
+    ) -> Result<usize, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
         }
+        interface::uart1_check_state()?;
+        crate::read_until(delimiter, buffer, timeout, interface::uart1_try_receive)
     }
-}
 
-impl Drop for Uart1 {
-    fn drop(&mut self) {
-        // ensure the Uart1 peripheral is released once this instance is dropped
-        interface::uart1_release();
+    /// Set the policy how the receive functions handle bytes received with an error, e.g. with a wrong parity. This
+    /// allows long transfers to tolerate isolated glitches. By default the receive call fails on the first error.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 9_600);
+    /// uart.set_parity(Parity::Even);
+    /// uart.set_retry_policy(RetryPolicy {
+    ///     max_retries: 3,
+    ///     on_error: RxErrorAction::Replace(b'?'),
+    /// });
+    /// # }
+    /// ```
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
     }
-}
 
-// to use the Uart1 as a console to output strings implement the respective trait
+    // fill the buffer with data received until the timeout has passed, handling the bytes received with an error as
+    // the retry policy demands. Returns the number of bytes received
+    fn receive_with_policy(&self, buffer: &mut [u8], timeout: Timeout) -> Result<usize, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        if buffer.is_empty() {
+            return Err(UartError::EmptyBuffer);
+        }
+        interface::uart1_check_state()?;
+        #[cfg(feature = "trace")]
+        let _span = trace::span(TracePeripheral::Uart1, TraceSpan::Receive);
+        let mut errors = 0;
+        crate::receive_timeout(buffer, timeout, interface::uart1_try_receive, |error| {
+            self.retry_policy.handle(&mut errors, error)
+        })
+    }
+
+    impl_binary_helpers!();
+
+    // send the bytes of a fixed width integer
+    fn send_raw(&self, bytes: &[u8]) {
+        self.send_data(bytes);
+    }
+
+    // receive the bytes of a fixed width integer
+    fn receive_raw(&self, bytes: &mut [u8]) -> Result<(), UartError> {
+        self.receive_data(bytes, Timeout::None).map(|_| ())
+    }
+
+    /// Enable Interrupts to be triggered by the miniUart. The ``i_type`` specifies the interrupts
+    /// that shall be triggered. To receive/handle the interrupts a corresponding interrupt handler need to be
+    /// implemented, for example by using the [``ruspiro-interrupt`` crate](https://crates.io/crates/ruspiro-interrupt).
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::InterruptType;
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// // enable the interrupt to be triggered when data is recieved by the miniUart
+    /// uart.enable_interrupts(InterruptType::Receive);
+    /// # }
+    /// ```
+    pub fn enable_interrupts(&self, i_type: InterruptType) {
+        if self.initialized {
+            interface::uart1_enable_interrupts(i_type);
+        }
+    }
+
+    /// Disable Interrupts from beeing triggered by the miniUart. The ``i_type`` specifies the interrupts
+    /// that shall disbabled.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::InterruptType;
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// // disable the interrupt to be triggered when data is recieved by the miniUart
+    /// uart.disable_interrupts(InterruptType::Receive);
+    /// # }
+    /// ```
+    pub fn disable_interrupts(&self, i_type: InterruptType) {
+        if self.initialized {
+            interface::uart1_disable_interrupts(i_type);
+        }
+    }
+
+    /// Set the time in micro seconds the console output is allowed to wait for the transmitter to accept the next byte.
+    /// If the transmitter stays busy for longer (e.g. the terminal has been disconnected while flow control is active)
+    /// the remaining output of the current console write is dropped and counted instead of blocking forever.
+    /// A timeout of ``0`` (the default) means the console output blocks until all data has been sent.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// // never wait more than 5ms for the transmitter while printing to the console
+    /// uart.set_console_timeout(5_000);
+    /// # }
+    /// ```
+    pub fn set_console_timeout(&mut self, timeout: u64) {
+        self.console_timeout = timeout;
+    }
+
+    /// Register the function switching to another task of a co-operative scheduler, e.g. the ``yield_now`` of an
+    /// RTOS. While the console output waits for the transmitter to accept more data the function is called instead
+    /// of spinning, so other tasks run while a long line is sent. The console timeout still applies. The tasks
+    /// switched to must not write to this console, as the console stays locked by the waiting write. ``None``
+    /// removes the function, which is the default.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn yield_now() {}
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.set_console_yield(Some(yield_now));
+    /// # }
+    /// ```
+    pub fn set_console_yield(&mut self, yield_now: Option<fn()>) {
+        self.console_yield = yield_now;
+    }
+
+    /// Get the number of bytes that have been dropped from the console output as the transmitter did not accept them
+    /// within the configured console timeout.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// # uart.set_console_timeout(5_000);
+    /// if uart.console_dropped_bytes() != 0 {
+    ///     // the console seem to be disconnected
+    /// }
+    /// # }
+    /// ```
+    pub fn console_dropped_bytes(&self) -> usize {
+        self.console_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Read the current interrupt status.
+    /// Bit 0 -> is set to 0 if an interrupt is pending
+    /// Bit [1:2] -> 01 = transmit register is empty
+    ///              10 = recieve register holds valid data
+    ///
+    /// This is the raw layout of the interrupt identify register, use [Uart1::interrupt_status] for the decoded
+    /// status instead.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// let irq_status = uart.get_interrupt_status();
+    /// if (irq_status & 0b010) != 0 {
+    ///     println!("transmit register empty raised");
+    /// }
+    /// # }
+    /// ```
+    #[deprecated(note = "use `interrupt_status` instead")]
+    pub fn get_interrupt_status(&self) -> u32 {
+        if self.initialized {
+            interface::uart1_iir() & 0b111
+        } else {
+            0
+        }
+    }
+
+    /// Hand the Uart1 over to manual register control, see the [raw](crate::raw) module. The peripheral keeps running
+    /// and it's pins stay claimed, as does the interrupt handling. The settings of this instance, e.g. the encoding,
+    /// are not kept. The driver treats the Uart1 as suspended until it is taken back with [Uart1::from_raw], so other
+    /// users of the driver do not send or receive in the meantime. Fails with ``Err(UartError::NotInitialized)`` if
+    /// the Uart1 is not initialized, and with ``Err(UartError::InitializationTimeout(_))`` if a send of another core
+    /// does not finish, the Uart1 is shut down like on dropping it then.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// let raw = uart.into_raw().unwrap();
+    /// // access the registers of the AUX block at raw.base_address()
+    /// let uart = Uart1::from_raw(raw);
+    /// uart.send_string("back again\r\n");
+    /// # }
+    /// ```
+    pub fn into_raw(mut self) -> Result<RawUart1, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::uart1_suspend_raw()?;
+        let (tx_pin, rx_pin, flow_control) = interface::uart1_claimed_pins();
+        let raw = RawUart1 {
+            tx_pin,
+            rx_pin,
+            flow_control,
+            divisor: interface::uart1_divisor(),
+            clock_rate: self.clock_rate,
+            baud_rate: self.baud_rate,
+        };
+        // the peripheral is not shut down once this instance is dropped
+        self.initialized = false;
+        Ok(raw)
+    }
+
+    /// Take the Uart1 back from manual register control without initializing it again. The registers need to be left
+    /// the way the driver has configured them, e.g. the enabled directions. The baud rate register recorded by
+    /// [Uart1::into_raw] is restored if it has been changed.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// let raw = uart.into_raw().unwrap();
+    /// let uart = Uart1::from_raw(raw);
+    /// # }
+    /// ```
+    pub fn from_raw(raw: RawUart1) -> Self {
+        interface::uart1_resume_raw(raw.divisor);
+        let mut uart = Uart1::new();
+        uart.initialized = true;
+        uart.clock_rate = raw.clock_rate;
+        uart.baud_rate = raw.baud_rate;
+        uart
+    }
+
+    /// Shut down the Uart1 peripheral and release the GPIO pins it occupies. The interrupts are disabled first, so no
+    /// interrupt raised while tearing down the peripheral can report events afterwards. Events not yet processed are
+    /// discarded. This is also done when the Uart1 is dropped. The Uart1 need to be initialized again to be used
+    /// afterwards.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::uart1::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.deinit();
+    /// # }
+    /// ```
+    pub fn deinit(&mut self) {
+        if self.initialized {
+            teardown::tear_down(&mut Uart1Teardown);
+            self.initialized = false;
+        }
+    }
+
+    /// Handle the interrupts raised by the Uart1. This need to be called from the interrupt handler of the Aux
+    /// interrupt, for example implemented with the
+    /// [``ruspiro-interrupt`` crate](https://crates.io/crates/ruspiro-interrupt). It does not require access to the
+    /// Uart1 instance. The pending interrupts are queued as [UartEvent]s to be processed with [Uart1::events].
+    /// As the miniUART keeps the interrupt raised as long as it's reason persists, the interrupt type that has been
+    /// reported is disabled. It need to be enabled again with [Uart1::enable_interrupts] once the event has been
+    /// processed, e.g. the received data has been read. Disabling the interrupt type is also the only way to
+    /// acknowledge a miniUART interrupt. Returns ``false`` without touching the peripheral if the miniUART was not a
+    /// source of the AUX interrupt, see [Uart1::pending_aux_irq]. Data sent or console output written from within the
+    /// handler is not waited for, as waiting for the transmitter might never end. Only what the transmit FIFO accepts
+    /// right away is sent, buffered console output is queued as usual. This only covers the locks of the Uart1 itself:
+    /// a ``print!`` or ``println!`` from within an interrupt handler still deadlocks if the interrupted code holds the
+    /// lock of the global console, so interrupt handlers should use [Uart1::send_data] instead.
+    /// # Example
+    /// ```ignore
+    /// # use ruspiro_uart::uart1::*;
+    /// #[IrqHandler(Aux, Uart1)]
+    /// fn uart1_handler() {
+    ///     Uart1::handle_interrupt();
+    /// }
+    /// ```
+    pub fn handle_interrupt() -> bool {
+        let _irq = interface::uart1_enter_irq();
+        Self::handle_pending_interrupt(interface::uart1_irq_snapshot())
+    }
+
+    // handle the interrupts with the pending state taken on entry of the interrupt handler, before the console queue
+    // refills the transmit FIFO and thereby removes the cause of the transmit interrupt
+    pub(crate) fn handle_pending_interrupt(pending: IrqSnapshot) -> bool {
+        // the queue is left alone while another core sends it out switching the console to synchronous output
+        if pending.uart1()
+            && (CONSOLE_BUFFERED.load(Ordering::Acquire)
+                || CONSOLE_THROTTLED.load(Ordering::Acquire))
+            && !CONSOLE_DRAINING.swap(true, Ordering::Acquire)
+        {
+            // refilling the transmit FIFO keeps the transmit interrupt enabled until the console output has been sent
+            interface::uart1_fill_fifo(|| CONSOLE_QUEUE.pop());
+            // the throttled output is sent directly again once the queue is empty. Output queued right before the flag
+            // is cleared keeps it set
+            if CONSOLE_QUEUE.is_empty() {
+                CONSOLE_THROTTLED.store(false, Ordering::Release);
+                if !CONSOLE_QUEUE.is_empty() {
+                    CONSOLE_THROTTLED.store(true, Ordering::Release);
+                }
+            }
+            CONSOLE_DRAINING.store(false, Ordering::Release);
+        }
+        interface::uart1_handle_interrupt(pending, |event| {
+            // if the application does not process the events fast enough the new ones are dropped
+            let _ = EVENTS.push(event);
+        })
+    }
+
+    /// Prepare handing the Uart1 over to another core, e.g. from the boot core that initialized the console to the
+    /// core running the logging task. All data still pending is sent out and the interrupts are routed to the new
+    /// core, so [Uart1::handle_interrupt] need to be called from there from now on. The Raspberry Pi routes all GPU
+    /// interrupts together, so this moves the interrupts of other peripherals as well. A memory barrier ensures all
+    /// changes of the Uart1 state made so far are visible to other cores before the Uart1 is passed on. Afterwards the
+    /// Uart1 is moved to the new core through any synchronization, e.g. a ``Singleton``, and must no longer be used
+    /// by this core. Fails with ``Err(UartError::InvalidCore)`` if the core id is not in the range 0 to 3.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.transfer_to_core(2).unwrap();
+    /// // pass the Uart1 to core 2 now
+    /// # }
+    /// ```
+    pub fn transfer_to_core(&mut self, core: usize) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        // the Raspberry Pi has 4 cores
+        if core > 3 {
+            return Err(UartError::InvalidCore);
+        }
+        interface::uart1_flush();
+        interface::uart1_route_irq(core);
+        fence(Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Check whether the miniUART is a source of the pending AUX interrupt. The AUX interrupt is shared with the SPI1
+    /// and SPI2 peripherals, so a handler serving all of them can use this to decide which peripheral need to be
+    /// serviced. The pending state is read from the ``AUX_IRQ`` register and cleared once the reason of the interrupt
+    /// has been handled by [Uart1::handle_interrupt].
+    /// # Example
+    /// ```ignore
+    /// # use ruspiro_uart::uart1::*;
+    /// #[IrqHandler(Aux)]
+    /// fn aux_handler() {
+    ///     if Uart1::pending_aux_irq() {
+    ///         Uart1::handle_interrupt();
+    ///     }
+    ///     // check and handle the SPI1 and SPI2 interrupts
+    /// }
+    /// ```
+    pub fn pending_aux_irq() -> bool {
+        interface::uart1_pending_aux_irq()
+    }
+
+    /// Get an iterator over the [UartEvent]s raised since the last call. Each event is only returned once.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// for event in uart.events() {
+    ///     if let UartEvent::DataAvailable(size) = event {
+    ///         let mut buffer = [0; 8];
+    ///         let _ = uart.try_receive_data(&mut buffer[..size.min(8)], Timeout::Cycles(0));
+    ///         uart.enable_interrupts(InterruptType::Receive);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn events(&self) -> impl Iterator<Item = UartEvent> {
+        core::iter::from_fn(|| EVENTS.pop())
+    }
+
+    /// Register a hook that is called periodically while blocking in long running sends or receives, to feed a
+    /// hardware watchdog that would otherwise reset the system in the middle of a large transfer. There is only one
+    /// watchdog in the system, so the hook is shared by all Uarts. Passing ``None`` removes the hook.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn feed_watchdog() {}
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// uart.set_watchdog_kick(Some(feed_watchdog));
+    /// # }
+    /// ```
+    pub fn set_watchdog_kick(&mut self, kick: Option<fn()>) {
+        watchdog::set_kick(kick);
+    }
+
+    /// Register a hook that is called once for each send blocking for longer than ``threshold`` micro seconds, e.g. as
+    /// the terminal is gone, with the state of the transmitter. See the [slowsend] module for details. There is only
+    /// one hook shared by all Uarts. Passing ``None`` removes the hook.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::slowsend::SlowSend;
+    /// # fn stuck(_: SlowSend) {}
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// uart.set_slow_send_hook(100_000, Some(stuck));
+    /// # }
+    /// ```
+    pub fn set_slow_send_hook(&mut self, threshold: u64, hook: Option<fn(SlowSend)>) {
+        slowsend::set_hook(threshold, hook);
+    }
+
+    /// The duration of a single bit on the line in nano seconds. This is calculated from the baud rate divisor actually
+    /// configured in the peripheral, which might slightly differ from the requested baud rate.
+    pub fn bit_time_ns(&self) -> Result<u32, UartError> {
+        if self.initialized {
+            Ok(interface::uart1_bit_time_ns(self.clock_rate))
+        } else {
+            Err(UartError::NotInitialized)
+        }
+    }
+
+    /// The duration of a single character on the line in nano seconds, including the start, parity and stop bits.
+    pub fn char_time_ns(&self) -> Result<u32, UartError> {
+        self.bit_time_ns()
+            .map(|bit_time| bit_time * interface::uart1_frame_bits())
+    }
+
+    /// Wait for the time it takes to transfer ``chars`` characters on the line. Protocols like Modbus RTU or DMX use
+    /// such idle times to separate frames.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 19_200);
+    /// // Modbus RTU requires a silent interval of 3.5 characters between frames, so wait 4
+    /// uart.delay_chars(4).unwrap();
+    /// # }
+    /// ```
+    pub fn delay_chars(&self, chars: u32) -> Result<(), UartError> {
+        self.char_time_ns().map(|char_time| {
+            let duration = char_time as u64 * chars as u64;
+            // add a micro second to never wait shorter than required due to the truncation
+            timer::sleep(duration / 1000 + 1);
+        })
+    }
+
+    /// Start sending the data at the given time of the [clock](crate::clock) in micro seconds, e.g. in the time slot a node owns on a
+    /// TDMA style RS-485 bus. All data still pending is sent out first, then this waits until the timestamp is
+    /// reached. The start bit of the first byte follows within [Uart1::send_at_latency_ns], plus the latency of
+    /// interrupts occurring right at the timestamp. Fails with ``Err(UartError::SlotMissed)`` without sending
+    /// anything if the timestamp has already passed.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 500_000);
+    /// # let slot_start = 1_000_000;
+    /// let start = slot_start - uart.send_at_latency_ns().unwrap() as u64 / 1000;
+    /// uart.send_at(start, b"node 3 reporting").unwrap();
+    /// # }
+    /// ```
+    pub fn send_at(&self, timestamp: u64, data: &[u8]) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::uart1_flush();
+        crate::wait_until(timestamp)?;
+        interface::uart1_send_data(data);
+        Ok(())
+    }
+
+    /// Validate the link with a jumper between the TX and the RX pin. The data of the pattern is sent for the duration
+    /// in micro seconds and each byte received is verified against the byte sent. The report contains the bit error
+    /// rate and the highest throughput sustained. Anything else received meanwhile is discarded.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::soak::Pattern;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// let report = uart.soak_test(1_000_000, Pattern::Fixed(0x55)).unwrap();
+    /// assert!(report.bit_error_rate() < 1e-6);
+    /// # }
+    /// ```
+    pub fn soak_test(&self, duration: u64, pattern: Pattern) -> Result<SoakReport, UartError> {
+        let char_time = self.char_time_ns()?;
+        Ok(soak::run(
+            duration,
+            pattern,
+            char_time,
+            interface::uart1_send_data,
+            interface::uart1_try_receive_tagged,
+        ))
+    }
+
+    /// Run the hardware test with the host script ``tools/hwtest.py``, see the [hwtest](crate::hwtest) module. The
+    /// Uart1 is switched to each of the given baud rates and back to the current one. Fails with
+    /// ``Err(UartError::NoAcknowledge)`` if the host script does not answer.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// let report = uart.run_hw_test(&[9_600, 921_600]).unwrap();
+    /// assert_eq!(report.first_failure, None);
+    /// # }
+    /// ```
+    #[cfg(feature = "hw-test")]
+    pub fn run_hw_test(&mut self, baud_rates: &[u32]) -> Result<HwTestReport, UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        let clock_rate = self.clock_rate;
+        let supported = baud_rates
+            .iter()
+            .all(|baud_rate| *baud_rate != 0 && *baud_rate <= clock_rate / 8);
+        if !supported {
+            return Err(UartError::UnsupportedBaudRate);
+        }
+        hwtest::run(clock_rate, self.baud_rate, baud_rates)
+    }
+
+    /// Route the receive interrupt of the Uart1 as FIQ to the given core for the lowest latency. Each byte received is
+    /// passed to the handler together with it's error state from [fiq::handle_fiq], or stored for [fiq::read] if no
+    /// handler is given. This replaces
+    /// any other FIQ source. Fails with ``Err(UartError::InvalidCore)`` if the core id is not in the range 0 to 3.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.route_rx_to_fiq(1, None).unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "fiq")]
+    pub fn route_rx_to_fiq(
+        &mut self,
+        core: usize,
+        handler: Option<fn(u8, RxFlag)>,
+    ) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        // the Raspberry Pi has 4 cores
+        if core > 3 {
+            return Err(UartError::InvalidCore);
+        }
+        interface::uart1_enable_interrupts(InterruptType::Receive);
+        fiq::route(Peripheral::Uart1, core, handler);
+        Ok(())
+    }
+
+    /// Stop routing the receive interrupt of the Uart1 as FIQ.
+    #[cfg(feature = "fiq")]
+    pub fn release_fiq(&mut self) {
+        fiq::release(Peripheral::Uart1);
+    }
+
+    /// The fixed latency in nano seconds between the timestamp passed to [Uart1::send_at] and the start bit of the
+    /// first byte on the line. The transmitter starts with the next tick of the baud rate generator, which takes up to
+    /// a bit time, and the system timer has a resolution of a micro second.
+    pub fn send_at_latency_ns(&self) -> Result<u32, UartError> {
+        self.bit_time_ns().map(|bit_time| bit_time + 1_000)
+    }
+
+    /// Emulate a parity bit in software. The miniUART does not support a parity bit, but a character with 7 data bits
+    /// and a parity bit looks the same on the line as a character with 8 data bits. With a parity set, the most
+    /// significant bit of each byte sent is replaced with the parity bit, and the parity of each byte received is
+    /// verified and stripped. Receiving a byte with a wrong parity fails with ``Err(UartError::ParityError)``. This
+    /// allows to connect 7E1 or 7O1 devices to the miniUART.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 9_600);
+    /// uart.set_parity(Parity::Even);
+    /// # }
+    /// ```
+    pub fn set_parity(&mut self, parity: Parity) {
+        interface::uart1_set_parity(parity);
+    }
+
+    /// Send a break by holding the TX line low for the time it takes to transfer ``bit_times`` bits. Data already
+    /// handed to the Uart1 is sent out completely before the break starts. Protocols like LIN or DMX use a break to
+    /// signal the start of a new frame.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 250_000);
+    /// // DMX requires a break of at least 88us which are 22 bits at 250kBaud
+    /// uart.send_break(23).unwrap();
+    /// # }
+    /// ```
+    pub fn send_break(&self, bit_times: u32) -> Result<(), UartError> {
+        self.bit_time_ns().map(|bit_time| {
+            let duration = bit_time as u64 * bit_times as u64;
+            // add a micro second to never hold the break shorter than required due to the truncation
+            interface::uart1_send_break(duration / 1000 + 1);
+        })
+    }
+
+    /// Emulate a receive FIFO level interrupt. The miniUART raises the receive interrupt for every single byte, which
+    /// causes quite some overhead at high data rates. With a watermark set [Uart1::handle_interrupt] waits until the
+    /// receive FIFO holds ``level`` bytes, or no further byte has been received for the time it takes to transfer
+    /// ``idle_chars`` characters, before the received data is reported with [UartEvent::DataAvailable]. The level is
+    /// limited to the 8 bytes of the receive FIFO. The interrupt handler does not wait for the data: while the receive
+    /// FIFO is below the watermark the receive interrupt stays disabled and [Uart1::check_rx_watermark] need to be
+    /// called from a timer interrupt handler, at least once within the idle time, to check the level again. A level of
+    /// 1 restores the default behavior.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 921_600);
+    /// uart.set_rx_watermark(6, 2).unwrap();
+    /// uart.enable_interrupts(InterruptType::Receive);
+    /// // from now on Uart1::check_rx_watermark() is called regularly by a timer interrupt handler
+    /// # }
+    /// ```
+    pub fn set_rx_watermark(&mut self, level: usize, idle_chars: u32) -> Result<(), UartError> {
+        let char_time = self.char_time_ns()?;
+        let idle_timeout = char_time as u64 * idle_chars as u64 / 1000;
+        interface::uart1_set_rx_watermark(level, idle_timeout as u32);
+        Ok(())
+    }
+
+    /// Check the receive FIFO level against the watermark set with [Uart1::set_rx_watermark] while the receive interrupt
+    /// is held back. This need to be called regularly from a timer interrupt handler. It does not require access to
+    /// the Uart1 instance. Once the watermark has been reached or no further data has been received within the idle
+    /// time the received data is reported as [UartEvent::DataAvailable] to be processed with [Uart1::events].
+    /// # Example
+    /// ```ignore
+    /// # use ruspiro_uart::uart1::*;
+    /// #[IrqHandler(ArmTimer)]
+    /// fn timer_handler() {
+    ///     Uart1::check_rx_watermark();
+    /// }
+    /// ```
+    pub fn check_rx_watermark() {
+        interface::uart1_check_rx_watermark(&|event| {
+            // if the application does not process the events fast enough the new ones are dropped
+            let _ = EVENTS.push(event);
+        });
+    }
+
+    /// Let the miniUART handle the RTS and CTS lines on it's own. The transmitter pauses while CTS (GPIO16) is
+    /// de-asserted and RTS (GPIO17) is de-asserted once the receive FIFO has filled up to the given level. Both lines
+    /// are active low, like on common USB-serial adapters. The pins are released with [Uart1::release]. Fails with
+    /// ``Err(UartError::PinBusy)`` if one of the pins is used by another peripheral.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 921_600);
+    /// uart.enable_auto_flow_control(RtsLevel::FourFree).unwrap();
+    /// # }
+    /// ```
+    pub fn enable_auto_flow_control(&mut self, level: RtsLevel) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::uart1_enable_auto_flow_control(level as u32)
+    }
+
+    /// Switch the Uart1 to a character mode for the lowest latency. The FIFOs of the miniUART can not be disabled, so
+    /// with [FifoMode::Disabled] at most one byte is queued in the transmit FIFO behind the one on the line and the
+    /// receive interrupt reports each single byte, which also removes a watermark set with [Uart1::set_rx_watermark].
+    /// The transmit interrupt is therefore raised for each character sent as well.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.set_fifo_mode(FifoMode::Disabled).unwrap();
+    /// # }
+    /// ```
+    pub fn set_fifo_mode(&mut self, mode: FifoMode) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::uart1_set_fifo_enabled(mode == FifoMode::Enabled);
+        Ok(())
+    }
+
+    /// Let the receive interrupt drain the receive FIFO into a receive buffer of 256 bytes, so data arriving while the
+    /// CPU is busy is not lost. [Uart1::handle_interrupt] need to be called from the Aux interrupt handler. All receive
+    /// functions take their data from this buffer while enabled, and [Uart1::read_buffered] takes it without waiting.
+    /// Data left in the buffer when disabling is discarded. Fails with ``Err(UartError::RxBufferInUse)`` while the
+    /// poll service is attached, as the buffer can only be filled by one of them.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.set_rx_buffered(true).unwrap();
+    /// let mut buffer = [0u8; 64];
+    /// if uart.rx_available() != 0 {
+    ///     if let Ok(outcome) = uart.read_buffered(&mut buffer) {
+    ///         let count = outcome.count();
+    ///         // process the data received
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn set_rx_buffered(&mut self, enabled: bool) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        interface::uart1_set_rx_irq_buffered(enabled)?;
+        if enabled {
+            interface::uart1_enable_interrupts(InterruptType::Receive);
+        }
+        Ok(())
+    }
+
+    /// Take the data from the receive buffer filled by the receive interrupt or the poll service, without waiting
+    /// for data to arrive. Bytes received with a wrong parity are skipped. Returns [ReadOutcome::Complete] if the
+    /// buffer has been filled and [ReadOutcome::Partial] with the number of bytes copied into the buffer otherwise.
+    /// Fails with ``Err(UartError::RxNotBuffered)`` if neither the receive interrupt nor the poll service fills the
+    /// receive buffer.
+    pub fn read_buffered(&self, buffer: &mut [u8]) -> Result<ReadOutcome, UartError> {
+        if !interface::uart1_rx_buffered() {
+            return Err(UartError::RxNotBuffered);
+        }
+        let mut count = 0;
+        while count < buffer.len() {
+            match interface::uart1_try_receive() {
+                Some(Ok(data)) => {
+                    buffer[count] = data;
+                    count += 1;
+                }
+                Some(Err(_)) => (),
+                None => break,
+            }
+        }
+        if count == buffer.len() {
+            Ok(ReadOutcome::Complete(count))
+        } else {
+            Ok(ReadOutcome::Partial(count))
+        }
+    }
+
+    /// The number of bytes waiting in the receive buffer filled by the receive interrupt or the poll service
+    pub fn rx_available(&self) -> usize {
+        interface::uart1_rx_available()
+    }
+
+    /// Run the receive path without interrupts. Once attached, [Uart1::poll_service] need to be called periodically,
+    /// e.g. from a timer interrupt handler, at least every ``period`` micro seconds. Each call drains the receive FIFO
+    /// into a receive buffer and reports the data added as [UartEvent::DataAvailable]. All receive functions take
+    /// their data from this buffer while the service is attached. Fails with ``Err(UartError::PollPeriodTooLong)`` if
+    /// the 8 byte receive FIFO would overflow within the period at the baud rate in use, and with
+    /// ``Err(UartError::RxBufferInUse)`` while the receive interrupt fills the receive buffer, see
+    /// [Uart1::set_rx_buffered].
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.attach_poll_service(500).unwrap();
+    /// // from now on Uart1::poll_service() is called every 500us by a timer interrupt handler
+    /// # }
+    /// ```
+    pub fn attach_poll_service(&mut self, period: u64) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        if period > interface::uart1_max_poll_period(self.clock_rate) {
+            return Err(UartError::PollPeriodTooLong);
+        }
+        interface::uart1_set_poll_service(true)
+    }
+
+    /// Detach the poll service, so the receive functions read from the receive FIFO again. Data left in the receive
+    /// buffer is discarded.
+    pub fn detach_poll_service(&mut self) {
+        // detaching never fails
+        let _ = interface::uart1_set_poll_service(false);
+    }
+
+    /// Drain the receive FIFO into the receive buffer of the poll service. This need to be called periodically once the
+    /// service has been attached with [Uart1::attach_poll_service]. It does not require access to the Uart1 instance.
+    /// The received data is reported as [UartEvent] to be processed with [Uart1::events], data lost as the receive
+    /// buffer is full is reported as ``UartEvent::Error(RxError::Overrun)``. If the service is not attached this does
+    /// nothing.
+    /// # Example
+    /// ```ignore
+    /// # use ruspiro_uart::uart1::*;
+    /// #[IrqHandler(ArmTimer)]
+    /// fn timer_handler() {
+    ///     Uart1::poll_service();
+    /// }
+    /// ```
+    pub fn poll_service() {
+        interface::uart1_poll_service(|event| {
+            // if the application does not process the events fast enough the new ones are dropped
+            let _ = EVENTS.push(event);
+        });
+    }
+
+    /// Register a command with it's description shown by the built-in ``help`` command. Received lines starting with
+    /// the command name are dispatched to the ``handler`` by [Uart1::process_commands], passing the remaining
+    /// arguments of the line. Registering a command with the same name again replaces the previous one.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.register_command("led", "switch the LED on or off: led <on|off>", |args| {
+    ///     let _on = args == "on";
+    /// });
+    /// # }
+    /// ```
+    pub fn register_command(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        handler: CommandHandler,
+    ) {
+        self.commands.register(name, help, handler);
+    }
+
+    /// Set the handler called with the whole line for received lines that do not match any registered command. Without
+    /// a fallback handler an unknown command is reported back to the sender.
+    pub fn set_command_fallback(&mut self, fallback: Option<CommandHandler>) {
+        self.commands.set_fallback(fallback);
+    }
+
+    /// Process the received data without blocking and dispatch each complete line to the registered commands. This
+    /// need to be called regularly, e.g. from the main loop or once a [UartEvent::DataAvailable] has been raised.
+    /// Returns the number of commands dispatched.
+    pub fn process_commands(&mut self) -> usize {
+        if !self.initialized {
+            return 0;
+        }
+        let mut dispatched = 0;
+        while let Some(received) = interface::uart1_try_receive() {
+            // bytes with a wrong parity are dropped
+            if let Ok(byte) = received {
+                if self.commands.feed(byte, interface::uart1_send_data) {
+                    dispatched += 1;
+                }
+            }
+        }
+        dispatched
+    }
+
+    /// Stage the console output in a queue per core, so no core has to wait for another core sending a long log line.
+    /// The first core finding no other core sending the queued output sends it, all other cores continue right away.
+    /// The memory of the given storage is split evenly between the cores. Output that does not fit into the queue of
+    /// a core is dropped and counted in [Uart1::console_dropped_bytes]. Console output from an interrupt handler that
+    /// interrupted a console output on the same core might corrupt the queued output.
+    ///
+    /// The staging can be set up on a shared reference, as only the first call takes effect and later calls are
+    /// ignored. It only pays off when the cores write to a shared ``Uart1`` like the one of a ``OnceUart1``, because the
+    /// console singleton keeps the console locked while one core writes to it.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::buffer::BufferStorage;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.set_console_staging(BufferStorage::Alloc(4 * 1024));
+    /// # }
+    /// ```
+    pub fn set_console_staging(&self, storage: BufferStorage) {
+        let _ = self.staging.get_or_try_init(
+            |staging| {
+                *staging = Some(ConsoleStaging::new(storage.into_slice()));
+                Ok::<(), ()>(())
+            },
+            || Ok(()),
+            || (),
+        );
+    }
+
+    /// Switch between synchronous and buffered console output at runtime. During early boot or while handling a
+    /// panic the output need to be sent before the write returns, as there might be no interrupts to send it later
+    /// on. During normal operation [ConsoleMode::Buffered] queues the output, which is then sent by
+    /// [Uart1::handle_interrupt] from the Aux interrupt, so writing to the console returns right away. Output that does
+    /// not fit into the queue is dropped and counted in [Uart1::console_dropped_bytes]. Switching back to
+    /// [ConsoleMode::Sync] sends out all output still queued before this returns. The mode is kept in an atomic, so it
+    /// can be switched through a shared reference, e.g. from the panic handler of another core. The queued output is
+    /// only ever sent by one core at a time, switching waits for the interrupt handler of another core to finish
+    /// sending. Switching from within [Uart1::handle_interrupt] on the core sending the queue leaves the queued output
+    /// to the interrupted handler.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// // the Aux interrupt calling Uart1::handle_interrupt is active from now on
+    /// uart.set_console_mode(ConsoleMode::Buffered);
+    /// // ... and while handling a panic
+    /// uart.set_console_mode(ConsoleMode::Sync);
+    /// # }
+    /// ```
+    pub fn set_console_mode(&self, mode: ConsoleMode) {
+        match mode {
+            ConsoleMode::Buffered => CONSOLE_BUFFERED.store(true, Ordering::Release),
+            ConsoleMode::Sync => {
+                if CONSOLE_BUFFERED.swap(false, Ordering::AcqRel) {
+                    interface::uart1_disable_interrupts(InterruptType::Transmit);
+                    while CONSOLE_DRAINING.swap(true, Ordering::Acquire) {
+                        if interface::uart1_in_irq() {
+                            return;
+                        }
+                        watchdog::kick();
+                    }
+                    let mut chunk = [0u8; 32];
+                    loop {
+                        let mut count = 0;
+                        while count < chunk.len() {
+                            match CONSOLE_QUEUE.pop() {
+                                Some(byte) => chunk[count] = byte,
+                                None => break,
+                            }
+                            count += 1;
+                        }
+                        if count == 0 {
+                            break;
+                        }
+                        interface::uart1_send_data(&chunk[..count]);
+                    }
+                    CONSOLE_DRAINING.store(false, Ordering::Release);
+                }
+            }
+        }
+    }
+
+    /// Send a status frame every ``period`` micro seconds on the Uart1, filled by the given function. The frames are sent
+    /// from [heartbeat::service], which need to be called regularly from a timer interrupt handler. This replaces a
+    /// heartbeat enabled on another Uart.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_uart::heartbeat::HEARTBEAT_FRAME_SIZE;
+    /// fn status(frame: &mut heapless::Vec<u8, HEARTBEAT_FRAME_SIZE>) {
+    ///     let _ = frame.extend_from_slice(b"[alive]\r\n");
+    /// }
+    ///
+    /// # fn doc() {
+    /// # let mut uart = Uart1::new();
+    /// # let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.enable_heartbeat(5_000_000, status).unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "heartbeat")]
+    pub fn enable_heartbeat(&mut self, period: u64, f: HeartbeatFn) -> Result<(), UartError> {
+        if !self.initialized {
+            return Err(UartError::NotInitialized);
+        }
+        heartbeat::enable(Peripheral::Uart1, period, f);
+        Ok(())
+    }
+
+    /// Stop sending the status frames on the Uart1.
+    #[cfg(feature = "heartbeat")]
+    pub fn disable_heartbeat(&mut self) {
+        heartbeat::disable(Peripheral::Uart1);
+    }
+
+    /// Wait the given time in milli seconds after each carriage return of the console output, so slow devices like
+    /// thermal printers finish moving the carriage before the next character arrives. The delay starts once the
+    /// carriage return has left the transmitter, a delay of 0 disables it. The delays do not apply
+    /// while the console is buffered.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_console::ConsoleImpl;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 9_600);
+    /// uart.set_line_delay(50);
+    /// uart.set_char_delay(500);
+    /// uart.puts("receipt\r\n");
+    /// # }
+    /// ```
+    pub fn set_line_delay(&mut self, ms: u32) {
+        self.pacing.line_delay = ms as u64 * 1000;
+    }
+
+    /// Wait the given time in micro seconds after each character of the console output once it has left the
+    /// transmitter, a delay of 0 disables it. The delays do not apply
+    /// while the console is buffered.
+    pub fn set_char_delay(&mut self, us: u32) {
+        self.pacing.char_delay = us as u64;
+    }
+
+    /// Limit the time the synchronous console output may keep the core busy to the given percentage of each
+    /// ``interval`` in micro seconds, so a large debug dump can not starve a real-time loop. Once the share of the
+    /// current interval is used up, the rest of the output is queued to be sent by [Uart1::handle_interrupt] as with
+    /// [ConsoleMode::Buffered], and any further output is queued as well until the queue has been sent. The Aux
+    /// interrupt calling [Uart1::handle_interrupt] therefore need to be active. Output that does not fit into the
+    /// queue is dropped and counted in [Uart1::console_dropped_bytes]. An interval of 0 removes the limit, which is the
+    /// default. The limit does not apply while a character or line delay is set.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// // spend at most 10% of each millisecond on sending console output
+    /// uart.set_console_throttle(1_000, 10);
+    /// # }
+    /// ```
+    pub fn set_console_throttle(&mut self, interval: u64, percent: u8) {
+        self.throttle.set(interval, percent);
+    }
+
+    /// Add a prefix to each line of the console output, so the output of several cores can be ordered and timed on
+    /// the host without changing every print call. A new line starts after each ``\n``. Only the output written
+    /// through the [ConsoleImpl] is prefixed.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_console::ConsoleImpl;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.set_log_prefix(Prefix::Both);
+    /// // sends "[    12.345678 core 0] booting\r\n"
+    /// uart.puts("booting\r\n");
+    /// # }
+    /// ```
+    pub fn set_log_prefix(&mut self, prefix: Prefix) {
+        self.prefix = prefix;
+    }
+
+    /// Replace the non-printable bytes of the console output, so binary data printed by accident does not corrupt the
+    /// terminal. Tabs, line endings and bytes above ``0x7F`` are always sent as they are. Use [Escape::None] to switch
+    /// back to the raw output, e.g. while a file transfer shares the console. Only the output written through the
+    /// [ConsoleImpl] is escaped.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_console::ConsoleImpl;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.set_console_escape(Escape::Caret);
+    /// // sends "^[[2J\r\n"
+    /// uart.puts("\x1B[2J\r\n");
+    /// # }
+    /// ```
+    pub fn set_console_escape(&self, escape: Escape) {
+        self.escape.store(escape as u8, Ordering::Relaxed);
+    }
+
+    /// Append a checksum marker `` #XX`` to each line of the console output, so lines corrupted by flaky cabling can
+    /// be detected on the host. See [linecheck](crate::linecheck) for the format. Only the output written through the
+    /// [ConsoleImpl] is checked.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use ruspiro_console::ConsoleImpl;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// uart.set_line_check(true);
+    /// // sends "booting #98\r\n"
+    /// uart.puts("booting\r\n");
+    /// # }
+    /// ```
+    pub fn set_line_check(&mut self, enabled: bool) {
+        self.line_check.set_enabled(enabled);
+    }
+
+    /// Check the configuration of the peripheral and it's pins for the common causes of garbled data, like a baud
+    /// rate divisor not matching the requested baud rate or a pin claimed by another function. Returns the problems
+    /// found, an empty list if everything looks fine.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// for finding in uart.diagnose() {
+    ///     // report the finding through some other channel
+    /// }
+    /// # }
+    /// ```
+    pub fn diagnose(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        if self.initialized {
+            interface::uart1_diagnose(self.clock_rate, self.baud_rate, |finding| {
+                findings.push(finding)
+            });
+        } else {
+            findings.push(Finding::NotInitialized);
+        }
+        findings
+    }
+
+    /// Write a human readable dump of the active configuration to ``w``. The line settings are read back from the
+    /// peripheral registers, so the dump shows what the hardware actually uses rather than what has been requested.
+    /// # Example
+    /// ```no_run
+    /// # use ruspiro_uart::*;
+    /// # use core::fmt::Write;
+    /// # fn doc() {
+    /// let mut uart = Uart1::new();
+    /// let _ = uart.initialize(250_000_000, 115_200);
+    /// let mut dump = String::new();
+    /// uart.describe(&mut dump).unwrap();
+    /// uart.send_string(&dump);
+    /// # }
+    /// ```
+    pub fn describe<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "Uart1 (miniUART)")?;
+        writeln!(w, "  initialized:      {}", self.initialized)?;
+        if self.initialized {
+            writeln!(w, "  clock rate:       {} Hz", self.clock_rate)?;
+            writeln!(w, "  requested baud:   {}", self.baud_rate)?;
+            interface::uart1_describe(w, self.clock_rate)?;
+        }
+        writeln!(w, "  encoding:         {:?}", self.encoding)?;
+        writeln!(w, "  console timeout:  {} us", self.console_timeout)?;
+        let mode = if CONSOLE_BUFFERED.load(Ordering::Relaxed) {
+            ConsoleMode::Buffered
+        } else {
+            ConsoleMode::Sync
+        };
+        writeln!(w, "  console mode:     {:?}", mode)?;
+        writeln!(w, "  event queue:      {} entries", EVENT_QUEUE_SIZE)?;
+        #[cfg(feature = "early_log")]
+        writeln!(
+            w,
+            "  early log:        {} bytes",
+            crate::early::EARLY_LOG_SIZE
+        )?;
+        crate::describe_features(w)
+    }
+}
+
+// the steps tearing down the Uart1
+struct Uart1Teardown;
+
+impl Teardown for Uart1Teardown {
+    fn disable_interrupts(&mut self) {
+        interface::uart1_disable_interrupts(InterruptType::RecieveTransmit);
+    }
+
+    fn detach(&mut self) {
+        #[cfg(feature = "fiq")]
+        fiq::release(Peripheral::Uart1);
+        #[cfg(feature = "heartbeat")]
+        heartbeat::disable(Peripheral::Uart1);
+    }
+
+    fn discard_events(&mut self) {
+        while EVENTS.pop().is_some() {}
+    }
+
+    fn shutdown(&mut self) {
+        interface::uart1_shutdown();
+    }
+
+    fn release_pins(&mut self) {
+        interface::uart1_release();
+    }
+
+    fn reset(&mut self) {
+        // disabling the producers of the receive buffer never fails
+        let _ = interface::uart1_set_poll_service(false);
+        let _ = interface::uart1_set_rx_irq_buffered(false);
+        interface::uart1_set_rx_watermark(1, 0);
+        interface::uart1_set_fifo_enabled(true);
+        CONSOLE_BUFFERED.store(false, Ordering::Release);
+        CONSOLE_THROTTLED.store(false, Ordering::Release);
+        // the interrupt handler might still be sending the queue on another core
+        while CONSOLE_DRAINING.swap(true, Ordering::Acquire) {
+            watchdog::kick();
+        }
+        while CONSOLE_QUEUE.pop().is_some() {}
+        CONSOLE_DRAINING.store(false, Ordering::Release);
+        interface::uart1_set_parity(Parity::None);
+    }
+}
+
+impl Drop for Uart1 {
+    fn drop(&mut self) {
+        // ensure the Uart1 peripheral is released once this instance is dropped
+        self.deinit();
+    }
+}
+
+impl Uart1 {
+    // send data as console output respecting the configured console timeout
+    fn console_send(&self, data: &[u8]) {
+        let deadline = crate::console_deadline();
+        let escape = escape::load(&self.escape);
+        if escape == Escape::None {
+            self.console_prefixed(data, deadline);
+        } else {
+            escape::write_escaped(escape, data, |chunk| self.console_prefixed(chunk, deadline));
+        }
+    }
+
+    // add the prefix to each line of the console output if configured
+    fn console_prefixed(&self, data: &[u8], deadline: Option<u64>) {
+        if self.prefix == Prefix::None {
+            self.console_checked(data, deadline);
+        } else {
+            prefix::write_prefixed(self.prefix, &self.line_start, data, |chunk| {
+                self.console_checked(chunk, deadline)
+            });
+        }
+    }
+
+    // add the line check marker to the console output if enabled
+    fn console_checked(&self, data: &[u8], deadline: Option<u64>) {
+        if self.line_check.is_enabled() {
+            self.line_check
+                .write(data, |chunk| self.console_output(chunk, deadline));
+        } else {
+            self.console_output(data, deadline);
+        }
+    }
+
+    // pass the console output to the staging queues or send it right away
+    fn console_output(&self, data: &[u8], deadline: Option<u64>) {
+        if !self.initialized {
+            #[cfg(feature = "early_log")]
+            crate::early::EARLY_LOG.write(data);
+            return;
+        }
+        match self.staging.get().and_then(Option::as_ref) {
+            // the interrupted code might be writing to the staging queue itself
+            Some(staging) if !interface::uart1_in_irq() => {
+                if !staging.write(data, |chunk| self.console_send_direct(chunk, deadline)) {
+                    self.console_dropped
+                        .fetch_add(data.len(), Ordering::Relaxed);
+                }
+            }
+            _ => self.console_send_direct(data, deadline),
+        }
+    }
+
+    // send the console output right away, or queue it to be sent by the interrupt handler
+    fn console_send_direct(&self, data: &[u8], deadline: Option<u64>) {
+        if CONSOLE_BUFFERED.load(Ordering::Acquire) {
+            self.console_queue(data);
+        } else if interface::uart1_in_irq() {
+            let mut bytes = data.iter().copied();
+            let sent = interface::uart1_fill_fifo(|| bytes.next());
+            self.console_dropped
+                .fetch_add(data.len() - sent, Ordering::Relaxed);
+        } else if self.pacing.is_active() {
+            let passed = self.pacing.send(
+                data,
+                deadline,
+                self.console_timeout,
+                |byte| self.console_send_blocking(byte, deadline),
+                interface::uart1_flush_timeout,
+            );
+            self.console_dropped
+                .fetch_add(data.len() - passed, Ordering::Relaxed);
+        } else if self.throttle.is_active() {
+            self.console_send_throttled(data, deadline);
+        } else {
+            self.console_send_blocking(data, deadline);
+        }
+    }
+
+    // send the console output as long as the time budget of the current interval and the deadline of the console
+    // write last, and queue the rest to be sent by the interrupt handler. While output is queued all further output
+    // is queued as well to keep it's order
+    fn console_send_throttled(&self, data: &[u8], deadline: Option<u64>) {
+        if CONSOLE_THROTTLED.load(Ordering::Acquire) {
+            self.console_queue(data);
+            return;
+        }
+        let start = clock::now();
+        let budget = self.throttle.remaining(start);
+        let mut sent = 0;
+        loop {
+            let now = clock::now();
+            if sent == data.len()
+                || now.saturating_sub(start) >= budget
+                || matches!(deadline, Some(deadline) if now >= deadline)
+            {
+                break;
+            }
+            let mut bytes = data[sent..].iter().copied();
+            match interface::uart1_fill_fifo(|| bytes.next()) {
+                0 => watchdog::kick(),
+                count => sent += count,
+            }
+        }
+        self.throttle.charge(clock::elapsed_since(start));
+        if sent < data.len() {
+            CONSOLE_THROTTLED.store(true, Ordering::Release);
+            self.console_queue(&data[sent..]);
+        }
+    }
+
+    // send the console output before returning, respecting the configured console timeout and the deadline of the
+    // console write
+    fn console_send_blocking(&self, data: &[u8], deadline: Option<u64>) {
+        let sent = match (deadline, self.console_yield) {
+            (_, Some(yield_now)) => crate::console_send_yielding(
+                data,
+                self.console_timeout,
+                deadline,
+                yield_now,
+                |chunk| {
+                    if interface::uart1_tx_ready() {
+                        let mut bytes = chunk.iter().copied();
+                        Some(interface::uart1_fill_fifo(|| bytes.next()))
+                    } else {
+                        None
+                    }
+                },
+            ),
+            (None, None) if self.console_timeout == 0 => {
+                interface::uart1_send_data(data);
+                data.len()
+            }
+            (None, None) => interface::uart1_send_data_timeout(data, self.console_timeout),
+            (Some(deadline), None) => data
+                .iter()
+                .take_while(|byte| {
+                    match crate::console_byte_timeout(self.console_timeout, deadline) {
+                        Some(timeout) => {
+                            interface::uart1_send_data_timeout(
+                                core::slice::from_ref(*byte),
+                                timeout,
+                            ) == 1
+                        }
+                        None => false,
+                    }
+                })
+                .count(),
+        };
+        if sent < data.len() {
+            self.console_dropped
+                .fetch_add(data.len() - sent, Ordering::Relaxed);
+        }
+    }
+
+    // queue the console output and enable the transmit interrupt to send it. Output not fitting into the queue is
+    // dropped, as well as output from within the interrupt handler while the interrupted code holds the queue
+    fn console_queue(&self, data: &[u8]) {
+        while CONSOLE_QUEUE_LOCK.swap(true, Ordering::Acquire) {
+            if interface::uart1_in_irq() {
+                self.console_dropped
+                    .fetch_add(data.len(), Ordering::Relaxed);
+                return;
+            }
+            watchdog::kick();
+        }
+        let queued = data
+            .iter()
+            .take_while(|byte| CONSOLE_QUEUE.push(**byte))
+            .count();
+        CONSOLE_QUEUE_LOCK.store(false, Ordering::Release);
+        if queued < data.len() {
+            self.console_dropped
+                .fetch_add(data.len() - queued, Ordering::Relaxed);
+        }
+        interface::uart1_enable_interrupts(InterruptType::Transmit);
+    }
+}
+
+// to use the Uart1 as a console to output strings implement the respective trait
 impl ConsoleImpl for Uart1 {
     fn putc(&self, c: char) {
-        self.send_char(c);
+        let data: [u8; 1] = if self.encoding == Encoding::Utf8 {
+            [c as u8]
+        } else {
+            [self.encoding.encode(c, self.fallback)]
+        };
+        self.console_send(&data);
     }
 
     fn puts(&self, s: &str) {
-        self.send_string(s);
+        if self.encoding == Encoding::Utf8 {
+            self.console_send(s.as_bytes());
+        } else {
+            encoding::encode_str(s, self.encoding, self.fallback, |data| {
+                self.console_send(data)
+            });
+        }
     }
 }
@@ -0,0 +1,48 @@
+/***********************************************************************************************************************
+ * Copyright (c) 2019 by the authors
+ *
+ * Author: André Borrmann
+ * License: Apache License 2.0
+ **********************************************************************************************************************/
+
+//! # Watchdog Kick
+//!
+//! Sending or receiving large amounts of data blocks for quite some time at typical baud rates. Systems running with a
+//! hardware watchdog would be reset in the middle of such a transfer. Therefore a hook can be registered that is
+//! called periodically from all blocking wait loops of the Uarts to feed the watchdog. There is only one watchdog in
+//! the system, so the hook is shared by all Uarts.
+//!
+
+use crate::clock;
+use crate::sealed::FnHook;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+// the minimum time in micro seconds between two calls of the hook
+const KICK_INTERVAL: u32 = 10_000;
+
+// the registered hook
+static KICK: FnHook<fn()> = FnHook::new();
+// the lower 32 bits of the time the hook has been called the last time
+static LAST_KICK: AtomicU32 = AtomicU32::new(0);
+
+// register the hook or remove it if ``None`` is given
+pub(crate) fn set_kick(kick: Option<fn()>) {
+    KICK.set(kick);
+}
+
+// call the hook if one is registered and it has not been called within the kick interval
+pub(crate) fn kick() {
+    let kick = match KICK.get() {
+        Some(kick) => kick,
+        None => return,
+    };
+    let now = clock::now() as u32;
+    let last = LAST_KICK.load(Ordering::Relaxed);
+    if now.wrapping_sub(last) >= KICK_INTERVAL
+        && LAST_KICK
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    {
+        kick();
+    }
+}
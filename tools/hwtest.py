@@ -0,0 +1,114 @@
+#!/usr/bin/env python3
+# Copyright (c) 2019 by the authors
+#
+# Author: André Borrmann
+# License: Apache License 2.0
+"""Host side of the ruspiro-uart hardware test.
+
+Connect the Uart1 of the Raspberry Pi (GPIO14/15) to an USB-serial adapter, start this script and run
+``Uart1::run_hw_test`` on the device (e.g. the ``hw_test`` example). The protocol is described in the ``hwtest``
+module of the crate.
+
+    python3 tools/hwtest.py /dev/ttyUSB0 --baud 115200
+
+Requires pyserial.
+"""
+
+import argparse
+import struct
+import sys
+import time
+
+import serial
+
+HELLO = b"\x1bTh"
+ECHO = b"\x1bTe"
+BAUD = b"\x1bTb"
+BREAK = b"\x1bTk"
+PARITY = b"\x1bTp"
+DONE = b"\x1bTq"
+ACK = b"\x1bTa"
+BREAK_SEEN = b"\x1bTs"
+PARITY_PROBE = bytes([0x41, 0xC1])
+
+# the time in seconds to wait for the next command of the device
+COMMAND_TIMEOUT = 30.0
+# the time in seconds the device gives the host to echo a chunk of data
+ECHO_TIMEOUT = 0.1
+
+
+def read_command(port):
+    """Wait for the next command and return it together with it's argument."""
+    deadline = time.monotonic() + COMMAND_TIMEOUT
+    window = b""
+    while time.monotonic() < deadline:
+        byte = port.read(1)
+        if not byte:
+            continue
+        window = (window + byte)[-3:]
+        if len(window) == 3 and window[:2] == b"\x1bT":
+            argument = port.read(4)
+            if len(argument) == 4:
+                return window, struct.unpack("<I", argument)[0]
+    return None, 0
+
+
+def echo(port, count):
+    """Send back every byte received until ``count`` bytes have been echoed."""
+    port.timeout = ECHO_TIMEOUT
+    echoed = 0
+    while echoed < count:
+        # the device waits for each chunk to be echoed, so send back whatever has arrived right away
+        data = port.read(1)
+        if not data:
+            return False
+        data += port.read(min(port.in_waiting, count - echoed - 1))
+        port.write(data)
+        echoed += len(data)
+    return True
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("port", help="the serial port the device is connected to")
+    parser.add_argument("--baud", type=int, default=115200, help="the initial baud rate of the device")
+    args = parser.parse_args()
+
+    port = serial.Serial(args.port, args.baud, timeout=0.05)
+    print("waiting for the device on {} at {} baud".format(args.port, args.baud))
+    while True:
+        command, argument = read_command(port)
+        if command is None:
+            print("no command received within {}s".format(COMMAND_TIMEOUT))
+            return 1
+        port.write(ACK)
+        port.flush()
+        if command == HELLO:
+            print("device connected")
+        elif command == ECHO:
+            result = "ok" if echo(port, argument) else "incomplete"
+            print("echo of {} bytes at {} baud: {}".format(argument, port.baudrate, result))
+            port.timeout = 0.05
+        elif command == BAUD:
+            port.baudrate = argument
+            print("switched to {} baud".format(argument))
+        elif command == BREAK:
+            # with the default termios settings of pyserial a break is read as a single 0x00 byte
+            port.timeout = argument / 1_000_000 + 0.5
+            data = port.read(1)
+            port.timeout = 0.05
+            if data == b"\x00":
+                port.write(BREAK_SEEN)
+                print("break detected")
+            else:
+                print("break not detected")
+        elif command == PARITY:
+            port.write(PARITY_PROBE)
+            print("parity probe sent")
+        elif command == DONE:
+            print("test finished, {} step(s) failed".format(argument))
+            return 0 if argument == 0 else 2
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,89 @@
+#!/usr/bin/env python3
+# Copyright (c) 2019 by the authors
+#
+# Author: André Borrmann
+# License: Apache License 2.0
+"""Host side decoder of the ruspiro-uart binary telemetry.
+
+Reads the records sent by a ``TelemetryWriter`` from a serial port and prints one line per record. The frame format is
+described in the ``telemetry`` module of the crate. Given a ``struct`` format the records are unpacked, otherwise
+they are printed as hex. Lost and corrupted records are reported on stderr.
+
+    python3 tools/telemetry.py /dev/ttyUSB0 --baud 921600 --format "<Iff"
+
+Requires pyserial.
+"""
+
+import argparse
+import struct
+import sys
+
+import serial
+
+SYNC = b"\xa5\x5a"
+HEADER_SIZE = 5
+CRC_SIZE = 2
+
+
+def crc16(data):
+    """The CRC-16/CCITT with the initial value 0xFFFF."""
+    crc = 0xFFFF
+    for byte in data:
+        crc ^= byte << 8
+        for _ in range(8):
+            crc = ((crc << 1) ^ 0x1021) if crc & 0x8000 else crc << 1
+            crc &= 0xFFFF
+    return crc
+
+
+def frames(port):
+    """Yield the sequence number and the record of each valid frame received."""
+    data = b""
+    while True:
+        data += port.read(max(1, port.in_waiting))
+        while True:
+            start = data.find(SYNC)
+            if start < 0:
+                # keep a trailing first byte of the sync word
+                data = data[-1:]
+                break
+            data = data[start:]
+            if len(data) < HEADER_SIZE:
+                break
+            size = data[4]
+            end = HEADER_SIZE + size
+            if len(data) < end + CRC_SIZE:
+                break
+            crc = struct.unpack(">H", data[end:end + CRC_SIZE])[0]
+            if crc != crc16(data[2:end]):
+                print("corrupted frame", file=sys.stderr)
+                # the sync word might have been part of a record, search again right after it
+                data = data[2:]
+                continue
+            sequence = struct.unpack("<H", data[2:4])[0]
+            yield sequence, data[HEADER_SIZE:end]
+            data = data[end + CRC_SIZE:]
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.split("\n")[0])
+    parser.add_argument("port", help="the serial port connected to the Uart, e.g. /dev/ttyUSB0")
+    parser.add_argument("--baud", type=int, default=115200, help="the baud rate of the Uart")
+    parser.add_argument("--format", help="the struct format of a record, e.g. '<Iff'")
+    args = parser.parse_args()
+
+    port = serial.Serial(args.port, args.baud, timeout=0.1)
+    expected = None
+    for sequence, record in frames(port):
+        if expected is not None and sequence != expected:
+            print("lost %d records" % ((sequence - expected) & 0xFFFF), file=sys.stderr)
+        expected = (sequence + 1) & 0xFFFF
+        if args.format and struct.calcsize(args.format) == len(record):
+            values = struct.unpack(args.format, record)
+            print(sequence, " ".join(str(value) for value in values))
+        else:
+            print(sequence, record.hex())
+
+
+if __name__ == "__main__":
+    main()